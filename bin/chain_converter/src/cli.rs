@@ -0,0 +1,14 @@
+/// Version number if build using cargo (is set and evaluated at compile time).
+const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
+
+/// Builds the cli argument parser and parses the arguments.
+pub fn build_cli() -> ::clap::ArgMatches<'static> {
+    clap_app!(chain_converter =>
+              (version: VERSION.unwrap_or("unknown version")) // if not build using cargo
+              (author: "Valentin Brandl <mail@vbrandl.net>")
+              (about: "Converts a persisted blockchain to another hash algorithm")
+              (@arg HASH: --hash +takes_value +required "Hash algorithm of the converted chain: sha256, sha512-256, sha3-256 or blake2b")
+              (@arg INPUT: +required "Path to the persisted blockchain. The file is not modified")
+              (@arg OUTPUT: +required "Path to write the converted blockchain to. The file must not exist")
+             ).get_matches()
+}
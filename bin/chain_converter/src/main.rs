@@ -0,0 +1,88 @@
+#![deny(warnings, missing_docs)]
+//! This crate converts a persisted blockchain to another hash algorithm. Every block is re-linked
+//! to its converted parent and mined again, while its transactions and their signatures are kept.
+//! The converted chain is written to a new store, the original store is not modified.
+
+#[macro_use]
+extern crate clap;
+#[macro_use]
+extern crate failure;
+
+extern crate env_logger;
+#[macro_use]
+extern crate log;
+
+#[macro_use]
+extern crate data;
+
+mod cli;
+
+use std::fmt::Debug;
+use std::path::Path;
+
+use failure::Error;
+
+use data::ChainStore;
+use data::checkpoint::Checkpoints;
+use data::hash::{HashAlgorithm, HashFunction};
+use data::store;
+
+fn main() {
+    env_logger::init();
+    let matches = cli::build_cli();
+
+    let input = matches.value_of("INPUT").unwrap();
+    let output = matches.value_of("OUTPUT").unwrap();
+    let to = HashAlgorithm::from_name(matches.value_of("HASH").unwrap())
+        .expect("Unknown hash algorithm");
+    let from = store::hash_algorithm(input)
+        .expect("Cannot read the blockchain store")
+        .expect("The blockchain store is empty");
+    info!("Converting {} from {} to {}", input, from, to);
+    let converted = with_hash!(from, H => {
+        with_hash!(to, H2 => convert::<H, H2>(input, output))
+    }).expect("Cannot convert the blockchain");
+    info!("Converted {} blocks into {}", converted, output);
+}
+
+/// Loads and validates the chain persisted at `input`, whose blocks are hashed using `H`, re-links
+/// it under `H2` and writes it to a new store at `output`. The new store is loaded again to
+/// validate the converted chain. Returns the number of converted blocks.
+fn convert<H, H2>(input: &str, output: &str) -> Result<usize, Error>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    H2: HashFunction,
+    H2::OutputSize: Debug + Clone,
+{
+    if Path::new(output).exists() {
+        bail!("The output {} already exists", output);
+    }
+    let source: ChainStore<H> = ChainStore::open(input)?;
+    let spec = source
+        .spec()?
+        .ok_or_else(|| format_err!("The blockchain store {} has no chain spec", input))?;
+    info!("Loading {} blocks of chain {}", source.len(), spec.chain_id());
+    let chain = source.load_with_spec(&spec, &Checkpoints::default())?;
+
+    info!("Re-linking the blocks");
+    let spec = spec.rehash::<H2>();
+    let converted = chain.rehash::<H2>();
+    let mut target: ChainStore<H2> = ChainStore::open(output)?;
+    // the genesis block of the spec is written by `init_spec`
+    target.init_spec(&spec)?;
+    for block in converted.iter_chronological().skip(1) {
+        target.append(block)?;
+    }
+
+    info!("Validating the converted chain");
+    let loaded = target.load_with_spec(&spec, &Checkpoints::default())?;
+    if loaded.len() != chain.len() {
+        bail!(
+            "The converted chain has {} blocks, expected {}",
+            loaded.len(),
+            chain.len()
+        );
+    }
+    Ok(loaded.len())
+}
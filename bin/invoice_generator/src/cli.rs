@@ -17,12 +17,15 @@ pub fn build_cli() -> ::clap::ArgMatches<'static> {
                (version: VERSION.unwrap_or("unknown version"))
                (@arg PUBKEY: -p --publickey +takes_value +required "Public key to initialize the billing process for")
                (@arg HOST: -h --host +takes_value +required "URL of the webservice")
+               (@arg THREADS: -t --threads +takes_value "Number of threads used for mining (Defaults to 1)")
               )
               (@subcommand create_invoice =>
                (about: "Create an invoice for a public key")
                (version: VERSION.unwrap_or("unknown version"))
                (@arg PUBKEY: -p --publickey +takes_value +required "Public key to initialize the billing process for")
                (@arg HOST: -h --host +takes_value +required "URL of the webservice")
+               (@arg THREADS: -t --threads +takes_value "Number of threads used for mining (Defaults to 1)")
+               (@arg LIGHT: -l --light "Only synchronize the block headers and fetch the blocks since the last billing one at a time")
               )
              ).get_matches()
 }
@@ -11,23 +11,41 @@ extern crate log;
 
 extern crate client;
 extern crate cryptography;
+#[macro_use]
 extern crate data;
 
+#[cfg(test)]
+extern crate tempdir;
+
 mod cli;
 mod invoice;
 mod ask;
 
 use std::collections::BTreeSet;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fmt::Debug;
 
 use invoice::{Invoice, InvoicePosition};
 
 use data::Block;
-use data::tx::Data;
+use data::clock::{Clock, SystemClock};
+use data::hash::{HashAlgorithm, HashFunction, Output};
+use data::pow::PowAlgorithm;
+use data::tx::{BlockData, Data};
+
+use client::light::LightClient;
+use client::mining::MiningConfig;
 
-use cryptography::{validate_signature, BillingQuery};
+use cryptography::{validate_versioned_signature, BillingQuery};
 
-fn create_invoice(matches: &clap::ArgMatches<'static>) {
+/// Default number of threads used for mining.
+const DEFAULT_THREADS: usize = 1;
+
+fn create_invoice<H>(matches: &clap::ArgMatches<'static>)
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send,
+{
     let key_pair = matches
         .value_of("KEYPAIR")
         .unwrap_or(cryptography::DEFAULT_KEY_PATH);
@@ -38,37 +56,34 @@ fn create_invoice(matches: &clap::ArgMatches<'static>) {
     info!("Loading public key from {}", pub_key);
     let pub_key = cryptography::PublicKey::load_from_file(pub_key).expect("Cannot load public key");
     let url = matches.value_of("HOST").unwrap();
+    let client: client::Client<H> = client::Client::new(url).expect("Invalid host");
+    info!("Receiving the chain spec");
+    let spec = client.spec().expect("Error receiving the chain spec");
+    let chain_id = spec.chain_id();
+    let pow = *spec.rules().pow_algorithm();
     info!("Receiving latest billing operation");
-    let client = client::Client::new(url).expect("Invalid host");
     let query = BillingQuery::new(key_pair.public_key_bytes(), pub_key.fingerprint());
-    let result = client
-        .since_last_billing(&query)
-        .expect("Error requesting the latest billing");
-    if let Some(chain) = result {
-        info!("Received subchain, calculating invoice...");
-        let positions = chain
-            .iter()
-            .filter(|blk| match *blk.data().data() {
-                Data::Usage(_) => true,
-                _ => false,
-            })
-            .filter(|blk| validate_signature(&pub_key, blk.data()).unwrap_or(false))
-            .map(|blk| {
-                InvoicePosition::new(
-                    blk.time(),
-                    match *blk.data().data() {
-                        Data::Usage(usg) => usg,
-                        _ => unreachable!(),
-                    },
-                )
+    let result = if matches.is_present("LIGHT") {
+        light_positions::<H>(url, &query, &pub_key, chain_id, pow)
+    } else {
+        client
+            .since_last_billing(&query)
+            .expect("Error requesting the latest billing")
+            .map(|chain| {
+                info!("Received subchain, calculating invoice...");
+                chain
+                    .iter()
+                    .filter_map(|blk| position(blk, &pub_key, chain_id))
+                    .collect::<BTreeSet<_>>()
             })
-            .collect::<BTreeSet<_>>();
+    };
+    if let Some(positions) = result {
         if positions.is_empty() {
             error!("No new usage transactions since the last billing operation. Exiting...");
             std::process::exit(0);
         }
         let invoice = Invoice::new(pub_key.clone(), positions);
-        let out_file = format!("{}_{}.txt", invoice.user(), timestamp());
+        let out_file = format!("{}_{}.txt", invoice.user(), SystemClock.now());
         info!("Writing invoice to file: {}", out_file);
         invoice
             .write_to_file(out_file)
@@ -78,20 +93,9 @@ fn create_invoice(matches: &clap::ArgMatches<'static>) {
             info!("Creating billing block");
             let data = pub_key.to_billing();
             info!("Signing data");
-            let signed_data =
-                cryptography::sign_data(&key_pair, data).expect("Error while signing the data");
-            info!("Receiving latest block");
-            let client = client::Client::new(url).expect("Invalid host");
-            let latest = client.latest_block().map(|b| b.hash()).unwrap_or_default();
-            // .expect(&format!("Can't get latest block from {}", url));
-            info!("Generating new block");
-            let block: Block =
-                data::block::Block::new_with_hash(signed_data, latest, data::DIFFICULTY);
-            info!("Performing proof of work");
-            let block = block.proof_of_work();
-            client
-                .append(&block)
-                .expect("Error while appending the block");
+            let signed_data = cryptography::sign_data(&key_pair, chain_id, data)
+                .expect("Error while signing the data");
+            mine_and_append(&client, vec![signed_data], threads(matches), pow);
             info!("New block has been appended to the blockchain");
         }
     } else {
@@ -100,6 +104,105 @@ fn create_invoice(matches: &clap::ArgMatches<'static>) {
     }
 }
 
+/// Synchronizes the block headers and walks the chain backwards, fetching one verified block at a
+/// time, until the latest billing operation for the queried user is found. Returns the invoice
+/// positions of the visited blocks or `None` if billing has not been initialized. Only
+/// transactions signed for the given chain id are considered. The headers are validated using the
+/// proof of work function `pow` of the chain.
+fn light_positions<H>(
+    url: &str,
+    query: &BillingQuery,
+    pub_key: &cryptography::PublicKey,
+    chain_id: &str,
+    pow: PowAlgorithm,
+) -> Option<BTreeSet<InvoicePosition>>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    let client: client::Client<H> = client::Client::new(url).expect("Invalid host");
+    let versions = client
+        .versions()
+        .expect("Error receiving the block versions");
+    let mut light = LightClient::new(client)
+        .with_versions(versions)
+        .with_pow(pow);
+    info!("Synchronizing block headers");
+    let synced = light.sync().expect("Error synchronizing the block headers");
+    info!(
+        "Validated {} headers, searching the latest billing",
+        synced.connected()
+    );
+    let blocks = (0..light.len())
+        .rev()
+        .map(|height| light.block(height).expect("Error receiving a block"));
+    positions_since_billing(blocks, query, pub_key, chain_id)
+}
+
+/// Collects the invoice positions of the given blocks, newest block first, until the latest
+/// billing operation for the queried user is found. Returns `None` if the blocks do not contain a
+/// billing operation. The signatures are checked against the bytes signed for the version of their
+/// block, so billings and usages of legacy blocks are found as well.
+fn positions_since_billing<H, I>(
+    blocks: I,
+    query: &BillingQuery,
+    pub_key: &cryptography::PublicKey,
+    chain_id: &str,
+) -> Option<BTreeSet<InvoicePosition>>
+where
+    H: HashFunction,
+    I: Iterator<Item = Block<H>>,
+{
+    let mut positions = BTreeSet::new();
+    for blk in blocks {
+        positions.extend(position(&blk, pub_key, chain_id));
+        if blk.transactions().iter().any(|blockdata| match *blockdata.data() {
+            Data::Billing(ref fp) => {
+                fp == query.user()
+                    && validate_versioned_signature(
+                        query.signee(),
+                        chain_id,
+                        blk.version(),
+                        blockdata,
+                    ).unwrap_or(false)
+            }
+            _ => false,
+        }) {
+            return Some(positions);
+        }
+    }
+    // reached the genesis block and did not find billing operation
+    None
+}
+
+/// Combines the usages of a block into a single invoice position, since they share the block's
+/// timestamp. Returns `None` if the block does not contain usages signed by the user for the given
+/// chain id and the version of the block.
+fn position<H>(
+    blk: &Block<H>,
+    pub_key: &cryptography::PublicKey,
+    chain_id: &str,
+) -> Option<InvoicePosition>
+where
+    H: HashFunction,
+{
+    let usages = blk.transactions()
+        .iter()
+        .filter(|tx| {
+            validate_versioned_signature(pub_key, chain_id, blk.version(), tx).unwrap_or(false)
+        })
+        .filter_map(|tx| match *tx.data() {
+            Data::Usage(usg) => Some(usg),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if usages.is_empty() {
+        None
+    } else {
+        Some(InvoicePosition::new(blk.time(), usages.iter().sum()))
+    }
+}
+
 fn generate_keypair(matches: &clap::ArgMatches<'static>) {
     // generate keypair
     let path = matches
@@ -119,7 +222,12 @@ fn generate_keypair(matches: &clap::ArgMatches<'static>) {
     info!("Key pair has been generated");
 }
 
-fn initialize_billing(matches: &clap::ArgMatches<'static>) {
+fn initialize_billing<H>(matches: &clap::ArgMatches<'static>)
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send,
+{
     let key_pair = matches
         .value_of("KEYPAIR")
         .unwrap_or(cryptography::DEFAULT_KEY_PATH);
@@ -130,22 +238,16 @@ fn initialize_billing(matches: &clap::ArgMatches<'static>) {
     info!("Loading public key from {}", pub_key);
     let pub_key = cryptography::PublicKey::load_from_file(pub_key).expect("Cannot load public key");
     let url = matches.value_of("HOST").unwrap();
+    let client: client::Client<H> = client::Client::new(url).expect("Invalid host");
+    info!("Receiving the chain spec");
+    let spec = client.spec().expect("Error receiving the chain spec");
     info!("Generating data");
     let data = pub_key.to_billing();
     info!("Signing data");
-    let signed_data =
-        cryptography::sign_data(&key_pair, data).expect("Error while signing the data");
-    info!("Receiving latest block");
-    let client = client::Client::new(url).expect("Invalid host");
-    let latest = client.latest_block().map(|b| b.hash()).unwrap_or_default();
-    // .expect(&format!("Can't get latest block from {}", url));
-    info!("Generating new block");
-    let block: Block = data::block::Block::new_with_hash(signed_data, latest, data::DIFFICULTY);
-    info!("Performing proof of work");
-    let block = block.proof_of_work();
-    client
-        .append(&block)
-        .expect("Error while appending the block");
+    let signed_data = cryptography::sign_data(&key_pair, spec.chain_id(), data)
+        .expect("Error while signing the data");
+    let pow = *spec.rules().pow_algorithm();
+    mine_and_append(&client, vec![signed_data], threads(matches), pow);
     info!("New block has been appended to the blockchain");
 }
 
@@ -156,15 +258,148 @@ fn main() {
     if let Some(matches) = matches.subcommand_matches("generate_keypair") {
         generate_keypair(matches);
     } else if let Some(matches) = matches.subcommand_matches("initialize_billing") {
-        initialize_billing(matches);
+        with_hash!(hash_algorithm(matches), H => initialize_billing::<H>(matches));
     } else if let Some(matches) = matches.subcommand_matches("create_invoice") {
-        create_invoice(matches);
+        with_hash!(hash_algorithm(matches), H => create_invoice::<H>(matches));
     }
 }
 
-fn timestamp() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Error while getting timestamp")
-        .as_secs()
+/// Receives the hash algorithm of the chain, the blocks of the web service are hashed with.
+fn hash_algorithm(matches: &clap::ArgMatches<'static>) -> HashAlgorithm {
+    let url = matches.value_of("HOST").unwrap();
+    let client: client::Client = client::Client::new(url).expect("Invalid host");
+    info!("Receiving the hash algorithm");
+    client
+        .hash_algorithm()
+        .expect("Error receiving the hash algorithm")
+}
+
+/// Parses the number of threads used for mining.
+fn threads(matches: &clap::ArgMatches<'static>) -> usize {
+    matches
+        .value_of("THREADS")
+        .map(|t| t.parse().expect("Cannot parse thread count"))
+        .unwrap_or(DEFAULT_THREADS)
+}
+
+/// Mines a new block containing the transactions on top of the latest block using the supplied
+/// number of threads and proof of work function and appends it to the blockchain.
+fn mine_and_append<H>(
+    client: &client::Client<H>,
+    transactions: Vec<BlockData>,
+    threads: usize,
+    pow: PowAlgorithm,
+) where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send,
+{
+    info!("Performing proof of work using {} thread(s)", threads);
+    client
+        .mine_and_append(
+            transactions,
+            data::DIFFICULTY,
+            &MiningConfig::new(threads).pow(pow),
+            |progress| {
+                info!(
+                    "Tried {} hashes ({:.0} H/s)",
+                    progress.hashes(),
+                    progress.hash_rate()
+                )
+            },
+        )
+        .expect("Error while appending the block");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data::clock::ManualClock;
+    use tempdir::TempDir;
+
+    /// Duration of a day in seconds.
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn key_pair(dir: &TempDir) -> cryptography::KeyPair {
+        let path = dir.path().join("key.json");
+        let pwd = cryptography::Password::new("password".to_owned()).unwrap();
+        cryptography::EncryptedKeyPair::new(&pwd)
+            .unwrap()
+            .write_to_file(&path)
+            .unwrap();
+        cryptography::KeyPair::from_file(&path, &pwd).unwrap()
+    }
+
+    #[test]
+    fn invoice_covers_months_of_usages() {
+        let dir = TempDir::new("invoice_generator").unwrap();
+        let key = key_pair(&dir);
+        let pub_key = key.public_key_bytes();
+        let usage = |chain_id, usage| cryptography::sign_data(&key, chain_id, Data::Usage(usage));
+        let clock = ManualClock::new(data::GENESIS_TIME);
+        // a reading per day for three months
+        let chain = (1..91).fold(data::Blockchain::new(), |chain, day| {
+            clock.advance(DAY);
+            let transactions = vec![
+                usage(data::CHAIN_ID, day).unwrap(),
+                usage(data::CHAIN_ID, day * 1000).unwrap(),
+                usage("other", 1).unwrap(),
+            ];
+            let block: Block = chain.generate_block_with_clock(transactions, 0, &clock);
+            chain.insert(block).unwrap()
+        });
+        let positions = chain
+            .iter()
+            .filter_map(|blk| position(blk, &pub_key, data::CHAIN_ID))
+            .collect::<BTreeSet<_>>();
+        assert_eq!(positions.len(), 90);
+        let invoice = Invoice::new(pub_key.clone(), positions).to_string();
+        let lines = invoice.lines().skip(1).collect::<Vec<_>>();
+        assert_eq!(lines.len(), 90);
+        assert_eq!(lines[0], "\t2018-03-02 00:00:00: 1001");
+        assert_eq!(lines[29], "\t2018-03-31 00:00:00: 30030");
+        assert_eq!(lines[89], "\t2018-05-30 00:00:00: 90090");
+        assert!(chain.iter().all(|blk| position(blk, &pub_key, "other").is_some()));
+    }
+
+    #[test]
+    fn billing_is_found_in_migrated_legacy_prefix() {
+        use data::block::VERSION;
+        use data::version::LEGACY_VERSION;
+        let dir = TempDir::new("invoice_generator").unwrap();
+        let key = key_pair(&dir);
+        let pub_key = key.public_key_bytes();
+        let query = BillingQuery::new(key.public_key_bytes(), pub_key.fingerprint());
+        let sign = |version, data| {
+            cryptography::sign_versioned_data(&key, data::CHAIN_ID, version, data).unwrap()
+        };
+        let clock = ManualClock::new(data::GENESIS_TIME);
+        // the blocks up to height 30 are legacy blocks of a single transaction each, the block at
+        // height 10 contains the billing
+        let chain = data::Blockchain::from_spec(data::migrated_spec(31)).unwrap();
+        let chain = (1..61).fold(chain, |chain, day| {
+            clock.advance(DAY);
+            let version = if day < 31 { LEGACY_VERSION } else { VERSION };
+            let transactions = if day == 10 {
+                vec![sign(version, pub_key.to_billing())]
+            } else {
+                vec![sign(version, Data::Usage(day))]
+            };
+            let block: Block =
+                chain.generate_block_with_clock(transactions, data::DIFFICULTY, &clock);
+            assert_eq!(block.version(), version);
+            chain.insert(block).unwrap()
+        });
+        let blocks = chain.iter().cloned();
+        let positions = positions_since_billing(blocks, &query, &pub_key, data::CHAIN_ID);
+        let positions = positions.unwrap().iter().map(|pos| pos.to_string()).collect::<Vec<_>>();
+        assert_eq!(positions.len(), 50);
+        assert_eq!(positions[0], "2018-03-12 00:00:00: 11");
+        assert_eq!(positions[19], "2018-03-31 00:00:00: 30");
+        assert_eq!(positions[49], "2018-04-30 00:00:00: 60");
+        // the signature of the legacy billing does not commit to the chain id
+        let billing = &chain.get_by_height(10).unwrap().transactions()[0];
+        let valid = cryptography::validate_signature(query.signee(), data::CHAIN_ID, billing);
+        assert!(!valid.unwrap());
+    }
 }
@@ -13,11 +13,13 @@ pub fn build_cli() -> ::clap::ArgMatches<'static> {
                (@arg PATH: -p --path +takes_value "Path to write the key pair to (Defaults to ./default.key)")
               )
               (@subcommand generate_transaction =>
-               (about: "Generates a new transaction, mines a block and appends it to the blockchain")
+               (about: "Generates new transactions, mines a block containing them and appends it to the blockchain")
                (version: VERSION.unwrap_or("unknown version"))
                (@arg KEYPAIR: -k --keypair +takes_value "Path to the key pair (Defaults to ./default.key)")
                (@arg HOST: -h --host +takes_value +required "URL of the webservice")
-               (@arg USAGE: +required "Usage to be inserted into the blockchain")
+               (@arg THREADS: -t --threads +takes_value "Number of threads used for mining (Defaults to 1)")
+               (@arg RETRIES: -r --retries +takes_value "Number of times mining is restarted if the head of the chain moves (Defaults to 5)")
+               (@arg USAGE: +required +multiple "Usages to be inserted into the blockchain. Every usage becomes a transaction of the same block")
               )
               (@subcommand export_public_key =>
                (about: "Exports the public key associated with a key pair")
@@ -11,14 +11,21 @@ extern crate log;
 
 extern crate client;
 extern crate cryptography;
+#[macro_use]
 extern crate data;
 
 mod cli;
 
+use std::fmt::Debug;
 use std::io::{BufWriter, Write};
 use std::fs::OpenOptions;
 
-use data::Block;
+use client::mining::MiningConfig;
+
+use data::hash::{HashFunction, Output};
+
+/// Default number of threads used for mining.
+const DEFAULT_THREADS: usize = 1;
 
 fn main() {
     env_logger::init();
@@ -49,32 +56,30 @@ fn main() {
         let pwd = cryptography::get_password().expect("Cannot read password");
         let key_pair =
             cryptography::KeyPair::from_file(key_path, &pwd).expect("Cannot read keypair");
-        let usage: u64 = matches
-            .value_of("USAGE")
+        let usages = matches
+            .values_of("USAGE")
             .unwrap()
-            .parse()
-            .expect("Cannot parse usage");
+            .map(|usage| usage.parse::<u64>().expect("Cannot parse usage"))
+            .collect::<Vec<_>>();
+        let threads = matches
+            .value_of("THREADS")
+            .map(|t| t.parse().expect("Cannot parse thread count"))
+            .unwrap_or(DEFAULT_THREADS);
+        let config = MiningConfig::new(threads);
+        let config = if let Some(retries) = matches.value_of("RETRIES") {
+            config.max_retries(retries.parse().expect("Cannot parse retry limit"))
+        } else {
+            config
+        };
         info!("Loading key pair from {}", key_path);
-        let client = client::Client::new(url).expect("Invalid url");
-
-        info!("Generating data");
-        let tx = data::tx::Data::Usage(usage);
-        info!("Signing data");
-        let signed_data =
-            cryptography::sign_data(&key_pair, tx).expect("Error while signing the data");
-        info!("Receiving latest block");
-        let latest = client
-            .latest_block()
-            .expect(&format!("Can't get latest block from {}", url));
-        info!("Generating new block");
-        let block: Block =
-            data::block::Block::new_with_hash(signed_data, latest.hash(), data::DIFFICULTY);
-        info!("Performing proof of work");
-        let block = block.proof_of_work();
-        client
-            .append(&block)
-            .expect("Error while appending the block");
-        info!("New block has been appended to the blockchain");
+        let probe: client::Client = client::Client::new(url).expect("Invalid url");
+        info!("Receiving the hash algorithm");
+        let algorithm = probe
+            .hash_algorithm()
+            .expect("Error receiving the hash algorithm");
+        with_hash!(algorithm, H => {
+            generate_transaction::<H>(url, &key_pair, usages, threads, &config)
+        });
     } else if let Some(matches) = matches.subcommand_matches("export_public_key") {
         let key_path = matches
             .value_of("KEYPAIR")
@@ -100,3 +105,43 @@ fn main() {
         info!("Public key successfully exported to {}", out_path);
     }
 }
+
+/// Signs the usages for the chain behind `url`, mines a block containing them and appends it. The
+/// block is hashed using `H`, which must be the hash algorithm of the chain, and mined using the
+/// proof of work function of the chain's spec.
+fn generate_transaction<H>(
+    url: &str,
+    key_pair: &cryptography::KeyPair,
+    usages: Vec<u64>,
+    threads: usize,
+    config: &MiningConfig,
+) where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send,
+{
+    let client: client::Client<H> = client::Client::new(url).expect("Invalid url");
+    info!("Receiving the chain spec");
+    let spec = client.spec().expect("Error receiving the chain spec");
+
+    info!("Generating and signing {} transaction(s)", usages.len());
+    let transactions = usages
+        .into_iter()
+        .map(|usage| {
+            cryptography::sign_data(key_pair, spec.chain_id(), data::tx::Data::Usage(usage))
+                .expect("Error while signing the data")
+        })
+        .collect();
+    let config = config.clone().pow(*spec.rules().pow_algorithm());
+    info!("Performing proof of work using {} thread(s)", threads);
+    client
+        .mine_and_append(transactions, data::DIFFICULTY, &config, |progress| {
+            info!(
+                "Tried {} hashes ({:.0} H/s)",
+                progress.hashes(),
+                progress.hash_rate()
+            )
+        })
+        .expect("Error while appending the block");
+    info!("New block has been appended to the blockchain");
+}
@@ -0,0 +1,40 @@
+//! Loading of trusted checkpoints from the configuration. The checkpoint file contains one
+//! checkpoint per line, consisting of the height and the hex encoded hash of a block separated by
+//! whitespace. Empty lines and lines starting with `#` are ignored.
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use failure::Error;
+
+use data::checkpoint::{Checkpoint, Checkpoints};
+
+use server::decode_hex;
+
+/// Reads the checkpoints from the given file.
+pub fn load_checkpoints<P: AsRef<Path>>(path: P) -> Result<Checkpoints, Error> {
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    content
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()))
+        .filter(|&(_, line)| !(line.is_empty() || line.starts_with('#')))
+        .map(|(number, line)| -> Result<Checkpoint, Error> {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(height), Some(hash), None) => {
+                    let height = height
+                        .parse()
+                        .map_err(|_| format_err!("Invalid height in line {}", number))?;
+                    let hash = decode_hex(hash)
+                        .ok_or_else(|| format_err!("Invalid hash in line {}", number))?;
+                    Ok(Checkpoint::new(height, &hash))
+                }
+                _ => Err(format_err!("Invalid checkpoint in line {}", number)),
+            }
+        })
+        .collect::<Result<Vec<_>, Error>>()
+        .map(Checkpoints::new)
+}
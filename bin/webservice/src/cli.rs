@@ -10,5 +10,10 @@ pub fn build_cli() -> ::clap::ArgMatches<'static> {
               (@arg BLOCKCHAIN: -b --blockchain +takes_value "Path to the persisted blockchain")
               (@arg PORT: -p --port +takes_value "Port to listen on (Defaults to 1337)")
               (@arg ADDR: -a --address +takes_value "Address to listen on (Defaults to localhost)")
+              (@arg CHECKPOINTS: -c --checkpoints +takes_value "Path to a file of trusted checkpoints. Each line contains the height and the hex encoded hash of a block")
+              (@arg ACTIVATION: --activation +takes_value conflicts_with[SPEC] "Height from which blocks need the current block version, older blocks use the legacy version (Defaults to 0)")
+              (@arg SPEC: -s --spec +takes_value "Path to a JSON file containing the chain spec (chain id, genesis block and consensus rules). Defaults to the built-in spec")
+              (@arg RECOVER: --recover "Salvages the longest valid prefix of a damaged blockchain store into a new store with the extension .recovered appended and exits. The damaged store is kept")
+              (@arg HASH: --hash +takes_value "Hash algorithm of the blocks: sha256, sha512-256, sha3-256 or blake2b. An existing blockchain store must match it (Defaults to the algorithm of the store or sha256)")
              ).get_matches()
 }
@@ -10,10 +10,18 @@ pub enum BlockchainError {
     // KeyPairAlreadyExists { path: String },
     #[fail(display = "Invalid block")]
     InvalidBlock,
+    #[fail(display = "Invalid block: {}", _0)]
+    RejectedBlock(#[cause] ::data::BlockchainError),
     #[fail(display = "Cannot get lock")]
     CannotGetLock,
     #[fail(display = "Empty chain")]
     EmptyChain,
+    #[fail(display = "Cannot persist block")]
+    Persisting,
+    #[fail(display = "Invalid hash")]
+    InvalidHash,
+    #[fail(display = "Cannot serialize response")]
+    Serializing,
 }
 
 impl Responder<'static> for BlockchainError {
@@ -21,7 +29,8 @@ impl Responder<'static> for BlockchainError {
         use BlockchainError::*;
         let msg = format!("{}", self);
         let status = match self {
-            InvalidBlock => Status::NotAcceptable,
+            InvalidBlock | RejectedBlock(_) => Status::NotAcceptable,
+            InvalidHash => Status::BadRequest,
             EmptyChain => Status::Conflict,
             _ => Status::InternalServerError,
         };
@@ -12,24 +12,42 @@ extern crate untrusted;
 
 extern crate rocket;
 extern crate rocket_contrib;
+extern crate serde;
+extern crate serde_json;
 
 extern crate env_logger;
 #[macro_use]
 extern crate log;
 
 extern crate cryptography;
+#[macro_use]
 extern crate data;
 
+#[cfg(test)]
+extern crate tempdir;
+
+mod checkpoints;
 mod cli;
 mod error;
+mod migration;
 mod server;
+mod spec;
 mod state;
 mod wrapper;
 
+use std::fmt::{Debug, Display};
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
 use error::BlockchainError;
-use state::ServerState;
+use state::{ServerState, SharedState};
 
-use data::blockchain;
+use data::{ChainSpec, ChainStore, PersistingError};
+use data::checkpoint::Checkpoints;
+use data::clock::SystemClock;
+use data::hash::{HashAlgorithm, HashFunction};
+use data::store;
 
 /// Default path to look for the blockchain.
 const DEFAULT_BC_PATH: &str = "./blockchain.dat";
@@ -37,16 +55,75 @@ const DEFAULT_BC_PATH: &str = "./blockchain.dat";
 const DEFAULT_PORT: &str = "1337";
 /// Default address for the webserver to listen on.
 const DEFAULT_ADDRESS: &str = "localhost";
+/// Default height from which blocks need the current block version.
+const DEFAULT_ACTIVATION: &str = "0";
 
 fn main() {
     env_logger::init();
     let matches = cli::build_cli();
 
+    let activation = matches
+        .value_of("ACTIVATION")
+        .unwrap_or(DEFAULT_ACTIVATION)
+        .parse()
+        .expect("Cannot parse activation height");
+    let checkpoints = matches
+        .value_of("CHECKPOINTS")
+        .map(|path| {
+            info!("Loading checkpoints from {}", path);
+            checkpoints::load_checkpoints(path).expect("Cannot load the checkpoints")
+        })
+        .unwrap_or_default();
+
     let data_path = matches.value_of("BLOCKCHAIN").unwrap_or(DEFAULT_BC_PATH);
-    let blockchain = ServerState::new(
-        blockchain::Blockchain::load_from_disk(data_path).unwrap_or_default(),
-        data_path.to_owned(),
-    );
+    match migration::migrate(data_path) {
+        Ok(Some(len)) => info!(
+            "Migrated {} blocks of an earlier release into the blockchain store {}, the original \
+             file has been kept as {}.bak",
+            len, data_path, data_path
+        ),
+        Ok(None) => {}
+        Err(err) => {
+            error!("Cannot migrate the blockchain file {}: {}", data_path, err);
+            process::exit(1);
+        }
+    }
+    let stored = store::hash_algorithm(data_path).unwrap_or_else(|err| refuse(data_path, err));
+    // an existing store is opened using the algorithm it has been created with
+    let algorithm = matches
+        .value_of("HASH")
+        .map(|name| HashAlgorithm::from_name(name).expect("Unknown hash algorithm"))
+        .or(stored)
+        .unwrap_or_default();
+    info!("Using hash algorithm {}", algorithm);
+    let spec_path = matches.value_of("SPEC");
+
+    if matches.is_present("RECOVER") {
+        if stored.is_none() {
+            error!("There is no blockchain store at {} to recover", data_path);
+            process::exit(1);
+        }
+        let target = format!("{}.recovered", data_path);
+        let recovered = with_hash!(algorithm, H => {
+            recover::<H>(data_path, &target, spec_path, activation, &checkpoints)
+        });
+        match recovered {
+            Ok(len) => info!(
+                "Recovered {} blocks into {}, the original store {} has been kept",
+                len, target, data_path
+            ),
+            Err(err) => {
+                error!("Cannot recover the blockchain store {}: {}", data_path, err);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let blockchain = with_hash!(algorithm, H => {
+        load_state::<H>(data_path, spec_path, activation, checkpoints)
+            .map(|state| Box::new(state) as SharedState)
+    }).unwrap_or_else(|err| refuse(data_path, err));
 
     let port = matches
         .value_of("PORT")
@@ -60,3 +137,83 @@ fn main() {
         .expect("Error while creating the server")
         .launch();
 }
+
+/// Logs why the blockchain store cannot be served and exits. The store is never replaced by an
+/// empty chain, so its blocks can still be recovered.
+fn refuse<E: Display>(data_path: &str, err: E) -> ! {
+    error!(
+        "Refusing to start, the blockchain store {} cannot be served: {}",
+        data_path, err
+    );
+    error!("A damaged store can be salvaged into a new store using the --recover flag");
+    process::exit(1)
+}
+
+/// Loads the chain spec at `spec_path` or the built-in spec.
+fn chain_spec<H>(spec_path: Option<&str>, activation: usize) -> ChainSpec<H>
+where
+    H: HashFunction,
+{
+    let spec = spec_path
+        .map(|path| {
+            info!("Loading chain spec from {}", path);
+            spec::load_spec(path).expect("Cannot load the chain spec")
+        })
+        .unwrap_or_else(|| ::data::migrated_spec(activation));
+    info!("Using chain {}", spec.chain_id());
+    spec
+}
+
+/// Opens the blockchain store, whose blocks are hashed using `H`, and loads the chain of the spec
+/// at `spec_path` or of the built-in spec. A missing store is created, while a store, that cannot
+/// be read, belongs to another chain or contains an invalid block, is reported as an error. A store
+/// of an earlier release, that is not bound to a chain yet, keeps its genesis block. The time of
+/// new blocks is limited using the system time.
+fn load_state<H>(
+    data_path: &str,
+    spec_path: Option<&str>,
+    activation: usize,
+    checkpoints: Checkpoints,
+) -> Result<ServerState<H>, PersistingError>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    let spec = chain_spec(spec_path, activation);
+    if Path::new(data_path).exists() {
+        info!("Opening blockchain store {}", data_path);
+    } else {
+        info!("Creating blockchain store {}", data_path);
+    }
+    let mut store: ChainStore<H> = ChainStore::open(data_path)?;
+    // a store of an earlier release keeps its genesis block
+    let spec = store.adopt_spec(&spec)?;
+    let chain = store.load_with_spec(&spec, &checkpoints)?;
+    info!("Loaded {} blocks", chain.len());
+    Ok(ServerState::new(
+        chain,
+        store,
+        spec,
+        checkpoints,
+        Arc::new(SystemClock),
+    ))
+}
+
+/// Salvages the longest valid prefix of the store at `data_path`, whose blocks are hashed using
+/// `H`, into a new store at `target`. Returns the number of salvaged blocks.
+fn recover<H>(
+    data_path: &str,
+    target: &str,
+    spec_path: Option<&str>,
+    activation: usize,
+    checkpoints: &Checkpoints,
+) -> Result<usize, PersistingError>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    let spec = chain_spec(spec_path, activation);
+    info!("Recovering blockchain store {} into {}", data_path, target);
+    let store: ChainStore<H> = ChainStore::recover(data_path, target, &spec, checkpoints)?;
+    Ok(store.len())
+}
@@ -0,0 +1,99 @@
+//! Migration of the blockchain file of earlier releases. Before blocks were appended to a
+//! [`ChainStore`](../../data/type.ChainStore.html), the web service persisted the whole chain as a
+//! single `bincode` encoded list of its blocks, newest block first. Such a file is converted into a
+//! store when the web service starts, while the original file is kept as a backup.
+
+use std::fs::{self, File};
+use std::io::{BufReader, ErrorKind};
+
+use data::{Block, ChainStore, PersistingError};
+use data::version::read_legacy_chain;
+
+/// Converts the blockchain file of an earlier release at `data_path` into a store of SHA-256
+/// hashed blocks, the only hash function of these releases. The original file is copied to the
+/// path of the store with the extension `.bak` appended, before it is replaced by the store.
+/// Returns the number of migrated blocks or `None`, if the file does not exist or is not a
+/// blockchain file of an earlier release, e.g. because it already is a store.
+pub fn migrate(data_path: &str) -> Result<Option<usize>, PersistingError> {
+    let file = match File::open(data_path) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(_) => return Err(PersistingError::IoError),
+    };
+    let len = file.metadata().map_err(|_| PersistingError::IoError)?.len();
+    // neither the header nor the first record of a store decode as a list of blocks
+    let blocks: Vec<Block> = match read_legacy_chain(&mut BufReader::new(file), len) {
+        Ok(blocks) => blocks,
+        Err(PersistingError::DeserializingError) => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    fs::copy(data_path, format!("{}.bak", data_path)).map_err(|_| PersistingError::IoError)?;
+    // the store is written next to the original file and replaces it, once it is complete
+    let migrating = format!("{}.migrating", data_path);
+    match fs::remove_file(&migrating) {
+        Err(ref err) if err.kind() != ErrorKind::NotFound => return Err(PersistingError::IoError),
+        _ => {}
+    }
+    {
+        let mut store: ChainStore = ChainStore::open(&migrating)?;
+        for block in &blocks {
+            store.append(block)?;
+        }
+    }
+    fs::rename(&migrating, data_path).map_err(|_| PersistingError::IoError)?;
+    Ok(Some(blocks.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    use data::HashBytes;
+    use server::decode_hex;
+    use tempdir::TempDir;
+
+    /// Chain of two blocks, that has been persisted by an earlier release.
+    const LEGACY_CHAIN: &str = "\
+        0200000000000000012000000000000000112eefc78c9e9ae0984f8c03564ffe4735e3220f5deb7540bbf790\
+        b7018347ec3c2f68590000000003000000000000000200000000000000222222222222222222222222222222\
+        2222222222222222222222222222222222222222222222222222222222222222222222222222222222222222\
+        222222222201000000e803000000000000012000000000000000000000000000000000000000000000000000\
+        0000000000000000000000000000002f68590000000003000000000000000200000000000000111111111111\
+        1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111\
+        1111111111111111111111111111000000000300000000000000010203";
+
+    #[test]
+    fn legacy_chain_is_migrated() {
+        let dir = TempDir::new("migration_").unwrap();
+        let path = dir.path().join("blockchain.dat");
+        let path = path.to_str().unwrap();
+        let legacy = decode_hex(LEGACY_CHAIN).unwrap();
+        File::create(path).unwrap().write_all(&legacy).unwrap();
+        assert_eq!(migrate(path).unwrap(), Some(2));
+        let mut backup = Vec::new();
+        File::open(format!("{}.bak", path))
+            .unwrap()
+            .read_to_end(&mut backup)
+            .unwrap();
+        assert_eq!(backup, legacy);
+        // the store is bound to the genesis block of the migrated chain
+        let mut store: ChainStore = ChainStore::open(path).unwrap();
+        let spec = store.adopt_spec(&::data::migrated_spec(2)).unwrap();
+        let chain = store.load_with_spec(&spec, &Default::default()).unwrap();
+        let hashes = chain
+            .iter()
+            .map(|block| HashBytes::from(&block.hash()[..]).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            hashes,
+            vec![
+                "0b7a9f975093f576cf0267f1b3ec597b4c695905322e491754e9a40b7d773692",
+                "112eefc78c9e9ae0984f8c03564ffe4735e3220f5deb7540bbf790b7018347ec",
+            ]
+        );
+        // the store is not migrated again
+        assert_eq!(migrate(path).unwrap(), None);
+        assert_eq!(migrate(dir.path().join("missing").to_str().unwrap()).unwrap(), None);
+    }
+}
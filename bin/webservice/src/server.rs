@@ -4,11 +4,16 @@ use rocket::http::Status;
 use rocket::response::status;
 use rocket_contrib::Json;
 
+use serde_json::Value;
+
 use failure::Error;
 
 use error::BlockchainError;
-use state::ServerState;
-use data::{Block, Blockchain};
+use state::SharedState;
+use data::hash::HashAlgorithm;
+use data::target::Target;
+use data::tx::BlockData;
+use data::version::VersionSchedule;
 use cryptography::BillingQuery;
 
 const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
@@ -21,6 +26,16 @@ fn index() -> String {
 
     The following operations are supported:
 
+    GET /spec
+
+        Returns the spec of the chain, containing the chain id, the genesis block and the
+        consensus rules. Transactions are signed for the chain id
+
+    GET /hash
+
+        Returns the hash algorithm of the chain. Blocks, headers and proofs are hashed using this
+        algorithm
+
     POST /append
 
         Appends a new block (passed als "application/json")
@@ -29,40 +44,185 @@ fn index() -> String {
 
         Returns the latest block as a JSON string
 
+    GET /latest_header
+
+        Returns the header of the latest block as a JSON string
+
+    GET /headers/<start>
+
+        Returns the headers from height <start> up to the latest block, oldest first. The genesis
+        block has height 0. Light clients use this to validate the chain without the transactions
+
+    GET /body/<height>
+
+        Returns the transactions of the block at height <height>. The transactions can be checked
+        against the Merkle root of the block's header
+
+    GET /block_by_height/<height>
+
+        Returns the block at height <height> as a JSON string
+
+    GET /block_by_hash/<hash>
+
+        Returns the block with the hex encoded hash <hash> as a JSON string
+
+    GET /height_of/<hash>
+
+        Returns the height of the block with the hex encoded hash <hash>
+
+    GET /difficulty
+
+        Returns the difficulty required for the next block as number of leading zero bits
+
+    GET /target
+
+        Returns the highest difficulty target of the next block as 32 big endian bytes. The target
+        allows finer difficulties than the number of leading zero bits
+
+    GET /version
+
+        Returns the block version required for the next block
+
+    GET /versions
+
+        Returns the accepted block versions and their activation heights. Blocks before the
+        activation of the current version are validated under the rules of their own version
+
     POST /since_last_billing
 
         Returns the part of the blockchain since the last billing for a specified user
+
+    POST /inclusion_proof
+
+        Returns a proof, that the transaction (passed as "application/json") is part of the
+        blockchain. The proof contains the block headers up to the latest block
             "#,
         VERSION.unwrap_or("unknown")
     )
 }
 
 #[get("/latest_block")]
-fn latest_block(state: State<ServerState>) -> Result<Json<Block>, BlockchainError> {
+fn latest_block(state: State<SharedState>) -> Result<Json<Value>, BlockchainError> {
     state.latest_block().map(Json)
 }
 
+#[get("/latest_header")]
+fn latest_header(state: State<SharedState>) -> Result<Json<Value>, BlockchainError> {
+    state.latest_header().map(Json)
+}
+
+#[get("/headers/<start>")]
+fn headers(state: State<SharedState>, start: usize) -> Result<Json<Value>, BlockchainError> {
+    state.headers(start).map(Json)
+}
+
+#[get("/body/<height>")]
+fn body(
+    state: State<SharedState>,
+    height: usize,
+) -> Result<Option<Json<Vec<BlockData>>>, BlockchainError> {
+    state.body(height).map(|opt| opt.map(Json))
+}
+
+#[get("/block_by_height/<height>")]
+fn block_by_height(
+    state: State<SharedState>,
+    height: usize,
+) -> Result<Option<Json<Value>>, BlockchainError> {
+    state.block_by_height(height).map(|opt| opt.map(Json))
+}
+
+#[get("/block_by_hash/<hash>")]
+fn block_by_hash(
+    state: State<SharedState>,
+    hash: String,
+) -> Result<Option<Json<Value>>, BlockchainError> {
+    let hash = decode_hex(&hash).ok_or(BlockchainError::InvalidHash)?;
+    state.block_by_hash(&hash).map(|opt| opt.map(Json))
+}
+
+#[get("/height_of/<hash>")]
+fn height_of(
+    state: State<SharedState>,
+    hash: String,
+) -> Result<Option<Json<usize>>, BlockchainError> {
+    let hash = decode_hex(&hash).ok_or(BlockchainError::InvalidHash)?;
+    state.height_of(&hash).map(|opt| opt.map(Json))
+}
+
+/// Decodes a hex encoded hash. Returns `None` if the string is not valid hex.
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            ::std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+#[get("/difficulty")]
+fn difficulty(state: State<SharedState>) -> Result<Json<usize>, BlockchainError> {
+    state.required_difficulty().map(Json)
+}
+
+#[get("/target")]
+fn target(state: State<SharedState>) -> Result<Json<Target>, BlockchainError> {
+    state.required_target().map(Json)
+}
+
+#[get("/version")]
+fn version(state: State<SharedState>) -> Result<Json<u8>, BlockchainError> {
+    state.next_version().map(Json)
+}
+
+#[get("/versions")]
+fn versions(state: State<SharedState>) -> Result<Json<VersionSchedule>, BlockchainError> {
+    state.versions().map(Json)
+}
+
+#[get("/spec")]
+fn spec(state: State<SharedState>) -> Result<Json<Value>, BlockchainError> {
+    state.spec().map(Json)
+}
+
+#[get("/hash")]
+fn hash(state: State<SharedState>) -> Json<HashAlgorithm> {
+    Json(state.hash_algorithm())
+}
+
 #[post("/append", format = "application/json", data = "<block>")]
 fn append(
-    state: State<ServerState>,
-    block: Json<Block>,
+    state: State<SharedState>,
+    block: Json<Value>,
 ) -> Result<status::Custom<&'static str>, BlockchainError> {
-    let path = state.path();
     state
-        .append(block.0, path)
+        .append(block.0)
         .map(|_| status::Custom(Status::Accepted, "block was appended"))
 }
 
 #[post("/since_last_billing", format = "application/json", data = "<query>")]
 fn since_last_billing(
-    state: State<ServerState>,
+    state: State<SharedState>,
     query: Json<BillingQuery>,
-) -> Result<Option<Json<Blockchain>>, BlockchainError> {
+) -> Result<Option<Json<Value>>, BlockchainError> {
     state.last_billing(&query.0).map(|opt| opt.map(Json))
 }
 
+#[post("/inclusion_proof", format = "application/json", data = "<transaction>")]
+fn inclusion_proof(
+    state: State<SharedState>,
+    transaction: Json<BlockData>,
+) -> Result<Option<Json<Value>>, BlockchainError> {
+    state.inclusion_proof(&transaction.0).map(|opt| opt.map(Json))
+}
+
 pub fn prepare_server(
-    state: ServerState,
+    state: SharedState,
     address: &str,
     port: u16,
 ) -> Result<::rocket::Rocket, Error> {
@@ -74,7 +234,25 @@ pub fn prepare_server(
     Ok(::rocket::custom(config, true)
         .mount(
             "/",
-            routes![index, latest_block, append, since_last_billing],
+            routes![
+                index,
+                latest_block,
+                latest_header,
+                headers,
+                body,
+                block_by_height,
+                block_by_hash,
+                height_of,
+                difficulty,
+                target,
+                version,
+                versions,
+                spec,
+                hash,
+                append,
+                since_last_billing,
+                inclusion_proof
+            ],
         )
         .manage(state))
 }
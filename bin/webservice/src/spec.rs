@@ -0,0 +1,22 @@
+//! Loading of the chain spec from the configuration. The spec file contains the JSON encoded
+//! [`ChainSpec`](../../data/type.ChainSpec.html), e.g. as returned by the `/spec` route of another
+//! instance of the web service.
+
+use std::fs::File;
+use std::path::Path;
+
+use failure::Error;
+
+use data::ChainSpec;
+use data::hash::HashFunction;
+
+/// Reads the chain spec from the given file. The genesis block is decoded using the hash function
+/// `H`.
+pub fn load_spec<H, P>(path: P) -> Result<ChainSpec<H>, Error>
+where
+    H: HashFunction,
+    P: AsRef<Path>,
+{
+    let file = File::open(path)?;
+    Ok(::serde_json::from_reader(file)?)
+}
@@ -1,42 +1,115 @@
+use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
-use std::sync::RwLock;
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+use serde_json::Value;
 
 use error::BlockchainError;
-use data::{Block, Blockchain};
-use data::tx::Data;
+use data::{Blockchain, ChainSpec, ChainStore};
+use data::checkpoint::Checkpoints;
+use data::clock::Clock;
+use data::hash::{HashAlgorithm, HashFunction};
+use data::target::Target;
+use data::tx::{BlockData, Data};
+use data::version::VersionSchedule;
 use wrapper::WrappedChain;
-use cryptography::{validate_signature, BillingQuery};
+use cryptography::{validate_versioned_signature, BillingQuery};
+
+/// Operations of the web service on a chain. The hash algorithm of the chain is selected at
+/// runtime, so blocks, headers and chains are passed as JSON values, that are encoded and decoded
+/// using the hash function of the chain.
+pub trait ChainService {
+    fn hash_algorithm(&self) -> HashAlgorithm;
+
+    fn last_billing(&self, query: &BillingQuery) -> Result<Option<Value>, BlockchainError>;
+
+    fn append(&self, block: Value) -> Result<(), BlockchainError>;
+
+    fn spec(&self) -> Result<Value, BlockchainError>;
+
+    fn latest_block(&self) -> Result<Value, BlockchainError>;
+
+    fn latest_header(&self) -> Result<Value, BlockchainError>;
+
+    fn headers(&self, start: usize) -> Result<Value, BlockchainError>;
+
+    fn body(&self, height: usize) -> Result<Option<Vec<BlockData>>, BlockchainError>;
+
+    fn block_by_height(&self, height: usize) -> Result<Option<Value>, BlockchainError>;
+
+    fn block_by_hash(&self, hash: &[u8]) -> Result<Option<Value>, BlockchainError>;
+
+    fn height_of(&self, hash: &[u8]) -> Result<Option<usize>, BlockchainError>;
 
-pub struct ServerState {
-    chain: RwLock<WrappedChain>,
-    path: String,
+    fn required_difficulty(&self) -> Result<usize, BlockchainError>;
+
+    fn required_target(&self) -> Result<Target, BlockchainError>;
+
+    fn next_version(&self) -> Result<u8, BlockchainError>;
+
+    fn versions(&self) -> Result<VersionSchedule, BlockchainError>;
+
+    fn inclusion_proof(&self, transaction: &BlockData) -> Result<Option<Value>, BlockchainError>;
+}
+
+/// The state managed by rocket.
+pub type SharedState = Box<ChainService + Send + Sync>;
+
+pub struct ServerState<H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    chain: RwLock<WrappedChain<H>>,
 }
 
-impl ServerState {
-    pub fn new(chain: Blockchain, path: String) -> Self {
+impl<H> ServerState<H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    pub fn new(
+        chain: Blockchain<H>,
+        store: ChainStore<H>,
+        spec: ChainSpec<H>,
+        checkpoints: Checkpoints,
+        clock: Arc<Clock>,
+    ) -> Self {
         Self {
-            chain: RwLock::new(WrappedChain::new(chain)),
-            path: path,
+            chain: RwLock::new(WrappedChain::new(chain, spec, checkpoints, store, clock)),
         }
     }
+}
+
+impl<H> ChainService for ServerState<H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        H::ALGORITHM
+    }
 
-    pub fn last_billing(
-        &self,
-        query: &BillingQuery,
-    ) -> Result<Option<Blockchain>, BlockchainError> {
+    fn last_billing(&self, query: &BillingQuery) -> Result<Option<Value>, BlockchainError> {
         if let Ok(chain) = self.chain.read() {
             let chain = chain.deref();
-            let mut cloned = Vec::new();
-            for blk in chain.iter() {
-                cloned.push(blk.clone());
-                let blockdata = blk.data();
-                if match *blockdata.data() {
+            let chain_id = chain.spec().chain_id();
+            let mut start = 0;
+            for (height, blk) in chain.range(0).enumerate().rev() {
+                if blk.transactions().iter().any(|blockdata| match *blockdata.data() {
                     Data::Billing(ref fp) => {
                         fp == query.user()
-                            && validate_signature(query.signee(), blockdata).unwrap_or(false)
+                            && validate_versioned_signature(
+                                query.signee(),
+                                chain_id,
+                                blk.version(),
+                                blockdata,
+                            ).unwrap_or(false)
                     }
                     _ => false,
-                } {
+                }) {
+                    start = height;
                     break;
                 }
 
@@ -45,35 +118,144 @@ impl ServerState {
                     return Ok(None);
                 }
             }
-            cloned.reverse();
-            Ok(cloned
-                .into_iter()
-                .fold(Ok(Blockchain::new()), |acc, blk| {
-                    acc.and_then(|chain| chain.insert(blk))
-                })
-                .ok())
+            // the blocks since the billing operation have already been validated, including blocks
+            // of older versions
+            to_json(&chain.since(start)).map(Some)
         } else {
             Err(BlockchainError::CannotGetLock)
         }
     }
 
-    pub fn append(&self, block: Block, path: &str) -> Result<(), BlockchainError> {
+    fn append(&self, block: Value) -> Result<(), BlockchainError> {
+        let block = ::serde_json::from_value(block).map_err(|_| BlockchainError::InvalidBlock)?;
         if let Ok(mut chain) = self.chain.write() {
-            chain.deref_mut().append(block, path)
+            chain.deref_mut().append(block)
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn spec(&self) -> Result<Value, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            to_json(chain.spec())
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn latest_block(&self) -> Result<Value, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            chain
+                .latest_block()
+                .ok_or(BlockchainError::EmptyChain)
+                .and_then(|block| to_json(&block))
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn latest_header(&self) -> Result<Value, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            chain
+                .latest_header()
+                .ok_or(BlockchainError::EmptyChain)
+                .and_then(|header| to_json(&header))
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn headers(&self, start: usize) -> Result<Value, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            to_json(&chain.headers(start))
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn body(&self, height: usize) -> Result<Option<Vec<BlockData>>, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            Ok(chain.body(height))
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn block_by_height(&self, height: usize) -> Result<Option<Value>, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            chain
+                .block_by_height(height)
+                .map(|block| to_json(&block))
+                .map_or(Ok(None), |json| json.map(Some))
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn block_by_hash(&self, hash: &[u8]) -> Result<Option<Value>, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            chain
+                .block_by_hash(hash)
+                .map(|block| to_json(&block))
+                .map_or(Ok(None), |json| json.map(Some))
         } else {
             Err(BlockchainError::CannotGetLock)
         }
     }
 
-    pub fn latest_block(&self) -> Result<Block, BlockchainError> {
+    fn height_of(&self, hash: &[u8]) -> Result<Option<usize>, BlockchainError> {
         if let Ok(chain) = self.chain.read() {
-            chain.latest_block().ok_or(BlockchainError::EmptyChain)
+            Ok(chain.height_of(hash))
         } else {
             Err(BlockchainError::CannotGetLock)
         }
     }
 
-    pub fn path(&self) -> &str {
-        &self.path
+    fn required_difficulty(&self) -> Result<usize, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            Ok(chain.required_difficulty())
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
     }
+
+    fn required_target(&self) -> Result<Target, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            Ok(chain.required_target())
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn next_version(&self) -> Result<u8, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            Ok(chain.next_version())
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn versions(&self) -> Result<VersionSchedule, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            Ok(chain.versions())
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+
+    fn inclusion_proof(&self, transaction: &BlockData) -> Result<Option<Value>, BlockchainError> {
+        if let Ok(chain) = self.chain.read() {
+            chain
+                .inclusion_proof(transaction)
+                .map(|proof| to_json(&proof))
+                .map_or(Ok(None), |json| json.map(Some))
+        } else {
+            Err(BlockchainError::CannotGetLock)
+        }
+    }
+}
+
+/// Encodes a response as JSON value.
+fn to_json<T: Serialize>(value: &T) -> Result<Value, BlockchainError> {
+    ::serde_json::to_value(value).map_err(|_| BlockchainError::Serializing)
 }
@@ -1,38 +1,214 @@
 //! Due to the way, server state is handled by rocket, we need a wrapper class around the
 //! functional implementation of the blockchain and work with impure functions.
 
-use data::{BcIter, Block, Blockchain};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use data::{BcRange, Block, Blockchain, ChainSpec, ChainStore, Header, InclusionProof};
+use data::checkpoint::Checkpoints;
+use data::clock::Clock;
+use data::consensus::ConsensusRules;
+use data::hash::HashFunction;
+use data::target::Target;
+use data::tx::BlockData;
+use data::version::VersionSchedule;
 
 use error::BlockchainError;
 
-/// Impure wrapper for the blockchain.
-pub struct WrappedChain {
-    chain: Blockchain,
+/// Impure wrapper for the blockchain, whose blocks are hashed using `H`.
+pub struct WrappedChain<H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    chain: Blockchain<H>,
+    spec: ChainSpec<H>,
+    checkpoints: Checkpoints,
+    store: ChainStore<H>,
 }
 
-impl WrappedChain {
-    /// Wraps a blockchain.
-    pub fn new(chain: Blockchain) -> Self {
-        Self { chain: chain }
+impl<H> WrappedChain<H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    /// Wraps a blockchain, that has been loaded from the store. Blocks are appended according to
+    /// the consensus rules of the spec and the supplied checkpoints and persisted in the store.
+    /// The time of new blocks is limited using the supplied clock.
+    pub fn new(
+        chain: Blockchain<H>,
+        spec: ChainSpec<H>,
+        checkpoints: Checkpoints,
+        store: ChainStore<H>,
+        clock: Arc<Clock>,
+    ) -> Self {
+        let rules = spec.rules().clone().clock(clock);
+        let spec = spec.with_rules(rules);
+        Self {
+            chain: chain,
+            spec: spec,
+            checkpoints: checkpoints,
+            store: store,
+        }
     }
 
-    /// Append a new block to the chain by modifying the struct (impure).
-    pub fn append(&mut self, block: Block, path: &str) -> Result<(), BlockchainError> {
-        if let Ok(new) = self.chain.insert(block) {
-            self.chain = new;
-            self.chain.persist_to_disk(path).ok();
-            Ok(())
-        } else {
-            Err(BlockchainError::InvalidBlock)
+    /// Append a new block to the chain by modifying the struct (impure). The block is persisted
+    /// before the chain is modified, so an accepted block is never lost.
+    pub fn append(&mut self, block: Block<H>) -> Result<(), BlockchainError> {
+        let new = self.chain
+            .insert_with_checkpoints(block, self.spec.rules(), &self.checkpoints)
+            .map_err(|err| {
+                info!("Rejected block: {}", err);
+                BlockchainError::RejectedBlock(err)
+            })?;
+        if let Some(head) = new.iter().next() {
+            self.store.append(head).map_err(|err| {
+                error!("Cannot persist block: {}", err);
+                BlockchainError::Persisting
+            })?;
         }
+        self.chain = new;
+        Ok(())
+    }
+
+    /// Returns the spec of the chain.
+    pub fn spec(&self) -> &ChainSpec<H> {
+        &self.spec
     }
 
     /// Returns a copy of the latest block.
-    pub fn latest_block(&self) -> Option<Block> {
+    pub fn latest_block(&self) -> Option<Block<H>> {
         self.chain.tail().0.cloned()
     }
 
-    pub fn iter(&self) -> BcIter {
-        self.chain.iter()
+    /// Returns a copy of the latest header.
+    pub fn latest_header(&self) -> Option<Header<H>> {
+        self.chain.headers().next().cloned()
+    }
+
+    /// Returns copies of the headers from height `start` up to the latest header, oldest first.
+    pub fn headers(&self, start: usize) -> Vec<Header<H>> {
+        self.chain
+            .range(start, self.chain.len())
+            .map(|blk| blk.header().clone())
+            .collect()
+    }
+
+    /// Returns a copy of the transactions of the block at the given height.
+    pub fn body(&self, height: usize) -> Option<Vec<BlockData>> {
+        self.chain
+            .get_by_height(height)
+            .map(|blk| blk.transactions().to_vec())
+    }
+
+    /// Returns a copy of the block at the given height.
+    pub fn block_by_height(&self, height: usize) -> Option<Block<H>> {
+        self.chain.get_by_height(height).cloned()
+    }
+
+    /// Returns a copy of the block with the given hash.
+    pub fn block_by_hash(&self, hash: &[u8]) -> Option<Block<H>> {
+        self.chain.get_by_hash(hash).cloned()
+    }
+
+    /// Returns the height of the block with the given hash.
+    pub fn height_of(&self, hash: &[u8]) -> Option<usize> {
+        self.chain.height_of(hash)
+    }
+
+    /// Returns the difficulty required for the next block.
+    pub fn required_difficulty(&self) -> usize {
+        self.spec.rules().required_difficulty(self.chain.headers())
+    }
+
+    /// Returns the highest target of the next block.
+    pub fn required_target(&self) -> Target {
+        self.spec.rules().required_target(self.chain.headers())
+    }
+
+    /// Returns the version required for the next block.
+    pub fn next_version(&self) -> u8 {
+        self.spec.rules().schedule().version_at(self.chain.len())
+    }
+
+    /// Returns the block versions accepted by the consensus rules and their activation heights.
+    pub fn versions(&self) -> VersionSchedule {
+        self.spec.rules().schedule().clone()
+    }
+
+    /// Returns a proof, that the transaction is part of the chain, relative to the current head.
+    pub fn inclusion_proof(&self, transaction: &BlockData) -> Option<InclusionProof<H>> {
+        self.chain.prove_inclusion(|tx| {
+            tx.signature()[..] == transaction.signature()[..] && tx.data() == transaction.data()
+        })
+    }
+
+    /// Returns an iterator over the blocks from height `start` up to the latest block, oldest
+    /// first.
+    pub fn range(&self, start: usize) -> BcRange<H> {
+        self.chain.range(start, self.chain.len())
+    }
+
+    /// Returns a copy of the chain from height `start` up to the latest block.
+    pub fn since(&self, start: usize) -> Blockchain<H> {
+        self.chain.slice(start, self.chain.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use data::clock::ManualClock;
+    use tempdir::TempDir;
+
+    /// Duration of a month of 30 days in seconds.
+    const MONTH: u64 = 30 * 24 * 60 * 60;
+
+    fn open(dir: &Path, clock: &ManualClock) -> WrappedChain<::data::Sha256> {
+        let spec = ::data::spec();
+        let mut store = ChainStore::open(dir.join("blockchain.dat")).unwrap();
+        store.init_spec(&spec).unwrap();
+        let chain = store
+            .load_with_spec(&spec, &Checkpoints::default())
+            .unwrap();
+        let clock = Arc::new(clock.clone());
+        WrappedChain::new(chain, spec, Checkpoints::default(), store, clock)
+    }
+
+    /// Mines the next block at the time of the clock.
+    fn next_block(chain: &WrappedChain<::data::Sha256>, clock: &ManualClock) -> Block {
+        let prev_hash = chain.latest_block().unwrap().hash();
+        Block::new_with_clock(
+            vec![Default::default()],
+            prev_hash,
+            chain.required_difficulty(),
+            clock,
+        ).set_version(chain.next_version())
+            .set_target(chain.required_target())
+            .proof_of_work_with_clock(clock)
+    }
+
+    #[test]
+    fn blocks_are_appended_over_months() {
+        let dir = TempDir::new("webservice").unwrap();
+        let clock = ManualClock::new(::data::GENESIS_TIME);
+        let mut chain = open(dir.path(), &clock);
+        for _ in 0..12 {
+            clock.advance(MONTH);
+            let block = next_block(&chain, &clock);
+            assert!(chain.append(block).is_ok());
+        }
+        let latest = chain.latest_block().unwrap();
+        assert_eq!(latest.time(), ::data::GENESIS_TIME + 12 * MONTH);
+        // a block from the next month is rejected until the month has come
+        let early = next_block(&chain, &ManualClock::new(clock.now() + MONTH));
+        assert!(chain.append(early.clone()).is_err());
+        clock.advance(MONTH);
+        assert!(chain.append(early).is_ok());
+        drop(chain);
+        let reopened = open(dir.path(), &clock);
+        assert_eq!(reopened.range(0).count(), 14);
     }
 }
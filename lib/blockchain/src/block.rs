@@ -1,36 +1,51 @@
 //! This module contains the definition of a generic block.
 
-use std::time::{SystemTime, UNIX_EPOCH};
-
 use generic_array::GenericArray;
-use generic_array::typenum::Unsigned;
 
-/// Version number
-pub const VERSION: u8 = 1;
+use serde::Serialize;
+
+use canonical::Canonical;
+use clock::{Clock, SystemClock};
+use header::Header;
+use pow::PowFunction;
+use seal::ProofOfWork;
+use target::Target;
+use version::{encode, Hashable, LEGACY_VERSION};
+
+use super::{BlockchainError, HashBytes};
 
-/// A block that contains a version number, hash of the previous block, the time it was created,
-/// the difficulty factor, the `PoW` nonce and generic data.
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Block<D, H>
+/// Version number. Version `3` hashes headers and transactions using their canonical encoding
+/// (see the [`canonical`](../canonical/index.html) module) and stores the difficulty as compact
+/// target (see the [`target`](../target/index.html) module).
+pub const VERSION: u8 = ::version::TARGET_VERSION;
+
+/// A block that consists of a [`Header`](../header/struct.Header.html) and a body of generic
+/// transactions. The header contains a version number, hash of the previous block, the Merkle root
+/// of the transactions, the time it was created, the difficulty factor, the `PoW` nonce and a
+/// generic seal.
+///
+/// The hash of a block is the hash of its header. The transactions are committed to through the
+/// Merkle root (see the [`merkle`](../merkle/index.html) module).
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "D: Serialize, S: Serialize",
+              deserialize = "D: ::serde::Deserialize<'de>, S: ::serde::Deserialize<'de> + Default"))]
+pub struct Block<D, H, S = ()>
 where
     H: ::digest::Digest,
 {
-    version: u8,
-    prev_hash: GenericArray<u8, H::OutputSize>,
-    time: u64,
-    difficulty: usize,
-    nonce: u64,
-    data: D,
+    header: Header<H, S>,
+    transactions: Vec<D>,
 }
 
-impl<D, H> Block<D, H>
+impl<D, H, S> Block<D, H, S>
 where
-    D: Default,
+    D: Canonical,
     H: ::digest::Digest,
+    S: Default,
 {
-    /// Creates a new block with the given data and difficulty. The nonce is initialized to `0` and
-    /// an empty `prev_hash` is set so the block is in an invalid state. The time field is
-    /// initialized with the current Unix timestamp.
+    /// Creates a new block with the given transactions and difficulty. The nonce is initialized to
+    /// `0` and an empty `prev_hash` is set so the block is in an invalid state. The time field is
+    /// initialized with the current system time.
     ///
     /// # Examples
     /// ```
@@ -38,23 +53,19 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::Block;
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(5, 1);
-    /// assert_eq!(block.data(), &5);
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![5], 1);
+    /// assert_eq!(block.transactions(), &[5]);
     /// assert_eq!(block.difficulty(), 1);
     /// # }
     /// ```
-    pub fn new(data: D, difficulty: usize) -> Self {
-        Self::assert_difficulty(difficulty);
-        Self {
-            data: data,
-            difficulty: difficulty,
-            ..Default::default()
-        }
+    pub fn new(transactions: Vec<D>, difficulty: usize) -> Self {
+        Self::new_with_hash(transactions, Default::default(), difficulty)
     }
 
-    /// Creates a new block with the given data, `prev_hash` and difficulty. The nonce is
+    /// Creates a new block with the given transactions, `prev_hash` and difficulty. The nonce is
     /// initialized to `0`, so the block might be in an invalid state, since its own hash doesn't
-    /// match the difficulty. The time field is initialized with the current Unix timestamp.
+    /// match the difficulty. The time field is initialized with the current system time, see
+    /// [`new_with_clock`](#method.new_with_clock) to use another clock.
     ///
     /// # Examples
     /// ```
@@ -65,51 +76,117 @@ where
     /// # fn main() {
     /// use generic_array::GenericArray;
     /// use blockchain::block::Block;
-    /// let mut hash = Block::<_, ::sha2::Sha256>::new(5, 1).hash();
+    /// let mut hash = Block::<_, ::sha2::Sha256>::new(vec![5], 1).hash();
     /// hash[0] = 0xff;
-    /// let block: Block<_, ::sha2::Sha256> = Block::new_with_hash(5, hash, 1);
-    /// assert_eq!(block.data(), &5);
+    /// let block: Block<_, ::sha2::Sha256> = Block::new_with_hash(vec![5], hash, 1);
+    /// assert_eq!(block.transactions(), &[5]);
     /// assert_eq!(block.difficulty(), 1);
     /// assert_eq!(block.prev_hash(), &hash);
     /// # }
     /// ```
     pub fn new_with_hash(
-        data: D,
+        transactions: Vec<D>,
         prev_hash: GenericArray<u8, H::OutputSize>,
         difficulty: usize,
     ) -> Self {
-        Self::assert_difficulty(difficulty);
+        Self::new_with_clock(transactions, prev_hash, difficulty, &SystemClock)
+    }
+
+    /// Creates a new block like [`new_with_hash`](#method.new_with_hash), but initializes the
+    /// time field using the given clock.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::clock::FixedClock;
+    /// let block: Block<_, ::sha2::Sha256> =
+    ///     Block::new_with_clock(vec![5], Default::default(), 1, &FixedClock(1337));
+    /// assert_eq!(block.time(), 1337);
+    /// # }
+    /// ```
+    pub fn new_with_clock<C>(
+        transactions: Vec<D>,
+        prev_hash: GenericArray<u8, H::OutputSize>,
+        difficulty: usize,
+        clock: &C,
+    ) -> Self
+    where
+        C: Clock + ?Sized,
+    {
         Self {
-            data: data,
-            prev_hash: prev_hash,
-            difficulty: difficulty,
-            ..Default::default()
+            header: Header::new(
+                prev_hash,
+                ::merkle::root::<H, D>(&transactions),
+                difficulty,
+                clock.now(),
+            ),
+            transactions: transactions,
         }
     }
 
     /// Returns the length of a hash in bytes.
     pub fn hash_length_byte() -> usize {
-        H::OutputSize::to_usize()
+        Header::<H, S>::hash_length_byte()
     }
 
     /// Returns the length of a hash in bits.
     pub fn hash_length_bit() -> usize {
-        Self::hash_length_byte() * 8
+        Header::<H, S>::hash_length_bit()
     }
+}
 
-    #[inline]
-    fn assert_difficulty(difficulty: usize) {
-        assert!(
-            Self::hash_length_bit() >= difficulty,
-            "Difficulty cannot be larger than the hash length"
-        );
+impl<D, H, S> Block<D, H, S>
+where
+    D: Hashable,
+    H: ::digest::Digest,
+{
+    /// Assembles a block from a header and a body, e.g. after fetching the body of a known
+    /// header. Returns an error if the transactions do not match the Merkle root of the header.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![1, 2], 1);
+    /// let header = block.header().clone();
+    /// assert!(Block::from_parts(header.clone(), vec![1, 2]).is_ok());
+    /// assert!(Block::from_parts(header, vec![2, 1]).is_err());
+    /// # }
+    /// ```
+    pub fn from_parts(header: Header<H, S>, transactions: Vec<D>) -> Result<Self, BlockchainError> {
+        let root = ::merkle::versioned_root::<H, D>(header.version(), &transactions);
+        if root == *header.merkle_root() {
+            Ok(Self {
+                header: header,
+                transactions: transactions,
+            })
+        } else {
+            Err(BlockchainError::InvalidMerkleRoot(HashBytes::from(
+                &header.merkle_root()[..],
+            )))
+        }
     }
 }
 
-impl<D, H> Block<D, H>
+impl<D, H, S> Block<D, H, S>
 where
     H: ::digest::Digest,
 {
+    /// Returns the header of the block.
+    pub fn header(&self) -> &Header<H, S> {
+        &self.header
+    }
+
+    /// Splits the block into its header and its transactions.
+    pub fn into_parts(self) -> (Header<H, S>, Vec<D>) {
+        (self.header, self.transactions)
+    }
+
     /// Returns the block version.
     ///
     /// # Examples
@@ -118,12 +195,12 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::{Block, VERSION};
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(42, 1);
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
     /// assert_eq!(block.version(), VERSION);
     /// # }
     /// ```
     pub fn version(&self) -> u8 {
-        self.version
+        self.header.version()
     }
 
     /// Returns the difficulty of a block.
@@ -134,21 +211,27 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::Block;
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(42, 1);
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
     /// assert_eq!(block.difficulty(), 1);
     /// # }
     /// ```
     pub fn difficulty(&self) -> usize {
-        self.difficulty
+        self.header.difficulty()
+    }
+
+    /// Returns the difficulty target of a block (see
+    /// [`Header::target`](../header/struct.Header.html#method.target)).
+    pub fn target(&self) -> Result<Target, BlockchainError> {
+        self.header.target()
     }
 
     /// Returns the time, a block was created or last modified as a Unix timestamp. The time is
     /// updated every time a value in a block is changed.
     pub fn time(&self) -> u64 {
-        self.time
+        self.header.time()
     }
 
-    /// Returns a reference to the data inside a block.
+    /// Returns the transactions inside a block.
     ///
     /// # Examples
     /// ```
@@ -156,12 +239,27 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::Block;
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(42, 1);
-    /// assert_eq!(block.data(), &42);
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42, 43], 1);
+    /// assert_eq!(block.transactions(), &[42, 43]);
     /// # }
     /// ```
-    pub fn data(&self) -> &D {
-        &self.data
+    pub fn transactions(&self) -> &[D] {
+        &self.transactions
+    }
+
+    #[cfg(test)]
+    /// Replaces the transactions of the block without updating the Merkle root in its header.
+    /// (Only used for tests)
+    pub(crate) fn replace_transactions(self, transactions: Vec<D>) -> Self {
+        Self {
+            header: self.header,
+            transactions: transactions,
+        }
+    }
+
+    /// Returns the Merkle root of the transactions, that is stored in the block header.
+    pub fn merkle_root(&self) -> &GenericArray<u8, H::OutputSize> {
+        self.header.merkle_root()
     }
 
     /// Returns the hash of the previous block.
@@ -172,18 +270,45 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::Block;
-    /// let block0: Block<_, ::sha2::Sha256> = Block::new(42, 1);
-    /// let block1: Block<_, ::sha2::Sha256> = Block::new_with_hash(43, block0.hash(), 1);
+    /// let block0: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
+    /// let block1: Block<_, ::sha2::Sha256> = Block::new_with_hash(vec![43], block0.hash(), 1);
     /// assert_eq!(&block0.hash(), block1.prev_hash());
     /// # }
     /// ```
     pub fn prev_hash(&self) -> &GenericArray<u8, H::OutputSize> {
-        &self.prev_hash
+        self.header.prev_hash()
     }
 
-    /// Returns true if the block is a genesis block, e.g. its `prev_hash` is all 0x00.
+    /// Returns true if the block is a genesis block, e.g. its `prev_hash` is all 0x00. Any such
+    /// block looks like a genesis block, use
+    /// [`ChainSpec::check_genesis`](../spec/struct.ChainSpec.html#method.check_genesis) to check if
+    /// it is the genesis block of a certain chain.
     pub fn is_genesis(&self) -> bool {
-        self.prev_hash().iter().all(|b| *b == 0)
+        self.header.is_genesis()
+    }
+
+    /// Returns a reference to the seal of a block.
+    pub fn seal(&self) -> &S {
+        self.header.seal()
+    }
+
+    /// Replaces the seal of a block. The method returns a new block and consumes the old one.
+    pub fn set_seal(self, seal: S) -> Self {
+        Self {
+            header: self.header.set_seal(seal),
+            ..self
+        }
+    }
+
+    /// Sets the skip hash of a block (see
+    /// [`Header::set_skip_hash`](../header/struct.Header.html#method.set_skip_hash)), e.g. to
+    /// [`Blockchain::next_skip_hash`](../blockchain/struct.Blockchain.html#method.next_skip_hash).
+    /// The method returns a new block and consumes the old one.
+    pub fn set_skip_hash(self, skip_hash: GenericArray<u8, H::OutputSize>) -> Self {
+        Self {
+            header: self.header.set_skip_hash(skip_hash),
+            ..self
+        }
     }
 
     /// Returns the nonce.
@@ -194,12 +319,12 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::Block;
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(42, 1);
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
     /// assert_eq!(block.nonce(), 0);
     /// # }
     /// ```
     pub fn nonce(&self) -> u64 {
-        self.nonce
+        self.header.nonce()
     }
 
     /// Sets the nonce to an arbitrary value and sets the the `time` attribute. The method returns
@@ -210,22 +335,23 @@ where
     /// extern crate sha2;
     /// # extern crate blockchain;
     /// # fn main() {
-    /// use blockchain::block::{Block, current_time};
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(42, 1);
+    /// use blockchain::block::Block;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
     /// let block = block.set_nonce(1337, 0);
     /// assert_eq!(block.nonce(), 1337);
     /// # }
     /// ```
     pub fn set_nonce(self, nonce: u64, time: u64) -> Self {
         Self {
-            nonce: nonce,
-            time: time,
+            header: self.header.set_nonce(nonce, time),
             ..self
         }
     }
 
-    /// Increments the nonce by 1 and updates the `time` attribute. The method returns a new block
-    /// and consumes the old one. If the nonce overflows, it will start again at 0.
+    /// Sets the difficulty target (see
+    /// [`Header::set_target`](../header/struct.Header.html#method.set_target)). This allows
+    /// difficulties between two numbers of leading zero bits. Targets, that cannot be encoded
+    /// exactly, are rounded down. The method returns a new block and consumes the old one.
     ///
     /// # Examples
     /// ```
@@ -233,95 +359,266 @@ where
     /// # extern crate blockchain;
     /// # fn main() {
     /// use blockchain::block::Block;
-    /// let block: Block<_, ::sha2::Sha256> = Block::new(42, 1);
+    /// use blockchain::target::Target;
+    /// use blockchain::work::Work;
+    /// let target = Target::from_work(&Work::from(3));
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 0).set_target(target);
+    /// assert!(block.target().unwrap() <= target);
+    /// assert_eq!(block.difficulty(), 1);
+    /// assert!(block.proof_of_work().validate_difficulty());
+    /// # }
+    /// ```
+    pub fn set_target(self, target: Target) -> Self {
+        Self {
+            header: self.header.set_target(target),
+            ..self
+        }
+    }
+
+    /// Increments the nonce by 1. The `time` attribute is not changed. The method returns a new
+    /// block and consumes the old one. If the nonce overflows, it will start again at 0.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1).set_nonce(0, 1337);
     /// assert_eq!(block.nonce(), 0);
-    /// let block = block.increment_nonce(0);
-    /// assert_eq!(block.nonce(), 1);
+    /// let block = block.increment_nonce();
+    /// assert_eq!((block.nonce(), block.time()), (1, 1337));
     /// let block = block.set_nonce(u64::max_value(), 0);
-    /// let block = block.increment_nonce(0);
+    /// let block = block.increment_nonce();
     /// assert_eq!(block.nonce(), 0);
     /// # }
-    pub fn increment_nonce(self, time: u64) -> Self {
-        let nonce = self.nonce;
+    pub fn increment_nonce(self) -> Self {
+        let (nonce, time) = (self.nonce(), self.time());
         self.set_nonce(nonce.wrapping_add(1), time)
     }
 }
 
-impl<D, H> Block<D, H>
+impl<D, H, S> Block<D, H, S>
 where
-    D: ::serde::Serialize,
+    D: Serialize,
     H: ::digest::Digest,
+    S: Serialize,
 {
-    /// Serializes the block into a vec of bytes.
+    /// Serializes the block into a vec of bytes. This is the format used for persisting, hashes
+    /// are calculated over the canonical encoding instead.
     pub fn as_bytes(&self) -> Vec<u8> {
         ::bincode::serialize(self, ::bincode::Infinite).unwrap()
     }
+}
+
+impl<D, H, S> Block<D, H, S>
+where
+    D: Hashable,
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    /// Sets the version of the block and recalculates the Merkle root, which depends on the
+    /// version (see the [`version`](../version/index.html) module). This is needed to create
+    /// blocks for heights, at which another version than `VERSION` is active. The method returns a
+    /// new block and consumes the old one.
+    ///
+    /// Blocks of version `1` (`version::LEGACY_VERSION`) contain exactly one transaction, whose
+    /// serialization is stored in the header (see [`Header`](../header/struct.Header.html)).
+    /// Blocks of version `1` with another number of transactions are invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::version::LEGACY_VERSION;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
+    /// let legacy = block.clone().set_version(LEGACY_VERSION);
+    /// assert_eq!(legacy.version(), LEGACY_VERSION);
+    /// assert!(legacy.validate_merkle_root());
+    /// assert!(legacy.merkle_root() != block.merkle_root());
+    /// let batch: Block<_, ::sha2::Sha256> = Block::new(vec![42, 43], 1);
+    /// assert!(!batch.set_version(LEGACY_VERSION).validate_merkle_root());
+    /// # }
+    /// ```
+    pub fn set_version(self, version: u8) -> Self {
+        let merkle_root = ::merkle::versioned_root::<H, D>(version, &self.transactions);
+        let header = self.header.set_version(version, merkle_root);
+        let header = match (version, self.transactions.len()) {
+            (LEGACY_VERSION, 1) => header.set_legacy_data(encode(version, &self.transactions[0])),
+            (LEGACY_VERSION, _) => header.set_legacy_data(Vec::new()),
+            _ => header,
+        };
+        Self {
+            header: header,
+            ..self
+        }
+    }
 
-    /// Calculates the hash value of a block.
+    /// Calculates the hash value of a block. This is the hash of the block header, which includes
+    /// the Merkle root of the transactions and the seal.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1).set_nonce(0, 0);
+    /// let other: Block<_, ::sha2::Sha256> = Block::new(vec![43], 1).set_nonce(0, 0);
+    /// assert!(block.hash() != other.hash());
+    /// assert_eq!(block.hash(), block.header().hash());
+    /// # }
+    /// ```
     pub fn hash(&self) -> GenericArray<u8, H::OutputSize> {
-        H::digest(&self.as_bytes())
+        self.header.hash()
+    }
+
+    /// Calculates the hash value of a block without its seal. This is the hash a sealing scheme
+    /// like proof of authority signs (see
+    /// [`Header::seal_hash`](../header/struct.Header.html#method.seal_hash)).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 1);
+    /// assert!(block.seal_hash() != block.hash());
+    /// let sealed: Block<_, ::sha2::Sha256, u8> = Block::new(vec![42], 1).set_seal(7);
+    /// assert_eq!(sealed.seal_hash(), sealed.clone().set_seal(0).seal_hash());
+    /// assert!(sealed.seal_hash() != sealed.hash());
+    /// # }
+    /// ```
+    pub fn seal_hash(&self) -> GenericArray<u8, H::OutputSize> {
+        self.header.seal_hash()
+    }
+
+    /// Validates if the Merkle root in the block header matches the transactions of the block.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![1, 2, 3], 1);
+    /// assert!(block.validate_merkle_root());
+    /// # }
+    /// ```
+    pub fn validate_merkle_root(&self) -> bool {
+        ::merkle::versioned_root::<H, D>(self.version(), &self.transactions)
+            == *self.header.merkle_root()
     }
 
-    /// Validates if the block matches its own difficulty factor.
+    /// Validates if the block matches its own difficulty factor and if the Merkle root matches the
+    /// transactions.
     pub fn validate_difficulty(&self) -> bool {
-        self.hash()
-            .iter()
-            .take((self.difficulty / 8) + 1)
-            .fold((self.difficulty, true), |(d, b), byte| {
-                let leading_zeros = byte.leading_zeros();
-                if d >= 8 {
-                    (d - 8, b && leading_zeros == 8)
-                } else {
-                    (d, b && leading_zeros >= d as u32)
-                }
-            })
-            .1
+        self.validate_merkle_root() && self.header.validate_difficulty()
     }
 
-    /// Performs proof of work by incrementing the nonce by `1`, if the difficulty factor does not
-    /// match. If the difficulty already matches, the block is returned without modification.
+    /// Performs proof of work by incrementing the nonce by `1` and updating the time, until the
+    /// difficulty factor matches. If the difficulty already matches, the block is returned without
+    /// modification.
+    /// This uses the current thread only, see [`Miner`](../miner/struct.Miner.html) for a
+    /// multi-threaded and cancellable alternative.
     pub fn proof_of_work(self) -> Self {
-        if self.validate_difficulty() {
-            self
-        } else {
-            self.increment_nonce(current_time()).proof_of_work()
-        }
+        self.proof_of_work_with_clock(&SystemClock)
     }
-}
 
-/// Returns the time in seconds since `1970-01-01`.
-pub fn current_time() -> u64 {
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()   // TODO: don't unwrap()
-        .as_secs()
+    /// Performs proof of work like [`proof_of_work`](#method.proof_of_work), but updates the time
+    /// field using the given clock.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::clock::FixedClock;
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 8);
+    /// let block = block.proof_of_work_with_clock(&FixedClock(1337));
+    /// assert!(block.validate_difficulty());
+    /// assert!(block.nonce() == 0 || block.time() == 1337);
+    /// # }
+    /// ```
+    pub fn proof_of_work_with_clock<C>(self, clock: &C) -> Self
+    where
+        C: Clock + ?Sized,
+    {
+        self.proof_of_work_with_pow(&ProofOfWork, clock)
+    }
+
+    /// Performs proof of work like [`proof_of_work_with_clock`](#method.proof_of_work_with_clock),
+    /// but until the given proof of work function matches the difficulty factor, e.g. the
+    /// [`PowAlgorithm`](../pow/enum.PowAlgorithm.html) of a chain's consensus rules.
+    pub fn proof_of_work_with_pow<P, C>(self, pow: &P, clock: &C) -> Self
+    where
+        P: PowFunction<H, S> + ?Sized,
+        C: Clock + ?Sized,
+    {
+        let mut block = self;
+        // the nonce does not affect the Merkle root, so only the header needs to be checked
+        while !pow.meets_difficulty(&block.header) {
+            let nonce = block.nonce().wrapping_add(1);
+            block = block.set_nonce(nonce, clock.now());
+        }
+        block
+    }
 }
 
-impl<D, H> Default for Block<D, H>
+impl<D, H, S> Default for Block<D, H, S>
 where
-    D: Default,
     H: ::digest::Digest,
+    S: Default,
 {
+    /// Creates an empty block with difficulty and time `0` and an empty `prev_hash`.
     fn default() -> Self {
         Self {
-            version: VERSION,
-            prev_hash: Default::default(),
-            difficulty: 0,
-            nonce: 0,
-            time: current_time(),
-            data: Default::default(),
+            header: Header::new(Default::default(), Default::default(), 0, 0),
+            transactions: Vec::new(),
         }
     }
 }
 
-impl<D, H> PartialEq for Block<D, H>
+impl<D, H, S> PartialEq for Block<D, H, S>
 where
     D: PartialEq,
     H: ::digest::Digest,
+    S: PartialEq,
 {
     fn eq(&self, other: &Self) -> bool {
-        self.version == other.version && self.prev_hash == other.prev_hash
-            && self.difficulty == other.difficulty && self.nonce == other.nonce
-            && self.time == other.time && self.data == other.data
+        self.header == other.header && self.transactions == other.transactions
+    }
+}
+
+impl<D, H, S> Clone for Block<D, H, S>
+where
+    D: Clone,
+    H: ::digest::Digest,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            header: self.header.clone(),
+            transactions: self.transactions.clone(),
+        }
+    }
+}
+
+impl<D, H, S> ::std::fmt::Debug for Block<D, H, S>
+where
+    D: ::std::fmt::Debug,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: ::std::fmt::Debug,
+    S: ::std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("Block")
+            .field("header", &self.header)
+            .field("transactions", &self.transactions)
+            .finish()
     }
 }
@@ -3,34 +3,96 @@
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::{BufReader, BufWriter};
+use std::io::BufWriter;
 use std::path::Path;
+use std::sync::Arc;
 
-use super::{BlockchainError, PersistingError};
+use super::{BlockchainError, HashBytes, MiningError, PersistingError};
 
 use serde::ser::{Serialize, Serializer};
 use serde::de::{Deserialize, Deserializer};
 
-use block::{current_time, Block};
+use generic_array::GenericArray;
+
+use block::{Block, VERSION};
+use clock::{Clock, SystemClock};
+use consensus::{ConsensusRules, FixedDifficulty};
+use format::{encode_u32, Checksummed, FileHeader, SNAPSHOT_MAGIC};
+use hash::HashFunction;
+use header::{skip_height, Header};
+use headerchain::validate_header;
+use index::HashIndex;
+use seal::{ProofOfWork, SealingScheme, SharedScheme, WithScheme};
+use miner::{CancellationToken, Miner, Progress};
+use pow::PowAlgorithm;
+use report::Scope;
+use spec::ChainSpec;
 use stack::Stack;
+use stream::BlockReader;
+use version::Hashable;
 
-/// The blockchain itself as a stack of blocks.
-#[derive(Debug, Clone)]
-pub struct Blockchain<D, H>
+/// The blockchain itself as a stack of blocks. The blocks are sealed using proof of work by
+/// default. Chains using another sealing scheme store the scheme's proof in the generic seal `S`.
+/// A chain, that has been created from a [`ChainSpec`](../spec/struct.ChainSpec.html), is bound
+/// to the spec's chain id, genesis block and consensus rules. A chain, that has been configured
+/// using [`with_scheme`](#method.with_scheme), verifies the seals of its blocks using the scheme.
+#[derive(Debug)]
+pub struct Blockchain<D, H, S = ()>
 where
     H: ::digest::Digest,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
-    blocks: Stack<Block<D, H>>,
+    blocks: Stack<Block<D, H, S>>,
+    index: HashIndex,
+    spec: Option<Arc<ChainSpec<D, H, S>>>,
+    scheme: Option<SharedScheme<H, S>>,
 }
 
 /// Iterator over a blockchain. This wraps a stack iterator over generic blocks.
-pub type BlockchainIter<'a, D, H> = ::stack::Iter<'a, Block<D, H>>;
+pub type BlockchainIter<'a, D, H, S = ()> = ::stack::Iter<'a, Block<D, H, S>>;
+
+/// Double-ended iterator over a range of blocks, oldest block first. This wraps a range iterator
+/// over a stack of generic blocks.
+pub type BlockchainRange<'a, D, H, S = ()> = ::stack::Range<'a, Block<D, H, S>>;
+
+/// Iterator over the headers of a blockchain, newest header first. See
+/// [`Blockchain::headers`](struct.Blockchain.html#method.headers).
+pub struct HeaderIter<'a, D, H, S = ()>
+where
+    D: 'a,
+    H: ::digest::Digest + 'a,
+    S: 'a,
+{
+    blocks: BlockchainIter<'a, D, H, S>,
+}
 
-impl<D, H> Blockchain<D, H>
+impl<'a, D, H, S> Iterator for HeaderIter<'a, D, H, S>
+where
+    H: ::digest::Digest,
+{
+    type Item = &'a Header<H, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.blocks.next().map(Block::header)
+    }
+}
+
+impl<'a, D, H, S> Clone for HeaderIter<'a, D, H, S>
+where
+    H: ::digest::Digest,
+{
+    fn clone(&self) -> Self {
+        HeaderIter {
+            blocks: self.blocks.clone(),
+        }
+    }
+}
+
+impl<D, H, S> Blockchain<D, H, S>
 where
     D: Default,
     H: ::digest::Digest,
+    S: Default,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
     /// Creates a new and empty blockchain.
@@ -59,7 +121,7 @@ where
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
     /// assert_eq!(bc.len(), 0);
-    /// let bc = unsafe { bc.unchecked_append(3) };
+    /// let bc = unsafe { bc.unchecked_append(vec![3]) };
     /// assert_eq!(bc.len(), 1);
     /// # }
     /// ```
@@ -78,7 +140,7 @@ where
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
     /// assert!(bc.is_empty());
-    /// let bc = unsafe { bc.unchecked_append(3) };
+    /// let bc = unsafe { bc.unchecked_append(vec![3]) };
     /// assert!(!bc.is_empty());
     /// # }
     /// ```
@@ -86,6 +148,38 @@ where
         self.blocks.len() == 0
     }
 
+    /// Returns the spec, the chain has been created from.
+    pub fn spec(&self) -> Option<&ChainSpec<D, H, S>> {
+        self.spec.as_ref().map(|spec| &**spec)
+    }
+
+    /// Returns the id of the chain, if it has been created from a spec.
+    pub fn chain_id(&self) -> Option<&str> {
+        self.spec().map(ChainSpec::chain_id)
+    }
+
+    /// Creates an empty chain, that is bound to the spec. The first block, that is appended, must
+    /// be the spec's genesis block.
+    pub(crate) fn with_spec(spec: ChainSpec<D, H, S>) -> Self {
+        Self {
+            spec: Some(Arc::new(spec)),
+            ..Self::default()
+        }
+    }
+
+    /// Configures the sealing scheme, the seals of blocks are verified with by
+    /// [`insert`](#method.insert) and [`validate_chain`](#method.validate_chain), instead of proof
+    /// of work. The scheme is kept by all chains, that are derived from this chain.
+    pub fn with_scheme<Sc>(self, scheme: Sc) -> Self
+    where
+        Sc: SealingScheme<H, S> + Send + Sync + 'static,
+    {
+        Self {
+            scheme: Some(SharedScheme::new(scheme)),
+            ..self
+        }
+    }
+
     /// Removes the latest block from the blockchain. Returns an optional reference to the removed
     /// block and a new blockchain object.
     ///
@@ -99,21 +193,36 @@ where
     /// let (head, bc) = bc.tail();
     /// assert_eq!(head, None);
     /// assert_eq!(bc.len(), 0);
-    /// let bc = bc.append(42, 0);
+    /// let bc = bc.append(vec![42], 0).unwrap();
     /// let (head, bc) = bc.tail();
-    /// assert_eq!(head.map(|b| b.data()), Some(&42));
+    /// assert_eq!(head.map(|b| b.transactions()), Some(&[42][..]));
     /// assert_eq!(bc.len(), 0);
     /// # }
     /// ```
-    pub fn tail(&self) -> (Option<&Block<D, H>>, Blockchain<D, H>) {
+    pub fn tail(&self) -> (Option<&Block<D, H, S>>, Blockchain<D, H, S>)
+    where
+        S: Hashable,
+    {
         let tail = self.blocks.tail();
-        (tail.0, Self { blocks: tail.1 })
+        let index = match tail.0 {
+            Some(head) => self.index.remove(&head.header().hash(), tail.1.len()),
+            None => self.index.clone(),
+        };
+        (
+            tail.0,
+            Self {
+                blocks: tail.1,
+                index: index,
+                spec: self.spec.clone(),
+                scheme: self.scheme.clone(),
+            },
+        )
     }
 
     /// Appends a new block with difficulty 0 and an empty previous hash to the chain without
     /// checking. This method is unsafe in a logical sense and therefore marked as unsafe in the
     /// Rust sense. It might corrupt your blockchain, use with caution. For production use, you
-    /// should use [`insert(&self, block: Block<D, H>)`](#method.insert).
+    /// should use [`insert(&self, block: Block<D, H, S>)`](#method.insert).
     ///
     /// # Examples
     ///
@@ -123,18 +232,54 @@ where
     /// # fn main() {
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
-    /// let bc = unsafe { bc.unchecked_append(3) };
-    /// let bc = unsafe { bc.unchecked_append(5) };
+    /// let bc = unsafe { bc.unchecked_append(vec![3]) };
+    /// let bc = unsafe { bc.unchecked_append(vec![5]) };
     /// assert!(!bc.validate_chain());
     /// # }
     /// ```
-    pub unsafe fn unchecked_append(&self, data: D) -> Self {
-        let block = Block::new(data, 0);
+    pub unsafe fn unchecked_append(&self, transactions: Vec<D>) -> Self
+    where
+        D: Hashable,
+        S: Hashable,
+    {
+        self.push(Block::new(transactions, 0))
+    }
+
+    /// Appends an existing block without validating it, e.g. when loading a persisted chain. The
+    /// block is added to the hash index.
+    pub(crate) fn push(&self, block: Block<D, H, S>) -> Self
+    where
+        S: Hashable,
+    {
+        let index = self.index.insert(&block.header().hash(), self.len());
         Self {
             blocks: self.blocks.append(block),
+            index: index,
+            spec: self.spec.clone(),
+            scheme: self.scheme.clone(),
         }
     }
 
+    /// Returns the block at the given height. The genesis block has height `0`. The lookup takes
+    /// `O(log n)` steps.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![5], 0).unwrap().append(vec![42], 0).unwrap();
+    /// assert_eq!(bc.get_by_height(0).unwrap().transactions(), &[5]);
+    /// assert_eq!(bc.get_by_height(1).unwrap().transactions(), &[42]);
+    /// assert_eq!(bc.get_by_height(2), None);
+    /// # }
+    /// ```
+    pub fn get_by_height(&self, height: usize) -> Option<&Block<D, H, S>> {
+        self.blocks.get(height)
+    }
+
     /// Creates an iterator over the blockchain, that iterates the chain in reverse order (newest
     /// block first).
     ///
@@ -145,27 +290,214 @@ where
     /// # fn main() {
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
-    /// let bc = bc.append(5, 0);
-    /// let bc = bc.append(42, 8);
+    /// let bc = bc.append(vec![5], 0).unwrap();
+    /// let bc = bc.append(vec![42], 8).unwrap();
     /// let mut iter = bc.iter();
-    /// assert_eq!(iter.next().unwrap().data(), &42);
-    /// assert_eq!(iter.next().unwrap().data(), &5);
+    /// assert_eq!(iter.next().unwrap().transactions(), &[42]);
+    /// assert_eq!(iter.next().unwrap().transactions(), &[5]);
     /// assert_eq!(iter.next(), None);
     /// # }
     /// ```
-    pub fn iter(&self) -> BlockchainIter<D, H> {
+    pub fn iter(&self) -> BlockchainIter<D, H, S> {
         self.blocks.iter()
     }
+
+    /// Creates an iterator over the headers of the blockchain, that iterates the chain in reverse
+    /// order (newest header first).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![5], 0).unwrap().append(vec![42], 8).unwrap();
+    /// let mut headers = bc.headers();
+    /// assert_eq!(headers.next().unwrap().difficulty(), 8);
+    /// assert_eq!(headers.next().unwrap().difficulty(), 0);
+    /// assert_eq!(headers.next(), None);
+    /// # }
+    /// ```
+    pub fn headers(&self) -> HeaderIter<D, H, S> {
+        HeaderIter {
+            blocks: self.blocks.iter(),
+        }
+    }
+
+    /// Creates an iterator over the blockchain in chronological order (oldest block first). The
+    /// iterator is double-ended, so it can be reversed.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![5], 0).unwrap().append(vec![42], 0).unwrap();
+    /// let mut iter = bc.iter_chronological();
+    /// assert_eq!(iter.next().unwrap().transactions(), &[5]);
+    /// assert_eq!(iter.next().unwrap().transactions(), &[42]);
+    /// assert_eq!(iter.next(), None);
+    /// assert!(bc.iter_chronological().rev().eq(bc.iter()));
+    /// # }
+    /// ```
+    pub fn iter_chronological(&self) -> BlockchainRange<D, H, S> {
+        self.blocks.range(0, self.len())
+    }
+
+    /// Creates an iterator over the blocks from height `start` (inclusive) to height `end`
+    /// (exclusive), oldest block first. The genesis block has height `0`. The bounds are clamped
+    /// to the length of the chain.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc
+    ///     .append(vec![1], 0)
+    ///     .unwrap()
+    ///     .append(vec![2], 0)
+    ///     .unwrap()
+    ///     .append(vec![3], 0)
+    ///     .unwrap();
+    /// let txs = bc.range(1, 5).map(|blk| blk.transactions()[0]).collect::<Vec<_>>();
+    /// assert_eq!(txs, vec![2, 3]);
+    /// # }
+    /// ```
+    pub fn range(&self, start: usize, end: usize) -> BlockchainRange<D, H, S> {
+        self.blocks.range(start, end)
+    }
+
+    /// Creates an iterator over the blocks with a time between `from` (inclusive) and `to`
+    /// (exclusive), oldest block first. The bounds are found using binary search, which relies on
+    /// the times of the blocks not decreasing. This is enforced when inserting blocks and when
+    /// validating a chain.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![1], 0).unwrap();
+    /// let time = bc.iter().next().unwrap().time();
+    /// assert_eq!(bc.time_range(time, time + 1).count(), 1);
+    /// assert_eq!(bc.time_range(time + 1, time + 2).count(), 0);
+    /// # }
+    /// ```
+    pub fn time_range(&self, from: u64, to: u64) -> BlockchainRange<D, H, S> {
+        self.range(self.first_height_at(from), self.first_height_at(to))
+    }
+
+    /// Returns the height of the first block with a time equal to or later than `time` or the
+    /// length of the chain, if there is no such block.
+    fn first_height_at(&self, time: u64) -> usize {
+        let (mut low, mut high) = (0, self.len());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.blocks.get(mid).map_or(false, |blk| blk.time() < time) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
 }
 
-impl<D, H> Blockchain<D, H>
+impl<D, H, S> Blockchain<D, H, S>
 where
-    D: Default + Serialize,
+    D: Default + Hashable,
     H: ::digest::Digest,
+    S: Default + Hashable,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
-    /// Validates the blockchain. Checks if each block contains the hash of the previous block and
-    /// if the hash of a block matches its difficulty.
+    /// Returns the height of the block with the given hash, if the block is part of the chain.
+    /// The lookup uses the hash index of the chain.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new().append(vec![5], 0).unwrap();
+    /// let hash = bc.iter().next().unwrap().hash();
+    /// let fork = bc.tail().1.append(vec![6], 0).unwrap();
+    /// assert_eq!(bc.height_of(&hash), Some(0));
+    /// // the block is not part of the fork
+    /// assert_eq!(fork.height_of(&hash), None);
+    /// # }
+    /// ```
+    pub fn height_of(&self, hash: &[u8]) -> Option<usize> {
+        self.index.get(hash)
+    }
+
+    /// Returns the block with the given hash, if it is part of the chain. See
+    /// [`height_of`](#method.height_of).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![5], 0).unwrap().append(vec![42], 0).unwrap();
+    /// let hash = bc.get_by_height(0).unwrap().hash();
+    /// assert_eq!(bc.get_by_hash(&hash).unwrap().transactions(), &[5]);
+    /// assert_eq!(bc.get_by_hash(&[0; 32]), None);
+    /// # }
+    /// ```
+    pub fn get_by_hash(&self, hash: &[u8]) -> Option<&Block<D, H, S>> {
+        self.height_of(hash).and_then(|height| self.get_by_height(height))
+    }
+
+    /// Copies the blocks from height `start` (inclusive) to height `end` (exclusive) into a new
+    /// chain, oldest block first. The blocks are not validated again, so blocks of any version can
+    /// be copied, and the first block of the new chain keeps the `prev_hash` of its parent. The new
+    /// chain is not bound to the spec of this chain.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc
+    ///     .append(vec![1], 0)
+    ///     .unwrap()
+    ///     .append(vec![2], 0)
+    ///     .unwrap()
+    ///     .append(vec![3], 0)
+    ///     .unwrap();
+    /// let slice = bc.slice(1, bc.len());
+    /// assert_eq!(slice.len(), 2);
+    /// assert!(slice.iter().eq(bc.iter().take(2)));
+    /// # }
+    /// ```
+    pub fn slice(&self, start: usize, end: usize) -> Self
+    where
+        D: Clone,
+        S: Clone,
+    {
+        self.range(start, end)
+            .fold(Self::new(), |chain, block| chain.push(block.clone()))
+    }
+
+    /// Validates the blockchain. Checks if each block contains the hash of the previous block, if
+    /// the time of a block is not earlier than the time of the previous block, if the Merkle root
+    /// of a block matches its transactions and if the hash of a block matches its difficulty. No
+    /// minimum difficulty is enforced, use
+    /// [`validate_chain_with_rules`](#method.validate_chain_with_rules) to validate against a set
+    /// of consensus rules.
     ///
     /// # Examples
     ///
@@ -176,31 +508,57 @@ where
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
     /// assert!(bc.validate_chain());
-    /// let bc = bc.append(5, 8); // appends a block with data `5` and difficulty `1` to the chain
+    /// // appends a block with transaction `5` and difficulty `8` to the chain
+    /// let bc = bc.append(vec![5], 8).unwrap();
     /// assert!(bc.validate_chain());
-    /// let bc = bc.append(42, 8);
+    /// let bc = bc.append(vec![42], 8).unwrap();
     /// assert!(bc.validate_chain());
     /// # }
     /// ```
     pub fn validate_chain(&self) -> bool {
-        self.iter()
-            .fold((None, true), |acc, blk| {
-                (
-                    Some(blk),
-                    acc.1
-                        && acc.0
-                            .map(|b| {
-                                *b.prev_hash() == blk.hash() && b.time() >= blk.time()
-                                    && Self::validate_block(blk).is_ok()
-                            })
-                            .unwrap_or(true),
-                )
-            })
-            .1
+        match (self.spec.as_ref(), self.scheme.as_ref()) {
+            (Some(spec), Some(scheme)) => {
+                self.validate_chain_with_rules(&WithScheme::new(spec.rules(), scheme))
+            }
+            (Some(spec), None) => self.validate_chain_with_rules(spec.rules()),
+            (None, Some(scheme)) => {
+                self.validate_chain_with_rules(&WithScheme::new(&FixedDifficulty(0), scheme))
+            }
+            (None, None) => self.validate_chain_with_rules(&FixedDifficulty(0)),
+        }
+    }
+
+    /// Validates the blockchain like [`validate_chain`](#method.validate_chain), but verifies the
+    /// seal of each block using the sealing scheme of the consensus rules and additionally checks
+    /// if the difficulty of each block is at least the difficulty required by the rules and if the
+    /// time of each block follows the time rules.
+    /// [`validation_report`](#method.validation_report) tells where and why validation fails.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![5], 8).unwrap();
+    /// assert!(bc.validate_chain_with_rules(&FixedDifficulty(8)));
+    /// assert!(!bc.validate_chain_with_rules(&FixedDifficulty(9)));
+    /// # }
+    /// ```
+    pub fn validate_chain_with_rules<R>(&self, rules: &R) -> bool
+    where
+        R: ConsensusRules<H, S>,
+    {
+        self.validation_report(rules, Scope::First).is_valid()
     }
 
     /// Appends a new block to the blockchain. The block gets validated and if validation fails an
-    /// error is returned. If the block is valid, a new head of the chain is returned.
+    /// error is returned. Besides the block itself, the linkage to the head of the chain and the
+    /// time of the block, which must not be earlier than the time of the head, are validated. If
+    /// the block is valid, a new head of the chain is returned.
     ///
     /// # Examples
     /// ```
@@ -209,36 +567,140 @@ where
     /// # fn main() {
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
-    /// let block0 = bc.generate_block(42, 8);
+    /// let block0 = bc.generate_block(vec![42], 8);
     /// let bc = bc.insert(block0);
     /// assert!(bc.is_ok());
     /// let bc = bc.unwrap().insert(Default::default()); // insertion if invalid block
     /// assert!(bc.is_err());
     /// # }
     /// ```
-    pub fn insert(&self, block: Block<D, H>) -> Result<Self, BlockchainError> {
-        self.blocks
-            .head()
-            .map_or(Ok(()), |head| {
-                if head.hash() == *block.prev_hash() {
-                    Ok(())
-                } else {
-                    Err(BlockchainError::InvalidPrevHash(
-                        format!("{:?}", block.prev_hash()),
-                        format!("{:?}", head.hash()),
-                    ))
-                }
-            })
-            .and_then(|_| Self::validate_block(&block))
-            .map(|_| Self {
-                blocks: self.blocks.append(block),
-            })
+    pub fn insert(&self, block: Block<D, H, S>) -> Result<Self, BlockchainError> {
+        match (self.spec.as_ref(), self.scheme.as_ref()) {
+            (Some(spec), Some(scheme)) => {
+                self.insert_with_rules(block, &WithScheme::new(spec.rules(), scheme))
+            }
+            (Some(spec), None) => self.insert_with_rules(block, spec.rules()),
+            (None, Some(scheme)) => {
+                self.insert_with_rules(block, &WithScheme::new(&FixedDifficulty(0), scheme))
+            }
+            (None, None) => self.insert_with_rules(block, &FixedDifficulty(0)),
+        }
+    }
+
+    /// Appends a new block to the blockchain like [`insert`](#method.insert), but verifies the
+    /// block's seal using the sealing scheme of the consensus rules and additionally checks if
+    /// the block's difficulty is at least the difficulty required by the rules and if its time
+    /// follows the time rules (see
+    /// [`ConsensusRules::median_time_span`](../consensus/trait.ConsensusRules.html#method.median_time_span)
+    /// and
+    /// [`ConsensusRules::max_future_drift`](../consensus/trait.ConsensusRules.html#method.max_future_drift)).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let rules = FixedDifficulty(8);
+    /// assert!(bc.insert_with_rules(bc.generate_block(vec![42], 4), &rules).is_err());
+    /// assert!(bc.insert_with_rules(bc.generate_block(vec![42], 8), &rules).is_ok());
+    /// # }
+    /// ```
+    pub fn insert_with_rules<R>(
+        &self,
+        block: Block<D, H, S>,
+        rules: &R,
+    ) -> Result<Self, BlockchainError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        self.validate_child(&block, rules)
+            .map(|_| self.push(block))
+    }
+
+    /// Validates a block, that is appended to this chain, against the head of the chain and the
+    /// consensus rules.
+    pub(crate) fn validate_child<R>(
+        &self,
+        block: &Block<D, H, S>,
+        rules: &R,
+    ) -> Result<(), BlockchainError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        self.check_genesis(block)?;
+        validate_header(block.header(), self.len(), self.headers(), rules)?;
+        validate_merkle_root(block)
+    }
+
+    /// Validates the block at the given height against its ancestors and the consensus rules, like
+    /// [`validate_child`](#method.validate_child) validates it against the chain below it. The
+    /// ancestors are iterated in place, so no chain is created for them.
+    pub(crate) fn validate_at<R>(&self, height: usize, rules: &R) -> Result<(), BlockchainError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        let block = self.get_by_height(height)
+            .ok_or(BlockchainError::UnknownHeight(height))?;
+        match self.spec {
+            Some(ref spec) if height == 0 => spec.check_genesis(block)?,
+            _ => {}
+        }
+        let parent = HeaderIter {
+            blocks: self.blocks.iter_below(height),
+        };
+        validate_header(block.header(), height, parent, rules)?;
+        validate_merkle_root(block)
+    }
+
+    /// Checks if the block is the genesis block of the spec, if it is appended to an empty chain,
+    /// that is bound to a spec.
+    pub(crate) fn check_genesis(&self, block: &Block<D, H, S>) -> Result<(), BlockchainError> {
+        match self.spec {
+            Some(ref spec) if self.is_empty() => spec.check_genesis(block),
+            _ => Ok(()),
+        }
+    }
+
+    /// Creates a chain from a spec. The chain contains the spec's genesis block, which is
+    /// validated against the spec's consensus rules. Blocks, that are inserted using
+    /// [`insert`](#method.insert), are validated against these rules as well.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::Retarget;
+    /// use blockchain::spec::ChainSpec;
+    /// let genesis = Block::new(vec![0], 4).proof_of_work();
+    /// let spec = ChainSpec::new("test", genesis, Retarget::new(4, 60, 16));
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::from_spec(spec).unwrap();
+    /// assert_eq!(bc.len(), 1);
+    /// assert_eq!(bc.chain_id(), Some("test"));
+    /// // the difficulty is lower than required by the spec
+    /// assert!(bc.insert(bc.generate_block(vec![1], 2)).is_err());
+    /// # }
+    /// ```
+    pub fn from_spec(spec: ChainSpec<D, H, S>) -> Result<Self, BlockchainError>
+    where
+        D: Clone,
+        S: Clone,
+    {
+        let genesis = spec.genesis().clone();
+        Self::with_spec(spec).insert(genesis)
     }
 
     /// Generates a new block ready to append to the blockchain. The block will contain the hash of
     /// the previous block from the chain and a nonce that `hash(block)` matches the given
     /// difficulty. This method blocks the current thread until the block's hash matches the
-    /// difficulty. This might take a very long time.
+    /// difficulty. This might take a very long time. If the chain has been created from a spec,
+    /// the block has the version, that the spec requires at its height, and the proof of work is
+    /// performed using the proof of work function of the spec.
     ///
     /// # Examples
     /// ```
@@ -247,30 +709,156 @@ where
     /// # fn main() {
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
-    /// let block0 = bc.generate_block(42, 8);
+    /// let block0 = bc.generate_block(vec![42], 8);
     /// let bc = bc.insert(block0.clone());
     /// assert!(bc.is_ok());
     /// let bc = bc.unwrap();
-    /// let block1 = bc.generate_block(1337, 8);
+    /// let block1 = bc.generate_block(vec![1337], 8);
     /// let bc = bc.insert(block1);
     /// assert!(bc.is_ok());
     /// let bc = bc.unwrap().insert(block0); // after inserting block1, block0 is no longer valid
     /// assert!(bc.is_err());
     /// # }
     /// ```
-    pub fn generate_block(&self, data: D, difficulty: usize) -> Block<D, H> {
-        let mut block = Block::new_with_hash(
-            data,
+    pub fn generate_block(&self, transactions: Vec<D>, difficulty: usize) -> Block<D, H, S> {
+        self.generate_block_with_clock(transactions, difficulty, &SystemClock)
+    }
+
+    /// Returns the skip hash of a block, that is appended to the chain, which is the hash of the
+    /// block at [`skip_height`](../header/fn.skip_height.html). Only blocks since version `4`
+    /// (`version::SKIP_VERSION`) contain a skip hash (see
+    /// [`Block::set_skip_hash`](../block/struct.Block.html#method.set_skip_hash)).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::version::SKIP_VERSION;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc
+    ///     .append(vec![1], 0)
+    ///     .unwrap()
+    ///     .append(vec![2], 0)
+    ///     .unwrap()
+    ///     .append(vec![3], 0)
+    ///     .unwrap();
+    /// // the block at height 3 links to the genesis block
+    /// let block = bc.generate_block(vec![4], 0)
+    ///     .set_version(SKIP_VERSION)
+    ///     .set_skip_hash(bc.next_skip_hash());
+    /// assert_eq!(block.header().skip_hash(), &bc.get_by_height(0).unwrap().hash());
+    /// # }
+    /// ```
+    pub fn next_skip_hash(&self) -> GenericArray<u8, H::OutputSize> {
+        self.get_by_height(skip_height(self.len()))
+            .map(|blk| blk.hash())
+            .unwrap_or_default()
+    }
+
+    /// Generates a new block like [`generate_block`](#method.generate_block), but takes the time
+    /// of the block from the given clock.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::clock::{Clock, ManualClock};
+    /// let clock = ManualClock::new(0);
+    /// let bc: Blockchain<_, sha2::Sha256> = (0..12).fold(Blockchain::new(), |bc, month| {
+    ///     clock.advance(30 * 24 * 60 * 60);
+    ///     bc.insert(bc.generate_block_with_clock(vec![month], 4, &clock)).unwrap()
+    /// });
+    /// assert_eq!(bc.iter().next().unwrap().time(), clock.now());
+    /// # }
+    /// ```
+    pub fn generate_block_with_clock<C>(
+        &self,
+        transactions: Vec<D>,
+        difficulty: usize,
+        clock: &C,
+    ) -> Block<D, H, S>
+    where
+        C: Clock + ?Sized,
+    {
+        self.next_block(transactions, difficulty, clock)
+            .proof_of_work_with_pow(&self.pow_algorithm(), clock)
+    }
+
+    /// Creates the next block of the chain without performing the proof of work. The block has the
+    /// version, that the spec of the chain requires at its height, or `block::VERSION`, if the
+    /// chain is not bound to a spec.
+    fn next_block<C>(&self, transactions: Vec<D>, difficulty: usize, clock: &C) -> Block<D, H, S>
+    where
+        C: Clock + ?Sized,
+    {
+        let version = self.spec
+            .as_ref()
+            .map_or(VERSION, |spec| spec.rules().schedule().version_at(self.len()));
+        Block::new_with_clock(
+            transactions,
             self.blocks.head().map(|blk| blk.hash()).unwrap_or_default(),
             difficulty,
-        );
-        while Self::validate_block(&block).is_err() {
-            block = block.increment_nonce(current_time());
-        }
-        block
+            clock,
+        ).set_version(version)
+            .set_skip_hash(self.next_skip_hash())
+    }
+
+    /// Returns the proof of work function of the spec of the chain or the leading zero proof of
+    /// work, if the chain is not bound to a spec.
+    fn pow_algorithm(&self) -> PowAlgorithm {
+        self.spec
+            .as_ref()
+            .map(|spec| *spec.rules().pow_algorithm())
+            .unwrap_or_default()
+    }
+
+    /// Generates a new block like [`generate_block`](#method.generate_block) but performs the
+    /// proof of work using the supplied multi-threaded miner and the proof of work function of the
+    /// chain's spec. Mining can be interrupted using the cancellation token, in which case `None`
+    /// is returned. Fails, if a worker thread of the miner panics.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::miner::{CancellationToken, Miner};
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let token = CancellationToken::new();
+    /// let block0 = bc.mine_block(vec![42], 8, &Miner::new(2), &token, |_| ());
+    /// let bc = bc.insert(block0.unwrap().unwrap());
+    /// assert!(bc.is_ok());
+    /// # }
+    /// ```
+    pub fn mine_block<F>(
+        &self,
+        transactions: Vec<D>,
+        difficulty: usize,
+        miner: &Miner,
+        token: &CancellationToken,
+        progress: F,
+    ) -> Result<Option<Block<D, H, S>>, MiningError>
+    where
+        D: Clone + Send + 'static,
+        H: Clone + 'static,
+        S: Clone + Send + 'static,
+        GenericArray<u8, H::OutputSize>: Send,
+        F: FnMut(&Progress),
+    {
+        let block = self.next_block(transactions, difficulty, &SystemClock);
+        miner.mine_with_pow(block, &self.pow_algorithm(), token, progress)
     }
 
-    /// Appends a new block. This method blocks until the given difficulty is reached.
+    /// Appends a new block, that is generated using
+    /// [`generate_block`](#method.generate_block). This method blocks until the given difficulty
+    /// is reached. Fails like [`insert`](#method.insert), e.g. if the chain has been created from
+    /// a spec and the difficulty or the time of the block does not meet the spec's consensus
+    /// rules.
     ///
     /// # Examples
     /// ```
@@ -279,108 +867,273 @@ where
     /// # fn main() {
     /// use blockchain::blockchain::Blockchain;
     /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
-    /// let bc = bc.append(5, 8); // appends a block with data `5` and difficulty `1` to the chain
+    /// // appends a block with transaction `5` and difficulty `8` to the chain
+    /// let bc = bc.append(vec![5], 8).unwrap();
+    /// assert_eq!(bc.len(), 1);
     /// # }
     /// ```
-    pub fn append(&self, data: D, difficulty: usize) -> Blockchain<D, H> {
-        self.insert(self.generate_block(data, difficulty))
-            .expect("This cannot happen!") // this cannot fail since we just created a valid block
+    pub fn append(
+        &self,
+        transactions: Vec<D>,
+        difficulty: usize,
+    ) -> Result<Blockchain<D, H, S>, BlockchainError> {
+        self.insert(self.generate_block(transactions, difficulty))
     }
 
     /// Validates a block. The following properties are checked:
-    /// * The version number matches `block::VERSION`
+    /// * The version number is supported (see the [`version`](../version/index.html) module)
+    /// * The Merkle root matches the block's transactions
     /// * The difficulty factor matches the block's hash
-    pub fn validate_block(block: &Block<D, H>) -> Result<(), BlockchainError> {
-        let valid_difficulty = block.validate_difficulty();
-        if block.version() != ::block::VERSION {
+    pub fn validate_block(block: &Block<D, H, S>) -> Result<(), BlockchainError> {
+        Self::validate_block_with_scheme(block, &ProofOfWork)
+    }
+
+    /// Validates a block using a sealing scheme. The following properties are checked:
+    /// * The version number is supported (see the [`version`](../version/index.html) module)
+    /// * The Merkle root matches the block's transactions
+    /// * The seal is valid according to the sealing scheme
+    pub fn validate_block_with_scheme<Sc>(
+        block: &Block<D, H, S>,
+        scheme: &Sc,
+    ) -> Result<(), BlockchainError>
+    where
+        Sc: SealingScheme<H, S>,
+    {
+        if !::version::is_supported(block.version()) {
             Err(BlockchainError::UnknownVersion(block.version()))
-        } else if !valid_difficulty {
-            Err(BlockchainError::InvalidBlockHash(
-                format!("{:?}", block.hash()),
-                block.difficulty(),
-            ))
+        } else if !block.validate_merkle_root() {
+            Err(BlockchainError::InvalidMerkleRoot(HashBytes::from(
+                &block.merkle_root()[..],
+            )))
         } else {
-            Ok(())
+            scheme.verify_seal(block.header())
         }
     }
 }
 
-impl<D, H> Blockchain<D, H>
+impl<D, H, S> Blockchain<D, H, S>
 where
     D: Serialize,
     H: ::digest::Digest,
+    S: Serialize,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
-    /// Persists a blockchain to disk.
-    pub fn persist_to_disk<P: AsRef<Path>>(&self, filename: P) -> Result<(), ::failure::Error> {
-        let encoded: Vec<u8> = ::bincode::serialize(self, ::bincode::Infinite)?;
+    /// Persists a blockchain to disk. The file starts with a header containing the hash algorithm
+    /// and the chain id, followed by the blocks and a checksum (see the
+    /// [`stream`](../stream/index.html) module). The blocks are written one at a time, oldest
+    /// block first, so the chain is not serialized into memory as a whole. The file can be read
+    /// using [`load_from_disk`](#method.load_from_disk) or block by block using a
+    /// [`BlockReader`](../stream/struct.BlockReader.html).
+    pub fn persist_to_disk<P: AsRef<Path>>(&self, filename: P) -> Result<(), ::failure::Error>
+    where
+        H: HashFunction,
+    {
+        let header = FileHeader::new(
+            H::ALGORITHM,
+            self.spec.as_ref().map_or("", |spec| spec.chain_id()),
+        )?;
         let mut file = BufWriter::new(File::create(filename)?);
-        file.write_all(&encoded).map_err(From::from)
+        file.write_all(&header.encode(SNAPSHOT_MAGIC))?;
+        let checksum = {
+            let mut body = Checksummed::new(&mut file, header.encoded_len());
+            ::bincode::serialize_into(&mut body, self, ::bincode::Infinite)?;
+            body.checksum()
+        };
+        file.write_all(&encode_u32(checksum))?;
+        file.flush().map_err(From::from)
     }
 }
 
-impl<D, H> Blockchain<D, H>
+impl<D, H, S> Blockchain<D, H, S>
 where
-    D: Serialize,
+    D: Hashable,
     for<'de> D: Deserialize<'de>,
     H: ::digest::Digest,
+    S: Hashable + Default,
+    for<'de> S: Deserialize<'de>,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
-    /// Loads a blockchain from disk.
-    pub fn load_from_disk<P: AsRef<Path>>(filename: P) -> Result<Self, PersistingError> {
-        let mut file = BufReader::new(File::open(filename).map_err(|_| PersistingError::IoError)?);
-        ::bincode::deserialize_from(&mut file, ::bincode::Infinite)
-            .map_err(|_| PersistingError::DeserializingError)
+    /// Loads a blockchain from disk. The header and the checksum of the file are verified, but the
+    /// blocks are not validated. Files, that have been persisted before the header was introduced,
+    /// are read without a header and checksum (see
+    /// [`BlockReader::new`](../stream/struct.BlockReader.html#method.new)).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use std::fs::OpenOptions;
+    /// use blockchain::PersistingError;
+    /// use blockchain::blockchain::Blockchain;
+    /// let dir = tempdir::TempDir::new("chain").unwrap();
+    /// let path = dir.path().join("chain.bin");
+    /// let bc: Blockchain<u32, sha2::Sha256> = Blockchain::new().append(vec![1, 2], 0).unwrap();
+    /// bc.persist_to_disk(&path).unwrap();
+    /// assert_eq!(Blockchain::load_from_disk(&path).unwrap(), bc);
+    /// let len = std::fs::metadata(&path).unwrap().len();
+    /// OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 2).unwrap();
+    /// match Blockchain::<u32, sha2::Sha256>::load_from_disk(&path) {
+    ///     Err(PersistingError::Truncated(offset)) => assert_eq!(offset, len - 4),
+    ///     _ => panic!("the checksum is truncated"),
+    /// }
+    /// # }
+    /// ```
+    pub fn load_from_disk<P: AsRef<Path>>(filename: P) -> Result<Self, PersistingError>
+    where
+        D: Default,
+        H: HashFunction,
+    {
+        let reader: BlockReader<D, H, S> = BlockReader::open_any(filename)?;
+        reader.fold(Ok(Self::new()), |acc, block| {
+            acc.and_then(|chain| block.map(|block| chain.push(block)))
+        })
+    }
+
+    /// Loads a blockchain from disk one block at a time. Each block is validated against the
+    /// already loaded part of the chain using the consensus rules, like in
+    /// [`insert_with_rules`](#method.insert_with_rules). If a block cannot be decoded or is
+    /// invalid, loading stops and the height of the block is reported, together with the reason
+    /// why an invalid block has been rejected. Like in
+    /// [`load_from_disk`](#method.load_from_disk), files without a header are supported.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::{BlockchainError, PersistingError};
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// let dir = tempdir::TempDir::new("chain").unwrap();
+    /// let path = dir.path().join("chain.bin");
+    /// let bc: Blockchain<u32, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![1], 8).unwrap().append(vec![2], 4).unwrap();
+    /// bc.persist_to_disk(&path).unwrap();
+    /// let loaded = Blockchain::load_from_disk_with_rules(&path, &FixedDifficulty(4)).unwrap();
+    /// assert_eq!(loaded, bc);
+    /// let strict = FixedDifficulty(8);
+    /// match Blockchain::<u32, sha2::Sha256>::load_from_disk_with_rules(&path, &strict) {
+    ///     Err(PersistingError::InvalidBlock(1, BlockchainError::InsufficientDifficulty(..))) => {}
+    ///     _ => panic!("the second block is invalid"),
+    /// }
+    /// # }
+    /// ```
+    pub fn load_from_disk_with_rules<P, R>(filename: P, rules: &R) -> Result<Self, PersistingError>
+    where
+        P: AsRef<Path>,
+        R: ConsensusRules<H, S>,
+        D: Default,
+        H: HashFunction,
+    {
+        let reader: BlockReader<D, H, S> = BlockReader::open_any(filename)?;
+        let mut chain = Self::default();
+        for (height, block) in reader.enumerate() {
+            chain = chain
+                .insert_with_rules(block?, rules)
+                .map_err(|err| PersistingError::InvalidBlock(height, err))?;
+        }
+        Ok(chain)
+    }
+}
+
+/// Checks if the Merkle root in the header of a block matches its transactions.
+fn validate_merkle_root<D, H, S>(block: &Block<D, H, S>) -> Result<(), BlockchainError>
+where
+    D: Hashable,
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    if block.validate_merkle_root() {
+        Ok(())
+    } else {
+        Err(BlockchainError::InvalidMerkleRoot(HashBytes::from(
+            &block.merkle_root()[..],
+        )))
     }
 }
 
-impl<D, H> Default for Blockchain<D, H>
+impl<D, H, S> Default for Blockchain<D, H, S>
 where
     D: Default,
     H: ::digest::Digest,
+    S: Default,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
     fn default() -> Self {
         Self {
             blocks: Default::default(),
+            index: Default::default(),
+            spec: None,
+            scheme: None,
         }
     }
 }
 
-impl<D, H> ::serde::Serialize for Blockchain<D, H>
+impl<D, H, S> ::serde::Serialize for Blockchain<D, H, S>
 where
     D: ::serde::Serialize,
     H: ::digest::Digest,
+    S: ::serde::Serialize,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    fn serialize<Se>(&self, serializer: Se) -> Result<Se::Ok, Se::Error>
     where
-        S: Serializer,
+        Se: Serializer,
     {
         self.blocks.serialize(serializer)
     }
 }
 
-impl<'de, D, H> Deserialize<'de> for Blockchain<D, H>
+impl<'de, D, H, S> Deserialize<'de> for Blockchain<D, H, S>
 where
     D: Deserialize<'de> + Serialize,
     H: ::digest::Digest,
+    S: Deserialize<'de> + Hashable + Default,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
-    fn deserialize<S>(deserializer: S) -> Result<Self, S::Error>
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
     where
-        S: Deserializer<'de>,
+        De: Deserializer<'de>,
     {
+        // the blocks are serialized oldest first, so they are indexed while they are appended.
+        // Chains of earlier releases, that were serialized newest block first, are read by
+        // `version::read_legacy_chain`.
+        let mut index = HashIndex::default();
+        let blocks = Stack::deserialize_with(deserializer, |block: &Block<D, H, S>, height| {
+            index = index.insert(&block.header().hash(), height);
+        })?;
         Ok(Self {
-            blocks: Stack::deserialize(deserializer)?,
+            blocks: blocks,
+            index: index,
+            spec: None,
+            scheme: None,
         })
     }
 }
 
-impl<D, H> PartialEq for Blockchain<D, H>
+impl<D, H, S> Clone for Blockchain<D, H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            blocks: self.blocks.clone(),
+            index: self.index.clone(),
+            spec: self.spec.clone(),
+            scheme: self.scheme.clone(),
+        }
+    }
+}
+
+impl<D, H, S> PartialEq for Blockchain<D, H, S>
 where
     D: PartialEq,
     H: ::digest::Digest,
+    S: PartialEq,
     <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
 {
     fn eq(&self, other: &Self) -> bool {
@@ -395,7 +1148,7 @@ mod tests {
 
     impl<A> Arbitrary for Blockchain<A, ::sha2::Sha256>
     where
-        A: Arbitrary + ::std::marker::Sync + Default + ::serde::Serialize,
+        A: Arbitrary + ::std::marker::Sync + Default + Hashable,
         for<'de> A: Deserialize<'de>,
     {
         fn arbitrary<G: Gen>(g: &mut G) -> Self {
@@ -404,7 +1157,11 @@ mod tests {
                 g.gen_range(0, s)
             };
             (0..size).fold(Blockchain::new(), |acc, _| {
-                acc.append(Arbitrary::arbitrary(g), 0)
+                // keep the batches small, the chain itself is already up to `size` blocks long
+                let transactions = (0..g.gen_range(0, 4))
+                    .map(|_| Arbitrary::arbitrary(g))
+                    .collect();
+                acc.append(transactions, 0).unwrap()
             })
         }
     }
@@ -423,9 +1180,166 @@ mod tests {
         }
     }
 
+    #[test]
+    fn chain_without_header_is_loaded() {
+        use consensus::FixedDifficulty;
+        let dir = ::tempdir::TempDir::new("blockchain_").unwrap();
+        let path = dir.path().join("chain.bin");
+        let chain: Blockchain<u32, ::sha2::Sha256> =
+            Blockchain::new().append(vec![1], 0).unwrap().append(vec![2, 3], 0).unwrap();
+        // persist the body only, like before the header was introduced
+        let body = ::bincode::serialize(&chain, ::bincode::Infinite).unwrap();
+        File::create(&path).unwrap().write_all(&body).unwrap();
+        assert_eq!(Blockchain::load_from_disk(&path).unwrap(), chain);
+        let loaded = Blockchain::load_from_disk_with_rules(&path, &FixedDifficulty(0)).unwrap();
+        assert_eq!(loaded, chain);
+        // a file, that starts with the magic bytes, needs a valid header
+        File::create(&path).unwrap().write_all(b"EBSS").unwrap();
+        assert!(Blockchain::<u32, ::sha2::Sha256>::load_from_disk(&path).is_err());
+    }
+
+    #[test]
+    fn tampered_transactions_are_rejected() {
+        let chain: Blockchain<u8, ::sha2::Sha256> = Blockchain::new()
+            .append(vec![1, 2], 0)
+            .unwrap();
+        let block = chain.iter().next().unwrap();
+        // the header and therefore the Merkle root of the original transactions are kept
+        let tampered = block.clone().replace_transactions(vec![1, 3]);
+        assert_eq!(tampered.transactions(), &[1, 3]);
+        assert_eq!(tampered.hash(), block.hash());
+        assert!(!tampered.validate_merkle_root());
+        match Blockchain::new().insert(tampered.clone()) {
+            Err(BlockchainError::InvalidMerkleRoot(_)) => {}
+            _ => panic!("the transactions do not match the Merkle root"),
+        }
+        assert!(!Blockchain::new().push(tampered).validate_chain());
+    }
+
+    quickcheck! {
+        fn blocks_are_found_by_hash_and_height(chain: Blockchain<bool, ::sha2::Sha256>) -> bool {
+            // the index of a deserialized chain is rebuilt from the blocks
+            let bytes = ::bincode::serialize(&chain, ::bincode::Infinite).unwrap();
+            let loaded: Blockchain<bool, ::sha2::Sha256> = ::bincode::deserialize(&bytes).unwrap();
+            chain.iter().enumerate().all(|(depth, block)| {
+                let height = chain.len() - 1 - depth;
+                chain.get_by_height(height) == Some(block)
+                    && chain.height_of(&block.hash()) == Some(height)
+                    && loaded.get_by_hash(&block.hash()) == Some(block)
+            })
+        }
+    }
+
+    #[test]
+    fn deserialized_chains_are_indexed() {
+        let chain: Blockchain<u32, ::sha2::Sha256> = (1..4).fold(Blockchain::new(), |chain, n| {
+            chain.append(vec![n], 0).unwrap()
+        });
+        let blob = ::bincode::serialize(&chain, ::bincode::Infinite).unwrap();
+        let loaded: Blockchain<u32, ::sha2::Sha256> = ::bincode::deserialize(&blob).unwrap();
+        assert_eq!(loaded, chain);
+        assert!(loaded.validate_chain());
+        assert_eq!(loaded.get_by_height(0).unwrap().transactions(), &[1]);
+        for (depth, block) in chain.iter().enumerate() {
+            assert_eq!(loaded.height_of(&block.hash()), Some(2 - depth));
+        }
+    }
+
+    #[test]
+    fn forks_only_find_their_own_blocks() {
+        let chain: Blockchain<u32, ::sha2::Sha256> =
+            Blockchain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let common = chain.get_by_height(0).unwrap().hash();
+        let head = chain.iter().next().unwrap().hash();
+        let fork = chain.tail().1.append(vec![3], 0).unwrap().append(vec![4], 0).unwrap();
+        let fork_head = fork.iter().next().unwrap().hash();
+        assert_eq!(fork.height_of(&common), Some(0));
+        assert_eq!(fork.height_of(&fork_head), Some(2));
+        assert_eq!(fork.height_of(&head), None);
+        assert_eq!(chain.height_of(&head), Some(1));
+        assert_eq!(chain.height_of(&fork_head), None);
+        // every chain only indexes its own blocks
+        assert_eq!(chain.index.len(), chain.len());
+        assert_eq!(fork.index.len(), fork.len());
+        assert_eq!(fork.tail().1.tail().1.index.len(), 1);
+    }
+
+    #[test]
+    fn time_range_selects_blocks_by_time() {
+        let chain: Blockchain<u8, ::sha2::Sha256> = [10, 20, 20, 30]
+            .iter()
+            .fold(Blockchain::new(), |chain, time| {
+                // with difficulty 0, every nonce and time results in a valid block
+                let block = chain.generate_block(vec![], 0).set_nonce(0, *time);
+                chain.insert(block).unwrap()
+            });
+        let times = |from, to| {
+            chain
+                .time_range(from, to)
+                .map(|blk| blk.time())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(times(0, 100), vec![10, 20, 20, 30]);
+        assert_eq!(times(20, 30), vec![20, 20]);
+        assert_eq!(times(11, 20), Vec::<u64>::new());
+        assert_eq!(times(25, 100), vec![30]);
+    }
+
+    #[test]
+    fn blocks_are_generated_for_the_rules_of_the_spec() {
+        use consensus::Retarget;
+        use pow::MemoryHard;
+        use version::{VersionSchedule, LEGACY_VERSION};
+        // a migrated chain, whose blocks are legacy blocks up to height 2
+        let versions = VersionSchedule::new(LEGACY_VERSION).activate(VERSION, 2);
+        let rules = Retarget::new(0, 60, 1).bounds(0, 0).versions(versions);
+        let genesis = Block::new(vec![0], 0).set_version(LEGACY_VERSION).proof_of_work();
+        let chain: Blockchain<u32, ::sha2::Sha256> =
+            Blockchain::from_spec(ChainSpec::new("test", genesis, rules)).unwrap();
+        let chain = (1..4).fold(chain, |chain, n| chain.append(vec![n], 0).unwrap());
+        let versions = chain.iter().map(Block::version).collect::<Vec<_>>();
+        assert_eq!(versions, vec![VERSION, VERSION, LEGACY_VERSION, LEGACY_VERSION]);
+        // the blocks of a memory-hard chain are mined using the memory-hard proof of work
+        let pow = PowAlgorithm::MemoryHard(MemoryHard::new(64, 1, 1).unwrap());
+        let rules = Retarget::new(2, 60, 1).bounds(2, 2).pow(pow);
+        let genesis = Block::new(vec![0], 2).proof_of_work_with_pow(&pow, &SystemClock);
+        let chain: Blockchain<u32, ::sha2::Sha256> =
+            Blockchain::from_spec(ChainSpec::new("test", genesis, rules)).unwrap();
+        let chain = chain.append(vec![1], 2).unwrap();
+        let token = CancellationToken::new();
+        let block = chain.mine_block(vec![2], 2, &Miner::new(2), &token, |_| ());
+        let chain = chain.insert(block.unwrap().unwrap()).unwrap();
+        assert_eq!(chain.len(), 3);
+        // the difficulty is lower than required by the spec
+        match chain.append(vec![3], 0) {
+            Err(BlockchainError::InsufficientDifficulty(..)) => {}
+            _ => panic!("the block does not meet the required difficulty"),
+        }
+    }
+
+    #[test]
+    fn skip_hashes_are_set_for_the_rules_of_the_spec() {
+        use consensus::Retarget;
+        use version::{VersionSchedule, SKIP_VERSION};
+        let versions = VersionSchedule::new(SKIP_VERSION);
+        let rules = Retarget::new(0, 60, 1).bounds(0, 0).versions(versions);
+        let genesis = Block::new(vec![0], 0).set_version(SKIP_VERSION).proof_of_work();
+        let chain: Blockchain<u32, ::sha2::Sha256> =
+            Blockchain::from_spec(ChainSpec::new("test", genesis, rules)).unwrap();
+        let chain = (1..14).fold(chain, |chain, n| chain.append(vec![n], 0).unwrap());
+        let token = CancellationToken::new();
+        let block = chain.mine_block(vec![14], 0, &Miner::new(2), &token, |_| ());
+        let block = block.unwrap().unwrap();
+        assert_eq!(block.version(), SKIP_VERSION);
+        assert_eq!(block.header().skip_hash(), &chain.get_by_height(7).unwrap().hash());
+        let chain = chain.insert(block).unwrap();
+        assert_eq!(chain.len(), 15);
+        assert!(chain.validate_chain());
+    }
+
     quickcheck! {
         fn append_results_in_valid_chain(chain: Blockchain<bool, ::sha2::Sha256>) -> bool {
-            let chain = chain.append(false, 1);
+            let chain = chain.append(vec![false], 1).unwrap();
             chain.validate_chain()
         }
     }
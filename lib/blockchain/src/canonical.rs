@@ -0,0 +1,444 @@
+//! This module contains the canonical byte encoding, that is used to calculate the hash of block
+//! headers and the Merkle leaves of transactions. Unlike the serialization used for persisting, the
+//! canonical encoding is defined by this crate alone, so hashes do not change if a serialization
+//! library changes its format and the encoding can be reproduced by verifiers written in other
+//! languages.
+//!
+//! The encoding is defined as follows:
+//!
+//! * Unsigned integers are encoded big-endian using their fixed width. `usize` is always encoded
+//!   as a `u64`, independent of the platform.
+//! * Signed integers are encoded as two's complement using their fixed width. `isize` is always
+//!   encoded as an `i64`.
+//! * `bool` is encoded as a single byte, `0x00` for `false` and `0x01` for `true`.
+//! * `()` is encoded as zero bytes.
+//! * Fixed size arrays, e.g. hashes and signatures, are the concatenation of their elements.
+//! * Variable length sequences and strings are prefixed with their length as `u64`.
+//! * `Option` and other enums are prefixed with a one byte tag, that identifies the variant,
+//!   followed by the fields of the variant. `None` has the tag `0x00`, `Some` has the tag `0x01`.
+//! * Structs are the concatenation of their fields in declaration order.
+//!
+//! A block header is encoded as `version (u8) || prev_hash || merkle_root || time (u64) ||
+//! difficulty (u64) || nonce (u64) || seal`. The hash of a header is calculated over its encoding,
+//! prefixed with the domain tag [`HEADER_DOMAIN`](constant.HEADER_DOMAIN.html). The hash, that is
+//! signed by sealing schemes, is calculated over the encoding without the seal, prefixed with the
+//! domain tag [`SEAL_DOMAIN`](constant.SEAL_DOMAIN.html). A domain tag is
+//! hashed as its length in one byte, followed by the ASCII tag. Merkle leaves are prefixed with
+//! `0x00` and inner nodes with `0x01` (see the [`merkle`](../merkle/index.html) module), so the
+//! hash input of a header never starts like the hash input of a Merkle node.
+
+use generic_array::{ArrayLength, GenericArray};
+use generic_array::typenum::Unsigned;
+
+use target::{leading_zeros, Target};
+use version::{CANONICAL_VERSION, TARGET_VERSION};
+
+use super::{BlockchainError, HashBytes};
+
+/// Domain tag of block header hashes.
+pub const HEADER_DOMAIN: &[u8] = b"header";
+/// Domain tag of the hashes of block headers without their seal, that are signed by sealing
+/// schemes.
+pub const SEAL_DOMAIN: &[u8] = b"seal";
+
+/// Types with a canonical byte encoding. The encoding must be unambiguous, so two different
+/// values never have the same encoding.
+pub trait Canonical {
+    /// Appends the canonical encoding of the value to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Returns the canonical encoding of the value.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::canonical::Canonical;
+    /// assert_eq!(0x0102u16.canonical_bytes(), vec![0x01, 0x02]);
+    /// assert_eq!(1usize.canonical_bytes(), vec![0, 0, 0, 0, 0, 0, 0, 1]);
+    /// assert_eq!(vec![true].canonical_bytes(), vec![0, 0, 0, 0, 0, 0, 0, 1, 1]);
+    /// assert_eq!(Some(-1i8).canonical_bytes(), vec![1, 0xff]);
+    /// # }
+    /// ```
+    fn canonical_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+}
+
+/// Calculates the hash of the canonical encoding of `value`, prefixed with a domain tag.
+///
+/// # Panics
+/// Panics if the domain tag is longer than 255 bytes.
+pub fn digest<H, T>(domain: &[u8], value: &T) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+    T: Canonical + ?Sized,
+{
+    digest_encoded::<H>(domain, &value.canonical_bytes())
+}
+
+/// Calculates the hash of an already encoded value, prefixed with a domain tag.
+fn digest_encoded<H>(domain: &[u8], encoded: &[u8]) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+{
+    assert!(
+        domain.len() <= 0xff,
+        "Domain tags cannot be longer than 255 bytes"
+    );
+    let mut hasher = H::default();
+    hasher.input(&[domain.len() as u8]);
+    hasher.input(domain);
+    hasher.input(encoded);
+    hasher.result()
+}
+
+/// Verifies a chain of canonically encoded block headers, oldest header first. This checks the
+/// version, the linkage of the `prev_hash` fields, the time and the proof of work of each header
+/// by reading the fields directly from the encoding, so it does not depend on the `Header` type or
+/// any serialization library. The seal is hashed as part of the header but not interpreted, and
+/// consensus rules like a required difficulty are not checked. Only headers of the canonical
+/// version and of the target version (see the [`target`](../target/index.html) module) can be
+/// verified, headers of other versions are rejected.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::blockchain::Blockchain;
+/// use blockchain::canonical::{verify_encoded_headers, Canonical};
+/// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+/// let bc = bc.append(vec![1], 4).unwrap().append(vec![2], 4).unwrap();
+/// let encoded = bc.iter_chronological()
+///     .map(|blk| blk.header().canonical_bytes())
+///     .collect::<Vec<_>>();
+/// assert!(verify_encoded_headers::<sha2::Sha256, _>(&encoded).is_ok());
+/// assert!(verify_encoded_headers::<sha2::Sha256, _>(&encoded[1..]).is_err());
+/// # }
+/// ```
+pub fn verify_encoded_headers<H, T>(headers: &[T]) -> Result<(), BlockchainError>
+where
+    H: ::digest::Digest,
+    T: AsRef<[u8]>,
+{
+    let hash_len = H::OutputSize::to_usize();
+    let fields_len = 1 + 2 * hash_len + 3 * 8;
+    let mut prev: Option<(GenericArray<u8, H::OutputSize>, u64)> = None;
+    for (height, header) in headers.iter().enumerate() {
+        let bytes = header.as_ref();
+        if bytes.len() < fields_len {
+            return Err(BlockchainError::InvalidEncoding(height));
+        }
+        let version = bytes[0];
+        let prev_hash = &bytes[1..1 + hash_len];
+        let time = decode_u64(&bytes[1 + 2 * hash_len..]);
+        let difficulty = decode_u64(&bytes[1 + 2 * hash_len + 8..]);
+        let hash = digest_encoded::<H>(HEADER_DOMAIN, bytes);
+        if version != CANONICAL_VERSION && version != TARGET_VERSION {
+            return Err(BlockchainError::UnknownVersion(version));
+        }
+        match prev {
+            Some((ref prev_hash_expected, prev_time)) => {
+                if prev_hash != &prev_hash_expected[..] {
+                    return Err(BlockchainError::InvalidPrevHash(
+                        HashBytes::from(prev_hash),
+                        HashBytes::from(&prev_hash_expected[..]),
+                    ));
+                }
+                if time < prev_time {
+                    return Err(BlockchainError::InvalidTime(time, prev_time));
+                }
+            }
+            None => {
+                if prev_hash.iter().any(|b| *b != 0) {
+                    return Err(BlockchainError::UnknownParent(HashBytes::from(prev_hash)));
+                }
+            }
+        }
+        let (bits, met) = if version == TARGET_VERSION {
+            if difficulty > u64::from(u32::max_value()) {
+                return Err(BlockchainError::InvalidTarget(difficulty));
+            }
+            let target = Target::from_compact(difficulty as u32)?;
+            (target.difficulty(), target.is_met_by(&hash))
+        } else {
+            let bits = difficulty as usize;
+            (bits, difficulty <= hash_len as u64 * 8 && leading_zeros(&hash) >= bits)
+        };
+        if !met {
+            return Err(BlockchainError::InvalidBlockHash(
+                HashBytes::from(&hash[..]),
+                bits,
+            ));
+        }
+        prev = Some((hash, time));
+    }
+    Ok(())
+}
+
+/// Decodes a big-endian `u64` from the first 8 bytes.
+fn decode_u64(bytes: &[u8]) -> u64 {
+    bytes[..8]
+        .iter()
+        .fold(0, |acc, byte| (acc << 8) | u64::from(*byte))
+}
+
+macro_rules! impl_canonical_int {
+    ($($ty:ty => $unsigned:ty, $width:expr;)*) => {
+        $(
+            impl Canonical for $ty {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    let value = *self as $unsigned;
+                    for i in (0..$width).rev() {
+                        out.push((value >> (8 * i)) as u8);
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_canonical_int! {
+    u8 => u8, 1;
+    u16 => u16, 2;
+    u32 => u32, 4;
+    u64 => u64, 8;
+    usize => u64, 8;
+    i8 => u8, 1;
+    i16 => u16, 2;
+    i32 => u32, 4;
+    i64 => u64, 8;
+    isize => u64, 8;
+}
+
+impl Canonical for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(if *self { 0x01 } else { 0x00 });
+    }
+}
+
+impl Canonical for () {
+    fn encode(&self, _out: &mut Vec<u8>) {}
+}
+
+impl<T> Canonical for [T]
+where
+    T: Canonical,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T> Canonical for Vec<T>
+where
+    T: Canonical,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self[..].encode(out)
+    }
+}
+
+impl Canonical for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode(out)
+    }
+}
+
+impl Canonical for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode(out)
+    }
+}
+
+impl<T> Canonical for Option<T>
+where
+    T: Canonical,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            None => out.push(0x00),
+            Some(ref value) => {
+                out.push(0x01);
+                value.encode(out);
+            }
+        }
+    }
+}
+
+impl<'a, T> Canonical for &'a T
+where
+    T: Canonical + ?Sized,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out)
+    }
+}
+
+// fixed size, so the length is not encoded
+impl<T, N> Canonical for GenericArray<T, N>
+where
+    T: Canonical,
+    N: ArrayLength<T>,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        for item in self.iter() {
+            item.encode(out);
+        }
+    }
+}
+
+/// Encodes bytes as lowercase hex, e.g. to compare them to golden vectors. (Only used for tests)
+#[cfg(test)]
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    HashBytes::from(bytes).to_string()
+}
+
+/// Decodes lowercase or uppercase hex, e.g. a golden vector. (Only used for tests)
+#[cfg(test)]
+pub(crate) fn unhex(hex: &str) -> Vec<u8> {
+    (0..hex.len() / 2)
+        .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blockchain::Blockchain;
+    use header::Header;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    fn golden_header<S: Default>(version: u8) -> Header<::sha2::Sha256, S> {
+        Header::new(
+            GenericArray::clone_from_slice(&[0x11; 32]),
+            GenericArray::clone_from_slice(&[0x22; 32]),
+            3,
+            0x0102_0304_0506_0708,
+        )
+        .set_nonce(0x2a, 0x0102_0304_0506_0708)
+        .set_version(version, GenericArray::clone_from_slice(&[0x22; 32]))
+    }
+
+    #[test]
+    fn integers_are_big_endian() {
+        assert_eq!(0x0102_0304u32.canonical_bytes(), vec![1, 2, 3, 4]);
+        assert_eq!((-2i16).canonical_bytes(), vec![0xff, 0xfe]);
+        assert_eq!((-1isize).canonical_bytes(), (-1i64).canonical_bytes());
+        assert_eq!(7usize.canonical_bytes(), 7u64.canonical_bytes());
+    }
+
+    #[test]
+    fn sequences_are_length_prefixed() {
+        assert_eq!(
+            "ab".canonical_bytes(),
+            vec![0, 0, 0, 0, 0, 0, 0, 2, b'a', b'b']
+        );
+        assert_eq!(
+            vec![vec![1u8], vec![]].canonical_bytes(),
+            vec![0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(None::<u8>.canonical_bytes(), vec![0]);
+    }
+
+    #[test]
+    fn golden_header_encoding() {
+        let expected = format!(
+            "02{}{}{}{}{}",
+            "11".repeat(32),
+            "22".repeat(32),
+            "0102030405060708",
+            "0000000000000003",
+            "000000000000002a"
+        );
+        let header: Header<::sha2::Sha256> = golden_header(CANONICAL_VERSION);
+        assert_eq!(hex(&header.canonical_bytes()), expected);
+    }
+
+    #[test]
+    fn golden_target_header_encoding() {
+        let expected = format!(
+            "03{}{}{}{}{}",
+            "11".repeat(32),
+            "22".repeat(32),
+            "0102030405060708",
+            "00000000201fffff",
+            "000000000000002a"
+        );
+        let header: Header<::sha2::Sha256> = golden_header(TARGET_VERSION);
+        assert_eq!(hex(&header.canonical_bytes()), expected);
+    }
+
+    #[test]
+    fn golden_header_hash() {
+        let header: Header<::sha2::Sha256> = golden_header(CANONICAL_VERSION);
+        assert_eq!(
+            hex(&header.hash()),
+            "8f61fbeafa45408408e742fc17567d523b43edbf288453fe81cad0f97719c228"
+        );
+        assert_eq!(
+            hex(&header.seal_hash()),
+            "062a8d953ed8bf92e9ddf205868537e6a5aac17308d665d3d60f1f6002b0736f"
+        );
+        // the seal hash is domain separated from the hash, even without a seal
+        assert!(header.seal_hash() != header.hash());
+        let sealed: Header<::sha2::Sha256, u16> = golden_header(CANONICAL_VERSION);
+        let sealed = sealed.set_seal(0xbeef);
+        assert_eq!(sealed.seal_hash(), header.seal_hash());
+        assert!(sealed.hash() != header.hash());
+        assert!(sealed.canonical_bytes().ends_with(&[0xbe, 0xef]));
+    }
+
+    #[test]
+    fn golden_leaf_hash() {
+        assert_eq!(
+            hex(&::merkle::leaf_hash::<::sha2::Sha256, _>(&0x0102_0304u32)),
+            "08bb5e5d6eaac1049ede0893d30ed022b1a4d9b5b48db414871f51c9cb35283d"
+        );
+        assert_eq!(
+            hex(&::merkle::leaf_hash::<::sha2::Sha256, _>(&"ab".to_owned())),
+            "fcec7bb096ab7ba60fd9412d4b56a3fe177f076780b79f75ed7d5356014dc8bb"
+        );
+    }
+
+    #[test]
+    fn tampered_encoding_is_rejected() {
+        let chain = Chain::new().append(vec![1], 4).unwrap().append(vec![2], 4).unwrap();
+        let mut encoded = chain
+            .iter_chronological()
+            .map(|blk| blk.header().canonical_bytes())
+            .collect::<Vec<_>>();
+        assert!(verify_encoded_headers::<::sha2::Sha256, _>(&encoded).is_ok());
+        // change the time of the genesis block, which breaks the linkage
+        encoded[0][70] ^= 0x01;
+        assert!(verify_encoded_headers::<::sha2::Sha256, _>(&encoded).is_err());
+        encoded[0].truncate(10);
+        match verify_encoded_headers::<::sha2::Sha256, _>(&encoded) {
+            Err(BlockchainError::InvalidEncoding(height)) => assert_eq!(height, 0),
+            _ => panic!("the genesis header is truncated"),
+        }
+    }
+
+    quickcheck! {
+        fn verifier_agrees_with_chain(blocks: Vec<Vec<u32>>) -> bool {
+            let chain = blocks
+                .into_iter()
+                .take(8)
+                .fold(Chain::new(), |chain, txs| chain.append(txs, 2).unwrap());
+            let encoded = chain
+                .iter_chronological()
+                .map(|blk| blk.header().canonical_bytes())
+                .collect::<Vec<_>>();
+            chain.validate_chain()
+                && verify_encoded_headers::<::sha2::Sha256, _>(&encoded).is_ok()
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! This module contains trusted checkpoints. A checkpoint pins the hash of the block at a certain
+//! height. Since each block contains the hash of its parent, a matching checkpoint also vouches for
+//! every block below it, so these blocks do not need to be validated again when a persisted chain
+//! is loaded (see
+//! [`ChainStore::load_with_checkpoints`](../store/struct.ChainStore.html#method.load_with_checkpoints)).
+
+use std::fmt::Debug;
+
+use block::Block;
+use blockchain::Blockchain;
+use consensus::ConsensusRules;
+use version::Hashable;
+
+use super::{BlockchainError, HashBytes};
+
+/// Trusted hash of the block at a certain height.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    height: usize,
+    hash: HashBytes,
+}
+
+impl Checkpoint {
+    /// Creates a checkpoint for the block with the given hash at the given height. The genesis
+    /// block has height `0`.
+    pub fn new(height: usize, hash: &[u8]) -> Self {
+        Self {
+            height: height,
+            hash: HashBytes::from(hash),
+        }
+    }
+
+    /// Returns the height of the block.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the trusted hash of the block.
+    pub fn hash(&self) -> &HashBytes {
+        &self.hash
+    }
+}
+
+/// List of checkpoints, ordered by height.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::checkpoint::{Checkpoint, Checkpoints};
+/// let checkpoints = Checkpoints::new(vec![Checkpoint::new(20, &[2]), Checkpoint::new(10, &[1])]);
+/// assert_eq!(checkpoints.last().map(|c| c.height()), Some(20));
+/// assert_eq!(checkpoints.last_below(20).map(|c| c.height()), Some(10));
+/// assert!(checkpoints.check(10, &[1]).is_ok());
+/// assert!(checkpoints.check(10, &[2]).is_err());
+/// // there is no checkpoint at height 15
+/// assert!(checkpoints.check(15, &[2]).is_ok());
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoints(Vec<Checkpoint>);
+
+impl Checkpoints {
+    /// Creates a list of checkpoints. If there are several checkpoints at the same height, only
+    /// the first one is kept.
+    pub fn new(checkpoints: Vec<Checkpoint>) -> Self {
+        let mut checkpoints = checkpoints;
+        // the sort is stable, so the first checkpoint of each height stays in front
+        checkpoints.sort_by_key(|checkpoint| checkpoint.height);
+        checkpoints.dedup_by_key(|checkpoint| checkpoint.height);
+        Checkpoints(checkpoints)
+    }
+
+    /// Returns the checkpoints, ordered by height.
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.0
+    }
+
+    /// Checks if there are no checkpoints.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the checkpoint at the given height.
+    pub fn get(&self, height: usize) -> Option<&Checkpoint> {
+        self.0
+            .binary_search_by_key(&height, |checkpoint| checkpoint.height)
+            .ok()
+            .map(|idx| &self.0[idx])
+    }
+
+    /// Returns the highest checkpoint.
+    pub fn last(&self) -> Option<&Checkpoint> {
+        self.0.last()
+    }
+
+    /// Returns the highest checkpoint below the given height.
+    pub fn last_below(&self, height: usize) -> Option<&Checkpoint> {
+        self.0
+            .iter()
+            .take_while(|checkpoint| checkpoint.height < height)
+            .last()
+    }
+
+    /// Checks if the hash of the block at the given height matches the checkpoint at that height.
+    /// Blocks at heights without a checkpoint always match.
+    pub fn check(&self, height: usize, hash: &[u8]) -> Result<(), BlockchainError> {
+        match self.get(height) {
+            Some(checkpoint) if &checkpoint.hash[..] != hash => {
+                Err(BlockchainError::CheckpointMismatch(
+                    height,
+                    HashBytes::from(hash),
+                    checkpoint.hash.clone(),
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<D, H, S> Blockchain<D, H, S>
+where
+    D: Default + Hashable,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Default + Hashable,
+{
+    /// Appends a new block like
+    /// [`insert_with_rules`](../blockchain/struct.Blockchain.html#method.insert_with_rules), but
+    /// additionally rejects the block if its hash does not match the checkpoint at its height.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::checkpoint::{Checkpoint, Checkpoints};
+    /// use blockchain::consensus::FixedDifficulty;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let checkpoints = Checkpoints::new(vec![Checkpoint::new(0, &[0; 32])]);
+    /// let block = bc.generate_block(vec![1], 0);
+    /// assert!(bc.insert_with_checkpoints(block, &FixedDifficulty(0), &checkpoints).is_err());
+    /// # }
+    /// ```
+    pub fn insert_with_checkpoints<R>(
+        &self,
+        block: Block<D, H, S>,
+        rules: &R,
+        checkpoints: &Checkpoints,
+    ) -> Result<Self, BlockchainError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        checkpoints.check(self.len(), &block.hash())?;
+        self.insert_with_rules(block, rules)
+    }
+
+    /// Appends a block, that is vouched for by a checkpoint at or above its height. Only the
+    /// linkage to the head of the chain, the genesis block of the chain's spec, the checkpoint at
+    /// the block's height and the Merkle root are checked. The checkpoint vouches for the header
+    /// only, so the transactions must still match its Merkle root.
+    pub(crate) fn insert_trusted(
+        &self,
+        block: Block<D, H, S>,
+        checkpoints: &Checkpoints,
+    ) -> Result<Self, BlockchainError> {
+        self.check_genesis(&block)?;
+        if !block.validate_merkle_root() {
+            return Err(BlockchainError::InvalidMerkleRoot(HashBytes::from(
+                &block.merkle_root()[..],
+            )));
+        }
+        let expected = self.iter().next().map(|head| head.hash()).unwrap_or_default();
+        if *block.prev_hash() != expected {
+            return Err(BlockchainError::InvalidPrevHash(
+                HashBytes::from(&block.prev_hash()[..]),
+                HashBytes::from(&expected[..]),
+            ));
+        }
+        checkpoints.check(self.len(), &block.hash())?;
+        Ok(self.push(block))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use PersistingError;
+    use consensus::FixedDifficulty;
+    use store::ChainStore;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    fn checkpoint(chain: &Chain, height: usize) -> Checkpoint {
+        Checkpoint::new(height, &chain.get_by_height(height).unwrap().hash())
+    }
+
+    fn stored(chain: &Chain) -> (::tempdir::TempDir, ChainStore<u32, ::sha2::Sha256>) {
+        let dir = ::tempdir::TempDir::new("checkpoints").unwrap();
+        let mut store = ChainStore::open(dir.path().join("chain")).unwrap();
+        for block in chain.iter_chronological() {
+            store.append(block).unwrap();
+        }
+        (dir, store)
+    }
+
+    #[test]
+    fn history_below_checkpoint_is_not_revalidated() {
+        let chain = (0..5).fold(Chain::new(), |chain, i| chain.append(vec![i], 2).unwrap());
+        let (_dir, store) = stored(&chain);
+        // the stored blocks do not meet the required difficulty
+        assert!(store.load_with_rules(&FixedDifficulty(16)).is_err());
+        let checkpoints = Checkpoints::new(vec![checkpoint(&chain, 4)]);
+        let loaded = store
+            .load_with_checkpoints(&FixedDifficulty(16), &checkpoints)
+            .unwrap();
+        assert_eq!(loaded, chain);
+    }
+
+    #[test]
+    fn blocks_above_checkpoint_are_validated() {
+        let chain = (0..5).fold(Chain::new(), |chain, i| chain.append(vec![i], 2).unwrap());
+        let (_dir, store) = stored(&chain);
+        let checkpoints = Checkpoints::new(vec![checkpoint(&chain, 2)]);
+        assert!(
+            store
+                .load_with_checkpoints(&FixedDifficulty(16), &checkpoints)
+                .is_err()
+        );
+        assert!(
+            store
+                .load_with_checkpoints(&FixedDifficulty(2), &checkpoints)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn mismatching_checkpoint_is_rejected() {
+        let chain = (0..3).fold(Chain::new(), |chain, i| chain.append(vec![i], 0).unwrap());
+        let other = (3..6).fold(Chain::new(), |chain, i| chain.append(vec![i], 0).unwrap());
+        let (_dir, store) = stored(&chain);
+        let checkpoints = Checkpoints::new(vec![checkpoint(&other, 1)]);
+        match chain
+            .slice(0, 1)
+            .insert_trusted(chain.get_by_height(1).unwrap().clone(), &checkpoints)
+        {
+            Err(BlockchainError::CheckpointMismatch(1, ref found, ref expected)) => {
+                assert_eq!(&found[..], &chain.get_by_height(1).unwrap().hash()[..]);
+                assert_eq!(expected, checkpoints.checkpoints()[0].hash());
+            }
+            _ => panic!("the checkpoint does not match"),
+        }
+        assert!(
+            store
+                .load_with_checkpoints(&FixedDifficulty(0), &checkpoints)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tampered_transactions_below_checkpoint_are_rejected() {
+        let chain = (0..5).fold(Chain::new(), |chain, i| chain.append(vec![i], 0).unwrap());
+        let checkpoints = Checkpoints::new(vec![checkpoint(&chain, 4)]);
+        // the header and therefore the hash of the block are kept
+        let tampered = chain
+            .get_by_height(1)
+            .unwrap()
+            .clone()
+            .replace_transactions(vec![42]);
+        match chain.slice(0, 1).insert_trusted(tampered.clone(), &checkpoints) {
+            Err(BlockchainError::InvalidMerkleRoot(_)) => {}
+            _ => panic!("the transactions do not match the Merkle root"),
+        }
+        let tampered = chain
+            .range(2, 5)
+            .fold(chain.slice(0, 1).push(tampered), |tampered, block| {
+                tampered.push(block.clone())
+            });
+        let (_dir, store) = stored(&tampered);
+        match store.load_with_checkpoints(&FixedDifficulty(0), &checkpoints) {
+            Err(PersistingError::InvalidBlock(1, BlockchainError::InvalidMerkleRoot(_))) => {}
+            _ => panic!("the body of block 1 has been tampered with"),
+        }
+    }
+
+    quickcheck! {
+        fn checkpoints_are_ordered(heights: Vec<usize>) -> bool {
+            let checkpoints = Checkpoints::new(
+                heights.iter().map(|h| Checkpoint::new(*h, &[])).collect(),
+            );
+            checkpoints
+                .checkpoints()
+                .windows(2)
+                .all(|pair| pair[0].height() < pair[1].height())
+                && heights.iter().all(|h| checkpoints.get(*h).is_some())
+        }
+    }
+}
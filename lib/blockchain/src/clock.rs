@@ -0,0 +1,137 @@
+//! This module contains clocks, that supply the current time when blocks are created, mined or
+//! validated. Besides the [`SystemClock`](struct.SystemClock.html), there is a
+//! [`FixedClock`](struct.FixedClock.html) that always returns the same time and a
+//! [`ManualClock`](struct.ManualClock.html) that only moves when it is advanced, so time based
+//! behaviour can be tested deterministically.
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time as Unix timestamp in seconds.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time in seconds since `1970-01-01`.
+    fn now(&self) -> u64;
+}
+
+/// Clock that returns the time of the operating system.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    /// Returns the system time. A system time before `1970-01-01` results in `0`.
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// Clock that always returns the same time.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::clock::{Clock, FixedClock};
+/// assert_eq!(FixedClock(1337).now(), 1337);
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Clock that only moves when it is advanced or set. Cloning the clock results in a handle to the
+/// same time, so it can be advanced while another component, e.g. a set of consensus rules, reads
+/// it.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::clock::{Clock, ManualClock};
+/// let clock = ManualClock::new(100);
+/// let handle = clock.clone();
+/// handle.advance(30 * 24 * 60 * 60);
+/// assert_eq!(clock.now(), 2_592_100);
+/// handle.set(5);
+/// assert_eq!(clock.now(), 5);
+/// handle.set(u64::max_value());
+/// assert_eq!(clock.now(), u64::max_value());
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock(Arc<Mutex<u64>>);
+
+impl ManualClock {
+    /// Creates a new clock starting at the given time.
+    pub fn new(time: u64) -> Self {
+        ManualClock(Arc::new(Mutex::new(time)))
+    }
+
+    /// Moves the clock `seconds` into the future. The time saturates at `u64::max_value()`.
+    pub fn advance(&self, seconds: u64) {
+        let mut time = self.time();
+        *time = time.saturating_add(seconds);
+    }
+
+    /// Sets the clock to the given time.
+    pub fn set(&self, time: u64) {
+        *self.time() = time;
+    }
+
+    /// Locks the time. The time is a plain number, that is always valid, so a poisoned lock is
+    /// used anyway.
+    fn time(&self) -> MutexGuard<u64> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        *self.time()
+    }
+}
+
+impl<T> Clock for Arc<T>
+where
+    T: Clock + ?Sized,
+{
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+impl<'a, T> Clock for &'a T
+where
+    T: Clock + ?Sized,
+{
+    fn now(&self) -> u64 {
+        (**self).now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_clock_is_after_2018() {
+        assert!(SystemClock.now() > 1_514_764_800);
+    }
+
+    #[test]
+    fn manual_clock_is_shared() {
+        let clock = ManualClock::new(0);
+        let shared: Arc<Clock> = Arc::new(clock.clone());
+        clock.advance(60);
+        assert_eq!(shared.now(), 60);
+        assert_eq!((&clock).now(), 60);
+    }
+}
@@ -0,0 +1,659 @@
+//! This module contains the consensus rules that are enforced when inserting blocks into a chain
+//! or validating a chain. The rules define the sealing scheme blocks need to be sealed with and the
+//! highest difficulty target and the version a block at a certain height needs to have. They also
+//! limit the time of a block, which must be later than the median time of the previous blocks and
+//! must not be too far ahead of the current time. Since the rules only depend on block headers,
+//! they can be enforced by light clients as well.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use generic_array::typenum::Unsigned;
+
+use block::VERSION;
+use clock::{Clock, SystemClock};
+use header::Header;
+use pow::PowAlgorithm;
+use seal::{ProofOfWork, SealingScheme};
+use target::Target;
+use version::VersionSchedule;
+
+/// Rules every block of a chain has to follow.
+pub trait ConsensusRules<H, S = ()>
+where
+    H: ::digest::Digest,
+{
+    /// The sealing scheme, blocks need to be sealed with.
+    type Scheme: SealingScheme<H, S>;
+
+    /// Returns the sealing scheme, blocks need to be sealed with.
+    fn scheme(&self) -> &Self::Scheme;
+
+    /// Returns the highest target of a block that is appended to the chain of `parent` headers.
+    /// The headers are iterated newest first, e.g. using
+    /// [`Blockchain::headers`](../blockchain/struct.Blockchain.html#method.headers).
+    fn required_target<'a, I>(&self, parent: I) -> Target
+    where
+        I: Iterator<Item = &'a Header<H, S>>,
+        H: 'a,
+        S: 'a;
+
+    /// Returns the minimum difficulty as number of leading zero bits of a block that is appended
+    /// to the chain of `parent` headers. A block with this difficulty meets the
+    /// [`required_target`](#tymethod.required_target).
+    fn required_difficulty<'a, I>(&self, parent: I) -> usize
+    where
+        I: Iterator<Item = &'a Header<H, S>>,
+        H: 'a,
+        S: 'a,
+    {
+        self.required_target(parent).min_difficulty()
+    }
+
+    /// Returns the version, a block at the given height needs to have. The genesis block has
+    /// height `0`. By default, every block needs to have the current version `block::VERSION`.
+    fn version_at(&self, _height: usize) -> u8 {
+        VERSION
+    }
+
+    /// Returns the number of parent blocks, whose median time the time of a block at the given
+    /// height needs to exceed (see [`median_time_past`](fn.median_time_past.html)). Independent of
+    /// this rule, the time of a block must never be earlier than the time of its parent. By
+    /// default (`0`), the median time is not checked.
+    fn median_time_span(&self, _height: usize) -> usize {
+        0
+    }
+
+    /// Returns the number of seconds, the time of a block at the given height may be ahead of the
+    /// current time (see [`now`](#tymethod.now)). By default, the time of a block is not limited.
+    fn max_future_drift(&self, _height: usize) -> Option<u64> {
+        None
+    }
+
+    /// Returns the current time as Unix timestamp, that limits the time of new blocks. Defaults to
+    /// the system time (see [`SystemClock`](../clock/struct.SystemClock.html)).
+    fn now(&self) -> u64 {
+        SystemClock.now()
+    }
+}
+
+/// Returns the median time of the latest `span` headers of the chain of `parent` headers (newest
+/// first). If the chain contains less than `span` headers, the median of all headers is returned.
+/// For an even number of headers, the lower of the two middle times is used. Returns `None` if
+/// there are no headers or `span` is `0`.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::blockchain::Blockchain;
+/// use blockchain::consensus::median_time_past;
+/// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+/// assert_eq!(median_time_past(bc.headers(), 11), None);
+/// let bc = bc.insert(bc.generate_block(vec![1], 0).set_nonce(0, 30)).unwrap();
+/// let bc = bc.insert(bc.generate_block(vec![2], 0).set_nonce(0, 40)).unwrap();
+/// let bc = bc.insert(bc.generate_block(vec![3], 0).set_nonce(0, 50)).unwrap();
+/// assert_eq!(median_time_past(bc.headers(), 11), Some(40));
+/// assert_eq!(median_time_past(bc.headers(), 2), Some(40));
+/// # }
+/// ```
+pub fn median_time_past<'a, H, S, I>(parent: I, span: usize) -> Option<u64>
+where
+    H: ::digest::Digest + 'a,
+    S: 'a,
+    I: Iterator<Item = &'a Header<H, S>>,
+{
+    let mut times = parent.take(span).map(|header| header.time()).collect::<Vec<_>>();
+    times.sort();
+    let middle = times.len().saturating_sub(1) / 2;
+    times.get(middle).cloned()
+}
+
+/// Proof of work rule set that requires every block to have at least a fixed difficulty.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FixedDifficulty(pub usize);
+
+impl<H, S> ConsensusRules<H, S> for FixedDifficulty
+where
+    H: ::digest::Digest,
+    S: ::version::Hashable,
+{
+    type Scheme = ProofOfWork;
+
+    fn scheme(&self) -> &ProofOfWork {
+        &ProofOfWork
+    }
+
+    fn required_target<'a, I>(&self, _parent: I) -> Target
+    where
+        I: Iterator<Item = &'a Header<H, S>>,
+        H: 'a,
+        S: 'a,
+    {
+        Target::from_difficulty(self.0)
+    }
+}
+
+/// Proof of work rule set that retargets the difficulty towards a target block interval. The
+/// timestamps of the last `window` blocks are compared to the expected timespan of
+/// `window * target_interval` seconds. The required target is the target, that was required for
+/// the latest block, scaled by the ratio of the actual to the expected timespan, so blocks that
+/// have been created twice as fast need twice the work. The ratio is limited to `[1/4, 4]` per
+/// block. The target of the latest block itself does not matter, so mining a block with a lower
+/// target than required does not raise the difficulty of the following blocks.
+///
+/// Until the chain contains more than `window` blocks, the initial difficulty is required. The
+/// required target never leaves the targets of the difficulty bounds `[min, max]` and never
+/// exceeds the hash length.
+///
+/// Since the required target of a block depends on the required targets of all its ancestors, the
+/// required targets are cached by the hash of the header they follow. Clones of the rules share
+/// the cache.
+///
+/// The versions of the blocks follow a [`VersionSchedule`](../version/struct.VersionSchedule.html),
+/// which requires the current version `block::VERSION` by default.
+///
+/// The time of a block must not be earlier than the time of its parent. Use
+/// [`median_time`](#method.median_time) and [`future_drift`](#method.future_drift) to enforce
+/// stricter time rules and [`time_rules_from`](#method.time_rules_from) to enforce them only from
+/// a certain height on.
+///
+/// Blocks are sealed using the leading zero proof of work over the header hash by default. Use
+/// [`pow`](#method.pow) to require a memory-hard proof of work instead.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::blockchain::Blockchain;
+/// use blockchain::consensus::{ConsensusRules, Retarget};
+/// let rules = Retarget::new(2, 60, 4);
+/// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+/// assert_eq!(rules.required_difficulty(bc.headers()), 2);
+/// let bc = (0..5).fold(bc, |bc, i| bc.append(vec![i], 2).unwrap());
+/// // five blocks within a few seconds are way faster than the target interval
+/// assert_eq!(rules.required_difficulty(bc.headers()), 4);
+/// # }
+/// ```
+///
+/// The rules can be persisted, e.g. as part of a [`ChainSpec`](../spec/struct.ChainSpec.html).
+/// The clock is not persisted, deserialized rules use the system time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Retarget {
+    initial: usize,
+    min: usize,
+    max: usize,
+    target_interval: u64,
+    window: usize,
+    versions: VersionSchedule,
+    median_span: usize,
+    max_drift: Option<u64>,
+    #[serde(default)]
+    time_activation: usize,
+    #[serde(default)]
+    pow: PowAlgorithm,
+    #[serde(skip_serializing, skip_deserializing, default = "system_clock")]
+    clock: Arc<Clock>,
+    #[serde(skip_serializing, skip_deserializing)]
+    cache: TargetCache,
+}
+
+/// Maximum factor, by which [`Retarget`](struct.Retarget.html) changes the target per block.
+const MAX_ADJUSTMENT: u64 = 4;
+/// Number of required targets, [`Retarget`](struct.Retarget.html) caches, before the cache is
+/// cleared.
+const CACHE_CAPACITY: usize = 4096;
+
+/// Required targets of the blocks following a header, keyed by the hash of the header.
+#[derive(Clone, Default)]
+struct TargetCache(Arc<Mutex<HashMap<Vec<u8>, Target>>>);
+
+impl fmt::Debug for TargetCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = self.0.lock().map(|cache| cache.len()).unwrap_or(0);
+        write!(f, "TargetCache({} targets)", len)
+    }
+}
+
+/// Returns the clock, deserialized rules use.
+fn system_clock() -> Arc<Clock> {
+    Arc::new(SystemClock)
+}
+
+impl Retarget {
+    /// Creates a new rule set starting at the `initial` difficulty, that is also used as lower
+    /// bound. `target_interval` is the desired time between two blocks in seconds and `window`
+    /// the number of blocks that are considered when retargeting.
+    ///
+    /// # Panics
+    /// Panics if `window` is `0`.
+    pub fn new(initial: usize, target_interval: u64, window: usize) -> Self {
+        assert!(window > 0, "The retarget window cannot be empty");
+        Self {
+            initial: initial,
+            min: initial,
+            max: usize::max_value(),
+            target_interval: target_interval,
+            window: window,
+            versions: VersionSchedule::default(),
+            median_span: 0,
+            max_drift: None,
+            time_activation: 0,
+            pow: PowAlgorithm::Hash,
+            clock: system_clock(),
+            cache: TargetCache::default(),
+        }
+    }
+
+    /// Sets the lower and upper bound for the required difficulty.
+    pub fn bounds(self, min: usize, max: usize) -> Self {
+        Self {
+            min: min,
+            max: max,
+            cache: TargetCache::default(),
+            ..self
+        }
+    }
+
+    /// Sets the schedule, that decides which version a block at a certain height needs to have.
+    pub fn versions(self, versions: VersionSchedule) -> Self {
+        Self {
+            versions: versions,
+            ..self
+        }
+    }
+
+    /// Returns the version schedule.
+    pub fn schedule(&self) -> &VersionSchedule {
+        &self.versions
+    }
+
+    /// Requires the time of a block to be later than the median time of the previous `span`
+    /// blocks.
+    pub fn median_time(self, span: usize) -> Self {
+        Self {
+            median_span: span,
+            ..self
+        }
+    }
+
+    /// Rejects blocks with a time of more than `seconds` ahead of the current time.
+    pub fn future_drift(self, seconds: u64) -> Self {
+        Self {
+            max_drift: Some(seconds),
+            ..self
+        }
+    }
+
+    /// Enforces the time rules set by [`median_time`](#method.median_time) and
+    /// [`future_drift`](#method.future_drift) only for blocks from the given height on, so the
+    /// blocks of a chain, that has been created without these rules, stay valid. By default, the
+    /// rules are enforced from the genesis block on.
+    pub fn time_rules_from(self, height: usize) -> Self {
+        Self {
+            time_activation: height,
+            ..self
+        }
+    }
+
+    /// Sets the proof of work function, blocks need to be sealed with (see the
+    /// [`pow`](../pow/index.html) module).
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::clock::SystemClock;
+    /// use blockchain::consensus::Retarget;
+    /// use blockchain::pow::{MemoryHard, PowAlgorithm};
+    /// let pow = PowAlgorithm::MemoryHard(MemoryHard::new(64, 1, 1).unwrap());
+    /// let rules = Retarget::new(2, 60, 16).pow(pow);
+    /// let block = Block::new(vec![42], 2)
+    ///     .proof_of_work_with_pow(rules.pow_algorithm(), &SystemClock);
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// assert!(bc.insert_with_rules(block, &rules).is_ok());
+    /// # }
+    /// ```
+    pub fn pow(self, pow: PowAlgorithm) -> Self {
+        Self {
+            pow: pow,
+            ..self
+        }
+    }
+
+    /// Returns the proof of work function, blocks need to be sealed with.
+    pub fn pow_algorithm(&self) -> &PowAlgorithm {
+        &self.pow
+    }
+
+    /// Sets the clock, that limits the time of new blocks. Defaults to the system time.
+    pub fn clock<C>(self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        Self {
+            clock: Arc::new(clock),
+            ..self
+        }
+    }
+
+    fn clamp<H>(&self, target: Target) -> Target
+    where
+        H: ::digest::Digest,
+    {
+        let hash_bits = H::OutputSize::to_usize() * 8;
+        cmp::max(
+            cmp::min(target, Target::from_difficulty(self.min)),
+            Target::from_difficulty(cmp::min(self.max, hash_bits)),
+        )
+    }
+}
+
+impl<H, S> ConsensusRules<H, S> for Retarget
+where
+    H: ::digest::Digest,
+    S: ::version::Hashable,
+{
+    type Scheme = PowAlgorithm;
+
+    fn scheme(&self) -> &PowAlgorithm {
+        &self.pow
+    }
+
+    fn required_target<'a, I>(&self, parent: I) -> Target
+    where
+        I: Iterator<Item = &'a Header<H, S>>,
+        H: 'a,
+        S: 'a,
+    {
+        let initial = self.clamp::<H>(Target::from_difficulty(self.initial));
+        let mut cache = self.cache.0.lock().unwrap_or_else(PoisonError::into_inner);
+        // collect the headers back to the latest one with a cached successor target and the
+        // `window` headers before it, which are needed to calculate the timespans
+        let mut headers: Vec<(&Header<H, S>, _)> = Vec::new();
+        let mut known = None;
+        let mut linked = true;
+        for header in parent {
+            let hash = header.hash();
+            linked = linked
+                && headers
+                    .last()
+                    .map_or(true, |&(newer, _)| newer.prev_hash() == &hash);
+            if known.is_none() {
+                known = cache.get(&hash[..]).map(|target| (headers.len(), *target));
+            }
+            headers.push((header, hash));
+            if known.map_or(false, |(idx, _)| idx == 0 || headers.len() >= idx + self.window) {
+                break;
+            }
+        }
+        let (start, mut target) = known.unwrap_or((headers.len(), initial));
+        let expected = cmp::max(
+            self.target_interval.saturating_mul(self.window as u64),
+            1,
+        );
+        for idx in (0..start).rev() {
+            // the successor of the header at `idx` follows a chain of `headers.len() - idx` blocks
+            target = match headers.get(idx + self.window) {
+                Some(&(first, _)) => {
+                    let actual = cmp::min(
+                        cmp::max(
+                            headers[idx].0.time().saturating_sub(first.time()),
+                            expected / MAX_ADJUSTMENT,
+                        ),
+                        expected.saturating_mul(MAX_ADJUSTMENT),
+                    );
+                    self.clamp::<H>(target.scale(actual, expected))
+                }
+                None => initial,
+            };
+        }
+        // cached targets must only depend on the ancestors of the header, they are stored for
+        if linked {
+            if let Some(&(_, ref hash)) = headers.first() {
+                if cache.len() >= CACHE_CAPACITY {
+                    cache.clear();
+                }
+                cache.insert(hash.to_vec(), target);
+            }
+        }
+        target
+    }
+
+    fn version_at(&self, height: usize) -> u8 {
+        self.versions.version_at(height)
+    }
+
+    fn median_time_span(&self, height: usize) -> usize {
+        if height < self.time_activation {
+            0
+        } else {
+            self.median_span
+        }
+    }
+
+    fn max_future_drift(&self, height: usize) -> Option<u64> {
+        if height < self.time_activation {
+            None
+        } else {
+            self.max_drift
+        }
+    }
+
+    fn now(&self) -> u64 {
+        self.clock.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use block::Block;
+    use blockchain::Blockchain;
+    use clock::FixedClock;
+    use BlockchainError;
+
+    type Chain = Blockchain<u8, ::sha2::Sha256>;
+
+    /// Builds a chain of blocks with the given difficulty, created at the supplied times.
+    fn chain_with_times(times: &[u64], difficulty: usize) -> Chain {
+        times.iter().fold(Chain::new(), |chain, &time| {
+            let prev = chain.iter().next().map(|b| b.hash()).unwrap_or_default();
+            let mut block =
+                Block::new_with_hash(vec![0], prev, difficulty).set_nonce(0, time);
+            while !block.validate_difficulty() {
+                let nonce = block.nonce() + 1;
+                block = block.set_nonce(nonce, time);
+            }
+            chain.insert(block).unwrap()
+        })
+    }
+
+    #[test]
+    fn fixed_difficulty() {
+        let chain = chain_with_times(&[0, 1, 2], 3);
+        assert_eq!(FixedDifficulty(5).required_difficulty(chain.headers()), 5);
+    }
+
+    #[test]
+    fn retarget_keeps_difficulty_on_target() {
+        let rules = Retarget::new(2, 10, 2);
+        let chain = chain_with_times(&[0, 10, 20, 30], 2);
+        assert_eq!(rules.required_difficulty(chain.headers()), 2);
+    }
+
+    #[test]
+    fn retarget_ignores_target_of_latest_block() {
+        let rules = Retarget::new(2, 10, 2);
+        let chain = chain_with_times(&[0, 10, 20], 4);
+        assert_eq!(rules.required_difficulty(chain.headers()), 2);
+        let chain = chain_with_times(&[0, 10, 20], 6);
+        assert_eq!(
+            rules.required_target(chain.headers()),
+            Target::from_difficulty(2)
+        );
+    }
+
+    #[test]
+    fn retarget_lowers_difficulty_for_slow_blocks() {
+        let rules = Retarget::new(4, 10, 2).bounds(2, 8);
+        let chain = chain_with_times(&[0, 30, 60], 4);
+        assert_eq!(rules.required_difficulty(chain.headers()), 3);
+        // the target required for the previous block is scaled again
+        let chain = chain_with_times(&[0, 30, 60, 90], 4);
+        assert_eq!(
+            rules.required_target(chain.headers()),
+            Target::from_difficulty(2)
+        );
+    }
+
+    #[test]
+    fn retarget_raises_difficulty_for_fast_blocks() {
+        let rules = Retarget::new(2, 10, 2).bounds(2, 5);
+        let chain = chain_with_times(&[0, 1, 2], 2);
+        assert_eq!(rules.required_difficulty(chain.headers()), 4);
+        let chain = chain_with_times(&[0, 1, 2, 3], 4);
+        assert_eq!(rules.required_difficulty(chain.headers()), 5);
+    }
+
+    #[test]
+    fn retarget_derives_target_from_required_target_of_parent() {
+        let rules = Retarget::new(4, 10, 2).bounds(0, 16);
+        let expected = Target::from_difficulty(4).scale(9, 1);
+        let chain = chain_with_times(&[0, 30, 60, 90], 8);
+        assert_eq!(rules.required_target(chain.headers()), expected);
+        // the cached targets of the previous blocks lead to the same result
+        assert_eq!(rules.required_target(chain.headers()), expected);
+        let chain = chain_with_times(&[0, 30, 60, 90], 12);
+        assert_eq!(rules.clone().required_target(chain.headers()), expected);
+        let rules = Retarget::new(4, 10, 2).bounds(0, 16);
+        assert_eq!(rules.required_target(chain.headers()), expected);
+    }
+
+    #[test]
+    fn retarget_adjusts_target_proportionally() {
+        let rules = Retarget::new(4, 10, 2).bounds(2, 8);
+        let chain = chain_with_times(&[0, 15, 30], 4);
+        let target = rules.required_target(chain.headers());
+        assert_eq!(target, Target::from_difficulty(4).scale(3, 2));
+        assert!(target > Target::from_difficulty(4) && target < Target::from_difficulty(3));
+        assert_eq!(rules.required_difficulty(chain.headers()), 4);
+        // a block with the required target is accepted, one with the next higher target is not
+        let block = chain
+            .generate_block(vec![1], 0)
+            .set_target(target)
+            .set_nonce(0, 40)
+            .proof_of_work_with_clock(&FixedClock(40));
+        let next = chain.insert_with_rules(block, &rules).unwrap();
+        // the next target is derived from the required target, not the target of the block
+        assert_eq!(
+            rules.required_target(next.headers()),
+            target.scale(25, 20)
+        );
+        let easier = Target::from_compact(target.to_compact() + 1).unwrap();
+        let block = chain
+            .generate_block(vec![1], 0)
+            .set_target(easier)
+            .proof_of_work_with_clock(&FixedClock(40));
+        match chain.insert_with_rules(block, &rules) {
+            Err(BlockchainError::InsufficientDifficulty(actual, required)) => {
+                assert!(actual > required && required == target)
+            }
+            _ => panic!("the target is higher than required"),
+        }
+    }
+
+    #[test]
+    fn retarget_uses_initial_difficulty_for_short_chains() {
+        let rules = Retarget::new(2, 10, 2);
+        let chain = chain_with_times(&[0, 1], 7);
+        assert_eq!(rules.required_difficulty(chain.headers()), 2);
+    }
+
+    fn next_block(chain: &Chain, time: u64) -> Block<u8, ::sha2::Sha256> {
+        chain.generate_block(vec![1], 0).set_nonce(0, time)
+    }
+
+    #[test]
+    fn time_must_exceed_median_time_past() {
+        let rules = Retarget::new(0, 10, 2).bounds(0, 0).median_time(3);
+        let chain = chain_with_times(&[10, 20, 30, 40], 0);
+        assert_eq!(median_time_past(chain.headers(), 3), Some(30));
+        match chain.insert_with_rules(next_block(&chain, 31), &rules) {
+            Err(BlockchainError::InvalidTime(31, 40)) => (),
+            _ => panic!("the time must not be earlier than the parent"),
+        }
+        let chain = chain.insert_with_rules(next_block(&chain, 40), &rules).unwrap();
+        // the median is now the time of the parent
+        match chain.insert_with_rules(next_block(&chain, 40), &rules) {
+            Err(BlockchainError::TimeNotAfterMedian(40, 40)) => (),
+            _ => panic!("the time must be later than the median"),
+        }
+        assert!(chain.insert_with_rules(next_block(&chain, 41), &rules).is_ok());
+        assert!(chain.validate_chain_with_rules(&rules));
+    }
+
+    #[test]
+    fn time_must_not_drift_into_the_future() {
+        let rules = Retarget::new(0, 10, 2).future_drift(60).clock(FixedClock(1000));
+        let chain = chain_with_times(&[10], 0);
+        assert!(chain.insert_with_rules(next_block(&chain, 1060), &rules).is_ok());
+        match chain.insert_with_rules(next_block(&chain, 1061), &rules) {
+            Err(BlockchainError::FutureTime(1061, 1000, 60)) => (),
+            _ => panic!("the time is too far in the future"),
+        }
+        assert!(chain.validate_chain_with_rules(&rules));
+    }
+
+    #[test]
+    fn time_rules_are_activated_by_height() {
+        let rules = Retarget::new(0, 10, 2)
+            .bounds(0, 0)
+            .median_time(3)
+            .future_drift(60)
+            .clock(FixedClock(1000))
+            .time_rules_from(3);
+        // blocks before the activation may repeat the time of their parent and be far ahead
+        let chain = chain_with_times(&[2000, 2000], 0);
+        assert!(chain.validate_chain_with_rules(&rules));
+        let chain = chain.insert_with_rules(next_block(&chain, 2000), &rules).unwrap();
+        match chain.insert_with_rules(next_block(&chain, 2001), &rules) {
+            Err(BlockchainError::FutureTime(2001, 1000, 60)) => (),
+            _ => panic!("the time is too far in the future"),
+        }
+        let rules = rules.clock(FixedClock(2000));
+        match chain.insert_with_rules(next_block(&chain, 2000), &rules) {
+            Err(BlockchainError::TimeNotAfterMedian(2000, 2000)) => (),
+            _ => panic!("the time must be later than the median"),
+        }
+        let chain = chain.insert_with_rules(next_block(&chain, 2001), &rules).unwrap();
+        assert!(chain.validate_chain_with_rules(&rules));
+    }
+
+    quickcheck! {
+        fn median_is_a_parent_time(times: Vec<u64>, span: usize) -> bool {
+            let mut times = times.into_iter().take(8).collect::<Vec<_>>();
+            times.sort();
+            let chain = chain_with_times(&times, 0);
+            let span = span % 10;
+            match median_time_past(chain.headers(), span) {
+                Some(median) => {
+                    let window = chain.headers().take(span).collect::<Vec<_>>();
+                    let earlier = window.iter().filter(|h| h.time() < median).count();
+                    let later = window.iter().filter(|h| h.time() > median).count();
+                    times.contains(&median) && earlier <= window.len() / 2
+                        && later <= window.len() / 2
+                }
+                None => span == 0 || times.is_empty(),
+            }
+        }
+    }
+}
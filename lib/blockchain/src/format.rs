@@ -0,0 +1,309 @@
+//! This module contains the header of persisted chain files. It is shared by the append-only
+//! [`ChainStore`](../store/struct.ChainStore.html) and the snapshots written by
+//! [`Blockchain::persist_to_disk`](../blockchain/struct.Blockchain.html#method.persist_to_disk).
+//!
+//! The header consists of
+//!
+//! * the magic bytes of the file type (4 bytes), `EBSC` for stores and `EBSS` for snapshots,
+//! * the version of the file format (1 byte),
+//! * the id of the hash algorithm of the chain (1 byte, see
+//!   [`HashAlgorithm::id`](../hash/enum.HashAlgorithm.html#method.id)),
+//! * the length of the chain id (1 byte) followed by the UTF-8 encoded chain id and
+//! * a CRC-32 checksum of the previous fields (4 bytes, little endian).
+//!
+//! The header of stores of format version `1` ends after the id of the hash algorithm.
+
+use std::io::{self, ErrorKind, Read, Write};
+
+use hash::HashAlgorithm;
+
+use super::PersistingError;
+
+/// Magic bytes at the start of a store.
+pub(crate) const STORE_MAGIC: &[u8; 4] = b"EBSC";
+/// Magic bytes at the start of a snapshot.
+pub(crate) const SNAPSHOT_MAGIC: &[u8; 4] = b"EBSS";
+/// Version of the file format, that is written.
+pub const FORMAT_VERSION: u8 = 2;
+/// Version of the file format of stores, whose header contains neither the chain id nor a
+/// checksum.
+const LEGACY_FORMAT_VERSION: u8 = 1;
+/// Length of the magic bytes, the format version and the hash algorithm in bytes.
+const FIXED_LEN: u64 = 6;
+
+/// Header of a persisted chain file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHeader {
+    version: u8,
+    algorithm: HashAlgorithm,
+    chain_id: String,
+}
+
+impl FileHeader {
+    /// Creates a header of the current format version. An empty chain id is used for chains
+    /// without a spec. Fails, if the chain id is longer than 255 bytes.
+    pub fn new(algorithm: HashAlgorithm, chain_id: &str) -> Result<Self, PersistingError> {
+        if chain_id.len() > u8::max_value() as usize {
+            return Err(PersistingError::SerializingError);
+        }
+        Ok(Self {
+            version: FORMAT_VERSION,
+            algorithm: algorithm,
+            chain_id: chain_id.to_owned(),
+        })
+    }
+
+    /// Returns the version of the file format.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the hash algorithm of the chain.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns the id of the chain or `None`, if the chain has been persisted without a spec.
+    pub fn chain_id(&self) -> Option<&str> {
+        if self.chain_id.is_empty() {
+            None
+        } else {
+            Some(&self.chain_id)
+        }
+    }
+
+    /// Returns the length of the encoded header in bytes.
+    pub fn encoded_len(&self) -> u64 {
+        if self.version == LEGACY_FORMAT_VERSION {
+            FIXED_LEN
+        } else {
+            FIXED_LEN + 1 + self.chain_id.len() as u64 + 4
+        }
+    }
+
+    /// Encodes the header using the given magic bytes.
+    pub(crate) fn encode(&self, magic: &[u8; 4]) -> Vec<u8> {
+        let mut header = Vec::with_capacity(self.encoded_len() as usize);
+        header.extend_from_slice(magic);
+        header.push(self.version);
+        header.push(self.algorithm.id());
+        header.push(self.chain_id.len() as u8);
+        header.extend_from_slice(self.chain_id.as_bytes());
+        let checksum = crc32(&header);
+        header.extend_from_slice(&encode_u32(checksum));
+        header
+    }
+
+    /// Reads a header, that starts with the given magic bytes. Headers of format version `1` are
+    /// only accepted for stores. The fields of the header are validated as soon as they are read,
+    /// so [`Truncated`](../enum.PersistingError.html#variant.Truncated) is only returned, if the
+    /// available fields are valid.
+    pub(crate) fn read<R: Read>(reader: &mut R, magic: &[u8; 4]) -> Result<Self, PersistingError> {
+        let mut fixed = [0; FIXED_LEN as usize];
+        read_exact(reader, &mut fixed[..4], 0)?;
+        if &fixed[..4] != magic {
+            return Err(PersistingError::WrongMagic);
+        }
+        read_exact(reader, &mut fixed[4..5], 4)?;
+        let version = fixed[4];
+        match version {
+            FORMAT_VERSION => {}
+            LEGACY_FORMAT_VERSION if magic == STORE_MAGIC => {}
+            _ => return Err(PersistingError::UnsupportedVersion(version)),
+        }
+        read_exact(reader, &mut fixed[5..], 5)?;
+        let algorithm = HashAlgorithm::from_id(fixed[5])
+            .ok_or(PersistingError::UnknownHashAlgorithm(fixed[5]))?;
+        if version == LEGACY_FORMAT_VERSION {
+            return Ok(Self {
+                version: version,
+                algorithm: algorithm,
+                chain_id: String::new(),
+            });
+        }
+        let mut len = [0; 1];
+        read_exact(reader, &mut len, FIXED_LEN)?;
+        let mut chain_id = vec![0; len[0] as usize];
+        read_exact(reader, &mut chain_id, FIXED_LEN + 1)?;
+        let mut checksum = [0; 4];
+        read_exact(reader, &mut checksum, FIXED_LEN + 1 + chain_id.len() as u64)?;
+        let expected = crc32_update(crc32_update(crc32(&fixed), &len), &chain_id);
+        if decode_u32(&checksum) != expected {
+            return Err(PersistingError::ChecksumMismatch(0));
+        }
+        Ok(Self {
+            version: version,
+            algorithm: algorithm,
+            chain_id: String::from_utf8(chain_id)
+                .map_err(|_| PersistingError::DeserializingError)?,
+        })
+    }
+}
+
+/// Reader or writer, that calculates the CRC-32 checksum and counts the offset of the data passing
+/// through it.
+pub(crate) struct Checksummed<T> {
+    inner: T,
+    checksum: u32,
+    offset: u64,
+}
+
+impl<T> Checksummed<T> {
+    /// Wraps a reader or writer, that is positioned at the given offset.
+    pub(crate) fn new(inner: T, offset: u64) -> Self {
+        Self {
+            inner: inner,
+            checksum: 0,
+            offset: offset,
+        }
+    }
+
+    /// Returns the checksum of the data, that has passed through so far.
+    pub(crate) fn checksum(&self) -> u32 {
+        self.checksum
+    }
+
+    /// Returns the offset of the next byte.
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.checksum = crc32_update(self.checksum, &buf[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.checksum = crc32_update(self.checksum, &buf[..n]);
+        self.offset += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Fills the buffer with the data starting at the given offset. Fails with
+/// [`Truncated`](../enum.PersistingError.html#variant.Truncated), if the data ends before.
+pub(crate) fn read_exact<R: Read>(
+    reader: &mut R,
+    buf: &mut [u8],
+    offset: u64,
+) -> Result<(), PersistingError> {
+    reader.read_exact(buf).map_err(|err| {
+        if err.kind() == ErrorKind::UnexpectedEof {
+            PersistingError::Truncated(offset)
+        } else {
+            PersistingError::IoError
+        }
+    })
+}
+
+/// Encodes a number in little endian byte order.
+pub(crate) fn encode_u32(n: u32) -> [u8; 4] {
+    [n as u8, (n >> 8) as u8, (n >> 16) as u8, (n >> 24) as u8]
+}
+
+/// Decodes a number in little endian byte order.
+pub(crate) fn decode_u32(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, byte| (acc << 8) | u32::from(*byte))
+}
+
+/// Calculates the CRC-32 (IEEE) checksum of the data.
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+/// Continues the CRC-32 checksum of the previous data with the following data.
+fn crc32_update(checksum: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!checksum, |crc, byte| {
+        (0..8).fold(crc ^ u32::from(*byte), |crc, _| {
+            if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32_update(crc32(b"1234"), b"56789"), 0xcbf4_3926);
+        assert_eq!(decode_u32(&encode_u32(0x1234_5678)), 0x1234_5678);
+    }
+
+    #[test]
+    fn header_can_be_read() {
+        let header = FileHeader::new(HashAlgorithm::Blake2b, "test").unwrap();
+        let bytes = header.encode(SNAPSHOT_MAGIC);
+        assert_eq!(bytes.len() as u64, header.encoded_len());
+        assert_eq!(
+            FileHeader::read(&mut &bytes[..], SNAPSHOT_MAGIC).unwrap(),
+            header
+        );
+        assert_eq!(header.chain_id(), Some("test"));
+        let header = FileHeader::new(HashAlgorithm::Sha256, "").unwrap();
+        let bytes = header.encode(STORE_MAGIC);
+        let read = FileHeader::read(&mut &bytes[..], STORE_MAGIC).unwrap();
+        assert_eq!(read.chain_id(), None);
+        assert!(FileHeader::new(HashAlgorithm::Sha256, &"x".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn invalid_headers_are_reported() {
+        let bytes = FileHeader::new(HashAlgorithm::Sha256, "test")
+            .unwrap()
+            .encode(SNAPSHOT_MAGIC);
+        match FileHeader::read(&mut &bytes[..], STORE_MAGIC) {
+            Err(PersistingError::WrongMagic) => {}
+            _ => panic!("the magic bytes of a snapshot differ"),
+        }
+        let mut damaged = bytes.clone();
+        damaged[7] ^= 1;
+        match FileHeader::read(&mut &damaged[..], SNAPSHOT_MAGIC) {
+            Err(PersistingError::ChecksumMismatch(0)) => {}
+            _ => panic!("the chain id is damaged"),
+        }
+        let mut future = bytes.clone();
+        future[4] = FORMAT_VERSION + 1;
+        match FileHeader::read(&mut &future[..], SNAPSHOT_MAGIC) {
+            Err(PersistingError::UnsupportedVersion(version)) => {
+                assert_eq!(version, FORMAT_VERSION + 1)
+            }
+            _ => panic!("the version is not supported"),
+        }
+        // the version is checked, even if the header ends after it
+        match FileHeader::read(&mut &future[..5], SNAPSHOT_MAGIC) {
+            Err(PersistingError::UnsupportedVersion(_)) => {}
+            _ => panic!("the version is not supported"),
+        }
+        match FileHeader::read(&mut &bytes[..8], SNAPSHOT_MAGIC) {
+            Err(PersistingError::Truncated(7)) => {}
+            _ => panic!("the header ends within the chain id"),
+        }
+        // only stores have headers of version 1
+        let legacy = [b'E', b'B', b'S', b'S', LEGACY_FORMAT_VERSION, 1];
+        assert!(FileHeader::read(&mut &legacy[..], SNAPSHOT_MAGIC).is_err());
+        let legacy = [b'E', b'B', b'S', b'C', LEGACY_FORMAT_VERSION, 1];
+        let header = FileHeader::read(&mut &legacy[..], STORE_MAGIC).unwrap();
+        assert_eq!(header.encoded_len(), 6);
+        assert_eq!(header.hash_algorithm(), HashAlgorithm::Sha256);
+    }
+}
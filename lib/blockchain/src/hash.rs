@@ -0,0 +1,371 @@
+//! This module contains the hash algorithms, that can be used to hash the blocks of a chain. The
+//! algorithm of a chain is a type parameter of its blocks, so it is checked at compile time.
+//! Each algorithm has an id, that is persisted in the header of a
+//! [`ChainStore`](../store/struct.ChainStore.html), so a store can be opened using the right type
+//! at runtime (see [`store::hash_algorithm`](../store/fn.hash_algorithm.html)).
+//!
+//! A chain can be re-linked under another algorithm using
+//! [`Blockchain::rehash`](../blockchain/struct.Blockchain.html#method.rehash). The transactions of
+//! the blocks are kept as they are, but every block is mined again, since its hash changes.
+
+use std::fmt;
+
+use generic_array::GenericArray;
+
+use block::Block;
+use blockchain::Blockchain;
+use clock::FixedClock;
+use pow::{PowAlgorithm, PowFunction};
+use seal::ProofOfWork;
+use spec::ChainSpec;
+use version::Hashable;
+
+pub use blake2::Blake2b;
+pub use sha2::{Sha256, Sha512Trunc256};
+pub use sha3::Sha3_256;
+
+/// Hash value of the hash function `H`.
+pub type Output<H> = GenericArray<u8, <H as ::digest::FixedOutput>::OutputSize>;
+
+/// Hash algorithms, that can be used to hash the blocks of a chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    /// SHA-256. Chains, that were persisted without a record of their algorithm, use SHA-256.
+    Sha256,
+    /// SHA-512 truncated to 256 bits.
+    Sha512Trunc256,
+    /// SHA3-256.
+    Sha3_256,
+    /// BLAKE2b with 512 bits of output.
+    Blake2b,
+}
+
+/// All supported hash algorithms.
+pub const ALGORITHMS: &[HashAlgorithm] = &[
+    HashAlgorithm::Sha256,
+    HashAlgorithm::Sha512Trunc256,
+    HashAlgorithm::Sha3_256,
+    HashAlgorithm::Blake2b,
+];
+
+impl HashAlgorithm {
+    /// Returns the id of the algorithm, that is used to persist it.
+    pub fn id(&self) -> u8 {
+        match *self {
+            HashAlgorithm::Sha256 => 1,
+            HashAlgorithm::Sha512Trunc256 => 2,
+            HashAlgorithm::Sha3_256 => 3,
+            HashAlgorithm::Blake2b => 4,
+        }
+    }
+
+    /// Returns the algorithm with the given id.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::hash::HashAlgorithm;
+    /// let id = HashAlgorithm::Sha3_256.id();
+    /// assert_eq!(HashAlgorithm::from_id(id), Some(HashAlgorithm::Sha3_256));
+    /// assert_eq!(HashAlgorithm::from_id(0), None);
+    /// # }
+    /// ```
+    pub fn from_id(id: u8) -> Option<Self> {
+        ALGORITHMS.iter().find(|algorithm| algorithm.id() == id).cloned()
+    }
+
+    /// Returns the name of the algorithm, e.g. to select it on the command line.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Sha512Trunc256 => "sha512-256",
+            HashAlgorithm::Sha3_256 => "sha3-256",
+            HashAlgorithm::Blake2b => "blake2b",
+        }
+    }
+
+    /// Returns the algorithm with the given name. The name is not case sensitive.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::hash::HashAlgorithm;
+    /// assert_eq!(HashAlgorithm::from_name("SHA3-256"), Some(HashAlgorithm::Sha3_256));
+    /// assert_eq!(HashAlgorithm::from_name("md5"), None);
+    /// # }
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        ALGORITHMS
+            .iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> Self {
+        HashAlgorithm::Sha256
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Hash functions, that implement one of the supported hash algorithms.
+pub trait HashFunction: ::digest::Digest + Clone + Send + Sync + 'static {
+    /// The algorithm implemented by the hash function.
+    const ALGORITHM: HashAlgorithm;
+}
+
+impl HashFunction for Sha256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha256;
+}
+
+impl HashFunction for Sha512Trunc256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha512Trunc256;
+}
+
+impl HashFunction for Sha3_256 {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha3_256;
+}
+
+impl HashFunction for Blake2b {
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Blake2b;
+}
+
+impl<D, H> Block<D, H>
+where
+    D: Hashable + Clone,
+    H: ::digest::Digest,
+{
+    /// Re-links the block under the hash function `H2`. The new block has the given `prev_hash`
+    /// and the same transactions, version, difficulty and time as this block. The Merkle root is
+    /// calculated using `H2` and the block is mined again, so it matches its difficulty.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::hash::Sha3_256;
+    /// let block: Block<_, sha2::Sha256> = Block::new(vec![1, 2], 4).proof_of_work();
+    /// let rehashed: Block<_, Sha3_256> = block.rehash(Default::default());
+    /// assert_eq!(rehashed.transactions(), block.transactions());
+    /// assert_eq!(rehashed.time(), block.time());
+    /// assert!(rehashed.validate_difficulty());
+    /// # }
+    /// ```
+    pub fn rehash<H2>(&self, prev_hash: Output<H2>) -> Block<D, H2>
+    where
+        H2: ::digest::Digest,
+    {
+        self.rehash_with_pow(prev_hash, &ProofOfWork)
+    }
+
+    /// Re-links the block like [`rehash`](#method.rehash), but mines it using the given proof of
+    /// work function.
+    pub fn rehash_with_pow<H2, P>(&self, prev_hash: Output<H2>, pow: &P) -> Block<D, H2>
+    where
+        H2: ::digest::Digest,
+        P: PowFunction<H2, ()> + ?Sized,
+    {
+        self.relink_with_pow(prev_hash, Default::default(), pow)
+    }
+
+    /// Re-links the block like [`rehash_with_pow`](#method.rehash_with_pow), but additionally
+    /// sets the skip hash of blocks, that contain one.
+    fn relink_with_pow<H2, P>(
+        &self,
+        prev_hash: Output<H2>,
+        skip_hash: Output<H2>,
+        pow: &P,
+    ) -> Block<D, H2>
+    where
+        H2: ::digest::Digest,
+        P: PowFunction<H2, ()> + ?Sized,
+    {
+        let clock = FixedClock(self.time());
+        Block::new_with_clock(
+            self.transactions().to_vec(),
+            prev_hash,
+            self.difficulty(),
+            &clock,
+        ).set_version(self.version())
+            .set_skip_hash(skip_hash)
+            .set_target(self.target().unwrap_or_default())
+            .proof_of_work_with_pow(pow, &clock)
+    }
+}
+
+impl<D, H> ChainSpec<D, H>
+where
+    D: Hashable + Clone,
+    H: ::digest::Digest,
+{
+    /// Re-links the genesis block under the hash function `H2` (see
+    /// [`Block::rehash`](../block/struct.Block.html#method.rehash)). The chain id and the
+    /// consensus rules are kept and the genesis block is mined using the proof of work function
+    /// of the rules.
+    pub fn rehash<H2>(&self) -> ChainSpec<D, H2>
+    where
+        H2: ::digest::Digest,
+    {
+        let pow = self.rules().pow_algorithm();
+        ChainSpec::new(
+            self.chain_id(),
+            self.genesis().rehash_with_pow(Default::default(), pow),
+            self.rules().clone(),
+        )
+    }
+}
+
+impl<D, H> Blockchain<D, H>
+where
+    D: Default + Hashable + Clone,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: ::std::fmt::Debug + Clone,
+{
+    /// Re-links the chain under the hash function `H2`. Each block is re-linked to the already
+    /// converted parent (see [`Block::rehash`](../block/struct.Block.html#method.rehash)), so the
+    /// transactions and their signatures stay the same, but every block is mined again. If the
+    /// chain is bound to a spec, the new chain is bound to the re-linked spec and the blocks are
+    /// mined using the proof of work function of the spec's rules.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::hash::Blake2b;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new()
+    ///     .append(vec![1], 2)
+    ///     .unwrap()
+    ///     .append(vec![2], 2)
+    ///     .unwrap();
+    /// let rehashed: Blockchain<_, Blake2b> = bc.rehash();
+    /// assert_eq!(rehashed.len(), 2);
+    /// assert!(rehashed.validate_chain());
+    /// assert_eq!(rehashed.get_by_height(1).unwrap().transactions(), &[2]);
+    /// # }
+    /// ```
+    pub fn rehash<H2>(&self) -> Blockchain<D, H2>
+    where
+        H2: ::digest::Digest,
+        <H2 as ::digest::FixedOutput>::OutputSize: ::std::fmt::Debug + Clone,
+    {
+        let (chain, pow) = match self.spec() {
+            Some(spec) => (
+                Blockchain::with_spec(spec.rehash()),
+                *spec.rules().pow_algorithm(),
+            ),
+            None => (Blockchain::new(), PowAlgorithm::Hash),
+        };
+        self.iter_chronological().fold(chain, |chain, block| {
+            let prev_hash = chain.iter().next().map(|head| head.hash()).unwrap_or_default();
+            let skip_hash = chain.next_skip_hash();
+            chain.push(block.relink_with_pow(prev_hash, skip_hash, &pow))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use canonical::hex;
+    use consensus::Retarget;
+
+    type Chain = Blockchain<u32, Sha256>;
+
+    fn empty<H: HashFunction>() -> String {
+        hex(&H::digest(b""))
+    }
+
+    #[test]
+    fn algorithms_match_reference() {
+        assert_eq!(
+            empty::<Sha256>(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            empty::<Sha512Trunc256>(),
+            "c672b8d1ef56ed28ab87c3622c5114069bdd3ad7b8f9737498d0c01ecef0967a"
+        );
+        assert_eq!(
+            empty::<Sha3_256>(),
+            "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"
+        );
+        assert_eq!(
+            empty::<Blake2b>(),
+            "786a02f742015903c6c6fd852552d272912f4740e15847618a86e217f71f5419\
+             d25e1031afee585313896444934eb04b903a685b1448b755d56f701afe9be2ce"
+        );
+    }
+
+    #[test]
+    fn algorithms_have_distinct_ids_and_names() {
+        for algorithm in ALGORITHMS {
+            assert_eq!(HashAlgorithm::from_id(algorithm.id()), Some(*algorithm));
+            assert_eq!(HashAlgorithm::from_name(algorithm.name()), Some(*algorithm));
+        }
+        assert_eq!(Blake2b::ALGORITHM.to_string(), "blake2b");
+    }
+
+    #[test]
+    fn rehashed_spec_matches_rehashed_chain() {
+        let genesis = Block::new(vec![0], 2).proof_of_work();
+        let spec = ChainSpec::new("test", genesis, Retarget::new(2, 60, 16));
+        let chain = Chain::from_spec(spec.clone()).unwrap().append(vec![1], 2).unwrap();
+        let rehashed: Blockchain<u32, Sha3_256> = chain.rehash();
+        let rehashed_spec: ChainSpec<u32, Sha3_256> = spec.rehash();
+        assert_eq!(rehashed.chain_id(), Some("test"));
+        assert!(rehashed.spec().unwrap().is_same_chain(&rehashed_spec));
+        assert!(rehashed.validate_chain_with_rules(rehashed_spec.rules()));
+        // the rehashed chain can be rebuilt from the rehashed spec
+        let rebuilt = Blockchain::from_spec(rehashed_spec)
+            .unwrap()
+            .insert(rehashed.iter().next().unwrap().clone())
+            .unwrap();
+        assert!(rebuilt == rehashed);
+    }
+
+    #[test]
+    fn rehash_uses_pow_of_spec() {
+        let pow = PowAlgorithm::MemoryHard(::pow::MemoryHard::new(64, 1, 1).unwrap());
+        let rules = Retarget::new(4, 60, 16).pow(pow);
+        let clock = FixedClock(1337);
+        let genesis = Block::new_with_clock(vec![0], Default::default(), 4, &clock)
+            .proof_of_work_with_pow(&pow, &clock);
+        let spec = ChainSpec::new("test", genesis.clone(), rules);
+        let chain = Chain::from_spec(spec.clone()).unwrap();
+        let block = Block::new_with_clock(vec![1], genesis.hash(), 4, &clock)
+            .proof_of_work_with_pow(&pow, &clock);
+        let chain = chain.insert(block).unwrap();
+        let rehashed: Blockchain<u32, Blake2b> = chain.rehash();
+        let rehashed_spec: ChainSpec<u32, Blake2b> = spec.rehash();
+        assert!(Blockchain::from_spec(rehashed_spec.clone()).is_ok());
+        assert!(rehashed.validate_chain_with_rules(rehashed_spec.rules()));
+    }
+
+    quickcheck! {
+        fn rehash_keeps_transactions(chain: Chain) -> bool {
+            let rehashed: Blockchain<u32, Sha512Trunc256> = chain.rehash();
+            rehashed.len() == chain.len()
+                && rehashed.validate_chain()
+                && rehashed
+                    .iter()
+                    .zip(chain.iter())
+                    .all(|(new, old)| {
+                        new.transactions() == old.transactions() && new.time() == old.time()
+                            && new.version() == old.version()
+                    })
+        }
+    }
+}
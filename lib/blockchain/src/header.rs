@@ -0,0 +1,674 @@
+//! This module contains the definition of a generic block header. The header contains everything
+//! that is needed to validate the linkage and the proof of work of a chain, so light clients can
+//! validate a chain without downloading the transactions of each block.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use generic_array::GenericArray;
+use generic_array::typenum::Unsigned;
+
+use block::VERSION;
+use canonical::{digest, Canonical, HEADER_DOMAIN, SEAL_DOMAIN};
+use target::{leading_zeros, Target};
+use version::{Hashable, LEGACY_VERSION, SKIP_VERSION, TARGET_VERSION};
+use work::Work;
+use BlockchainError;
+
+/// A block header that contains a version number, hash of the previous block, the Merkle root of
+/// the block's transactions, the time it was created, the difficulty factor, the `PoW` nonce and a
+/// generic seal. The seal is used by sealing schemes other than proof of work (see the
+/// [`seal`](../seal/index.html) module) and defaults to `()`, which does not take up any space in
+/// the serialized header.
+///
+/// Since version `3` (`version::TARGET_VERSION`), the difficulty factor is a compact target (see
+/// the [`target`](../target/index.html) module). Older versions store the number of leading zero
+/// bits, the hash of the header needs to have.
+///
+/// Since version `4` (`version::SKIP_VERSION`), the header additionally contains the hash of an
+/// older ancestor (see [`skip_height`](fn.skip_height.html)), so an ancestor can be reached from
+/// the head of a chain using a logarithmic number of headers. Older versions do not store this
+/// skip hash, it is all `0x00`.
+///
+/// Blocks of version `1` (`version::LEGACY_VERSION`) have been persisted by earlier releases
+/// without a header and contain a single transaction, whose serialization is part of the hash of
+/// the block. Their header contains this serialization, so their hash can be calculated without
+/// the transaction, and the Merkle root is the hash of the serialization as single leaf.
+pub struct Header<H, S = ()>
+where
+    H: ::digest::Digest,
+{
+    version: u8,
+    prev_hash: GenericArray<u8, H::OutputSize>,
+    merkle_root: GenericArray<u8, H::OutputSize>,
+    time: u64,
+    difficulty: usize,
+    nonce: u64,
+    seal: S,
+    skip_hash: GenericArray<u8, H::OutputSize>,
+    legacy_data: Vec<u8>,
+}
+
+/// Returns the height of the ancestor, the skip hash of a header at the given height links to
+/// (see [`Header::skip_hash`](struct.Header.html#method.skip_hash)). The height is the height of
+/// the header minus the smallest term of its greedy decomposition into numbers of the form
+/// `2^k - 1` (the jump pointers of a skew binary random access list). Following the skip hash
+/// whenever it does not skip the wanted ancestor and the `prev_hash` otherwise reaches every
+/// ancestor of a header in `O(log n)` steps. Headers at height `0` and `1` link to the genesis
+/// block.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::header::skip_height;
+/// assert_eq!(skip_height(1), 0);
+/// assert_eq!(skip_height(2), 1);
+/// assert_eq!(skip_height(7), 0);
+/// assert_eq!(skip_height(12), 11);
+/// assert_eq!(skip_height(14), 7);
+/// # }
+/// ```
+pub fn skip_height(height: usize) -> usize {
+    let bits = 8 * ::std::mem::size_of::<usize>() as u32;
+    let mut rest = height;
+    let mut term = 0;
+    while rest > 0 {
+        term = (1 << (bits - 1 - (rest + 1).leading_zeros())) - 1;
+        rest -= term;
+    }
+    height - term
+}
+
+impl<H, S> Header<H, S>
+where
+    H: ::digest::Digest,
+    S: Default,
+{
+    /// Creates a new header with the given `prev_hash`, Merkle root, difficulty and time. The
+    /// difficulty is the number of leading zero bits, that is stored as the equivalent target (see
+    /// [`Target::from_difficulty`](../target/struct.Target.html#method.from_difficulty)). The
+    /// nonce is initialized to `0`.
+    pub fn new(
+        prev_hash: GenericArray<u8, H::OutputSize>,
+        merkle_root: GenericArray<u8, H::OutputSize>,
+        difficulty: usize,
+        time: u64,
+    ) -> Self {
+        Self {
+            version: VERSION,
+            prev_hash: prev_hash,
+            merkle_root: merkle_root,
+            time: time,
+            difficulty: Target::from_difficulty(difficulty).to_compact() as usize,
+            nonce: 0,
+            seal: Default::default(),
+            skip_hash: Default::default(),
+            legacy_data: Vec::new(),
+        }
+    }
+
+    /// Creates a header of version `1` for a block of an earlier release with the given fields,
+    /// whose only transaction has the given serialization.
+    pub(crate) fn legacy(
+        prev_hash: GenericArray<u8, H::OutputSize>,
+        time: u64,
+        difficulty: usize,
+        nonce: u64,
+        data: Vec<u8>,
+    ) -> Self {
+        Self {
+            version: LEGACY_VERSION,
+            prev_hash: prev_hash,
+            merkle_root: Default::default(),
+            time: time,
+            difficulty: difficulty,
+            nonce: nonce,
+            seal: Default::default(),
+            skip_hash: Default::default(),
+            legacy_data: Vec::new(),
+        }.set_legacy_data(data)
+    }
+}
+
+impl<H, S> Header<H, S>
+where
+    H: ::digest::Digest,
+{
+    /// Returns the length of a hash in bytes.
+    pub fn hash_length_byte() -> usize {
+        H::OutputSize::to_usize()
+    }
+
+    /// Returns the length of a hash in bits.
+    pub fn hash_length_bit() -> usize {
+        Self::hash_length_byte() * 8
+    }
+
+    /// Returns the block version.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the hash of the previous block.
+    pub fn prev_hash(&self) -> &GenericArray<u8, H::OutputSize> {
+        &self.prev_hash
+    }
+
+    /// Returns the hash of the ancestor at [`skip_height`](fn.skip_height.html). The skip hash of
+    /// headers before version `4` is all `0x00`.
+    pub fn skip_hash(&self) -> &GenericArray<u8, H::OutputSize> {
+        &self.skip_hash
+    }
+
+    /// Returns the Merkle root of the block's transactions.
+    pub fn merkle_root(&self) -> &GenericArray<u8, H::OutputSize> {
+        &self.merkle_root
+    }
+
+    /// Returns the time, a block was created or last modified as a Unix timestamp.
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+
+    /// Returns the difficulty of a block as the number of leading zero bits. For headers with a
+    /// target, this is the number of leading zero bits of the target (see
+    /// [`Target::difficulty`](../target/struct.Target.html#method.difficulty)).
+    pub fn difficulty(&self) -> usize {
+        if self.version < TARGET_VERSION {
+            self.difficulty
+        } else {
+            self.target().unwrap_or_default().difficulty()
+        }
+    }
+
+    /// Returns the difficulty target of the header. The number of leading zero bits of headers
+    /// before version `3` is migrated using
+    /// [`Target::from_difficulty`](../target/struct.Target.html#method.from_difficulty). Returns
+    /// an error if the compact target is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::header::Header;
+    /// use blockchain::target::Target;
+    /// let header: Header<sha2::Sha256> =
+    ///     Header::new(Default::default(), Default::default(), 4, 0);
+    /// assert_eq!(header.target().unwrap(), Target::from_difficulty(4));
+    /// # }
+    /// ```
+    pub fn target(&self) -> Result<Target, BlockchainError> {
+        if self.version < TARGET_VERSION {
+            Ok(Target::from_difficulty(self.difficulty))
+        } else if self.difficulty > u32::max_value() as usize {
+            Err(BlockchainError::InvalidTarget(self.difficulty as u64))
+        } else {
+            Target::from_compact(self.difficulty as u32)
+        }
+    }
+
+    #[cfg(test)]
+    /// Replaces the compact encoding of the target without validating it. (Only used for tests)
+    pub(crate) fn set_compact_target(self, compact: u32) -> Self {
+        Self {
+            difficulty: compact as usize,
+            ..self
+        }
+    }
+
+    /// Returns the expected number of hashes, that is needed to meet the target of the header.
+    /// Fails, if the target of the header is invalid.
+    pub fn work(&self) -> Result<Work, BlockchainError> {
+        self.target().map(|target| target.work())
+    }
+
+    /// Returns true if the hash meets the difficulty of the header. Headers before version `3`
+    /// require the hash to start with the number of leading zero bits, headers with a target
+    /// require the hash to meet the target.
+    pub fn meets_target(&self, hash: &[u8]) -> bool {
+        if self.version < TARGET_VERSION {
+            leading_zeros(hash) >= self.difficulty
+        } else {
+            self.target().map_or(false, |target| target.is_met_by(hash))
+        }
+    }
+
+    /// Returns the nonce.
+    pub fn nonce(&self) -> u64 {
+        self.nonce
+    }
+
+    /// Returns a reference to the seal.
+    pub fn seal(&self) -> &S {
+        &self.seal
+    }
+
+    /// Returns true if the header belongs to a genesis block, e.g. its `prev_hash` is all 0x00.
+    pub fn is_genesis(&self) -> bool {
+        self.prev_hash.iter().all(|b| *b == 0)
+    }
+
+    /// Replaces the seal. The method returns a new header and consumes the old one.
+    pub fn set_seal(self, seal: S) -> Self {
+        Self { seal: seal, ..self }
+    }
+
+    /// Sets the hash of the ancestor at [`skip_height`](fn.skip_height.html). Headers before
+    /// version `4` do not contain a skip hash, so the hash is ignored. The method returns a new
+    /// header and consumes the old one.
+    pub fn set_skip_hash(self, skip_hash: GenericArray<u8, H::OutputSize>) -> Self {
+        if self.version < SKIP_VERSION {
+            self
+        } else {
+            Self {
+                skip_hash: skip_hash,
+                ..self
+            }
+        }
+    }
+
+    /// Sets the difficulty target. Headers before version `3` cannot store a target, so they
+    /// require the smallest number of leading zero bits, that meets the target (see
+    /// [`Target::min_difficulty`](../target/struct.Target.html#method.min_difficulty)). The
+    /// method returns a new header and consumes the old one.
+    pub fn set_target(self, target: Target) -> Self {
+        let difficulty = if self.version < TARGET_VERSION {
+            target.min_difficulty()
+        } else {
+            target.to_compact() as usize
+        };
+        Self {
+            difficulty: difficulty,
+            ..self
+        }
+    }
+
+    /// Sets the version and the Merkle root, which depends on the version. The difficulty is
+    /// converted, if only one of the versions stores a target, and the skip hash and the legacy
+    /// data are cleared, if the version does not contain them. The method returns a new header and
+    /// consumes the old one.
+    pub(crate) fn set_version(
+        self,
+        version: u8,
+        merkle_root: GenericArray<u8, H::OutputSize>,
+    ) -> Self {
+        let convert = (self.version < TARGET_VERSION) != (version < TARGET_VERSION);
+        let target = self.target().unwrap_or_default();
+        let skip_hash = if version < SKIP_VERSION {
+            Default::default()
+        } else {
+            self.skip_hash.clone()
+        };
+        let legacy_data = if version == LEGACY_VERSION {
+            self.legacy_data.clone()
+        } else {
+            Vec::new()
+        };
+        let header = Self {
+            version: version,
+            merkle_root: merkle_root,
+            skip_hash: skip_hash,
+            legacy_data: legacy_data,
+            ..self
+        };
+        if convert {
+            header.set_target(target)
+        } else {
+            header
+        }
+    }
+
+    /// Sets the serialization of the only transaction of a block of version `1` and the Merkle
+    /// root, which is the hash of the serialization as single leaf. The method returns a new
+    /// header and consumes the old one.
+    pub(crate) fn set_legacy_data(self, data: Vec<u8>) -> Self {
+        Self {
+            merkle_root: ::merkle::prefixed_leaf_hash::<H>(&data),
+            legacy_data: data,
+            ..self
+        }
+    }
+
+    /// Sets the nonce to an arbitrary value and sets the `time` attribute. The method returns a
+    /// new header and consumes the old one.
+    pub fn set_nonce(self, nonce: u64, time: u64) -> Self {
+        Self {
+            nonce: nonce,
+            time: time,
+            ..self
+        }
+    }
+}
+
+impl<H, S> Header<H, S>
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    /// Calculates the hash value of the header, which is the hash of the block. The encoding,
+    /// that is hashed, depends on the version of the header (see the
+    /// [`version`](../version/index.html) module).
+    pub fn hash(&self) -> GenericArray<u8, H::OutputSize> {
+        match self.version {
+            LEGACY_VERSION => self.legacy_hash(),
+            _ => digest::<H, _>(HEADER_DOMAIN, self),
+        }
+    }
+
+    /// Calculates the hash of a block of version `1`, which is the hash of its `bincode`
+    /// serialization as persisted by earlier releases: the version, `prev_hash`, time, difficulty
+    /// and nonce followed by the serialization of its only transaction.
+    fn legacy_hash(&self) -> GenericArray<u8, H::OutputSize> {
+        let fields = (
+            &self.version,
+            &self.prev_hash,
+            &self.time,
+            &self.difficulty,
+            &self.nonce,
+        );
+        let mut hasher = H::default();
+        hasher.input(&::bincode::serialize(&fields, ::bincode::Infinite).unwrap());
+        hasher.input(&self.legacy_data);
+        hasher.result()
+    }
+
+    /// Calculates the hash value of the header without its seal. This is the hash a sealing
+    /// scheme like proof of authority signs. It is hashed using the domain tag
+    /// `canonical::SEAL_DOMAIN`, so it differs from [`hash`](#method.hash) even for headers
+    /// without a seal and a signed seal hash cannot be passed off as a signed block hash. Blocks
+    /// of version `1` have never been sealed, their seal hash is their hash.
+    pub fn seal_hash(&self) -> GenericArray<u8, H::OutputSize> {
+        match self.version {
+            LEGACY_VERSION => self.legacy_hash(),
+            _ => digest::<H, _>(SEAL_DOMAIN, &Unsealed(self)),
+        }
+    }
+
+    /// Validates if the hash of the header matches its own difficulty factor (see
+    /// [`meets_target`](#method.meets_target)).
+    pub fn validate_difficulty(&self) -> bool {
+        self.meets_target(&self.hash())
+    }
+}
+
+impl<H, S> Canonical for Header<H, S>
+where
+    H: ::digest::Digest,
+    S: Canonical,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        Unsealed(self).encode(out);
+        self.seal.encode(out);
+    }
+}
+
+/// Every field of a header except the seal.
+struct Unsealed<'a, H, S>(&'a Header<H, S>)
+where
+    H: ::digest::Digest + 'a,
+    S: 'a;
+
+impl<'a, H, S> Canonical for Unsealed<'a, H, S>
+where
+    H: ::digest::Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        let header = self.0;
+        header.version.encode(out);
+        header.prev_hash.encode(out);
+        header.merkle_root.encode(out);
+        header.time.encode(out);
+        header.difficulty.encode(out);
+        header.nonce.encode(out);
+        if header.version >= SKIP_VERSION {
+            header.skip_hash.encode(out);
+        }
+    }
+}
+
+impl<H, S> PartialEq for Header<H, S>
+where
+    H: ::digest::Digest,
+    S: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.version == other.version && self.prev_hash == other.prev_hash
+            && self.merkle_root == other.merkle_root
+            && self.difficulty == other.difficulty && self.nonce == other.nonce
+            && self.time == other.time && self.seal == other.seal
+            && self.skip_hash == other.skip_hash
+            && self.legacy_data == other.legacy_data
+    }
+}
+
+// `H` is only used to determine the hash length, so it does not need to be `Clone`
+impl<H, S> Clone for Header<H, S>
+where
+    H: ::digest::Digest,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            version: self.version,
+            prev_hash: self.prev_hash.clone(),
+            merkle_root: self.merkle_root.clone(),
+            time: self.time,
+            difficulty: self.difficulty,
+            nonce: self.nonce,
+            seal: self.seal.clone(),
+            skip_hash: self.skip_hash.clone(),
+            legacy_data: self.legacy_data.clone(),
+        }
+    }
+}
+
+impl<H, S> ::std::fmt::Debug for Header<H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: ::std::fmt::Debug,
+    S: ::std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("Header")
+            .field("version", &self.version)
+            .field("prev_hash", &self.prev_hash)
+            .field("merkle_root", &self.merkle_root)
+            .field("time", &self.time)
+            .field("difficulty", &self.difficulty)
+            .field("nonce", &self.nonce)
+            .field("seal", &self.seal)
+            .field("skip_hash", &self.skip_hash)
+            .field("legacy_data", &self.legacy_data)
+            .finish()
+    }
+}
+
+/// Names of the serialized fields of a header. The skip hash is only serialized for headers since
+/// version `4`, so headers of older versions keep their serialization, and the legacy data is only
+/// serialized for headers of version `1`.
+const FIELDS: &[&str] = &[
+    "version",
+    "prev_hash",
+    "merkle_root",
+    "time",
+    "difficulty",
+    "nonce",
+    "seal",
+    "skip_hash",
+    "legacy_data",
+];
+
+impl<H, S> Serialize for Header<H, S>
+where
+    H: ::digest::Digest,
+    S: Serialize,
+{
+    fn serialize<Se>(&self, serializer: Se) -> Result<Se::Ok, Se::Error>
+    where
+        Se: Serializer,
+    {
+        let linked = self.version >= SKIP_VERSION;
+        let legacy = self.version == LEGACY_VERSION;
+        let len = FIELDS.len() - 2 + linked as usize + legacy as usize;
+        let mut state = serializer.serialize_struct("Header", len)?;
+        state.serialize_field(FIELDS[0], &self.version)?;
+        state.serialize_field(FIELDS[1], &self.prev_hash)?;
+        state.serialize_field(FIELDS[2], &self.merkle_root)?;
+        state.serialize_field(FIELDS[3], &self.time)?;
+        state.serialize_field(FIELDS[4], &self.difficulty)?;
+        state.serialize_field(FIELDS[5], &self.nonce)?;
+        state.serialize_field(FIELDS[6], &self.seal)?;
+        if linked {
+            state.serialize_field(FIELDS[7], &self.skip_hash)?;
+        }
+        if legacy {
+            state.serialize_field(FIELDS[8], &self.legacy_data)?;
+        }
+        state.end()
+    }
+}
+
+/// Visitor, that reads the skip hash and the legacy data of a header only if the version of the
+/// header contains them. The Merkle root of a header of version `1` needs to be the hash of its
+/// legacy data.
+struct HeaderVisitor<H, S>(PhantomData<fn() -> (H, S)>);
+
+impl<'de, H, S> Visitor<'de> for HeaderVisitor<H, S>
+where
+    H: ::digest::Digest,
+    S: Deserialize<'de> + Default,
+{
+    type Value = Header<H, S>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a block header")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        fn next<'de, A, T>(seq: &mut A, index: usize) -> Result<T, A::Error>
+        where
+            A: SeqAccess<'de>,
+            T: Deserialize<'de>,
+        {
+            seq.next_element()?
+                .ok_or_else(|| de::Error::invalid_length(index, &"a block header"))
+        }
+        let version: u8 = next(&mut seq, 0)?;
+        let prev_hash = next(&mut seq, 1)?;
+        let merkle_root = next(&mut seq, 2)?;
+        let time = next(&mut seq, 3)?;
+        let difficulty = next(&mut seq, 4)?;
+        let nonce = next(&mut seq, 5)?;
+        let seal = seq.next_element()?.unwrap_or_default();
+        let skip_hash = if version >= SKIP_VERSION {
+            next(&mut seq, 7)?
+        } else {
+            Default::default()
+        };
+        let legacy_data = if version == LEGACY_VERSION {
+            next(&mut seq, 8)?
+        } else {
+            Vec::new()
+        };
+        checked(Header {
+            version: version,
+            prev_hash: prev_hash,
+            merkle_root: merkle_root,
+            time: time,
+            difficulty: difficulty,
+            nonce: nonce,
+            seal: seal,
+            skip_hash: skip_hash,
+            legacy_data: legacy_data,
+        })
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut version = None;
+        let mut prev_hash = None;
+        let mut merkle_root = None;
+        let mut time = None;
+        let mut difficulty = None;
+        let mut nonce = None;
+        let mut seal = None;
+        let mut skip_hash = None;
+        let mut legacy_data = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => version = Some(map.next_value()?),
+                "prev_hash" => prev_hash = Some(map.next_value()?),
+                "merkle_root" => merkle_root = Some(map.next_value()?),
+                "time" => time = Some(map.next_value()?),
+                "difficulty" => difficulty = Some(map.next_value()?),
+                "nonce" => nonce = Some(map.next_value()?),
+                "seal" => seal = Some(map.next_value()?),
+                "skip_hash" => skip_hash = Some(map.next_value()?),
+                "legacy_data" => legacy_data = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let version: u8 = version.ok_or_else(|| de::Error::missing_field("version"))?;
+        let skip_hash = if version < SKIP_VERSION {
+            Default::default()
+        } else {
+            skip_hash.ok_or_else(|| de::Error::missing_field("skip_hash"))?
+        };
+        let legacy_data = if version == LEGACY_VERSION {
+            legacy_data.ok_or_else(|| de::Error::missing_field("legacy_data"))?
+        } else {
+            Vec::new()
+        };
+        checked(Header {
+            version: version,
+            prev_hash: prev_hash.ok_or_else(|| de::Error::missing_field("prev_hash"))?,
+            merkle_root: merkle_root.ok_or_else(|| de::Error::missing_field("merkle_root"))?,
+            time: time.ok_or_else(|| de::Error::missing_field("time"))?,
+            difficulty: difficulty.ok_or_else(|| de::Error::missing_field("difficulty"))?,
+            nonce: nonce.ok_or_else(|| de::Error::missing_field("nonce"))?,
+            seal: seal.unwrap_or_default(),
+            skip_hash: skip_hash,
+            legacy_data: legacy_data,
+        })
+    }
+}
+
+/// Rejects headers of version `1`, whose Merkle root is not the hash of their legacy data.
+fn checked<H, S, E>(header: Header<H, S>) -> Result<Header<H, S>, E>
+where
+    H: ::digest::Digest,
+    E: de::Error,
+{
+    if header.version == LEGACY_VERSION
+        && header.merkle_root != ::merkle::prefixed_leaf_hash::<H>(&header.legacy_data)
+    {
+        Err(de::Error::custom("the Merkle root does not match the legacy data"))
+    } else {
+        Ok(header)
+    }
+}
+
+impl<'de, H, S> Deserialize<'de> for Header<H, S>
+where
+    H: ::digest::Digest,
+    S: Deserialize<'de> + Default,
+{
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
+    where
+        De: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("Header", FIELDS, HeaderVisitor(PhantomData))
+    }
+}
@@ -0,0 +1,339 @@
+//! This module contains a chain of block headers, that can be used by light clients. A light
+//! client only synchronizes and validates the headers of a chain, e.g. the version, the linkage of
+//! the `prev_hash` fields, the time and the difficulty of each block. The transactions of a block
+//! can be fetched on demand and checked against the Merkle root in the header.
+
+use std::fmt::Debug;
+
+use block::Block;
+use consensus::{median_time_past, ConsensusRules, FixedDifficulty};
+use header::{skip_height, Header};
+use seal::SealingScheme;
+use stack::Stack;
+use version::{is_supported, Hashable, SKIP_VERSION};
+
+use super::{BlockchainError, HashBytes};
+
+/// Chain of block headers as a stack of headers.
+#[derive(Debug)]
+pub struct HeaderChain<H, S = ()>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    headers: Stack<Header<H, S>>,
+}
+
+/// Iterator over a header chain. This wraps a stack iterator over generic headers.
+pub type HeaderChainIter<'a, H, S = ()> = ::stack::Iter<'a, Header<H, S>>;
+
+impl<H, S> HeaderChain<H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    /// Creates a new and empty header chain.
+    pub fn new() -> Self {
+        Self {
+            headers: Stack::new(),
+        }
+    }
+
+    /// Returns the length of the header chain.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Checks if the header chain is empty.
+    pub fn is_empty(&self) -> bool {
+        self.headers.len() == 0
+    }
+
+    /// Returns the latest header.
+    pub fn head(&self) -> Option<&Header<H, S>> {
+        self.headers.head()
+    }
+
+    /// Removes the latest header from the chain. Returns an optional reference to the removed
+    /// header and a new header chain.
+    pub fn tail(&self) -> (Option<&Header<H, S>>, Self) {
+        let (head, headers) = self.headers.tail();
+        (head, Self { headers: headers })
+    }
+
+    /// Returns the header at the given height. The genesis block has height `0`.
+    pub fn get(&self, height: usize) -> Option<&Header<H, S>> {
+        self.headers.get(height)
+    }
+
+    /// Creates an iterator over the header chain, that iterates the chain in reverse order
+    /// (newest header first).
+    pub fn iter(&self) -> HeaderChainIter<H, S> {
+        self.headers.iter()
+    }
+}
+
+impl<H, S> HeaderChain<H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Hashable,
+{
+    /// Appends a new header to the chain without enforcing a minimum difficulty. See
+    /// [`insert_with_rules`](#method.insert_with_rules).
+    pub fn insert(&self, header: Header<H, S>) -> Result<Self, BlockchainError> {
+        self.insert_with_rules(header, &FixedDifficulty(0))
+    }
+
+    /// Appends a new header to the chain. The header is validated against the head of the chain
+    /// and the consensus rules, like a block that is inserted into a
+    /// [`Blockchain`](../blockchain/struct.Blockchain.html). If validation fails, an error is
+    /// returned.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// use blockchain::headerchain::HeaderChain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![1, 2], 8).unwrap().append(vec![3], 8).unwrap();
+    /// let mut headers = bc.headers().cloned().collect::<Vec<_>>();
+    /// headers.reverse();
+    /// let light = headers.iter().fold(HeaderChain::new(), |chain, header| {
+    ///     chain.insert_with_rules(header.clone(), &FixedDifficulty(8)).unwrap()
+    /// });
+    /// assert_eq!(light.len(), 2);
+    /// // the headers cannot be inserted in the wrong order
+    /// assert!(HeaderChain::new().insert(headers[0].clone()).unwrap().insert(headers[0].clone()).is_err());
+    /// # }
+    /// ```
+    pub fn insert_with_rules<R>(
+        &self,
+        header: Header<H, S>,
+        rules: &R,
+    ) -> Result<Self, BlockchainError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        validate_header(&header, self.len(), self.iter(), rules).map(|_| Self {
+            headers: self.headers.append(header),
+        })
+    }
+
+    /// Validates the header chain using the consensus rules. Each header is validated against its
+    /// predecessor like in [`insert_with_rules`](#method.insert_with_rules).
+    pub fn validate_chain_with_rules<R>(&self, rules: &R) -> bool
+    where
+        R: ConsensusRules<H, S>,
+    {
+        let mut headers = self.headers.clone();
+        loop {
+            let (valid, parent) = match headers.tail() {
+                (Some(header), parent) => (
+                    validate_header(header, parent.len(), parent.iter(), rules).is_ok(),
+                    parent,
+                ),
+                (None, _) => return true,
+            };
+            if !valid {
+                return false;
+            }
+            headers = parent;
+        }
+    }
+
+    /// Assembles the block at the given height from its header and the supplied transactions,
+    /// e.g. after fetching the body of a block on demand. Returns an error if there is no header
+    /// at that height or if the transactions do not match the Merkle root of the header.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::headerchain::HeaderChain;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new().append(vec![1, 2], 0).unwrap();
+    /// let light = HeaderChain::new().insert(bc.headers().next().unwrap().clone()).unwrap();
+    /// assert!(light.assemble_block(0, vec![1, 2]).is_ok());
+    /// assert!(light.assemble_block(0, vec![1, 3]).is_err());
+    /// assert!(light.assemble_block(1, vec![1, 2]).is_err());
+    /// # }
+    /// ```
+    pub fn assemble_block<D>(
+        &self,
+        height: usize,
+        transactions: Vec<D>,
+    ) -> Result<Block<D, H, S>, BlockchainError>
+    where
+        D: Hashable,
+        S: Clone,
+    {
+        self.get(height)
+            .ok_or(BlockchainError::UnknownHeight(height))
+            .and_then(|header| Block::from_parts(header.clone(), transactions))
+    }
+}
+
+/// Validates a header at the given height against the chain of its `parent` headers (newest
+/// first). The following properties are checked:
+/// * The version number is supported and matches the version, that the consensus rules require at
+///   the height of the header
+/// * The `prev_hash` is the hash of the parent header
+/// * The time is not earlier than the time of the parent header
+/// * The skip hash is the hash of the ancestor at the skip height, if the version of the header
+///   contains one (see [`skip_height`](../header/fn.skip_height.html))
+/// * The time is later than the median time of the parent headers, if the consensus rules define a
+///   median time span (see [`median_time_past`](../consensus/fn.median_time_past.html))
+/// * The time is not further ahead of the current time than the consensus rules allow
+/// * The target is valid and not higher than the target required by the consensus rules
+/// * The seal is valid according to the sealing scheme of the consensus rules
+///
+/// The seal is checked last, since verifying it can be expensive (e.g. for a memory-hard proof of
+/// work).
+pub(crate) fn validate_header<'a, H, S, R, I>(
+    header: &Header<H, S>,
+    height: usize,
+    parent: I,
+    rules: &R,
+) -> Result<(), BlockchainError>
+where
+    H: ::digest::Digest + 'a,
+    S: Hashable + 'a,
+    R: ConsensusRules<H, S>,
+    I: Iterator<Item = &'a Header<H, S>> + Clone,
+{
+    if !is_supported(header.version()) {
+        return Err(BlockchainError::UnknownVersion(header.version()));
+    }
+    let expected = rules.version_at(height);
+    if header.version() != expected {
+        return Err(BlockchainError::InactiveVersion(
+            header.version(),
+            height,
+            expected,
+        ));
+    }
+    if let Some(prev) = parent.clone().next() {
+        if *header.prev_hash() != prev.hash() {
+            return Err(BlockchainError::InvalidPrevHash(
+                HashBytes::from(&header.prev_hash()[..]),
+                HashBytes::from(&prev.hash()[..]),
+            ));
+        }
+        if header.time() < prev.time() {
+            return Err(BlockchainError::InvalidTime(header.time(), prev.time()));
+        }
+    }
+    if header.version() >= SKIP_VERSION && height > 0 {
+        let ancestor = parent.clone().nth(height - 1 - skip_height(height));
+        if let Some(ancestor) = ancestor {
+            if *header.skip_hash() != ancestor.hash() {
+                return Err(BlockchainError::InvalidSkipHash(
+                    HashBytes::from(&header.skip_hash()[..]),
+                    HashBytes::from(&ancestor.hash()[..]),
+                ));
+            }
+        }
+    }
+    if let Some(median) = median_time_past(parent.clone(), rules.median_time_span(height)) {
+        if header.time() <= median {
+            return Err(BlockchainError::TimeNotAfterMedian(header.time(), median));
+        }
+    }
+    if let Some(drift) = rules.max_future_drift(height) {
+        let now = rules.now();
+        if header.time() > now.saturating_add(drift) {
+            return Err(BlockchainError::FutureTime(header.time(), now, drift));
+        }
+    }
+    let target = header.target()?;
+    let required = rules.required_target(parent);
+    if target > required {
+        return Err(BlockchainError::InsufficientDifficulty(target, required));
+    }
+    rules.scheme().verify_seal(header)
+}
+
+impl<H, S> Clone for HeaderChain<H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            headers: self.headers.clone(),
+        }
+    }
+}
+
+impl<H, S> Default for HeaderChain<H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blockchain::Blockchain;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    fn light(chain: &Chain) -> HeaderChain<::sha2::Sha256> {
+        let mut headers = chain.headers().cloned().collect::<Vec<_>>();
+        headers.reverse();
+        headers
+            .into_iter()
+            .fold(HeaderChain::new(), |light, header| {
+                light.insert(header).unwrap()
+            })
+    }
+
+    #[test]
+    fn headers_of_valid_chain_are_valid() {
+        let chain = Chain::new()
+            .append(vec![1], 4).unwrap()
+            .append(vec![2, 3], 4).unwrap()
+            .append(vec![], 4).unwrap();
+        let light = light(&chain);
+        assert_eq!(light.len(), chain.len());
+        assert!(light.validate_chain_with_rules(&FixedDifficulty(4)));
+        assert!(!light.validate_chain_with_rules(&FixedDifficulty(5)));
+        assert_eq!(light.head(), chain.headers().next());
+    }
+
+    #[test]
+    fn bodies_are_checked_against_headers() {
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2, 3], 0).unwrap();
+        let light = light(&chain);
+        let block = light.assemble_block(1, vec![2, 3]).unwrap();
+        assert_eq!(Some(&block), chain.iter().next());
+        assert!(light.assemble_block(0, vec![2]).is_err());
+    }
+
+    #[test]
+    fn header_with_unknown_parent_is_rejected() {
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let header = chain.headers().next().unwrap().clone();
+        let light = HeaderChain::new()
+            .insert(
+                Chain::new()
+                    .append(vec![3], 0).unwrap()
+                    .headers()
+                    .next()
+                    .unwrap()
+                    .clone(),
+            )
+            .unwrap();
+        assert!(light.insert(header).is_err());
+    }
+}
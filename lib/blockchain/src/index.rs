@@ -0,0 +1,213 @@
+//! Implementation of a persistent index from hashes to heights. The index is a trie over the
+//! nibbles of the hashes, whose nodes are reference counted and never mutated. Inserting or
+//! removing a hash copies the path to its leaf and shares all other nodes with the previous index,
+//! so every chain derived from another chain owns an index of its own blocks, while the unchanged
+//! parts are stored only once. Since hashes are uniformly distributed, the trie has a depth of
+//! `O(log n)`.
+//!
+//! All hashes in an index are expected to have the same length, like the hashes of the blocks of a
+//! chain.
+
+use std::sync::Arc;
+
+type Link = Option<Arc<Node>>;
+
+/// Number of children of a branch, one for each value of a nibble.
+const FANOUT: usize = 16;
+
+#[derive(Debug)]
+enum Node {
+    /// A hash and the heights, it has been inserted at.
+    Leaf(Vec<u8>, Vec<usize>),
+    /// Children indexed by the nibble of the hash at the depth of the branch.
+    Branch([Link; FANOUT]),
+}
+
+/// Persistent index from hashes to heights.
+#[derive(Debug, Clone, Default)]
+pub struct HashIndex {
+    root: Link,
+}
+
+impl HashIndex {
+    /// Returns a new index, that additionally maps the hash to the height. A hash can be inserted
+    /// at several heights.
+    pub fn insert(&self, hash: &[u8], height: usize) -> Self {
+        Self {
+            root: Some(insert(&self.root, hash, height, 0)),
+        }
+    }
+
+    /// Returns a new index without the mapping from the hash to the height.
+    pub fn remove(&self, hash: &[u8], height: usize) -> Self {
+        Self {
+            root: remove(&self.root, hash, height, 0),
+        }
+    }
+
+    /// Returns the highest height, the hash has been inserted at.
+    pub fn get(&self, hash: &[u8]) -> Option<usize> {
+        let mut node = self.root.as_ref();
+        let mut depth = 0;
+        while let Some(current) = node {
+            match **current {
+                Node::Leaf(ref key, ref heights) => {
+                    return if key[..] == *hash {
+                        heights.iter().max().cloned()
+                    } else {
+                        None
+                    }
+                }
+                Node::Branch(ref children) => {
+                    node = children[nibble(hash, depth)].as_ref();
+                    depth += 1;
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the number of hashes in the index. (Only used for tests)
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        fn count(node: &Link) -> usize {
+            match *node {
+                None => 0,
+                Some(ref node) => match **node {
+                    Node::Leaf(..) => 1,
+                    Node::Branch(ref children) => children.iter().map(count).sum(),
+                },
+            }
+        }
+        count(&self.root)
+    }
+}
+
+/// Returns the nibble of the hash, that selects the child of a branch at the given depth.
+fn nibble(hash: &[u8], depth: usize) -> usize {
+    let byte = hash.get(depth / 2).cloned().unwrap_or(0);
+    if depth % 2 == 0 {
+        (byte >> 4) as usize
+    } else {
+        (byte & 0x0f) as usize
+    }
+}
+
+fn insert(node: &Link, hash: &[u8], height: usize, depth: usize) -> Arc<Node> {
+    match *node {
+        None => Arc::new(Node::Leaf(hash.to_vec(), vec![height])),
+        Some(ref current) => match **current {
+            Node::Leaf(ref key, ref heights) if key[..] == *hash => {
+                let mut heights = heights.clone();
+                if !heights.contains(&height) {
+                    heights.push(height);
+                }
+                Arc::new(Node::Leaf(key.clone(), heights))
+            }
+            Node::Leaf(ref key, _) => {
+                // split the leaf into a branch, that contains both hashes
+                let mut children: [Link; FANOUT] = Default::default();
+                children[nibble(key, depth)] = Some(current.clone());
+                insert(
+                    &Some(Arc::new(Node::Branch(children))),
+                    hash,
+                    height,
+                    depth,
+                )
+            }
+            Node::Branch(ref children) => {
+                let mut children = children.clone();
+                let idx = nibble(hash, depth);
+                children[idx] = Some(insert(&children[idx], hash, height, depth + 1));
+                Arc::new(Node::Branch(children))
+            }
+        },
+    }
+}
+
+fn remove(node: &Link, hash: &[u8], height: usize, depth: usize) -> Link {
+    let current = match *node {
+        Some(ref current) => current,
+        None => return None,
+    };
+    match **current {
+        Node::Leaf(ref key, ref heights) if key[..] == *hash => {
+            let heights = heights
+                .iter()
+                .filter(|h| **h != height)
+                .cloned()
+                .collect::<Vec<_>>();
+            if heights.is_empty() {
+                None
+            } else {
+                Some(Arc::new(Node::Leaf(key.clone(), heights)))
+            }
+        }
+        Node::Leaf(..) => Some(current.clone()),
+        Node::Branch(ref children) => {
+            let mut children = children.clone();
+            let idx = nibble(hash, depth);
+            children[idx] = remove(&children[idx], hash, height, depth + 1);
+            let mut remaining = children.iter().filter_map(Option::as_ref);
+            match (remaining.next(), remaining.next()) {
+                (None, _) => None,
+                // a single leaf does not need a branch
+                (Some(child), None) if is_leaf(child) => Some(child.clone()),
+                _ => Some(Arc::new(Node::Branch(children))),
+            }
+        }
+    }
+}
+
+fn is_leaf(node: &Node) -> bool {
+    match *node {
+        Node::Leaf(..) => true,
+        Node::Branch(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(n: u32) -> Vec<u8> {
+        use digest::Digest;
+        ::sha2::Sha256::digest(&[n as u8, (n >> 8) as u8]).to_vec()
+    }
+
+    #[test]
+    fn hashes_are_found() {
+        let index = (0..1000).fold(HashIndex::default(), |index, n| {
+            index.insert(&hash(n), n as usize)
+        });
+        assert_eq!(index.len(), 1000);
+        assert!((0..1000).all(|n| index.get(&hash(n)) == Some(n as usize)));
+        assert_eq!(index.get(&hash(1000)), None);
+    }
+
+    #[test]
+    fn removing_restores_the_previous_index() {
+        let index = (0..100).fold(HashIndex::default(), |index, n| {
+            index.insert(&hash(n), n as usize)
+        });
+        let removed = (50..100).fold(index.clone(), |index, n| {
+            index.remove(&hash(n), n as usize)
+        });
+        assert_eq!(removed.len(), 50);
+        assert!((0..50).all(|n| removed.get(&hash(n)) == Some(n as usize)));
+        assert!((50..100).all(|n| removed.get(&hash(n)).is_none()));
+        // the original index is not modified
+        assert_eq!(index.len(), 100);
+        let empty = (0..50).fold(removed, |index, n| index.remove(&hash(n), n as usize));
+        assert_eq!(empty.len(), 0);
+        assert!(empty.root.is_none());
+    }
+
+    #[test]
+    fn hash_can_be_inserted_at_several_heights() {
+        let index = HashIndex::default().insert(&hash(1), 3).insert(&hash(1), 7);
+        assert_eq!(index.get(&hash(1)), Some(7));
+        assert_eq!(index.remove(&hash(1), 7).get(&hash(1)), Some(3));
+        assert_eq!(index.len(), 1);
+    }
+}
@@ -1,8 +1,10 @@
 #![deny(warnings, missing_docs)]
 //! This crate provides an implementation of a `PoW` blockchain and a block, that is generic over the
-//! contained data and the used hash algorithm.
+//! contained transactions, the used hash algorithm and the sealing scheme.
 
+extern crate argon2rs;
 extern crate bincode;
+extern crate blake2;
 extern crate digest;
 #[macro_use]
 extern crate failure;
@@ -11,6 +13,7 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate sha2;
+extern crate sha3;
 
 #[cfg(test)]
 #[macro_use]
@@ -20,22 +23,156 @@ extern crate tempdir;
 
 pub mod blockchain;
 pub mod block;
+pub mod canonical;
+pub mod checkpoint;
+pub mod clock;
+pub mod consensus;
+pub mod format;
+pub mod hash;
+pub mod header;
+pub mod headerchain;
+pub mod merkle;
+pub mod miner;
+pub mod parallel;
+pub mod pow;
+pub mod proof;
+pub mod report;
+pub mod seal;
+pub mod spec;
+pub mod store;
+pub mod stream;
+pub mod target;
+pub mod tree;
+pub mod version;
+pub mod work;
 
 // only used internally. not exposed by the library
+mod index;
 mod stack;
 
+use std::fmt;
+
+use hash::HashAlgorithm;
+use target::Target;
+
+/// Raw bytes of a hash, e.g. of a block, that are displayed hex encoded. Errors carry hashes this
+/// way, so they can still be compared to other hashes.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::HashBytes;
+/// let hash = HashBytes::from(&[0x0a, 0xff][..]);
+/// assert_eq!(&hash[..], &[0x0a, 0xff]);
+/// assert_eq!(hash.to_string(), "0aff");
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct HashBytes(pub Vec<u8>);
+
+impl<'a> From<&'a [u8]> for HashBytes {
+    fn from(bytes: &'a [u8]) -> Self {
+        HashBytes(bytes.to_vec())
+    }
+}
+
+impl ::std::ops::Deref for HashBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for HashBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HashBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HashBytes({})", self)
+    }
+}
+
 /// Enumeration of errors that can occur when working with the blockchain.
-#[derive(Debug, Fail)]
+#[derive(Debug, Clone, Fail, Deserialize, Serialize)]
 pub enum BlockchainError {
     /// The hash of a block does not match its difficulty
     #[fail(display = "invalid block hash \"{}\" with difficulty {}", _0, _1)]
-    InvalidBlockHash(String, usize),
+    InvalidBlockHash(HashBytes, usize),
     /// The `prev_hash` field does not match the previous block
     #[fail(display = "invalid prev hash \"{}\", should be \"{}\"", _0, _1)]
-    InvalidPrevHash(String, String),
+    InvalidPrevHash(HashBytes, HashBytes),
+    /// The skip hash of a header does not match the ancestor at its skip height
+    #[fail(display = "invalid skip hash \"{}\", should be \"{}\"", _0, _1)]
+    InvalidSkipHash(HashBytes, HashBytes),
+    /// The target of a block is higher than the target required by the consensus rules
+    #[fail(display = "target {} is higher than the required target {}", _0, _1)]
+    InsufficientDifficulty(Target, Target),
+    /// A compact difficulty target is not the canonical encoding of a target.
+    #[fail(display = "invalid compact target {:#x}", _0)]
+    InvalidTarget(u64),
+    /// The Merkle root of a block does not match its transactions.
+    #[fail(display = "invalid merkle root \"{}\"", _0)]
+    InvalidMerkleRoot(HashBytes),
+    /// The seal of a block is invalid.
+    #[fail(display = "invalid seal: {}", _0)]
+    InvalidSeal(String),
+    /// The parameters of a proof of work function are invalid or exceed their limits.
+    #[fail(display = "invalid proof of work parameters: {}", _0)]
+    InvalidPowParameters(String),
     /// An unknown version number.
     #[fail(display = "unknown block version: {}", _0)]
     UnknownVersion(u8),
+    /// The version of a block is supported but not active at the block's height.
+    #[fail(display = "block version {} is not active at height {}, expected version {}", _0, _1,
+           _2)]
+    InactiveVersion(u8, usize, u8),
+    /// An inclusion proof is invalid.
+    #[fail(display = "invalid inclusion proof: {}", _0)]
+    InvalidProof(String),
+    /// A transaction of a block is invalid. Contains the index of the transaction inside the block
+    /// and the reason.
+    #[fail(display = "invalid transaction {}: {}", _0, _1)]
+    InvalidTransaction(usize, String),
+    /// The parent of a block is not known.
+    #[fail(display = "unknown parent block \"{}\"", _0)]
+    UnknownParent(HashBytes),
+    /// The block is already known.
+    #[fail(display = "duplicate block \"{}\"", _0)]
+    DuplicateBlock(HashBytes),
+    /// The time of a block is earlier than the time of its parent.
+    #[fail(display = "block time {} is earlier than the time of its parent {}", _0, _1)]
+    InvalidTime(u64, u64),
+    /// The time of a block is not later than the median time of the previous blocks.
+    #[fail(display = "block time {} is not later than the median time past {}", _0, _1)]
+    TimeNotAfterMedian(u64, u64),
+    /// The time of a block is too far ahead of the current time. Contains the time of the block,
+    /// the current time and the allowed drift in seconds.
+    #[fail(display = "block time {} is more than {} seconds ahead of the current time {}", _0, _2,
+           _1)]
+    FutureTime(u64, u64, u64),
+    /// The hash of a block does not match the checkpoint at its height. Contains the height, the
+    /// hash of the block and the hash of the checkpoint.
+    #[fail(display = "block \"{}\" at height {} does not match the checkpoint \"{}\"", _1, _0,
+           _2)]
+    CheckpointMismatch(usize, HashBytes, HashBytes),
+    /// The first block of a chain is not the genesis block of the chain's spec. Contains the hash
+    /// of the block and the hash of the genesis block.
+    #[fail(display = "block \"{}\" is not the genesis block \"{}\"", _0, _1)]
+    InvalidGenesis(HashBytes, HashBytes),
+    /// There is no block at the given height.
+    #[fail(display = "unknown block height {}", _0)]
+    UnknownHeight(usize),
+    /// The canonical encoding of the header at the given height is malformed.
+    #[fail(display = "malformed header encoding at height {}", _0)]
+    InvalidEncoding(usize),
 }
 
 /// Errors that can occur when persisting or loading a blockchain from/to disk.
@@ -50,6 +187,42 @@ pub enum PersistingError {
     /// An IO error occurred.
     #[fail(display = "IO error (read/write failed)")]
     IoError,
+    /// The block at the given height cannot be decoded.
+    #[fail(display = "Corrupt block at height {}", _0)]
+    CorruptBlock(usize),
+    /// The block at the given height is invalid. Contains the height and the reason.
+    #[fail(display = "Invalid block at height {}: {}", _0, _1)]
+    InvalidBlock(usize, #[cause] BlockchainError),
+    /// The persisted chain belongs to another chain than the given spec.
+    #[fail(display = "The persisted chain belongs to another chain")]
+    SpecMismatch,
+    /// The persisted chain is hashed using another algorithm. Contains the persisted and the
+    /// expected algorithm.
+    #[fail(display = "The persisted chain is hashed using {}, expected {}", _0, _1)]
+    HashMismatch(HashAlgorithm, HashAlgorithm),
+    /// The file does not start with the magic bytes of a persisted chain.
+    #[fail(display = "The file is not a persisted chain (wrong magic bytes)")]
+    WrongMagic,
+    /// The version of the file format is not supported.
+    #[fail(display = "Unsupported file format version {}", _0)]
+    UnsupportedVersion(u8),
+    /// The hash algorithm of the persisted chain is unknown.
+    #[fail(display = "Unknown hash algorithm id {}", _0)]
+    UnknownHashAlgorithm(u8),
+    /// The checksum of the data starting at the given offset does not match.
+    #[fail(display = "Checksum mismatch at offset {}", _0)]
+    ChecksumMismatch(u64),
+    /// The file ends within the data starting at the given offset.
+    #[fail(display = "Truncated at offset {}", _0)]
+    Truncated(u64),
+}
+
+/// Errors that can occur while mining a block.
+#[derive(Debug, Clone, Fail)]
+pub enum MiningError {
+    /// A worker thread of the miner panicked.
+    #[fail(display = "mining worker thread panicked")]
+    WorkerPanicked,
 }
 
 #[cfg(test)]
@@ -0,0 +1,302 @@
+//! This module contains the Merkle tree, that is used to commit to the transactions of a block.
+//! Leaves and inner nodes are hashed with different prefixes, so a leaf can never be mistaken for
+//! an inner node. If a level contains an odd number of nodes, the last node is promoted to the
+//! next level unchanged instead of being paired with itself.
+//!
+//! A [`MerkleProof`](struct.MerkleProof.html) shows that a single leaf is part of a tree without
+//! revealing the other leaves.
+
+use generic_array::GenericArray;
+
+use canonical::Canonical;
+use version::{encode, Hashable};
+
+/// Prefix of a hashed leaf.
+const LEAF_PREFIX: u8 = 0x00;
+/// Prefix of a hashed inner node.
+const NODE_PREFIX: u8 = 0x01;
+
+/// Calculates the hash of a single leaf, e.g. a transaction. The leaf is hashed using its canonical
+/// encoding (see the [`canonical`](../canonical/index.html) module).
+pub fn leaf_hash<H, T>(leaf: &T) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+    T: Canonical,
+{
+    prefixed_leaf_hash::<H>(&leaf.canonical_bytes())
+}
+
+/// Calculates the hash of a single leaf using the encoding of the given block version (see the
+/// [`version`](../version/index.html) module).
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::merkle::{leaf_hash, versioned_leaf_hash};
+/// use blockchain::version::{CANONICAL_VERSION, LEGACY_VERSION};
+/// let canonical = versioned_leaf_hash::<sha2::Sha256, _>(CANONICAL_VERSION, &1u32);
+/// assert_eq!(canonical, leaf_hash::<sha2::Sha256, _>(&1u32));
+/// assert!(versioned_leaf_hash::<sha2::Sha256, _>(LEGACY_VERSION, &1u32) != canonical);
+/// # }
+/// ```
+pub fn versioned_leaf_hash<H, T>(version: u8, leaf: &T) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+    T: Hashable,
+{
+    prefixed_leaf_hash::<H>(&encode(version, leaf))
+}
+
+/// Calculates the hash of an encoded leaf.
+pub(crate) fn prefixed_leaf_hash<H>(encoded: &[u8]) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+{
+    let mut hasher = H::default();
+    hasher.input(&[LEAF_PREFIX]);
+    hasher.input(encoded);
+    hasher.result()
+}
+
+/// Calculates the hash of an inner node from its children.
+pub fn node_hash<H>(
+    left: &GenericArray<u8, H::OutputSize>,
+    right: &GenericArray<u8, H::OutputSize>,
+) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+{
+    let mut hasher = H::default();
+    hasher.input(&[NODE_PREFIX]);
+    hasher.input(left);
+    hasher.input(right);
+    hasher.result()
+}
+
+/// Calculates the Merkle root of a list of leaves. The root of an empty list is all 0x00.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::merkle::{leaf_hash, node_hash, root};
+/// let root0 = root::<sha2::Sha256, u32>(&[]);
+/// assert!(root0.iter().all(|b| *b == 0));
+/// let root1 = root::<sha2::Sha256, _>(&[1u32]);
+/// assert_eq!(root1, leaf_hash::<sha2::Sha256, _>(&1u32));
+/// let root3 = root::<sha2::Sha256, _>(&[1u32, 2, 3]);
+/// let left = node_hash::<sha2::Sha256>(
+///     &leaf_hash::<sha2::Sha256, _>(&1u32),
+///     &leaf_hash::<sha2::Sha256, _>(&2u32),
+/// );
+/// assert_eq!(
+///     root3,
+///     node_hash::<sha2::Sha256>(&left, &leaf_hash::<sha2::Sha256, _>(&3u32))
+/// );
+/// # }
+/// ```
+pub fn root<H, T>(leaves: &[T]) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+    T: Canonical,
+{
+    root_of_hashes::<H>(leaves.iter().map(leaf_hash::<H, T>).collect())
+}
+
+/// Calculates the Merkle root of a list of leaves using the encoding of the given block version.
+pub fn versioned_root<H, T>(version: u8, leaves: &[T]) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+    T: Hashable,
+{
+    root_of_hashes::<H>(
+        leaves
+            .iter()
+            .map(|leaf| versioned_leaf_hash::<H, T>(version, leaf))
+            .collect(),
+    )
+}
+
+/// Calculates the Merkle root of a list of leaf hashes.
+pub fn root_of_hashes<H>(
+    leaf_hashes: Vec<GenericArray<u8, H::OutputSize>>,
+) -> GenericArray<u8, H::OutputSize>
+where
+    H: ::digest::Digest,
+{
+    let mut level = leaf_hashes;
+    if level.is_empty() {
+        return GenericArray::default();
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match pair.len() {
+                2 => node_hash::<H>(&pair[0], &pair[1]),
+                _ => pair[0].clone(),
+            })
+            .collect();
+    }
+    level.remove(0)
+}
+
+/// Proof that a leaf is part of a Merkle tree. The proof contains the position of the leaf, the
+/// number of leaves in the tree and the hashes of the siblings on the path from the leaf to the
+/// root, lowest level first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MerkleProof<H>
+where
+    H: ::digest::Digest,
+{
+    index: usize,
+    leaves: usize,
+    siblings: Vec<GenericArray<u8, H::OutputSize>>,
+}
+
+impl<H> MerkleProof<H>
+where
+    H: ::digest::Digest,
+{
+    /// Creates a proof for the leaf at `index`. Returns `None` if the index is out of bounds.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::merkle::{root, MerkleProof};
+    /// let leaves = [1u32, 2, 3, 4, 5];
+    /// let proof = MerkleProof::<sha2::Sha256>::new(&leaves, 4).unwrap();
+    /// assert_eq!(proof.root(&5u32), Some(root::<sha2::Sha256, _>(&leaves)));
+    /// assert!(proof.root(&4u32) != Some(root::<sha2::Sha256, _>(&leaves)));
+    /// assert!(MerkleProof::<sha2::Sha256>::new(&leaves, 5).is_none());
+    /// # }
+    /// ```
+    pub fn new<T>(leaves: &[T], index: usize) -> Option<Self>
+    where
+        T: Canonical,
+    {
+        Self::from_hashes(leaves.iter().map(leaf_hash::<H, T>).collect(), index)
+    }
+
+    /// Creates a proof for the leaf at `index` from the hashes of all leaves, e.g. leaves hashed
+    /// using [`versioned_leaf_hash`](fn.versioned_leaf_hash.html). Returns `None` if the index is
+    /// out of bounds.
+    pub fn from_hashes(
+        leaf_hashes: Vec<GenericArray<u8, H::OutputSize>>,
+        index: usize,
+    ) -> Option<Self> {
+        let leaves = leaf_hashes.len();
+        if index >= leaves {
+            return None;
+        }
+        let mut level = leaf_hashes;
+        let mut idx = index;
+        let mut siblings = Vec::new();
+        while level.len() > 1 {
+            let sibling = idx ^ 1;
+            if sibling < level.len() {
+                siblings.push(level[sibling].clone());
+            }
+            level = level
+                .chunks(2)
+                .map(|pair| match pair.len() {
+                    2 => node_hash::<H>(&pair[0], &pair[1]),
+                    _ => pair[0].clone(),
+                })
+                .collect();
+            idx /= 2;
+        }
+        Some(Self {
+            index: index,
+            leaves: leaves,
+            siblings: siblings,
+        })
+    }
+
+    /// Returns the position of the proven leaf.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the number of leaves in the tree.
+    pub fn leaves(&self) -> usize {
+        self.leaves
+    }
+
+    /// Calculates the Merkle root of the tree, assuming `leaf` is the proven leaf. The proof is
+    /// valid if the result equals the expected root. Returns `None` if the proof is malformed.
+    pub fn root<T>(&self, leaf: &T) -> Option<GenericArray<u8, H::OutputSize>>
+    where
+        T: Canonical,
+    {
+        self.root_of_hash(leaf_hash::<H, T>(leaf))
+    }
+
+    /// Calculates the Merkle root of the tree like [`root`](#method.root), but from the hash of
+    /// the proven leaf.
+    pub fn root_of_hash(
+        &self,
+        leaf_hash: GenericArray<u8, H::OutputSize>,
+    ) -> Option<GenericArray<u8, H::OutputSize>> {
+        if self.index >= self.leaves {
+            return None;
+        }
+        let mut siblings = self.siblings.iter();
+        let mut hash = leaf_hash;
+        let mut idx = self.index;
+        let mut len = self.leaves;
+        while len > 1 {
+            if idx % 2 == 1 {
+                hash = node_hash::<H>(siblings.next()?, &hash);
+            } else if idx + 1 < len {
+                hash = node_hash::<H>(&hash, siblings.next()?);
+            }
+            idx /= 2;
+            len = (len + 1) / 2;
+        }
+        if siblings.next().is_some() {
+            None
+        } else {
+            Some(hash)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    quickcheck! {
+        fn root_commits_to_every_leaf(xs: Vec<u32>, idx: usize) -> bool {
+            if xs.is_empty() {
+                return true;
+            }
+            let idx = idx % xs.len();
+            let mut changed = xs.clone();
+            changed[idx] = changed[idx].wrapping_add(1);
+            root::<::sha2::Sha256, _>(&xs) != root::<::sha2::Sha256, _>(&changed)
+        }
+    }
+
+    quickcheck! {
+        fn proof_matches_root(xs: Vec<u32>, idx: usize) -> bool {
+            if xs.is_empty() {
+                return MerkleProof::<::sha2::Sha256>::new(&xs, idx).is_none();
+            }
+            let idx = idx % xs.len();
+            let proof = MerkleProof::<::sha2::Sha256>::new(&xs, idx).unwrap();
+            proof.root(&xs[idx]) == Some(root::<::sha2::Sha256, _>(&xs))
+        }
+    }
+
+    #[test]
+    fn odd_node_is_not_duplicated() {
+        let three = root::<::sha2::Sha256, _>(&[1u32, 2, 3]);
+        let four = root::<::sha2::Sha256, _>(&[1u32, 2, 3, 3]);
+        assert!(three != four);
+    }
+}
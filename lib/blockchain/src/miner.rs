@@ -0,0 +1,360 @@
+//! This module contains a multi-threaded and cancellable proof of work miner. The nonce space is
+//! split across a configurable number of worker threads, each thread trying every `n`th nonce.
+//! Workers count their hashes locally and only add them to the shared progress in batches, so they
+//! do not contend for the shared counter.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use generic_array::GenericArray;
+
+use block::Block;
+use clock::{Clock, SystemClock};
+use pow::PowFunction;
+use seal::ProofOfWork;
+use MiningError;
+
+/// Default interval in which progress is reported.
+const DEFAULT_REPORT_INTERVAL_MS: u64 = 1000;
+/// Number of hashes a worker tries before adding them to the shared progress.
+const PROGRESS_BATCH: usize = 256;
+
+/// Token that can be used to cancel a running mining operation. Cloning the token results in a
+/// handle to the same cancellation state, so it can be passed to another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a new token that has not been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every operation that has been started using this token or one of its clones.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::miner::CancellationToken;
+    /// let token = CancellationToken::new();
+    /// let handle = token.clone();
+    /// assert!(!token.is_cancelled());
+    /// handle.cancel();
+    /// assert!(token.is_cancelled());
+    /// # }
+    /// ```
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns true if the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Progress of a mining operation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    hashes: usize,
+    elapsed: Duration,
+}
+
+impl Progress {
+    /// Returns the number of hashes that have been tried so far.
+    pub fn hashes(&self) -> usize {
+        self.hashes
+    }
+
+    /// Returns the time since mining has been started.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the average number of hashes per second since mining has been started.
+    pub fn hash_rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs() as f64
+            + f64::from(self.elapsed.subsec_nanos()) / 1_000_000_000.0;
+        if secs > 0.0 {
+            self.hashes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Multi-threaded proof of work miner.
+#[derive(Debug, Clone)]
+pub struct Miner {
+    threads: usize,
+    report_interval: Duration,
+    clock: Arc<Clock>,
+}
+
+impl Miner {
+    /// Creates a new miner using the given number of worker threads. At least one thread will be
+    /// used.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::miner::Miner;
+    /// assert_eq!(Miner::new(4).threads(), 4);
+    /// assert_eq!(Miner::new(0).threads(), 1);
+    /// # }
+    /// ```
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: if threads == 0 { 1 } else { threads },
+            report_interval: Duration::from_millis(DEFAULT_REPORT_INTERVAL_MS),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the interval in which the progress callback is called.
+    pub fn report_interval(self, interval: Duration) -> Self {
+        Self {
+            report_interval: interval,
+            ..self
+        }
+    }
+
+    /// Sets the clock, that is used to update the time of a block while mining. Defaults to the
+    /// system time.
+    pub fn clock<C>(self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        Self {
+            clock: Arc::new(clock),
+            ..self
+        }
+    }
+
+    /// Returns the number of worker threads.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Performs proof of work on a block until its hash matches the block's difficulty. Each
+    /// worker thread starts at a different nonce and tries every `n`th nonce, `n` being the number
+    /// of threads. The `progress` callback is called periodically on the current thread.
+    ///
+    /// Returns the first valid block that has been found or `None` if mining has been cancelled
+    /// using the supplied token. If a worker thread panics, mining is stopped and
+    /// `MiningError::WorkerPanicked` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::miner::{CancellationToken, Miner};
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 8);
+    /// let token = CancellationToken::new();
+    /// let block = Miner::new(2).mine(block, &token, |_| ()).unwrap().unwrap();
+    /// assert!(block.validate_difficulty());
+    /// # }
+    /// ```
+    pub fn mine<D, H, S, F>(
+        &self,
+        block: Block<D, H, S>,
+        token: &CancellationToken,
+        progress: F,
+    ) -> Result<Option<Block<D, H, S>>, MiningError>
+    where
+        D: ::version::Hashable + Clone + Send + 'static,
+        S: ::version::Hashable + Clone + Send + 'static,
+        H: ::digest::Digest + Clone + 'static,
+        GenericArray<u8, H::OutputSize>: Send,
+        F: FnMut(&Progress),
+    {
+        self.mine_with_pow(block, &ProofOfWork, token, progress)
+    }
+
+    /// Performs proof of work like [`mine`](#method.mine), but until the given proof of work
+    /// function matches the block's difficulty, e.g. the
+    /// [`PowAlgorithm`](../pow/enum.PowAlgorithm.html) of a chain's consensus rules. Every worker
+    /// thread uses a clone of the function.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::miner::{CancellationToken, Miner};
+    /// use blockchain::pow::{MemoryHard, PowFunction};
+    /// let pow = MemoryHard::new(64, 1, 1).unwrap();
+    /// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 4);
+    /// let token = CancellationToken::new();
+    /// let block = Miner::new(2)
+    ///     .mine_with_pow(block, &pow, &token, |_| ())
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert!(pow.meets_difficulty(block.header()));
+    /// # }
+    /// ```
+    pub fn mine_with_pow<D, H, S, P, F>(
+        &self,
+        block: Block<D, H, S>,
+        pow: &P,
+        token: &CancellationToken,
+        mut progress: F,
+    ) -> Result<Option<Block<D, H, S>>, MiningError>
+    where
+        D: ::version::Hashable + Clone + Send + 'static,
+        S: ::version::Hashable + Clone + Send + 'static,
+        H: ::digest::Digest + Clone + 'static,
+        GenericArray<u8, H::OutputSize>: Send,
+        P: PowFunction<H, S> + Clone + Send + 'static,
+        F: FnMut(&Progress),
+    {
+        let start = Instant::now();
+        let found = Arc::new(AtomicBool::new(false));
+        let panicked = Arc::new(AtomicBool::new(false));
+        let hashes = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = channel();
+        let first_nonce = block.nonce();
+        let step = self.threads as u64;
+
+        let workers = (0..self.threads)
+            .map(|idx| {
+                let block = block.clone();
+                let token = token.clone();
+                let found = Arc::clone(&found);
+                let panicked = Arc::clone(&panicked);
+                let hashes = Arc::clone(&hashes);
+                let tx = tx.clone();
+                let clock = Arc::clone(&self.clock);
+                let pow = pow.clone();
+                thread::spawn(move || {
+                    let _guard = PanicGuard(panicked);
+                    let mut block =
+                        block.set_nonce(first_nonce.wrapping_add(idx as u64), clock.now());
+                    let mut batch = 0;
+                    while !(found.load(Ordering::Relaxed) || token.is_cancelled()) {
+                        batch += 1;
+                        if batch == PROGRESS_BATCH {
+                            hashes.fetch_add(batch, Ordering::Relaxed);
+                            batch = 0;
+                        }
+                        if pow.meets_difficulty(block.header()) {
+                            found.store(true, Ordering::SeqCst);
+                            tx.send(block).ok();
+                            break;
+                        }
+                        let nonce = block.nonce().wrapping_add(step);
+                        block = block.set_nonce(nonce, clock.now());
+                    }
+                    hashes.fetch_add(batch, Ordering::Relaxed);
+                })
+            })
+            .collect::<Vec<_>>();
+        // drop the original sender, so `recv` fails once every worker has stopped
+        drop(tx);
+
+        let result = loop {
+            match rx.recv_timeout(self.report_interval) {
+                Ok(block) => break Some(block),
+                Err(RecvTimeoutError::Timeout) if panicked.load(Ordering::SeqCst) => break None,
+                Err(RecvTimeoutError::Timeout) => {
+                    progress(&Progress {
+                        hashes: hashes.load(Ordering::Relaxed),
+                        elapsed: start.elapsed(),
+                    });
+                }
+                Err(RecvTimeoutError::Disconnected) => break None,
+            }
+        };
+        found.store(true, Ordering::SeqCst);
+        let joined = workers
+            .into_iter()
+            .fold(true, |ok, worker| worker.join().is_ok() && ok);
+        if joined {
+            Ok(result)
+        } else {
+            Err(MiningError::WorkerPanicked)
+        }
+    }
+}
+
+/// Flags that a worker thread panicked when it is dropped during unwinding, so mining stops
+/// instead of waiting for the remaining workers.
+struct PanicGuard(Arc<AtomicBool>);
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Default for Miner {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use generic_array::typenum::U32;
+    use header::Header;
+
+    #[test]
+    fn mined_block_is_valid() {
+        let block: Block<_, ::sha2::Sha256> = Block::new(vec![1337u32], 12);
+        let token = CancellationToken::new();
+        let block = Miner::new(4).mine(block, &token, |_| ()).unwrap().unwrap();
+        assert!(block.validate_difficulty());
+        assert_eq!(block.transactions(), &[1337]);
+    }
+
+    #[test]
+    fn cancelled_mining_returns_none() {
+        let block: Block<_, ::sha2::Sha256> = Block::new(vec![1337u32], 256);
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        let mut reports = 0;
+        let miner = Miner::new(2).report_interval(Duration::from_millis(10));
+        let result = miner.mine(block, &token, |progress| {
+            reports += 1;
+            if reports >= 3 {
+                assert!(progress.hashes() > 0);
+                handle.cancel();
+            }
+        });
+        assert!(result.unwrap().is_none());
+    }
+
+    #[derive(Clone)]
+    struct PanickingPow;
+
+    impl PowFunction<::sha2::Sha256, ()> for PanickingPow {
+        fn pow_hash(&self, header: &Header<::sha2::Sha256>) -> GenericArray<u8, U32> {
+            if header.nonce() == 3 {
+                panic!("worker failed");
+            }
+            header.hash()
+        }
+    }
+
+    #[test]
+    fn panicking_worker_is_reported() {
+        let block: Block<_, ::sha2::Sha256> = Block::new(vec![1337u32], 256);
+        let token = CancellationToken::new();
+        let miner = Miner::new(2).report_interval(Duration::from_millis(10));
+        match miner.mine_with_pow(block, &PanickingPow, &token, |_| ()) {
+            Err(MiningError::WorkerPanicked) => {}
+            _ => panic!("a worker panicked"),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! This module contains a multi-threaded validation of a chain. The blocks are distributed across a
+//! configurable number of worker threads, each thread taking the next block that has not been
+//! validated yet. Every block is validated against its actual parent, so seals, Merkle roots,
+//! linkage and the transactions of different blocks are checked concurrently, but the result is
+//! the same as the result of the sequential
+//! [`validation_report`](../blockchain/struct.Blockchain.html#method.validation_report).
+
+use std::cmp;
+use std::fmt::Debug;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use generic_array::GenericArray;
+
+use block::Block;
+use blockchain::Blockchain;
+use consensus::ConsensusRules;
+use report::{Problem, Scope, ValidationReport};
+use version::Hashable;
+
+use super::BlockchainError;
+
+impl<D, H, S> Blockchain<D, H, S>
+where
+    D: Default + Hashable + Send + Sync + 'static,
+    H: ::digest::Digest + Send + Sync + 'static,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    GenericArray<u8, <H as ::digest::FixedOutput>::OutputSize>: Send + Sync,
+    S: Default + Hashable + Send + Sync + 'static,
+{
+    /// Validates the chain like
+    /// [`validate_chain_with_rules`](../blockchain/struct.Blockchain.html#method.validate_chain_with_rules)
+    /// using the given number of worker threads. At least one thread will be used.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![1], 8).unwrap().append(vec![2], 8).unwrap();
+    /// assert!(bc.validate_chain_parallel(&FixedDifficulty(8), 4));
+    /// assert!(!bc.validate_chain_parallel(&FixedDifficulty(9), 4));
+    /// # }
+    /// ```
+    pub fn validate_chain_parallel<R>(&self, rules: &R, threads: usize) -> bool
+    where
+        R: ConsensusRules<H, S> + Clone + Send + 'static,
+    {
+        self.validation_report_parallel(rules, Scope::First, threads, |_| Ok(()))
+            .is_valid()
+    }
+
+    /// Validates the chain like
+    /// [`validation_report`](../blockchain/struct.Blockchain.html#method.validation_report) using
+    /// the given number of worker threads. Blocks, that pass the consensus rules, are additionally
+    /// passed to `check`, e.g. to validate the signatures of their transactions. At least one
+    /// thread will be used. A panic inside `check` is propagated to the caller.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::BlockchainError;
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// use blockchain::report::{Reason, Scope};
+    /// let bc: Blockchain<u32, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc
+    ///     .append(vec![1, 2], 0)
+    ///     .unwrap()
+    ///     .append(vec![3, 4], 0)
+    ///     .unwrap()
+    ///     .append(vec![5], 0)
+    ///     .unwrap();
+    /// // odd numbers are invalid transactions
+    /// let report = bc.validation_report_parallel(&FixedDifficulty(0), Scope::All, 2, |block| {
+    ///     match block.transactions().iter().position(|tx| tx % 2 == 1) {
+    ///         Some(idx) => Err(BlockchainError::InvalidTransaction(idx, "odd".to_owned())),
+    ///         None => Ok(()),
+    ///     }
+    /// });
+    /// assert_eq!(report.checked(), 3);
+    /// assert_eq!(report.problems().len(), 3);
+    /// assert_eq!(report.first().unwrap().reason(), Reason::Payload);
+    /// # }
+    /// ```
+    pub fn validation_report_parallel<R, F>(
+        &self,
+        rules: &R,
+        scope: Scope,
+        threads: usize,
+        check: F,
+    ) -> ValidationReport
+    where
+        R: ConsensusRules<H, S> + Clone + Send + 'static,
+        F: Fn(&Block<D, H, S>) -> Result<(), BlockchainError> + Send + Sync + 'static,
+    {
+        let chain = Arc::new(self.clone());
+        let check = Arc::new(check);
+        // height of the next block that has not been taken by a worker
+        let next = Arc::new(AtomicUsize::new(0));
+        // height of the oldest invalid block found so far
+        let first = Arc::new(AtomicUsize::new(usize::max_value()));
+        let workers: Vec<_> = (0..cmp::max(threads, 1))
+            .map(|_| {
+                let chain = Arc::clone(&chain);
+                let check = Arc::clone(&check);
+                let next = Arc::clone(&next);
+                let first = Arc::clone(&first);
+                let rules = rules.clone();
+                thread::spawn(move || {
+                    let mut problems = Vec::new();
+                    loop {
+                        let height = next.fetch_add(1, Ordering::SeqCst);
+                        // heights are taken in ascending order, so every block below the oldest
+                        // invalid block is still validated
+                        if height >= chain.len()
+                            || (scope == Scope::First && height > first.load(Ordering::SeqCst))
+                        {
+                            break;
+                        }
+                        if let Some(block) = chain.get_by_height(height) {
+                            let result = chain
+                                .validate_at(height, &rules)
+                                .and_then(|_| check(block));
+                            if let Err(error) = result {
+                                lower(&first, height);
+                                problems.push(Problem::new(height, &block.hash(), error));
+                            }
+                        }
+                    }
+                    problems
+                })
+            })
+            .collect();
+        let mut problems = Vec::new();
+        for worker in workers {
+            match worker.join() {
+                Ok(found) => problems.extend(found),
+                Err(err) => panic::resume_unwind(err),
+            }
+        }
+        problems.sort_by_key(|problem| problem.height());
+        let checked = match scope {
+            Scope::First if !problems.is_empty() => {
+                problems.truncate(1);
+                problems[0].height() + 1
+            }
+            _ => chain.len(),
+        };
+        ValidationReport::new(checked, problems)
+    }
+}
+
+/// Lowers the value to `height` if it is currently higher.
+fn lower(value: &AtomicUsize, height: usize) {
+    let mut current = value.load(Ordering::SeqCst);
+    while height < current {
+        match value.compare_exchange(current, height, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(_) => break,
+            Err(actual) => current = actual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use consensus::FixedDifficulty;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    #[test]
+    fn oldest_problem_is_reported_first() {
+        let chain = (0..20).fold(Chain::new(), |chain, i| chain.append(vec![i], 2).unwrap());
+        let chain = chain
+            .append(vec![20], 0).unwrap()
+            .append(vec![21], 2).unwrap()
+            .append(vec![22], 0).unwrap();
+        let report =
+            chain.validation_report_parallel(&FixedDifficulty(2), Scope::First, 4, |_| Ok(()));
+        assert_eq!(report.checked(), 21);
+        assert_eq!(report.problems().len(), 1);
+        assert_eq!(report.first().map(|p| p.height()), Some(20));
+    }
+
+    #[test]
+    fn rejected_transactions_are_reported() {
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2, 3], 0).unwrap();
+        let report = chain.validation_report_parallel(
+            &FixedDifficulty(0),
+            Scope::All,
+            2,
+            |block| match block.transactions().iter().position(|tx| *tx == 3) {
+                Some(idx) => Err(BlockchainError::InvalidTransaction(idx, "three".to_owned())),
+                None => Ok(()),
+            },
+        );
+        assert_eq!(report.problems().len(), 1);
+        match *report.first().unwrap().error() {
+            BlockchainError::InvalidTransaction(1, _) => {}
+            _ => panic!("expected the second transaction to be rejected"),
+        }
+    }
+
+    #[test]
+    fn empty_chain_is_valid() {
+        let report =
+            Chain::new().validation_report_parallel(&FixedDifficulty(0), Scope::All, 4, |_| {
+                panic!("there are no blocks to check")
+            });
+        assert!(report.is_valid());
+        assert_eq!(report.checked(), 0);
+    }
+
+    quickcheck! {
+        fn rejected_block_is_reported(len: usize, rejected: usize, threads: usize) -> bool {
+            let len = len % 32 + 1;
+            let rejected = rejected % len;
+            let chain = (0..len).fold(Chain::new(), |chain, i| {
+                chain.append(vec![i as u32], 0).unwrap()
+            });
+            let report = chain.validation_report_parallel(
+                &FixedDifficulty(0),
+                Scope::All,
+                threads % 8,
+                move |block| if block.transactions()[0] as usize == rejected {
+                    Err(BlockchainError::InvalidTransaction(0, "rejected".to_owned()))
+                } else {
+                    Ok(())
+                },
+            );
+            report.checked() == len
+                && report.problems().len() == 1
+                && report.first().map(|problem| problem.height()) == Some(rejected)
+        }
+
+        fn parallel_agrees_with_sequential(chain: Chain, difficulty: usize, threads: usize) -> bool {
+            let rules = FixedDifficulty(difficulty % 4);
+            let threads = threads % 8;
+            [Scope::First, Scope::All].iter().all(|scope| {
+                let sequential = chain.validation_report(&rules, *scope);
+                let parallel = chain.validation_report_parallel(&rules, *scope, threads, |_| Ok(()));
+                sequential.checked() == parallel.checked()
+                    && sequential.problems().len() == parallel.problems().len()
+                    && sequential
+                        .problems()
+                        .iter()
+                        .zip(parallel.problems())
+                        .all(|(s, p)| s.height() == p.height() && s.hash() == p.hash())
+            }) && chain.validate_chain_parallel(&rules, threads)
+                == chain.validate_chain_with_rules(&rules)
+        }
+    }
+}
@@ -0,0 +1,341 @@
+//! This module contains the proof of work functions, a block can be mined with. The default
+//! function requires the hash of a block header to meet the difficulty target of the header. The
+//! memory-hard function requires the same of the Argon2d hash of the header hash, so every attempt
+//! needs a configurable amount of memory, which reduces the advantage of specialized mining
+//! hardware. The function of a chain is selected by its consensus rules (see
+//! [`Retarget::pow`](../consensus/struct.Retarget.html#method.pow)).
+//!
+//! Verifying a memory-hard proof of work costs as much as a single attempt of mining it. To keep
+//! the cost of validating a chain bounded, the parameters cannot exceed [`MAX_MEMORY_KIB`],
+//! [`MAX_PASSES`] and [`MAX_LANES`], not even in deserialized rules, and the seal of a header is
+//! only verified after every other check passed.
+//!
+//! [`MAX_MEMORY_KIB`]: constant.MAX_MEMORY_KIB.html
+//! [`MAX_PASSES`]: constant.MAX_PASSES.html
+//! [`MAX_LANES`]: constant.MAX_LANES.html
+
+use argon2rs::{Argon2, Variant};
+use generic_array::GenericArray;
+use serde::{Deserialize, Deserializer};
+
+use header::Header;
+use seal::{ProofOfWork, SealingScheme};
+use version::Hashable;
+
+use super::{BlockchainError, HashBytes};
+
+/// Maximum memory of a memory-hard proof of work in KiB (64 MiB).
+pub const MAX_MEMORY_KIB: u32 = 64 * 1024;
+/// Maximum number of passes over the memory of a memory-hard proof of work.
+pub const MAX_PASSES: u32 = 4;
+/// Maximum number of lanes, the memory of a memory-hard proof of work is split into.
+pub const MAX_LANES: u32 = 8;
+/// Minimum memory of a lane in KiB, as required by Argon2.
+const MIN_LANE_KIB: u32 = 8;
+
+/// Salt of the Argon2 hash. The hashed header hash is unique already, so the salt is fixed.
+const SALT: &[u8] = b"memory-hard-pow";
+
+/// A function, whose output needs to match the difficulty of a block header. Mining tries
+/// different nonces until the proof of work hash of the header meets the difficulty target of the
+/// header (see [`Header::meets_target`](../header/struct.Header.html#method.meets_target)).
+pub trait PowFunction<H, S>
+where
+    H: ::digest::Digest,
+{
+    /// Calculates the proof of work hash of a header.
+    fn pow_hash(&self, header: &Header<H, S>) -> GenericArray<u8, H::OutputSize>;
+
+    /// Returns true if the proof of work hash of the header matches the difficulty of the header.
+    fn meets_difficulty(&self, header: &Header<H, S>) -> bool {
+        header.meets_target(&self.pow_hash(header))
+    }
+}
+
+/// Verifies that the proof of work hash of a header matches the difficulty of the header.
+fn verify_pow<P, H, S>(pow: &P, header: &Header<H, S>) -> Result<(), BlockchainError>
+where
+    P: PowFunction<H, S> + ?Sized,
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    if pow.meets_difficulty(header) {
+        Ok(())
+    } else {
+        Err(BlockchainError::InvalidBlockHash(
+            HashBytes::from(&header.hash()[..]),
+            header.difficulty(),
+        ))
+    }
+}
+
+/// The proof of work hash is the hash of the header.
+impl<H, S> PowFunction<H, S> for ProofOfWork
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    fn pow_hash(&self, header: &Header<H, S>) -> GenericArray<u8, H::OutputSize> {
+        header.hash()
+    }
+
+    fn meets_difficulty(&self, header: &Header<H, S>) -> bool {
+        header.validate_difficulty()
+    }
+}
+
+/// Memory-hard proof of work. The proof of work hash is the Argon2d hash of the header hash, that
+/// has the length of the header hash.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::block::Block;
+/// use blockchain::clock::SystemClock;
+/// use blockchain::pow::{MemoryHard, PowFunction};
+/// let pow = MemoryHard::new(64, 1, 1).unwrap();
+/// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 4);
+/// let block = block.proof_of_work_with_pow(&pow, &SystemClock);
+/// assert!(pow.meets_difficulty(block.header()));
+/// assert!(MemoryHard::new(0, 1, 1).is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MemoryHard {
+    memory_kib: u32,
+    passes: u32,
+    lanes: u32,
+}
+
+impl MemoryHard {
+    /// Creates a memory-hard proof of work, that fills `memory_kib` KiB of memory `passes` times.
+    /// The memory is split into `lanes` lanes, that are filled in parallel. Returns an error if a
+    /// parameter is `0` or exceeds its maximum or if a lane has less than 8 KiB of memory.
+    pub fn new(memory_kib: u32, passes: u32, lanes: u32) -> Result<Self, BlockchainError> {
+        if passes == 0 || passes > MAX_PASSES {
+            Err(BlockchainError::InvalidPowParameters(format!(
+                "{} passes, expected 1 to {}",
+                passes, MAX_PASSES
+            )))
+        } else if lanes == 0 || lanes > MAX_LANES {
+            Err(BlockchainError::InvalidPowParameters(format!(
+                "{} lanes, expected 1 to {}",
+                lanes, MAX_LANES
+            )))
+        } else if memory_kib < lanes * MIN_LANE_KIB || memory_kib > MAX_MEMORY_KIB {
+            Err(BlockchainError::InvalidPowParameters(format!(
+                "{} KiB of memory, expected {} to {}",
+                memory_kib,
+                lanes * MIN_LANE_KIB,
+                MAX_MEMORY_KIB
+            )))
+        } else {
+            Ok(Self {
+                memory_kib: memory_kib,
+                passes: passes,
+                lanes: lanes,
+            })
+        }
+    }
+
+    /// Returns the memory in KiB.
+    pub fn memory_kib(&self) -> u32 {
+        self.memory_kib
+    }
+
+    /// Returns the number of passes over the memory.
+    pub fn passes(&self) -> u32 {
+        self.passes
+    }
+
+    /// Returns the number of lanes.
+    pub fn lanes(&self) -> u32 {
+        self.lanes
+    }
+}
+
+impl<H, S> PowFunction<H, S> for MemoryHard
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    fn pow_hash(&self, header: &Header<H, S>) -> GenericArray<u8, H::OutputSize> {
+        // the parameters have been checked when creating or deserializing `self`
+        let argon2 = Argon2::new(self.passes, self.lanes, self.memory_kib, Variant::Argon2d)
+            .expect("Invalid memory-hard proof of work parameters");
+        let mut hash = GenericArray::default();
+        argon2.hash(&mut hash, &header.hash(), SALT, &[], &[]);
+        hash
+    }
+}
+
+impl<H, S> SealingScheme<H, S> for MemoryHard
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    fn verify_seal(&self, header: &Header<H, S>) -> Result<(), BlockchainError> {
+        verify_pow(self, header)
+    }
+}
+
+/// Serialized parameters of a memory-hard proof of work, that are checked when deserializing.
+#[derive(Deserialize)]
+struct MemoryHardParameters {
+    memory_kib: u32,
+    passes: u32,
+    lanes: u32,
+}
+
+impl<'de> Deserialize<'de> for MemoryHard {
+    fn deserialize<De>(deserializer: De) -> Result<Self, De::Error>
+    where
+        De: Deserializer<'de>,
+    {
+        let params = MemoryHardParameters::deserialize(deserializer)?;
+        Self::new(params.memory_kib, params.passes, params.lanes)
+            .map_err(::serde::de::Error::custom)
+    }
+}
+
+/// The proof of work function of a chain, that can be persisted as part of its consensus rules.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PowAlgorithm {
+    /// The leading zero proof of work over the header hash (see
+    /// [`ProofOfWork`](../seal/struct.ProofOfWork.html)).
+    Hash,
+    /// The memory-hard proof of work (see [`MemoryHard`](struct.MemoryHard.html)).
+    MemoryHard(MemoryHard),
+}
+
+impl Default for PowAlgorithm {
+    fn default() -> Self {
+        PowAlgorithm::Hash
+    }
+}
+
+impl<H, S> PowFunction<H, S> for PowAlgorithm
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    fn pow_hash(&self, header: &Header<H, S>) -> GenericArray<u8, H::OutputSize> {
+        match *self {
+            PowAlgorithm::Hash => ProofOfWork.pow_hash(header),
+            PowAlgorithm::MemoryHard(ref pow) => pow.pow_hash(header),
+        }
+    }
+
+    fn meets_difficulty(&self, header: &Header<H, S>) -> bool {
+        match *self {
+            PowAlgorithm::Hash => ProofOfWork.meets_difficulty(header),
+            PowAlgorithm::MemoryHard(ref pow) => pow.meets_difficulty(header),
+        }
+    }
+}
+
+impl<H, S> SealingScheme<H, S> for PowAlgorithm
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    fn verify_seal(&self, header: &Header<H, S>) -> Result<(), BlockchainError> {
+        verify_pow(self, header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use block::Block;
+    use blockchain::Blockchain;
+    use clock::FixedClock;
+    use consensus::Retarget;
+    use version::CANONICAL_VERSION;
+
+    type Chain = Blockchain<u8, ::sha2::Sha256>;
+
+    fn memory_hard() -> PowAlgorithm {
+        PowAlgorithm::MemoryHard(MemoryHard::new(64, 1, 1).unwrap())
+    }
+
+    #[test]
+    fn parameters_are_bounded() {
+        assert!(MemoryHard::new(MAX_MEMORY_KIB, MAX_PASSES, MAX_LANES).is_ok());
+        assert!(MemoryHard::new(MAX_MEMORY_KIB + 1, 1, 1).is_err());
+        assert!(MemoryHard::new(64, MAX_PASSES + 1, 1).is_err());
+        assert!(MemoryHard::new(64, 1, MAX_LANES + 1).is_err());
+        assert!(MemoryHard::new(64, 0, 1).is_err());
+        assert!(MemoryHard::new(64, 1, 0).is_err());
+        // every lane needs at least 8 KiB
+        assert!(MemoryHard::new(15, 1, 2).is_err());
+        assert!(MemoryHard::new(16, 1, 2).is_ok());
+    }
+
+    #[derive(Serialize)]
+    struct Unchecked {
+        memory_kib: u32,
+        passes: u32,
+        lanes: u32,
+    }
+
+    #[test]
+    fn deserializing_checks_parameters() {
+        let pow = memory_hard();
+        let bytes = ::bincode::serialize(&pow, ::bincode::Infinite).unwrap();
+        assert_eq!(::bincode::deserialize::<PowAlgorithm>(&bytes).unwrap(), pow);
+        let unchecked = Unchecked {
+            memory_kib: MAX_MEMORY_KIB + 1,
+            passes: 1,
+            lanes: 1,
+        };
+        let bytes = ::bincode::serialize(&unchecked, ::bincode::Infinite).unwrap();
+        assert!(::bincode::deserialize::<MemoryHard>(&bytes).is_err());
+    }
+
+    #[test]
+    fn memory_hard_hash_differs_from_header_hash() {
+        let block: Block<u8, ::sha2::Sha256> = Block::new(vec![1], 0);
+        let header = block.header();
+        assert!(memory_hard().pow_hash(header) != header.hash());
+        assert!(PowAlgorithm::Hash.pow_hash(header) == header.hash());
+    }
+
+    #[test]
+    fn rules_enforce_memory_hard_pow() {
+        let rules = Retarget::new(6, 60, 16).pow(memory_hard());
+        let clock = FixedClock(1337);
+        let block = Block::new_with_clock(vec![1], Default::default(), 6, &clock)
+            .proof_of_work_with_pow(&memory_hard(), &clock);
+        assert!(memory_hard().meets_difficulty(block.header()));
+        let chain = Chain::new().insert_with_rules(block, &rules).unwrap();
+        assert!(chain.validate_chain_with_rules(&rules));
+        // a block, that only has a plain proof of work, is rejected
+        let prev_hash = chain.iter().next().unwrap().hash();
+        let plain = (0..)
+            .map(|nonce| Block::new_with_hash(vec![2], prev_hash.clone(), 6).set_nonce(nonce, 1337))
+            .find(|block| {
+                block.validate_difficulty() && !memory_hard().meets_difficulty(block.header())
+            })
+            .unwrap();
+        match chain.insert_with_rules(plain, &rules) {
+            Err(BlockchainError::InvalidBlockHash(_, 6)) => (),
+            _ => panic!("the memory-hard proof of work is missing"),
+        }
+    }
+
+    quickcheck! {
+        fn targets_agree_with_leading_zero_bits(hash: Vec<u8>, difficulty: usize) -> bool {
+            let difficulty = difficulty % 20;
+            let mut hash = hash;
+            hash.resize(32, 0);
+            let block: Block<u8, ::sha2::Sha256> = Block::new(vec![1], difficulty);
+            let legacy = block.clone().set_version(CANONICAL_VERSION);
+            legacy.difficulty() == difficulty
+                && block.header().meets_target(&hash) == legacy.header().meets_target(&hash)
+        }
+    }
+}
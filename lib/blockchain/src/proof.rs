@@ -0,0 +1,333 @@
+//! This module contains inclusion proofs, that show that a transaction has been recorded in a
+//! blockchain without transferring the whole chain. A proof consists of the transaction, a Merkle
+//! proof that the transaction is part of a block and a path of headers (see
+//! [`Header`](../header/struct.Header.html)) from that block up to the head of the chain.
+//!
+//! Each header of the path is linked to the previous header of the path by its `prev_hash` or,
+//! since block version `4` (`version::SKIP_VERSION`), by its skip hash. Skip hashes link to older
+//! ancestors (see [`skip_height`](../header/fn.skip_height.html)), so the path through a chain of
+//! such blocks contains `O(log n)` headers. Blocks of older versions are only linked to their
+//! parent, so the path contains every header above the newest of these blocks.
+
+use std::fmt::Debug;
+
+use generic_array::GenericArray;
+
+use blockchain::Blockchain;
+use header::{skip_height, Header};
+use merkle::{versioned_leaf_hash, MerkleProof};
+use version::{Hashable, SKIP_VERSION};
+
+use super::{BlockchainError, HashBytes};
+
+/// Proof that a transaction is part of a block, that is an ancestor of (or equal to) the head of a
+/// chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(serialize = "D: ::serde::Serialize, S: ::serde::Serialize",
+              deserialize = "D: ::serde::Deserialize<'de>, S: ::serde::Deserialize<'de> + Default"))]
+pub struct InclusionProof<D, H, S = ()>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug,
+{
+    transaction: D,
+    merkle_proof: MerkleProof<H>,
+    headers: Vec<Header<H, S>>,
+}
+
+impl<D, H, S> InclusionProof<D, H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug,
+{
+    /// Returns the proven transaction.
+    pub fn transaction(&self) -> &D {
+        &self.transaction
+    }
+
+    /// Returns the Merkle proof of the transaction.
+    pub fn merkle_proof(&self) -> &MerkleProof<H> {
+        &self.merkle_proof
+    }
+
+    /// Returns the path of headers from the block containing the transaction (first) up to the
+    /// head of the chain (last).
+    pub fn headers(&self) -> &[Header<H, S>] {
+        &self.headers
+    }
+
+    /// Returns the header of the block that contains the transaction.
+    pub fn header(&self) -> Option<&Header<H, S>> {
+        self.headers.first()
+    }
+}
+
+impl<D, H, S> Blockchain<D, H, S>
+where
+    D: Default + Hashable + Clone,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Default + Hashable + Clone,
+{
+    /// Creates an inclusion proof for the newest transaction, that matches the predicate. The
+    /// proof is relative to the current head of the chain. Returns `None` if no transaction
+    /// matches.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::proof::verify_inclusion;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![1, 2, 3], 4).unwrap().append(vec![4, 5], 4).unwrap();
+    /// let head = bc.iter().next().unwrap().hash();
+    /// let proof = bc.prove_inclusion(|tx| *tx == 2).unwrap();
+    /// // blocks of the current version are only linked to their parent
+    /// assert_eq!(proof.headers().len(), 2);
+    /// assert!(verify_inclusion(&proof, &head).is_ok());
+    /// assert!(bc.prove_inclusion(|tx| *tx == 6).is_none());
+    /// # }
+    /// ```
+    pub fn prove_inclusion<P>(&self, predicate: P) -> Option<InclusionProof<D, H, S>>
+    where
+        P: Fn(&D) -> bool,
+    {
+        let (depth, block, index) = self.iter()
+            .enumerate()
+            .filter_map(|(depth, block)| {
+                block
+                    .transactions()
+                    .iter()
+                    .position(&predicate)
+                    .map(|index| (depth, block, index))
+            })
+            .next()?;
+        let leaf_hashes = block
+            .transactions()
+            .iter()
+            .map(|tx| versioned_leaf_hash::<H, D>(block.version(), tx))
+            .collect();
+        let merkle_proof = MerkleProof::from_hashes(leaf_hashes, index)?;
+        let mut headers = self.path(self.len() - 1, self.len() - 1 - depth);
+        headers.reverse();
+        Some(InclusionProof {
+            transaction: block.transactions()[index].clone(),
+            merkle_proof: merkle_proof,
+            headers: headers,
+        })
+    }
+
+    /// Returns the headers of the path from the block at height `from` down to its ancestor at
+    /// height `to`. Skip hashes are followed, unless they skip the ancestor.
+    fn path(&self, from: usize, to: usize) -> Vec<Header<H, S>> {
+        let mut headers = Vec::new();
+        let mut height = from;
+        loop {
+            let header = self.get_by_height(height)
+                .expect("the path stays within the chain")
+                .header();
+            headers.push(header.clone());
+            if height == to {
+                return headers;
+            }
+            let skip = skip_height(height);
+            height = if header.version() >= SKIP_VERSION && skip >= to {
+                skip
+            } else {
+                height - 1
+            };
+        }
+    }
+}
+
+/// Verifies an inclusion proof against the hash of a trusted head. The following properties are
+/// checked:
+/// * The Merkle proof of the transaction matches the Merkle root of the containing block
+/// * Each header contains the hash of its predecessor in the proof as `prev_hash` or, since
+///   version `4`, as skip hash
+/// * The hash of each header matches its difficulty
+/// * The hash of the last header equals `head`
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::blockchain::Blockchain;
+/// use blockchain::proof::verify_inclusion;
+/// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new().append(vec![1, 2], 4).unwrap();
+/// let proof = bc.prove_inclusion(|tx| *tx == 1).unwrap();
+/// let bc = bc.append(vec![3], 4).unwrap();
+/// // the proof is not relative to the new head
+/// let head = bc.iter().next().unwrap().hash();
+/// assert!(verify_inclusion(&proof, &head).is_err());
+/// # }
+/// ```
+pub fn verify_inclusion<D, H, S>(
+    proof: &InclusionProof<D, H, S>,
+    head: &GenericArray<u8, H::OutputSize>,
+) -> Result<(), BlockchainError>
+where
+    D: Hashable,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug,
+    S: Hashable,
+{
+    let header = proof.header().ok_or_else(|| {
+        BlockchainError::InvalidProof("the proof does not contain any headers".to_owned())
+    })?;
+    let leaf_hash = versioned_leaf_hash::<H, D>(header.version(), &proof.transaction);
+    if proof.merkle_proof.root_of_hash(leaf_hash).as_ref() != Some(header.merkle_root()) {
+        return Err(BlockchainError::InvalidProof(
+            "the transaction is not part of the block".to_owned(),
+        ));
+    }
+    for pair in proof.headers.windows(2) {
+        let hash = pair[0].hash();
+        let skipped = pair[1].version() >= SKIP_VERSION && *pair[1].skip_hash() == hash;
+        if *pair[1].prev_hash() != hash && !skipped {
+            return Err(BlockchainError::InvalidPrevHash(
+                HashBytes::from(&pair[1].prev_hash()[..]),
+                HashBytes::from(&pair[0].hash()[..]),
+            ));
+        }
+    }
+    for header in &proof.headers {
+        if !header.validate_difficulty() {
+            return Err(BlockchainError::InvalidBlockHash(
+                HashBytes::from(&header.hash()[..]),
+                header.difficulty(),
+            ));
+        }
+    }
+    match proof.headers.last() {
+        Some(last) if last.hash() == *head => Ok(()),
+        _ => Err(BlockchainError::InvalidProof(
+            "the proof does not end in the head".to_owned(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use consensus::ConsensusRules;
+    use seal::ProofOfWork;
+    use target::Target;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    /// Rules without a required target, that require every block to contain a skip hash.
+    struct Linked;
+
+    impl ConsensusRules<::sha2::Sha256> for Linked {
+        type Scheme = ProofOfWork;
+
+        fn scheme(&self) -> &ProofOfWork {
+            &ProofOfWork
+        }
+
+        fn required_target<'a, I>(&self, _parent: I) -> Target
+        where
+            I: Iterator<Item = &'a Header<::sha2::Sha256>>,
+        {
+            Target::max_value()
+        }
+
+        fn version_at(&self, _height: usize) -> u8 {
+            SKIP_VERSION
+        }
+    }
+
+    fn linked_chain(len: u32) -> Chain {
+        (0..len).fold(Chain::new(), |chain, i| {
+            let block = chain
+                .generate_block(vec![i], 0)
+                .set_version(SKIP_VERSION)
+                .set_skip_hash(chain.next_skip_hash());
+            chain.insert_with_rules(block, &Linked).unwrap()
+        })
+    }
+
+    fn head(
+        chain: &Chain,
+    ) -> GenericArray<u8, <::sha2::Sha256 as ::digest::FixedOutput>::OutputSize> {
+        chain.iter().next().unwrap().hash()
+    }
+
+    #[test]
+    fn proof_for_head_block() {
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2, 3, 4], 0).unwrap();
+        let proof = chain.prove_inclusion(|tx| *tx == 4).unwrap();
+        assert_eq!(proof.headers().len(), 1);
+        assert_eq!(proof.transaction(), &4);
+        assert!(verify_inclusion(&proof, &head(&chain)).is_ok());
+    }
+
+    #[test]
+    fn tampered_transaction_is_rejected() {
+        let chain = Chain::new().append(vec![1, 2], 0).unwrap().append(vec![3], 0).unwrap();
+        let mut proof = chain.prove_inclusion(|tx| *tx == 1).unwrap();
+        proof.transaction = 2;
+        assert!(verify_inclusion(&proof, &head(&chain)).is_err());
+    }
+
+    #[test]
+    fn missing_header_is_rejected() {
+        let chain = Chain::new()
+            .append(vec![1], 0).unwrap()
+            .append(vec![2], 0).unwrap()
+            .append(vec![3], 0).unwrap();
+        let mut proof = chain.prove_inclusion(|tx| *tx == 1).unwrap();
+        proof.headers.remove(1);
+        assert!(verify_inclusion(&proof, &head(&chain)).is_err());
+    }
+
+    #[test]
+    fn skip_hashes_shorten_proofs() {
+        let chain = linked_chain(1000);
+        assert!(chain.validate_chain_with_rules(&Linked));
+        for tx in &[0, 1, 2, 500, 511, 512, 777, 998, 999] {
+            let proof = chain.prove_inclusion(|t| t == tx).unwrap();
+            assert!(proof.headers().len() <= 30, "{} headers", proof.headers().len());
+            assert_eq!(proof.header(), Some(chain.get_by_height(*tx as usize).unwrap().header()));
+            assert!(verify_inclusion(&proof, &head(&chain)).is_ok());
+        }
+        // the skip hashes of a chain are checked
+        let block = chain.generate_block(vec![1000], 0).set_version(SKIP_VERSION);
+        match chain.insert_with_rules(block, &Linked) {
+            Err(BlockchainError::InvalidSkipHash(_, _)) => {}
+            _ => panic!("the block does not contain a skip hash"),
+        }
+    }
+
+    #[test]
+    fn skipped_header_is_rejected() {
+        let chain = linked_chain(64);
+        let mut proof = chain.prove_inclusion(|tx| *tx == 5).unwrap();
+        assert!(proof.headers().len() < 64 - 5);
+        proof.headers.remove(1);
+        assert!(verify_inclusion(&proof, &head(&chain)).is_err());
+    }
+
+    #[test]
+    fn linked_headers_are_serialized() {
+        let chain = linked_chain(3);
+        let header = chain.headers().next().unwrap();
+        assert!(header.skip_hash().iter().any(|b| *b != 0));
+        let bytes = ::bincode::serialize(header, ::bincode::Infinite).unwrap();
+        let decoded: Header<::sha2::Sha256> = ::bincode::deserialize(&bytes).unwrap();
+        assert_eq!(&decoded, header);
+        assert_eq!(decoded.hash(), header.hash());
+        // older versions do not contain a skip hash
+        let unlinked = Chain::new().append(vec![1], 0).unwrap();
+        let header = unlinked.headers().next().unwrap();
+        let len = ::bincode::serialize(header, ::bincode::Infinite).unwrap().len();
+        let skip_len = ::bincode::serialize(header.skip_hash(), ::bincode::Infinite).unwrap().len();
+        assert_eq!(len + skip_len, bytes.len());
+        assert_eq!(header.clone().set_skip_hash(header.hash()), *header);
+    }
+}
@@ -0,0 +1,286 @@
+//! This module contains a detailed validation of a chain. Instead of a single `bool`, a
+//! [`ValidationReport`](struct.ValidationReport.html) lists the problems, that have been found,
+//! each with the height and hash of the offending block and a typed [`Reason`](enum.Reason.html).
+
+use std::fmt::Debug;
+
+use blockchain::Blockchain;
+use consensus::ConsensusRules;
+use version::Hashable;
+
+use super::{BlockchainError, HashBytes};
+
+/// Category of a validation problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Reason {
+    /// The block is not linked to its parent, e.g. its `prev_hash` does not match the hash of the
+    /// parent or the parent is unknown.
+    Linkage,
+    /// The seal of the block is invalid or its difficulty is lower than required.
+    Difficulty,
+    /// The version of the block is unknown or not active at the block's height.
+    Version,
+    /// The time of the block violates the time rules.
+    Timestamp,
+    /// The transactions of the block do not match its Merkle root, cannot be decoded or are
+    /// rejected, e.g. because of an invalid signature.
+    Payload,
+}
+
+impl BlockchainError {
+    /// Returns the category of the error.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::BlockchainError;
+    /// use blockchain::report::Reason;
+    /// use blockchain::target::Target;
+    /// assert_eq!(BlockchainError::InvalidTime(1, 2).reason(), Reason::Timestamp);
+    /// let (target, required) = (Target::from_difficulty(1), Target::from_difficulty(2));
+    /// assert_eq!(
+    ///     BlockchainError::InsufficientDifficulty(target, required).reason(),
+    ///     Reason::Difficulty
+    /// );
+    /// # }
+    /// ```
+    pub fn reason(&self) -> Reason {
+        match *self {
+            BlockchainError::InvalidPrevHash(..)
+            | BlockchainError::InvalidSkipHash(..)
+            | BlockchainError::UnknownParent(_)
+            | BlockchainError::DuplicateBlock(_)
+            | BlockchainError::CheckpointMismatch(..)
+            | BlockchainError::InvalidGenesis(..)
+            | BlockchainError::UnknownHeight(_) => Reason::Linkage,
+            BlockchainError::InvalidBlockHash(..)
+            | BlockchainError::InsufficientDifficulty(..)
+            | BlockchainError::InvalidSeal(_)
+            | BlockchainError::InvalidTarget(_)
+            | BlockchainError::InvalidPowParameters(_) => Reason::Difficulty,
+            BlockchainError::UnknownVersion(_) | BlockchainError::InactiveVersion(..) => {
+                Reason::Version
+            }
+            BlockchainError::InvalidTime(..)
+            | BlockchainError::TimeNotAfterMedian(..)
+            | BlockchainError::FutureTime(..) => Reason::Timestamp,
+            BlockchainError::InvalidMerkleRoot(_)
+            | BlockchainError::InvalidProof(_)
+            | BlockchainError::InvalidTransaction(..)
+            | BlockchainError::InvalidEncoding(_) => Reason::Payload,
+        }
+    }
+}
+
+/// Decides if validation stops at the first problem or continues to find all problems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// Stop at the first (oldest) block, that is invalid.
+    First,
+    /// Validate every block. Each block is validated against its actual parent, even if the
+    /// parent itself is invalid.
+    All,
+}
+
+/// A block, that failed validation.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    height: usize,
+    hash: HashBytes,
+    error: BlockchainError,
+}
+
+impl Problem {
+    /// Creates a problem for the block with the given height and hash.
+    pub(crate) fn new(height: usize, hash: &[u8], error: BlockchainError) -> Self {
+        Self {
+            height: height,
+            hash: HashBytes::from(hash),
+            error: error,
+        }
+    }
+
+    /// Returns the height of the block. The genesis block has height `0`.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns the hash of the block.
+    pub fn hash(&self) -> &HashBytes {
+        &self.hash
+    }
+
+    /// Returns the category of the problem.
+    pub fn reason(&self) -> Reason {
+        self.error.reason()
+    }
+
+    /// Returns the error, the block has been rejected with.
+    pub fn error(&self) -> &BlockchainError {
+        &self.error
+    }
+}
+
+/// Result of validating a chain. Problems are ordered by height, oldest block first.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    checked: usize,
+    problems: Vec<Problem>,
+}
+
+impl ValidationReport {
+    /// Creates a report from the number of validated blocks and the problems, ordered by height.
+    pub(crate) fn new(checked: usize, problems: Vec<Problem>) -> Self {
+        Self {
+            checked: checked,
+            problems: problems,
+        }
+    }
+
+    /// Returns true if no problems have been found.
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Returns the number of blocks, that have been validated.
+    pub fn checked(&self) -> usize {
+        self.checked
+    }
+
+    /// Returns the problem of the oldest invalid block.
+    pub fn first(&self) -> Option<&Problem> {
+        self.problems.first()
+    }
+
+    /// Returns all problems, that have been found, oldest block first.
+    pub fn problems(&self) -> &[Problem] {
+        &self.problems
+    }
+
+    /// Returns the error of the oldest invalid block or `Ok(())` if the chain is valid.
+    pub fn into_result(self) -> Result<(), BlockchainError> {
+        match self.problems.into_iter().next() {
+            Some(problem) => Err(problem.error),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<D, H, S> Blockchain<D, H, S>
+where
+    D: Default + Hashable,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Default + Hashable,
+{
+    /// Validates the chain against the consensus rules like
+    /// [`validate_chain_with_rules`](../blockchain/struct.Blockchain.html#method.validate_chain_with_rules),
+    /// oldest block first, and reports where and why validation fails.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::consensus::FixedDifficulty;
+    /// use blockchain::report::{Reason, Scope};
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc
+    ///     .append(vec![1], 8)
+    ///     .unwrap()
+    ///     .append(vec![2], 4)
+    ///     .unwrap()
+    ///     .append(vec![3], 4)
+    ///     .unwrap();
+    /// let report = bc.validation_report(&FixedDifficulty(8), Scope::All);
+    /// assert_eq!(report.checked(), 3);
+    /// assert_eq!(report.problems().len(), 2);
+    /// let first = report.first().unwrap();
+    /// assert_eq!(first.height(), 1);
+    /// assert_eq!(first.reason(), Reason::Difficulty);
+    /// assert_eq!(&first.hash()[..], &bc.get_by_height(1).unwrap().hash()[..]);
+    /// let report = bc.validation_report(&FixedDifficulty(8), Scope::First);
+    /// assert_eq!(report.checked(), 2);
+    /// assert_eq!(report.problems().len(), 1);
+    /// # }
+    /// ```
+    pub fn validation_report<R>(&self, rules: &R, scope: Scope) -> ValidationReport
+    where
+        R: ConsensusRules<H, S>,
+    {
+        let mut report = ValidationReport::default();
+        // every block is validated against its ancestors, oldest block first
+        for (height, block) in self.range(0, self.len()).enumerate() {
+            report.checked += 1;
+            if let Err(error) = self.validate_at(height, rules) {
+                report
+                    .problems
+                    .push(Problem::new(height, &block.hash(), error));
+                if scope == Scope::First {
+                    break;
+                }
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use consensus::FixedDifficulty;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    #[test]
+    fn valid_chain_has_no_problems() {
+        let chain = Chain::new().append(vec![1], 4).unwrap().append(vec![2], 4).unwrap();
+        let report = chain.validation_report(&FixedDifficulty(4), Scope::All);
+        assert!(report.is_valid());
+        assert_eq!(report.checked(), 2);
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn broken_linkage_is_reported() {
+        let chain = Chain::new().append(vec![1], 0).unwrap();
+        let orphan = Chain::new().append(vec![2], 0).unwrap().append(vec![3], 0).unwrap();
+        let block = orphan.iter().next().unwrap().clone();
+        let chain = chain.push(block.clone());
+        let report = chain.validation_report(&FixedDifficulty(0), Scope::All);
+        assert_eq!(report.problems().len(), 1);
+        let problem = report.first().unwrap();
+        assert_eq!(problem.height(), 1);
+        assert_eq!(problem.reason(), Reason::Linkage);
+        assert_eq!(&problem.hash()[..], &block.hash()[..]);
+        match *problem.error() {
+            BlockchainError::InvalidPrevHash(ref found, ref expected) => {
+                assert_eq!(&found[..], &block.prev_hash()[..]);
+                assert_eq!(&expected[..], &chain.get_by_height(0).unwrap().hash()[..]);
+            }
+            _ => panic!("expected a linkage error"),
+        }
+    }
+
+    #[test]
+    fn unsupported_version_is_reported() {
+        let chain = Chain::new().append(vec![1], 0).unwrap();
+        let block = chain.generate_block(vec![2], 0).set_version(7);
+        let chain = chain.push(block);
+        let report = chain.validation_report(&FixedDifficulty(0), Scope::First);
+        assert_eq!(report.first().map(|p| p.reason()), Some(Reason::Version));
+        assert_eq!(report.checked(), 2);
+    }
+
+    quickcheck! {
+        fn report_agrees_with_validate_chain(chain: Chain, difficulty: usize) -> bool {
+            let rules = FixedDifficulty(difficulty % 4);
+            let report = chain.validation_report(&rules, Scope::All);
+            report.is_valid() == chain.validate_chain_with_rules(&rules)
+                && report.problems().windows(2).all(|p| p[0].height() < p[1].height())
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! This module contains the sealing schemes, that decide whether a block has been sealed correctly.
+//! The leading zero proof of work is the default scheme. Other schemes (e.g. proof of authority)
+//! store their proof in the generic seal of a block.
+
+use std::fmt;
+use std::sync::Arc;
+
+use consensus::ConsensusRules;
+use header::Header;
+use target::Target;
+use version::Hashable;
+
+use super::{BlockchainError, HashBytes};
+
+/// A scheme that verifies the seal of a block. The seal is part of the block header, so it can be
+/// verified without the transactions of the block.
+pub trait SealingScheme<H, S>
+where
+    H: ::digest::Digest,
+{
+    /// Verifies the seal of a block header and returns an error if it is invalid.
+    fn verify_seal(&self, header: &Header<H, S>) -> Result<(), BlockchainError>;
+}
+
+/// Proof of work sealing scheme. A block is sealed if its hash has at least as many leading zero
+/// bits as its difficulty factor.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::block::Block;
+/// use blockchain::seal::{ProofOfWork, SealingScheme};
+/// let block: Block<_, ::sha2::Sha256> = Block::new(vec![42], 8).proof_of_work();
+/// assert!(ProofOfWork.verify_seal(block.header()).is_ok());
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ProofOfWork;
+
+impl<H, S> SealingScheme<H, S> for ProofOfWork
+where
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    fn verify_seal(&self, header: &Header<H, S>) -> Result<(), BlockchainError> {
+        if header.validate_difficulty() {
+            Ok(())
+        } else {
+            Err(BlockchainError::InvalidBlockHash(
+                HashBytes::from(&header.hash()[..]),
+                header.difficulty(),
+            ))
+        }
+    }
+}
+
+/// Sealing scheme, that is shared between all chains, that have been derived from a chain
+/// configured using
+/// [`Blockchain::with_scheme`](../blockchain/struct.Blockchain.html#method.with_scheme).
+pub(crate) struct SharedScheme<H, S>(Arc<SealingScheme<H, S> + Send + Sync>);
+
+impl<H, S> SharedScheme<H, S> {
+    /// Shares the sealing scheme.
+    pub(crate) fn new<Sc>(scheme: Sc) -> Self
+    where
+        Sc: SealingScheme<H, S> + Send + Sync + 'static,
+        H: ::digest::Digest,
+    {
+        SharedScheme(Arc::new(scheme))
+    }
+}
+
+impl<H, S> Clone for SharedScheme<H, S> {
+    fn clone(&self) -> Self {
+        SharedScheme(self.0.clone())
+    }
+}
+
+impl<H, S> fmt::Debug for SharedScheme<H, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SharedScheme")
+    }
+}
+
+impl<H, S> SealingScheme<H, S> for SharedScheme<H, S>
+where
+    H: ::digest::Digest,
+{
+    fn verify_seal(&self, header: &Header<H, S>) -> Result<(), BlockchainError> {
+        self.0.verify_seal(header)
+    }
+}
+
+/// Consensus rules, that verify seals using the given sealing scheme instead of the scheme of the
+/// underlying rules. All other rules are taken from the underlying rules.
+pub(crate) struct WithScheme<'a, R: 'a, Sc: 'a> {
+    rules: &'a R,
+    scheme: &'a Sc,
+}
+
+impl<'a, R, Sc> WithScheme<'a, R, Sc> {
+    /// Replaces the sealing scheme of the rules.
+    pub(crate) fn new(rules: &'a R, scheme: &'a Sc) -> Self {
+        Self {
+            rules: rules,
+            scheme: scheme,
+        }
+    }
+}
+
+impl<'a, H, S, R, Sc> ConsensusRules<H, S> for WithScheme<'a, R, Sc>
+where
+    H: ::digest::Digest,
+    R: ConsensusRules<H, S>,
+    Sc: SealingScheme<H, S>,
+{
+    type Scheme = Sc;
+
+    fn scheme(&self) -> &Sc {
+        self.scheme
+    }
+
+    fn required_target<'b, I>(&self, parent: I) -> Target
+    where
+        I: Iterator<Item = &'b Header<H, S>>,
+        H: 'b,
+        S: 'b,
+    {
+        self.rules.required_target(parent)
+    }
+
+    fn version_at(&self, height: usize) -> u8 {
+        self.rules.version_at(height)
+    }
+
+    fn median_time_span(&self, height: usize) -> usize {
+        self.rules.median_time_span(height)
+    }
+
+    fn max_future_drift(&self, height: usize) -> Option<u64> {
+        self.rules.max_future_drift(height)
+    }
+
+    fn now(&self) -> u64 {
+        self.rules.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use block::Block;
+    use blockchain::Blockchain;
+
+    type Chain = Blockchain<u8, ::sha2::Sha256, u8>;
+
+    /// Toy scheme, that accepts a block if its seal equals the first byte of its seal hash.
+    struct FirstByte;
+
+    impl SealingScheme<::sha2::Sha256, u8> for FirstByte {
+        fn verify_seal(
+            &self,
+            header: &Header<::sha2::Sha256, u8>,
+        ) -> Result<(), BlockchainError> {
+            if *header.seal() == header.seal_hash()[0] {
+                Ok(())
+            } else {
+                Err(BlockchainError::InvalidSeal("wrong first byte".to_owned()))
+            }
+        }
+    }
+
+    impl ConsensusRules<::sha2::Sha256, u8> for FirstByte {
+        type Scheme = Self;
+
+        fn scheme(&self) -> &Self {
+            self
+        }
+
+        fn required_target<'a, I>(&self, _parent: I) -> Target
+        where
+            I: Iterator<Item = &'a Header<::sha2::Sha256, u8>>,
+        {
+            Target::max_value()
+        }
+    }
+
+    #[test]
+    fn insert_enforces_scheme() {
+        let configured = Chain::new().with_scheme(FirstByte);
+        let block = Block::new(vec![42], 0);
+        let first = block.seal_hash()[0];
+        assert!(configured
+            .insert(block.clone().set_seal(first.wrapping_add(1)))
+            .is_err());
+        assert!(configured.insert(block.set_seal(first)).is_ok());
+        let chain = Chain::new();
+        let block = Block::new(vec![42], 0);
+        let first = block.seal_hash()[0];
+        assert!(chain
+            .insert_with_rules(block.clone().set_seal(first.wrapping_add(1)), &FirstByte)
+            .is_err());
+        let chain = chain
+            .insert_with_rules(block.set_seal(first), &FirstByte)
+            .unwrap();
+        assert!(chain.validate_chain_with_rules(&FirstByte));
+    }
+
+    #[test]
+    fn validate_chain_enforces_scheme() {
+        let chain = Chain::new();
+        let block = chain.generate_block(vec![42], 0);
+        let first = block.seal_hash()[0];
+        let chain = chain.push(block.set_seal(first.wrapping_add(1)));
+        assert!(!chain.validate_chain_with_rules(&FirstByte));
+        assert!(!chain.clone().with_scheme(FirstByte).validate_chain());
+        let chain = Chain::new().with_scheme(FirstByte);
+        let block = chain.generate_block(vec![42], 0);
+        let first = block.seal_hash()[0];
+        let chain = chain.insert(block.set_seal(first)).unwrap();
+        assert!(chain.validate_chain());
+        // derived chains keep the scheme
+        let (_, parent) = chain.tail();
+        let block = parent.generate_block(vec![43], 0);
+        let first = block.seal_hash()[0];
+        assert!(parent.insert(block.set_seal(first.wrapping_add(1))).is_err());
+    }
+}
@@ -0,0 +1,226 @@
+//! This module contains the specification of a chain. A [`ChainSpec`](struct.ChainSpec.html)
+//! gives a chain its identity: a chain id, a fixed genesis block and the consensus rules, the
+//! blocks of the chain are validated with. A chain, that has been created from a spec (see
+//! [`Blockchain::from_spec`](../blockchain/struct.Blockchain.html#method.from_spec)), only accepts
+//! the genesis block of its spec at height `0`. Every other block is linked to the genesis block
+//! through its ancestors, so blocks of another chain cannot be appended, even if they are valid on
+//! their own.
+//!
+//! The chain id is meant to be committed to by signatures of transactions, so transactions cannot
+//! be replayed on another chain.
+
+use std::fmt::Debug;
+
+use serde::Serialize;
+
+use block::Block;
+use consensus::Retarget;
+use version::Hashable;
+
+use super::{BlockchainError, HashBytes};
+
+/// Specification of a chain containing its id, its genesis block and its consensus rules.
+///
+/// # Examples
+/// ```
+/// extern crate sha2;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::block::Block;
+/// use blockchain::consensus::Retarget;
+/// use blockchain::spec::ChainSpec;
+/// let genesis: Block<_, sha2::Sha256> = Block::new(vec![1], 4).proof_of_work();
+/// let spec = ChainSpec::new("test", genesis.clone(), Retarget::new(4, 60, 16));
+/// assert_eq!(spec.chain_id(), "test");
+/// assert!(spec.check_genesis(&genesis).is_ok());
+/// assert!(spec.check_genesis(&Block::new(vec![2], 4).proof_of_work()).is_err());
+/// # }
+/// ```
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "D: Serialize, S: Serialize",
+              deserialize = "D: ::serde::Deserialize<'de>, S: ::serde::Deserialize<'de> + Default"))]
+pub struct ChainSpec<D, H, S = ()>
+where
+    H: ::digest::Digest,
+{
+    chain_id: String,
+    genesis: Block<D, H, S>,
+    rules: Retarget,
+}
+
+impl<D, H, S> ChainSpec<D, H, S>
+where
+    H: ::digest::Digest,
+{
+    /// Creates the specification of a chain. The genesis block is validated against the rules,
+    /// when a chain is created from the spec.
+    pub fn new(chain_id: &str, genesis: Block<D, H, S>, rules: Retarget) -> Self {
+        Self {
+            chain_id: chain_id.to_owned(),
+            genesis: genesis,
+            rules: rules,
+        }
+    }
+
+    /// Creates the spec of a chain, that has been started before chains had a spec, e.g. the chain
+    /// of a store written by an earlier release. The chain keeps its own genesis block and takes
+    /// the id and the consensus rules of the given spec.
+    pub fn from_existing_genesis(genesis: Block<D, H, S>, spec: Self) -> Self {
+        Self {
+            genesis: genesis,
+            ..spec
+        }
+    }
+
+    /// Returns the id of the chain.
+    pub fn chain_id(&self) -> &str {
+        &self.chain_id
+    }
+
+    /// Returns the genesis block.
+    pub fn genesis(&self) -> &Block<D, H, S> {
+        &self.genesis
+    }
+
+    /// Returns the consensus rules of the chain.
+    pub fn rules(&self) -> &Retarget {
+        &self.rules
+    }
+
+    /// Replaces the consensus rules, e.g. to activate a new block version or to inject a clock.
+    /// The method returns a new spec and consumes the old one.
+    pub fn with_rules(self, rules: Retarget) -> Self {
+        Self {
+            rules: rules,
+            ..self
+        }
+    }
+}
+
+impl<D, H, S> ChainSpec<D, H, S>
+where
+    D: Hashable,
+    H: ::digest::Digest,
+    S: Hashable,
+{
+    /// Checks if the block is the genesis block of the chain.
+    pub fn check_genesis(&self, block: &Block<D, H, S>) -> Result<(), BlockchainError> {
+        let expected = self.genesis.hash();
+        let found = block.hash();
+        if found == expected {
+            Ok(())
+        } else {
+            Err(BlockchainError::InvalidGenesis(
+                HashBytes::from(&found[..]),
+                HashBytes::from(&expected[..]),
+            ))
+        }
+    }
+
+    /// Checks if both specs describe the same chain, e.g. if they have the same id and the same
+    /// genesis block. The consensus rules are not compared, so they can change over the lifetime
+    /// of a chain.
+    pub fn is_same_chain(&self, other: &Self) -> bool {
+        self.chain_id == other.chain_id && self.genesis.hash() == other.genesis.hash()
+    }
+}
+
+impl<D, H, S> Clone for ChainSpec<D, H, S>
+where
+    D: Clone,
+    H: ::digest::Digest,
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            chain_id: self.chain_id.clone(),
+            genesis: self.genesis.clone(),
+            rules: self.rules.clone(),
+        }
+    }
+}
+
+impl<D, H, S> Debug for ChainSpec<D, H, S>
+where
+    D: Debug,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug,
+    S: Debug,
+{
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("ChainSpec")
+            .field("chain_id", &self.chain_id)
+            .field("genesis", &self.genesis)
+            .field("rules", &self.rules)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blockchain::Blockchain;
+    use report::{Reason, Scope};
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+    type Spec = ChainSpec<u32, ::sha2::Sha256>;
+
+    fn spec(chain_id: &str, genesis: u32) -> Spec {
+        let genesis = Block::new(vec![genesis], 2).proof_of_work();
+        ChainSpec::new(chain_id, genesis, Retarget::new(2, 60, 16))
+    }
+
+    #[test]
+    fn chain_starts_with_genesis() {
+        let spec = spec("test", 0);
+        let chain = Chain::from_spec(spec.clone()).unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain.get_by_height(0), Some(spec.genesis()));
+        assert_eq!(chain.chain_id(), Some("test"));
+    }
+
+    #[test]
+    fn blocks_of_other_chains_are_rejected() {
+        let production = Chain::from_spec(spec("production", 0)).unwrap();
+        let test = Chain::from_spec(spec("test", 1)).unwrap().append(vec![2], 2).unwrap();
+        let block = test.iter().next().unwrap().clone();
+        assert!(production.insert(block).is_err());
+        // a second genesis block is rejected as well
+        let genesis = test.get_by_height(0).unwrap().clone();
+        assert!(production.insert(genesis.clone()).is_err());
+        match production.tail().1.insert(genesis) {
+            Err(BlockchainError::InvalidGenesis(..)) => {}
+            _ => panic!("only the genesis block of the spec is accepted at height 0"),
+        }
+    }
+
+    #[test]
+    fn insert_enforces_rules_of_spec() {
+        let chain = Chain::from_spec(spec("test", 0)).unwrap();
+        assert!(chain.insert(chain.generate_block(vec![1], 1)).is_err());
+        assert!(chain.insert(chain.generate_block(vec![1], 2)).is_ok());
+    }
+
+    #[test]
+    fn validation_reports_foreign_genesis() {
+        let spec = spec("test", 0);
+        let chain = Chain::from_spec(spec.clone()).unwrap().append(vec![1], 2).unwrap();
+        let report = chain.validation_report(spec.rules(), Scope::First);
+        assert!(report.is_valid());
+        let foreign = Chain::new().append(vec![5], 2).unwrap();
+        let chain = Chain::from_spec(spec.clone()).unwrap();
+        let chain = chain.tail().1.push(foreign.iter().next().unwrap().clone());
+        let report = chain.validation_report(spec.rules(), Scope::First);
+        assert_eq!(report.first().map(|p| p.reason()), Some(Reason::Linkage));
+    }
+
+    #[test]
+    fn same_chain_ignores_rules() {
+        let spec = spec("test", 0);
+        let changed = spec.clone().with_rules(Retarget::new(8, 60, 16));
+        assert!(spec.is_same_chain(&changed));
+        assert!(!spec.is_same_chain(&self::spec("other", 0)));
+        assert!(!spec.is_same_chain(&self::spec("test", 1)));
+    }
+}
@@ -3,8 +3,15 @@
 //! Lists](http://cglab.ca/~abeinges/blah/too-many-lists/book/third.html). The stack contains
 //! reference counted nodes, implements push/append and pop/tail but never mutates itself.
 //! The implementation has been modified to contain a field `len` to enable getting the size of the
-//! stack in `O(1)`
-
+//! stack in `O(1)`.
+//!
+//! Each node additionally stores its height and a jump pointer to one of its ancestors. The jump
+//! pointers form a deterministic skip list (see Myers, "An applicative random-access stack"), so
+//! the element at a given height can be found in `O(log n)` steps. Since the jump pointers only
+//! point to ancestors, they are shared between all stacks containing the node, like the nodes
+//! themselves.
+
+use std::cmp;
 use std::fmt;
 use std::marker::PhantomData;
 use std::sync::Arc;
@@ -15,19 +22,18 @@ use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
 type Link<T> = Option<Arc<Node<T>>>;
 
 /// Persistent stack.
-#[derive(Debug, Clone)]
 pub struct Stack<T> {
     head: Link<T>,
     len: usize,
 }
 
-#[derive(Debug)]
 struct Node<T> {
     element: T,
     next: Link<T>,
+    jump: Link<T>,
+    height: usize,
 }
 
-#[derive(Clone)]
 pub struct Iter<'a, T>
 where
     T: 'a,
@@ -35,27 +41,99 @@ where
     next: Option<&'a Node<T>>,
 }
 
+/// Double-ended iterator over the elements of a stack between two heights, oldest first. Each
+/// step looks up the element by its height.
+pub struct Range<'a, T>
+where
+    T: 'a,
+{
+    stack: &'a Stack<T>,
+    front: usize,
+    back: usize,
+}
+
+// like the stack itself, the iterator only holds references, so `T` does not need to be `Clone`
+impl<'a, T> Clone for Iter<'a, T> {
+    fn clone(&self) -> Self {
+        Iter { next: self.next }
+    }
+}
+
 impl<T> Stack<T> {
     pub fn new() -> Self {
         Default::default()
     }
 
     pub fn append(&self, elem: T) -> Self {
+        // if the jumps of the parent and of the parent's jump target span the same distance, the
+        // new node jumps over both, otherwise it jumps to its parent
+        let jump = self.head.as_ref().and_then(|parent| {
+            match parent.jump.as_ref().and_then(|jump| jump.jump.as_ref().map(|jj| (jump, jj))) {
+                Some((jump, jump_jump))
+                    if parent.height - jump.height == jump.height - jump_jump.height =>
+                {
+                    Some(jump_jump.clone())
+                }
+                _ => Some(parent.clone()),
+            }
+        });
         Self {
             head: Some(Arc::new(Node {
                 element: elem,
                 next: self.head.clone(),
+                jump: jump,
+                height: self.len,
             })),
             len: self.len + 1,
         }
     }
 
+    /// Creates an iterator over the elements from height `start` (inclusive) to `end` (exclusive),
+    /// oldest first. The bounds are clamped to the length of the stack.
+    pub fn range(&self, start: usize, end: usize) -> Range<T> {
+        let end = cmp::min(end, self.len);
+        Range {
+            stack: self,
+            front: cmp::min(start, end),
+            back: end,
+        }
+    }
+
+    /// Returns the element at the given height. The oldest element has height `0`.
+    pub fn get(&self, height: usize) -> Option<&T> {
+        self.node(height).map(|node| &node.element)
+    }
+
+    /// Finds the node at the given height in `O(log n)` steps using the jump pointers.
+    fn node(&self, height: usize) -> Option<&Node<T>> {
+        let mut node = match self.head {
+            Some(ref node) if height < self.len => &**node,
+            _ => return None,
+        };
+        while node.height > height {
+            node = match (node.jump.as_ref(), node.next.as_ref()) {
+                (Some(jump), _) if jump.height >= height => &**jump,
+                (_, Some(next)) => &**next,
+                _ => return None,
+            };
+        }
+        Some(node)
+    }
+
     pub fn iter(&self) -> Iter<T> {
         Iter {
             next: self.head.as_ref().map(|node| &**node),
         }
     }
 
+    /// Creates an iterator over the elements below the given height, newest first, i.e. over the
+    /// ancestors of the element at that height. The first element is found in `O(log n)` steps.
+    pub fn iter_below(&self, height: usize) -> Iter<T> {
+        Iter {
+            next: height.checked_sub(1).and_then(|height| self.node(height)),
+        }
+    }
+
     pub fn tail(&self) -> (Option<&T>, Self) {
         let new_len = if self.head.is_some() {
             self.len - 1
@@ -91,6 +169,47 @@ impl<'a, T> Iterator for Iter<'a, T> {
     }
 }
 
+impl<'a, T> Clone for Range<'a, T> {
+    fn clone(&self) -> Self {
+        Range {
+            stack: self.stack,
+            front: self.front,
+            back: self.back,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Range<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front < self.back {
+            self.front += 1;
+            self.stack.get(self.front - 1)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> DoubleEndedIterator for Range<'a, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front < self.back {
+            self.back -= 1;
+            self.stack.get(self.back)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Range<'a, T> {}
+
 impl<T> Drop for Stack<T> {
     fn drop(&mut self) {
         let mut head = self.head.take();
@@ -104,6 +223,25 @@ impl<T> Drop for Stack<T> {
     }
 }
 
+impl<T> fmt::Debug for Stack<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+// cloning a stack only clones the reference to the head, so `T` does not need to be `Clone`
+impl<T> Clone for Stack<T> {
+    fn clone(&self) -> Self {
+        Self {
+            head: self.head.clone(),
+            len: self.len,
+        }
+    }
+}
+
 impl<T> Default for Stack<T> {
     fn default() -> Self {
         Self { head: None, len: 0 }
@@ -118,35 +256,41 @@ where
     where
         S: Serializer,
     {
+        // the elements are serialized oldest first, so they can be appended one at a time while
+        // deserializing
         let mut seq = serializer.serialize_seq(Some(self.len()))?;
-        for e in self.iter() {
+        for e in self.range(0, self.len()) {
             seq.serialize_element(e)?;
         }
         seq.end()
     }
 }
 
-struct StackVisitor<T>
+struct StackVisitor<T, F>
 where
     T: Serialize,
 {
+    on_append: F,
     marker: PhantomData<fn() -> Stack<T>>,
 }
 
-impl<T> StackVisitor<T>
+impl<T, F> StackVisitor<T, F>
 where
     T: Serialize,
+    F: FnMut(&T, usize),
 {
-    fn new() -> Self {
+    fn new(on_append: F) -> Self {
         Self {
+            on_append: on_append,
             marker: PhantomData,
         }
     }
 }
 
-impl<'de, T> Visitor<'de> for StackVisitor<T>
+impl<'de, T, F> Visitor<'de> for StackVisitor<T, F>
 where
     T: Deserialize<'de> + Serialize,
+    F: FnMut(&T, usize),
 {
     // The type that our Visitor is going to produce.
     type Value = Stack<T>;
@@ -159,29 +303,39 @@ where
     // Deserialize MyMap from an abstract "map" provided by the
     // Deserializer. The MapAccess input is a callback provided by
     // the Deserializer to let us see each entry in the map.
-    fn visit_seq<S>(self, mut access: S) -> Result<Self::Value, S::Error>
+    fn visit_seq<S>(mut self, mut access: S) -> Result<Self::Value, S::Error>
     where
         S: SeqAccess<'de>,
     {
-        // let mut map = MyMap::with_capacity(access.size_hint().unwrap_or(0));
-        let mut vec = Vec::with_capacity(access.size_hint().unwrap_or(0));
         let mut chain = Stack::new();
 
-        // While there are entries remaining in the input, add them
-        // into our map.
+        // While there are entries remaining in the input, append them to the stack. The elements
+        // are serialized oldest first, so no buffering is needed.
         while let Some(value) = access.next_element()? {
-            vec.push(value);
-            // chain = chain.append(value);
-        }
-
-        for val in vec.into_iter().rev() {
-            chain = chain.append(val);
+            (self.on_append)(&value, chain.len());
+            chain = chain.append(value);
         }
 
         Ok(chain)
     }
 }
 
+impl<T> Stack<T>
+where
+    T: Serialize,
+{
+    /// Deserializes a stack like `Deserialize`, but passes each element and its height to
+    /// `on_append`, before it is appended, e.g. to index the elements while they are streamed.
+    pub fn deserialize_with<'de, D, F>(deserializer: D, on_append: F) -> Result<Self, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+        F: FnMut(&T, usize),
+    {
+        deserializer.deserialize_seq(StackVisitor::new(on_append))
+    }
+}
+
 // This is the trait that informs Serde how to deserialize MyMap.
 impl<'de, T> Deserialize<'de> for Stack<T>
 where
@@ -193,7 +347,7 @@ where
     {
         // Instantiate our Visitor and ask the Deserializer to drive
         // it over the input data, resulting in an instance of MyMap.
-        deserializer.deserialize_seq(StackVisitor::new())
+        Self::deserialize_with(deserializer, |_, _| ())
     }
 }
 
@@ -310,4 +464,49 @@ mod tests {
             len + 1 == xs.append(false).len()
         }
     }
+
+    #[test]
+    fn get() {
+        let chain = (0..1000).fold(Stack::new(), |acc, x| acc.append(x));
+        assert!((0..1000).all(|x| chain.get(x) == Some(&x)));
+        assert_eq!(chain.get(1000), None);
+        let (_, shorter) = chain.tail();
+        assert_eq!(shorter.get(999), None);
+        assert_eq!(shorter.append(42).get(999), Some(&42));
+    }
+
+    #[test]
+    fn iter_below() {
+        let chain = (0..100).fold(Stack::new(), |acc, x| acc.append(x));
+        assert!(chain.iter_below(100).eq(chain.iter()));
+        assert_eq!(chain.iter_below(3).collect::<Vec<_>>(), vec![&2, &1, &0]);
+        assert_eq!(chain.iter_below(0).next(), None);
+        assert_eq!(chain.iter_below(1000).next(), None);
+    }
+
+    #[test]
+    fn range() {
+        let chain = Stack::new().append(1).append(2).append(3).append(4);
+        assert_eq!(chain.range(1, 3).collect::<Vec<_>>(), vec![&2, &3]);
+        assert_eq!(chain.range(2, 10).rev().collect::<Vec<_>>(), vec![&4, &3]);
+        assert_eq!(chain.range(3, 1).next(), None);
+        let mut range = chain.range(0, 4);
+        assert_eq!(range.next(), Some(&1));
+        assert_eq!(range.next_back(), Some(&4));
+        assert_eq!(range.len(), 2);
+    }
+
+    quickcheck! {
+        fn reversed_range_matches_iter(xs: Stack<u32>) -> bool {
+            xs.range(0, xs.len()).rev().eq(xs.iter())
+        }
+    }
+
+    quickcheck! {
+        fn get_matches_iter(xs: Stack<u32>) -> bool {
+            let mut elements = xs.iter().collect::<Vec<_>>();
+            elements.reverse();
+            elements.iter().enumerate().all(|(height, e)| xs.get(height) == Some(*e))
+        }
+    }
 }
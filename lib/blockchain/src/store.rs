@@ -0,0 +1,1141 @@
+//! This module contains an append-only storage engine for blockchains. Each block is appended to
+//! the file as a single record, so persisting a new block does not rewrite the whole chain.
+//!
+//! The file starts with a [header](../format/index.html) consisting of the magic bytes `EBSC`, the
+//! version of the file format, the id of the hash algorithm and the id of the chain, the store is
+//! bound to. Stores, that have been written before the header was introduced, start with the first
+//! record. They are hashed using SHA-256 and can still be opened and appended to. A file, that
+//! neither starts with the magic bytes nor with a valid record, is rejected.
+//!
+//! A record consists of the length of the serialized block (4 bytes), a CRC-32 checksum of the
+//! serialized block (4 bytes), both little endian, and the serialized block itself. If the process
+//! crashes while appending, the last record might be incomplete. Such a torn tail is detected and
+//! truncated when the store is opened. Blocks are limited to 16 MiB, so an incomplete record of a
+//! larger length is not the result of a torn write, just like a complete record, whose checksum
+//! does not match, even if it is the last record. Opening the store fails with the offset of such a
+//! record instead and the file is left untouched. Since the header is written before the first
+//! record, a file, that ends within a valid header, does not contain any blocks and is created
+//! again.
+//!
+//! The [`ChainSpec`](../spec/struct.ChainSpec.html) of the chain is persisted next to the store in
+//! a file with the path of the store and the extension `.spec` appended (see
+//! [`ChainStore::init_spec`](struct.ChainStore.html#method.init_spec)).
+
+use std::cmp;
+use std::fmt::Debug;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter, ErrorKind, SeekFrom};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use block::Block;
+use blockchain::Blockchain;
+use checkpoint::Checkpoints;
+use consensus::ConsensusRules;
+use format::{crc32, decode_u32, encode_u32, read_exact, FileHeader, STORE_MAGIC};
+use hash::{HashAlgorithm, HashFunction};
+use spec::ChainSpec;
+use version::Hashable;
+
+use super::PersistingError;
+
+/// Length of the record header (length and checksum) in bytes.
+const RECORD_HEADER_LEN: u64 = 8;
+/// Maximum length of a serialized block in bytes.
+const MAX_RECORD_LEN: u32 = 16 * 1024 * 1024;
+
+/// Append-only on-disk store of the blocks of a chain. The store keeps an index of the offset of
+/// each record, so single blocks can be read without reading the whole file.
+#[derive(Debug)]
+pub struct ChainStore<D, H, S = ()> {
+    file: File,
+    path: PathBuf,
+    header: Option<FileHeader>,
+    offsets: Vec<u64>,
+    end: u64,
+    _marker: PhantomData<(D, H, S)>,
+}
+
+impl<D, H, S> ChainStore<D, H, S>
+where
+    H: HashFunction,
+{
+    /// Opens the store at the given path or creates an empty store, if the file does not exist.
+    /// The records are scanned to build the offset index without modifying the file. Afterwards, a
+    /// torn tail, i.e. an incomplete last record, whose length does not exceed the maximum length
+    /// of a block, is truncated and a file, that ends within a valid header, is created again,
+    /// since the header is only written before the first record. Opening fails and leaves the file
+    /// untouched, if the store is hashed using another algorithm than `H` (see
+    /// [`hash_algorithm`](fn.hash_algorithm.html)), if its header is invalid or if a record is
+    /// damaged.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::store::ChainStore;
+    /// let dir = tempdir::TempDir::new("store").unwrap();
+    /// let store: ChainStore<u32, sha2::Sha256> = ChainStore::open(dir.path().join("chain")).unwrap();
+    /// assert!(store.is_empty());
+    /// # }
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PersistingError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path.as_ref())
+            .map_err(|_| PersistingError::IoError)?;
+        let file_len = file.metadata().map_err(|_| PersistingError::IoError)?.len();
+        let (header, torn_header) = match read_file_header(&file, file_len) {
+            // the header is only written to files without records, so a file, that ends within
+            // a valid header, has been torn while the header was written
+            Err(PersistingError::Truncated(_)) => (None, true),
+            header => (header?, false),
+        };
+        if let Some(ref header) = header {
+            if header.hash_algorithm() != H::ALGORITHM {
+                return Err(PersistingError::HashMismatch(
+                    header.hash_algorithm(),
+                    H::ALGORITHM,
+                ));
+            }
+        }
+        let start = header.as_ref().map(FileHeader::encoded_len).unwrap_or(0);
+        let (offsets, mut pos) = if torn_header {
+            (Vec::new(), 0)
+        } else {
+            match scan_records(&file, start, file_len) {
+                // neither a store with header nor a store, that has been written before the
+                // header was introduced
+                Ok((ref offsets, _)) if start == 0 && offsets.is_empty() => {
+                    return Err(PersistingError::WrongMagic)
+                }
+                Err(PersistingError::Truncated(0)) | Err(PersistingError::ChecksumMismatch(0)) => {
+                    return Err(PersistingError::WrongMagic)
+                }
+                scanned => scanned?,
+            }
+        };
+        if start == 0 && !offsets.is_empty() && H::ALGORITHM != HashAlgorithm::Sha256 {
+            return Err(PersistingError::HashMismatch(
+                HashAlgorithm::Sha256,
+                H::ALGORITHM,
+            ));
+        }
+        if pos < file_len {
+            // verified torn tail (see `scan_records`)
+            file.set_len(pos)
+                .and_then(|_| file.sync_all())
+                .map_err(|_| PersistingError::IoError)?;
+        }
+        let header = match header {
+            None if torn_header => {
+                // new store or an incomplete header, that does not belong to any blocks
+                let header = FileHeader::new(H::ALGORITHM, "")?;
+                write_file_header(&file, &header)?;
+                pos = header.encoded_len();
+                Some(header)
+            }
+            header => header,
+        };
+        Ok(Self {
+            file: file,
+            path: path.as_ref().to_path_buf(),
+            header: header,
+            offsets: offsets,
+            end: pos,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<D, H, S> ChainStore<D, H, S> {
+    /// Returns the number of stored blocks.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Checks if the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Returns the offset of the record of the block at the given height. The genesis block has
+    /// height `0`.
+    pub fn offset(&self, height: usize) -> Option<u64> {
+        self.offsets.get(height).cloned()
+    }
+
+    /// Returns the id of the chain, the store is bound to (see
+    /// [`init_spec`](#method.init_spec)). Returns `None`, if the store has not been bound to a
+    /// spec yet or the chain id has not been recorded in its header, because the store was bound
+    /// before the chain id was introduced to the header.
+    pub fn chain_id(&self) -> Option<&str> {
+        self.header.as_ref().and_then(FileHeader::chain_id)
+    }
+
+    /// Returns the path of the store.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of the file, the spec of the chain is persisted in. This is the path of the
+    /// store with the extension `.spec` appended.
+    pub fn spec_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".spec");
+        PathBuf::from(path)
+    }
+
+    /// Reads the payload of the record at the given offset and verifies its checksum.
+    fn read_payload(&self, offset: u64) -> Result<Vec<u8>, PersistingError> {
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|_| PersistingError::IoError)?;
+        let (len, checksum) = read_record_header(&mut file, offset)?;
+        let mut payload = vec![0; len as usize];
+        read_exact(&mut file, &mut payload, offset)?;
+        if crc32(&payload) == checksum {
+            Ok(payload)
+        } else {
+            Err(PersistingError::ChecksumMismatch(offset))
+        }
+    }
+}
+
+impl<D, H, S> ChainStore<D, H, S>
+where
+    D: Serialize,
+    H: ::digest::Digest,
+    S: Serialize,
+{
+    /// Appends a block to the store. The method returns after the record has been written and
+    /// flushed to disk. If writing fails, the partial record is removed again.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::store::ChainStore;
+    /// let dir = tempdir::TempDir::new("store").unwrap();
+    /// let path = dir.path().join("chain");
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new().append(vec![1, 2], 0).unwrap();
+    /// let mut store = ChainStore::open(&path).unwrap();
+    /// store.append(bc.iter().next().unwrap()).unwrap();
+    /// let store: ChainStore<u32, sha2::Sha256> = ChainStore::open(&path).unwrap();
+    /// assert_eq!(store.len(), 1);
+    /// assert_eq!(store.load().unwrap(), bc);
+    /// # }
+    /// ```
+    pub fn append(&mut self, block: &Block<D, H, S>) -> Result<(), PersistingError> {
+        let payload = ::bincode::serialize(block, ::bincode::Infinite)
+            .map_err(|_| PersistingError::SerializingError)?;
+        if payload.len() > MAX_RECORD_LEN as usize {
+            return Err(PersistingError::SerializingError);
+        }
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN as usize + payload.len());
+        record.extend_from_slice(&encode_u32(payload.len() as u32));
+        record.extend_from_slice(&encode_u32(crc32(&payload)));
+        record.extend_from_slice(&payload);
+        let offset = self.end;
+        let written = {
+            let mut file = &self.file;
+            file.seek(SeekFrom::Start(offset))
+                .and_then(|_| file.write_all(&record))
+                .and_then(|_| self.file.sync_data())
+        };
+        if written.is_ok() {
+            self.offsets.push(offset);
+            self.end += record.len() as u64;
+            Ok(())
+        } else {
+            self.file.set_len(offset).ok();
+            Err(PersistingError::IoError)
+        }
+    }
+
+    /// Persists the spec of the chain. The spec is written to a temporary file first, that is
+    /// synced to disk and replaces the old spec, so a crash never leaves a partial spec behind.
+    fn write_spec(&self, spec: &ChainSpec<D, H, S>) -> Result<(), PersistingError> {
+        let path = self.spec_path();
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut file = BufWriter::new(File::create(&tmp).map_err(|_| PersistingError::IoError)?);
+            ::bincode::serialize_into(&mut file, spec, ::bincode::Infinite)
+                .map_err(|_| PersistingError::SerializingError)?;
+            file.flush()
+                .and_then(|_| file.get_ref().sync_all())
+                .map_err(|_| PersistingError::IoError)?;
+        }
+        fs::rename(&tmp, &path).map_err(|_| PersistingError::IoError)?;
+        sync_parent(&path)
+    }
+}
+
+impl<D, H, S> ChainStore<D, H, S>
+where
+    for<'de> D: Deserialize<'de>,
+    H: ::digest::Digest,
+    for<'de> S: Deserialize<'de> + Default,
+{
+    /// Reads the block at the given height using the offset index. Returns `None` if there is no
+    /// block at that height.
+    pub fn read(&self, height: usize) -> Result<Option<Block<D, H, S>>, PersistingError> {
+        match self.offset(height) {
+            Some(offset) => self.read_payload(offset).and_then(|payload| {
+                ::bincode::deserialize(&payload)
+                    .map(Some)
+                    .map_err(|_| PersistingError::DeserializingError)
+            }),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the persisted spec of the chain. Returns `None` if no spec has been persisted.
+    pub fn spec(&self) -> Result<Option<ChainSpec<D, H, S>>, PersistingError> {
+        match File::open(self.spec_path()) {
+            Ok(file) => ::bincode::deserialize_from(&mut BufReader::new(file), ::bincode::Infinite)
+                .map(Some)
+                .map_err(|_| PersistingError::DeserializingError),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(_) => Err(PersistingError::IoError),
+        }
+    }
+}
+
+impl<D, H, S> ChainStore<D, H, S>
+where
+    for<'de> D: Deserialize<'de> + Default,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    for<'de> S: Deserialize<'de> + Default + Hashable,
+{
+    /// Loads all stored blocks into a blockchain. Like
+    /// [`Blockchain::load_from_disk`](../blockchain/struct.Blockchain.html#method.load_from_disk),
+    /// the blocks are not validated.
+    pub fn load(&self) -> Result<Blockchain<D, H, S>, PersistingError> {
+        (0..self.len()).fold(Ok(Blockchain::new()), |acc, height| {
+            acc.and_then(|chain| {
+                self.read(height)?
+                    .map(|block| chain.push(block))
+                    .ok_or(PersistingError::DeserializingError)
+            })
+        })
+    }
+}
+
+impl<D, H, S> ChainStore<D, H, S>
+where
+    for<'de> D: Deserialize<'de> + Default + Hashable,
+    H: HashFunction,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    for<'de> S: Deserialize<'de> + Default + Hashable,
+{
+    /// Binds the store to the spec of its chain. If the store is empty, the spec's genesis block is
+    /// appended, otherwise the first stored block must be the genesis block. The spec is persisted
+    /// (see [`spec_path`](#method.spec_path)) and the chain id is recorded in the header of an
+    /// empty store, so the store cannot be bound to the spec of another chain later on. If the
+    /// store is already bound to a spec with the same chain id and genesis block, the persisted
+    /// consensus rules are replaced. Fails without modifying the store, if the chain id is longer
+    /// than 255 bytes.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::block::Block;
+    /// use blockchain::consensus::Retarget;
+    /// use blockchain::spec::ChainSpec;
+    /// use blockchain::store::ChainStore;
+    /// let dir = tempdir::TempDir::new("store").unwrap();
+    /// let genesis = Block::new(vec![0], 2).proof_of_work();
+    /// let spec = ChainSpec::new("test", genesis, Retarget::new(2, 60, 16));
+    /// let mut store: ChainStore<u32, sha2::Sha256> = ChainStore::open(dir.path().join("chain")).unwrap();
+    /// store.init_spec(&spec).unwrap();
+    /// assert_eq!(store.len(), 1);
+    /// assert_eq!(store.chain_id(), Some("test"));
+    /// let other = ChainSpec::new("other", spec.genesis().clone(), Retarget::new(2, 60, 16));
+    /// assert!(store.init_spec(&other).is_err());
+    /// let chain = store.load_with_spec(&spec, &Default::default()).unwrap();
+    /// assert_eq!(chain.chain_id(), Some("test"));
+    /// # }
+    /// ```
+    pub fn init_spec(&mut self, spec: &ChainSpec<D, H, S>) -> Result<(), PersistingError> {
+        if self.chain_id().map_or(false, |id| id != spec.chain_id()) {
+            return Err(PersistingError::SpecMismatch);
+        }
+        if let Some(persisted) = self.spec()? {
+            if !persisted.is_same_chain(spec) {
+                return Err(PersistingError::SpecMismatch);
+            }
+        }
+        if let Some(block) = self.read(0)? {
+            spec.check_genesis(&block)
+                .map_err(|_| PersistingError::SpecMismatch)?;
+        }
+        let header = FileHeader::new(H::ALGORITHM, spec.chain_id())?;
+        self.write_spec(spec)?;
+        if self.is_empty() {
+            // the header can only grow as long as no record follows it
+            write_file_header(&self.file, &header)?;
+            self.end = header.encoded_len();
+            self.header = Some(header);
+            self.append(spec.genesis())?;
+        }
+        Ok(())
+    }
+
+    /// Binds the store to the spec like [`init_spec`](#method.init_spec), but adopts the genesis
+    /// block of a store, that is not bound to a chain id, e.g. a store written before chains had
+    /// a spec. Such a store keeps its first block as genesis block (see
+    /// [`ChainSpec::from_existing_genesis`](../spec/struct.ChainSpec.html#method.from_existing_genesis))
+    /// instead of being rejected for a foreign genesis block. Returns the spec, the store has been
+    /// bound to.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::block::Block;
+    /// use blockchain::consensus::Retarget;
+    /// use blockchain::spec::ChainSpec;
+    /// use blockchain::store::ChainStore;
+    /// let dir = tempdir::TempDir::new("store").unwrap();
+    /// let bc: Blockchain<u32, sha2::Sha256> = Blockchain::new().append(vec![1], 0).unwrap();
+    /// let mut store = ChainStore::open(dir.path().join("chain")).unwrap();
+    /// store.append(bc.iter().next().unwrap()).unwrap();
+    /// let genesis = Block::new(vec![0], 0).proof_of_work();
+    /// let spec = ChainSpec::new("test", genesis, Retarget::new(0, 60, 16));
+    /// assert!(store.init_spec(&spec).is_err());
+    /// let adopted = store.adopt_spec(&spec).unwrap();
+    /// assert_eq!(adopted.chain_id(), "test");
+    /// assert_eq!(adopted.genesis(), bc.iter().next().unwrap());
+    /// # }
+    /// ```
+    pub fn adopt_spec(
+        &mut self,
+        spec: &ChainSpec<D, H, S>,
+    ) -> Result<ChainSpec<D, H, S>, PersistingError>
+    where
+        D: Clone,
+        S: Clone,
+    {
+        let spec = match self.read(0)? {
+            Some(genesis) if self.chain_id().is_none() => {
+                ChainSpec::from_existing_genesis(genesis, spec.clone())
+            }
+            _ => spec.clone(),
+        };
+        self.init_spec(&spec)?;
+        Ok(spec)
+    }
+
+    /// Salvages the longest valid prefix of the possibly damaged store at `path` into a new store
+    /// at `target`, that is bound to the spec. The records are read in order until a record is
+    /// incomplete, damaged or cannot be decoded, or its block is invalid according to the spec's
+    /// consensus rules and the checkpoints. The spec's genesis block is always salvaged. The
+    /// damaged store is not modified and the new store must not exist yet.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use std::fs::OpenOptions;
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::block::Block;
+    /// use blockchain::consensus::Retarget;
+    /// use blockchain::spec::ChainSpec;
+    /// use blockchain::store::ChainStore;
+    /// let dir = tempdir::TempDir::new("store").unwrap();
+    /// let path = dir.path().join("chain");
+    /// let genesis = Block::new(vec![0], 0).proof_of_work();
+    /// let spec = ChainSpec::new("test", genesis, Retarget::new(0, 60, 16));
+    /// let bc: Blockchain<u32, sha2::Sha256> = Blockchain::from_spec(spec.clone()).unwrap();
+    /// let bc = bc.append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+    /// let mut store = ChainStore::open(&path).unwrap();
+    /// store.init_spec(&spec).unwrap();
+    /// for block in bc.iter_chronological().skip(1) {
+    ///     store.append(block).unwrap();
+    /// }
+    /// // cut the last block in half
+    /// let len = std::fs::metadata(&path).unwrap().len();
+    /// let half = (len - store.offset(2).unwrap()) / 2;
+    /// OpenOptions::new().write(true).open(&path).unwrap().set_len(len - half).unwrap();
+    /// let target = dir.path().join("recovered");
+    /// let recovered = ChainStore::recover(&path, &target, &spec, &Default::default()).unwrap();
+    /// assert_eq!(recovered.len(), 2);
+    /// assert_eq!(std::fs::metadata(&path).unwrap().len(), len - half);
+    /// # }
+    /// ```
+    pub fn recover<P, Q>(
+        path: P,
+        target: Q,
+        spec: &ChainSpec<D, H, S>,
+        checkpoints: &Checkpoints,
+    ) -> Result<Self, PersistingError>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+        D: Clone,
+        S: Clone,
+    {
+        let file = File::open(path).map_err(|_| PersistingError::IoError)?;
+        let file_len = file.metadata().map_err(|_| PersistingError::IoError)?.len();
+        let (start, algorithm) = match read_file_header(&file, file_len)? {
+            Some(ref header) if header.chain_id().map_or(false, |id| id != spec.chain_id()) => {
+                return Err(PersistingError::SpecMismatch)
+            }
+            Some(header) => (header.encoded_len(), header.hash_algorithm()),
+            None => (0, HashAlgorithm::Sha256),
+        };
+        if algorithm != H::ALGORITHM {
+            return Err(PersistingError::HashMismatch(algorithm, H::ALGORITHM));
+        }
+        let mut chain =
+            Blockchain::from_spec(spec.clone()).map_err(|_| PersistingError::SpecMismatch)?;
+        {
+            let mut reader = BufReader::new(&file);
+            reader
+                .seek(SeekFrom::Start(start))
+                .map_err(|_| PersistingError::IoError)?;
+            let mut pos = start;
+            let mut height = 0;
+            while file_len - pos >= RECORD_HEADER_LEN {
+                let (len, checksum) = read_record_header(&mut reader, pos)?;
+                let end = pos + RECORD_HEADER_LEN + u64::from(len);
+                if end > file_len {
+                    break;
+                }
+                let mut payload = vec![0; len as usize];
+                read_exact(&mut reader, &mut payload, pos)?;
+                if crc32(&payload) != checksum {
+                    break;
+                }
+                let block = match ::bincode::deserialize(&payload) {
+                    Ok(block) => block,
+                    Err(_) => break,
+                };
+                if height == 0 {
+                    if spec.check_genesis(&block).is_err() {
+                        break;
+                    }
+                } else {
+                    match chain.insert_with_checkpoints(block, spec.rules(), checkpoints) {
+                        Ok(inserted) => chain = inserted,
+                        Err(_) => break,
+                    }
+                }
+                pos = end;
+                height += 1;
+            }
+        }
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(target.as_ref())
+            .map_err(|_| PersistingError::IoError)?;
+        let mut store = Self::open(target)?;
+        store.init_spec(spec)?;
+        for block in chain.iter_chronological().skip(1) {
+            store.append(block)?;
+        }
+        Ok(store)
+    }
+
+    /// Loads the stored blocks one at a time and validates each block against the already loaded
+    /// part of the chain using the consensus rules. If a block cannot be read or is invalid,
+    /// loading stops and the height of the block is reported.
+    pub fn load_with_rules<R>(&self, rules: &R) -> Result<Blockchain<D, H, S>, PersistingError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        self.load_with_checkpoints(rules, &Checkpoints::default())
+    }
+
+    /// Loads the stored blocks like [`load_with_rules`](#method.load_with_rules), but trusts the
+    /// blocks up to the highest checkpoint within the store. For these blocks, only the linkage
+    /// and the hashes of the blocks at checkpoint heights are checked. The blocks above are
+    /// validated using the consensus rules and the checkpoints.
+    pub fn load_with_checkpoints<R>(
+        &self,
+        rules: &R,
+        checkpoints: &Checkpoints,
+    ) -> Result<Blockchain<D, H, S>, PersistingError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        self.load_into(Blockchain::new(), rules, checkpoints)
+    }
+
+    /// Loads the stored blocks like [`load_with_checkpoints`](#method.load_with_checkpoints) into
+    /// a chain, that is bound to the spec. The first block must be the spec's genesis block and
+    /// the other blocks are validated using the spec's consensus rules.
+    pub fn load_with_spec(
+        &self,
+        spec: &ChainSpec<D, H, S>,
+        checkpoints: &Checkpoints,
+    ) -> Result<Blockchain<D, H, S>, PersistingError>
+    where
+        D: Clone,
+        S: Clone,
+    {
+        self.load_into(
+            Blockchain::with_spec(spec.clone()),
+            spec.rules(),
+            checkpoints,
+        )
+    }
+
+    /// Loads the stored blocks on top of the given empty chain.
+    fn load_into<R>(
+        &self,
+        chain: Blockchain<D, H, S>,
+        rules: &R,
+        checkpoints: &Checkpoints,
+    ) -> Result<Blockchain<D, H, S>, PersistingError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        let trusted = checkpoints
+            .last_below(self.len())
+            .map(|checkpoint| checkpoint.height() + 1)
+            .unwrap_or(0);
+        (0..self.len()).fold(Ok(chain), |acc, height| {
+            acc.and_then(|chain| match self.read(height) {
+                Ok(Some(block)) => {
+                    let inserted = if height < trusted {
+                        chain.insert_trusted(block, checkpoints)
+                    } else {
+                        chain.insert_with_checkpoints(block, rules, checkpoints)
+                    };
+                    inserted.map_err(|err| PersistingError::InvalidBlock(height, err))
+                }
+                _ => Err(PersistingError::CorruptBlock(height)),
+            })
+        })
+    }
+}
+
+/// Reads the hash algorithm of the store at the given path without opening it, e.g. to decide
+/// which type to open the store with. Returns `None` if the file does not exist or is empty.
+///
+/// # Examples
+/// ```
+/// extern crate tempdir;
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::hash::{HashAlgorithm, Sha3_256};
+/// use blockchain::store::{hash_algorithm, ChainStore};
+/// let dir = tempdir::TempDir::new("store").unwrap();
+/// let path = dir.path().join("chain");
+/// assert_eq!(hash_algorithm(&path).unwrap(), None);
+/// let _: ChainStore<u32, Sha3_256> = ChainStore::open(&path).unwrap();
+/// assert_eq!(hash_algorithm(&path).unwrap(), Some(HashAlgorithm::Sha3_256));
+/// # }
+/// ```
+pub fn hash_algorithm<P: AsRef<Path>>(path: P) -> Result<Option<HashAlgorithm>, PersistingError> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(_) => return Err(PersistingError::IoError),
+    };
+    let file_len = file.metadata().map_err(|_| PersistingError::IoError)?.len();
+    match read_file_header(&file, file_len) {
+        Ok(Some(header)) => Ok(Some(header.hash_algorithm())),
+        // a torn header does not belong to any blocks
+        Err(PersistingError::Truncated(_)) => Ok(None),
+        Err(err) => Err(err),
+        Ok(None) if file_len >= RECORD_HEADER_LEN => Ok(Some(HashAlgorithm::Sha256)),
+        Ok(None) => Ok(None),
+    }
+}
+
+/// Reads the file header. Returns `None` if the file does not start with the magic bytes. Fails
+/// with [`Truncated`](../enum.PersistingError.html#variant.Truncated), if the file ends within a
+/// header, whose available bytes are valid, including empty files.
+fn read_file_header(file: &File, file_len: u64) -> Result<Option<FileHeader>, PersistingError> {
+    let mut magic = vec![0; cmp::min(file_len, STORE_MAGIC.len() as u64) as usize];
+    let mut file = file;
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_exact(&mut magic))
+        .and_then(|_| file.seek(SeekFrom::Start(0)))
+        .map_err(|_| PersistingError::IoError)?;
+    if !STORE_MAGIC.starts_with(&magic) {
+        Ok(None)
+    } else {
+        FileHeader::read(&mut BufReader::new(file), STORE_MAGIC).map(Some)
+    }
+}
+
+/// Replaces the content of the file by the file header.
+fn write_file_header(file: &File, header: &FileHeader) -> Result<(), PersistingError> {
+    let mut file = file;
+    file.set_len(0)
+        .and_then(|_| file.seek(SeekFrom::Start(0)))
+        .and_then(|_| file.write_all(&header.encode(STORE_MAGIC)))
+        .and_then(|_| file.sync_all())
+        .map_err(|_| PersistingError::IoError)
+}
+
+/// Syncs the directory containing the given path, so a file, that has been renamed to the path, is
+/// found there after a crash.
+fn sync_parent(path: &Path) -> Result<(), PersistingError> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)
+        .and_then(|dir| dir.sync_all())
+        .map_err(|_| PersistingError::IoError)
+}
+
+/// Scans the records, that follow the header ending at `start`, without modifying the file.
+/// Returns the offsets of the complete records and the end of the last one. Only a torn tail may
+/// follow the end, i.e. an incomplete last record, whose length does not exceed the maximum length
+/// of a block. Fails with the offset of a damaged record or of an incomplete record, that cannot
+/// have been torn.
+fn scan_records(
+    file: &File,
+    start: u64,
+    file_len: u64,
+) -> Result<(Vec<u64>, u64), PersistingError> {
+    let mut reader = BufReader::new(file);
+    reader
+        .seek(SeekFrom::Start(start))
+        .map_err(|_| PersistingError::IoError)?;
+    let mut offsets = Vec::new();
+    let mut pos = start;
+    while pos < file_len {
+        if file_len - pos < RECORD_HEADER_LEN {
+            // torn within the length or the checksum
+            break;
+        }
+        let (len, checksum) = read_record_header(&mut reader, pos)?;
+        let end = pos + RECORD_HEADER_LEN + u64::from(len);
+        if end > file_len {
+            if len > MAX_RECORD_LEN {
+                // the length has been damaged, since it has never been written
+                return Err(PersistingError::Truncated(pos));
+            }
+            // torn within the block
+            break;
+        }
+        let mut payload = vec![0; len as usize];
+        read_exact(&mut reader, &mut payload, pos)?;
+        if crc32(&payload) != checksum {
+            return Err(PersistingError::ChecksumMismatch(pos));
+        }
+        offsets.push(pos);
+        pos = end;
+    }
+    Ok((offsets, pos))
+}
+
+/// Reads the length and the checksum of the record at the given offset.
+fn read_record_header<R: Read>(reader: &mut R, offset: u64) -> Result<(u32, u32), PersistingError> {
+    let mut header = [0; RECORD_HEADER_LEN as usize];
+    read_exact(reader, &mut header, offset)?;
+    Ok((decode_u32(&header[..4]), decode_u32(&header[4..])))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::OpenOptions;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+    type Store = ChainStore<u32, ::sha2::Sha256>;
+
+    fn persist(store: &mut Store, chain: &Chain) {
+        let mut blocks = chain.iter().collect::<Vec<_>>();
+        blocks.reverse();
+        for block in blocks {
+            store.append(block).unwrap();
+        }
+    }
+
+    #[test]
+    fn blocks_can_be_read_by_height() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2, 3], 0).unwrap();
+        let mut store = Store::open(dir.path().join("chain")).unwrap();
+        persist(&mut store, &chain);
+        let header = FileHeader::new(HashAlgorithm::Sha256, "").unwrap();
+        assert_eq!(store.offset(0), Some(header.encoded_len()));
+        assert_eq!(store.read(1).unwrap().as_ref(), chain.iter().next());
+        assert_eq!(store.read(2).unwrap(), None);
+    }
+
+    #[test]
+    fn torn_tail_is_truncated() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        let len = ::std::fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.load().unwrap(), chain.tail().1);
+        // the store can be appended to after truncating the tail
+        store.append(chain.iter().next().unwrap()).unwrap();
+        assert_eq!(Store::open(&path).unwrap().load().unwrap(), chain);
+    }
+
+    #[test]
+    fn spec_is_persisted() {
+        use consensus::Retarget;
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let genesis = Block::new(vec![0], 0).proof_of_work();
+        let spec = ChainSpec::new("test", genesis, Retarget::new(0, 60, 16));
+        let chain = Chain::from_spec(spec.clone()).unwrap().append(vec![1], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        assert!(store.spec().unwrap().is_none());
+        store.init_spec(&spec).unwrap();
+        store.append(chain.iter().next().unwrap()).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        let persisted = store.spec().unwrap().unwrap();
+        assert!(persisted.is_same_chain(&spec));
+        assert_eq!(store.load_with_spec(&persisted, &Default::default()).unwrap(), chain);
+        // the rules can be replaced, but the store stays bound to its chain
+        store
+            .init_spec(&spec.clone().with_rules(Retarget::new(1, 60, 16)))
+            .unwrap();
+        assert_eq!(store.len(), 2);
+        let foreign = ChainSpec::new(
+            "test",
+            Block::new(vec![1], 0).proof_of_work(),
+            Retarget::new(0, 60, 16),
+        );
+        assert!(store.init_spec(&foreign).is_err());
+        // a store without spec is only bound to a spec with a matching genesis block
+        let legacy = Chain::new().append(vec![7], 0).unwrap();
+        let mut store = Store::open(dir.path().join("legacy")).unwrap();
+        persist(&mut store, &legacy);
+        assert!(store.init_spec(&spec).is_err());
+    }
+
+    #[test]
+    fn store_without_spec_is_adopted() {
+        use consensus::Retarget;
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        // remove the header, like in stores written before chains had a spec
+        let mut content = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut content).unwrap();
+        File::create(&path)
+            .unwrap()
+            .write_all(&content[store.offset(0).unwrap() as usize..])
+            .unwrap();
+        let genesis = Block::new(vec![0], 0).proof_of_work();
+        let spec = ChainSpec::new("test", genesis, Retarget::new(0, 60, 16));
+        let mut store = Store::open(&path).unwrap();
+        match store.init_spec(&spec) {
+            Err(PersistingError::SpecMismatch) => {}
+            _ => panic!("the store has another genesis block"),
+        }
+        let adopted = store.adopt_spec(&spec).unwrap();
+        assert!(adopted.check_genesis(chain.get_by_height(0).unwrap()).is_ok());
+        let loaded = store.load_with_spec(&adopted, &Default::default()).unwrap();
+        assert_eq!(loaded.chain_id(), Some("test"));
+        assert_eq!(loaded, chain);
+        let next = loaded.append(vec![3], 0).unwrap();
+        store.append(next.iter().next().unwrap()).unwrap();
+        // the adopted spec is kept, when the store is opened again
+        let mut store = Store::open(&path).unwrap();
+        assert!(store.adopt_spec(&spec).unwrap().is_same_chain(&adopted));
+        assert_eq!(store.spec().unwrap().map(|spec| spec.is_same_chain(&adopted)), Some(true));
+        assert_eq!(store.load_with_spec(&adopted, &Default::default()).unwrap(), next);
+        let other = ChainSpec::new("other", spec.genesis().clone(), Retarget::new(0, 60, 16));
+        assert!(store.adopt_spec(&other).is_err());
+        // a store, that is bound to a chain id, keeps its genesis block
+        let mut store = Store::open(dir.path().join("bound")).unwrap();
+        assert!(store.adopt_spec(&spec).unwrap().is_same_chain(&spec));
+        assert!(store.adopt_spec(&spec).unwrap().is_same_chain(&spec));
+    }
+
+    #[test]
+    fn damaged_record_is_rejected() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        let offset = store.offset(0).unwrap();
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(offset + RECORD_HEADER_LEN + 1))
+            .unwrap();
+        file.write_all(&[0xff]).unwrap();
+        match store.read(0) {
+            Err(PersistingError::ChecksumMismatch(at)) => assert_eq!(at, offset),
+            _ => panic!("the first record is damaged"),
+        }
+        match Store::open(&path) {
+            Err(PersistingError::ChecksumMismatch(at)) => assert_eq!(at, offset),
+            _ => panic!("the first record is damaged"),
+        }
+    }
+
+    #[test]
+    fn damaged_last_record_is_rejected() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        let offset = store.offset(1).unwrap();
+        let len = ::std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(offset + RECORD_HEADER_LEN + 1))
+            .unwrap();
+        file.write_all(&[0xff]).unwrap();
+        // the record is complete, so it has not been torn
+        match Store::open(&path) {
+            Err(PersistingError::ChecksumMismatch(at)) => assert_eq!(at, offset),
+            _ => panic!("the last record is damaged"),
+        }
+        assert_eq!(::std::fs::metadata(&path).unwrap().len(), len);
+    }
+
+    #[test]
+    fn chain_id_is_recorded_in_header() {
+        use consensus::Retarget;
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let genesis = Block::new(vec![0], 0).proof_of_work();
+        let spec = ChainSpec::new("test", genesis, Retarget::new(0, 60, 16));
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.chain_id(), None);
+        store.init_spec(&spec).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.chain_id(), Some("test"));
+        assert_eq!(store.read(0).unwrap().as_ref(), Some(spec.genesis()));
+        // the header is checked, even if the spec file has been lost
+        ::std::fs::remove_file(store.spec_path()).unwrap();
+        let other = ChainSpec::new("other", spec.genesis().clone(), Retarget::new(0, 60, 16));
+        match store.init_spec(&other) {
+            Err(PersistingError::SpecMismatch) => {}
+            _ => panic!("the store belongs to another chain"),
+        }
+        // a chain id, that does not fit into the header, is rejected before anything is written
+        let mut store = Store::open(dir.path().join("long")).unwrap();
+        let genesis = spec.genesis().clone();
+        let long = ChainSpec::new(&"x".repeat(256), genesis, Retarget::new(0, 60, 16));
+        match store.init_spec(&long) {
+            Err(PersistingError::SerializingError) => {}
+            _ => panic!("the chain id is too long"),
+        }
+        assert!(!store.spec_path().exists());
+        assert!(store.is_empty());
+        assert_eq!(store.chain_id(), None);
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        File::create(&path)
+            .unwrap()
+            .write_all(b"not a blockchain store")
+            .unwrap();
+        match Store::open(&path) {
+            Err(PersistingError::WrongMagic) => {}
+            _ => panic!("the file is not a store"),
+        }
+        // the file is not truncated
+        assert_eq!(::std::fs::metadata(&path).unwrap().len(), 22);
+        let header = FileHeader::new(HashAlgorithm::Sha256, "test")
+            .unwrap()
+            .encode(STORE_MAGIC);
+        File::create(&path)
+            .unwrap()
+            .write_all(&header[..header.len() - 1])
+            .unwrap();
+        // a torn header does not belong to any blocks, so the store is created again
+        assert_eq!(hash_algorithm(&path).unwrap(), None);
+        let store = Store::open(&path).unwrap();
+        assert!(store.is_empty());
+        assert_eq!(store.chain_id(), None);
+        assert_eq!(hash_algorithm(&path).unwrap(), Some(HashAlgorithm::Sha256));
+        File::create(&path).unwrap().write_all(b"EB").unwrap();
+        assert!(Store::open(&path).unwrap().is_empty());
+        // a file, that ends within an invalid header, is not a torn header
+        let mut future = header.clone();
+        future[4] += 1;
+        for content in &[&b"xy"[..], &future[..5]] {
+            File::create(&path).unwrap().write_all(content).unwrap();
+            assert!(Store::open(&path).is_err());
+            assert_eq!(::std::fs::metadata(&path).unwrap().len(), content.len() as u64);
+        }
+    }
+
+    #[test]
+    fn damaged_length_is_not_truncated() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        let mut content = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut content).unwrap();
+        for height in 0..2 {
+            let offset = store.offset(height).unwrap();
+            let mut damaged = content.clone();
+            damaged[offset as usize..offset as usize + 4]
+                .copy_from_slice(&encode_u32(MAX_RECORD_LEN + 1));
+            File::create(&path).unwrap().write_all(&damaged).unwrap();
+            // the record exceeds the file, but it is too long to have been torn
+            match Store::open(&path) {
+                Err(PersistingError::Truncated(at)) => assert_eq!(at, offset),
+                _ => panic!("the length of the record is damaged"),
+            }
+            assert_eq!(::std::fs::metadata(&path).unwrap().len(), content.len() as u64);
+        }
+    }
+
+    #[test]
+    fn valid_prefix_is_recovered() {
+        use consensus::Retarget;
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let target = dir.path().join("recovered");
+        let genesis = Block::new(vec![0], 0).proof_of_work();
+        let spec = ChainSpec::new("test", genesis, Retarget::new(0, 60, 16));
+        let chain = Chain::from_spec(spec.clone())
+            .unwrap()
+            .append(vec![1], 0).unwrap()
+            .append(vec![2], 0).unwrap()
+            .append(vec![3], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        store.init_spec(&spec).unwrap();
+        for block in chain.iter_chronological().skip(1) {
+            store.append(block).unwrap();
+        }
+        // flip a byte of the block at height 2
+        let offset = store.offset(2).unwrap() + RECORD_HEADER_LEN;
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut byte = [0; 1];
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.read_exact(&mut byte).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(&[!byte[0]]).unwrap();
+        let mut original = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut original).unwrap();
+        assert!(Store::open(&path).is_err());
+
+        let recovered = Store::recover(&path, &target, &spec, &Default::default()).unwrap();
+        assert_eq!(recovered.len(), 2);
+        assert_eq!(recovered.chain_id(), Some("test"));
+        let loaded = recovered
+            .load_with_spec(&spec, &Default::default())
+            .unwrap();
+        assert!(loaded.iter_chronological().eq(chain.iter_chronological().take(2)));
+        // the damaged store is kept and an existing store is not overwritten
+        let mut content = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, original);
+        assert!(Store::recover(&path, &target, &spec, &Default::default()).is_err());
+        // a store of another chain is not recovered
+        let other = ChainSpec::new("other", spec.genesis().clone(), Retarget::new(0, 60, 16));
+        let target = dir.path().join("other");
+        match Store::recover(&path, &target, &other, &Default::default()) {
+            Err(PersistingError::SpecMismatch) => {}
+            _ => panic!("the store belongs to another chain"),
+        }
+    }
+
+    #[test]
+    fn header_of_version_one_is_supported() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        // replace the header by a header of version 1
+        let mut content = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut content).unwrap();
+        let mut file = File::create(&path).unwrap();
+        file.write_all(STORE_MAGIC).unwrap();
+        file.write_all(&[1, HashAlgorithm::Sha256.id()]).unwrap();
+        file.write_all(&content[store.offset(0).unwrap() as usize..])
+            .unwrap();
+        let store = Store::open(&path).unwrap();
+        assert_eq!(store.offset(0), Some(6));
+        assert_eq!(store.chain_id(), None);
+        assert_eq!(store.load().unwrap(), chain);
+    }
+
+    #[test]
+    fn hash_algorithm_is_recorded() {
+        use hash::Sha3_256;
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain: Blockchain<u32, Sha3_256> = Blockchain::new().append(vec![1], 0).unwrap();
+        let mut store: ChainStore<u32, Sha3_256> = ChainStore::open(&path).unwrap();
+        store.append(chain.iter().next().unwrap()).unwrap();
+        assert_eq!(hash_algorithm(&path).unwrap(), Some(HashAlgorithm::Sha3_256));
+        match Store::open(&path) {
+            Err(PersistingError::HashMismatch(HashAlgorithm::Sha3_256, HashAlgorithm::Sha256)) => {}
+            _ => panic!("the store is hashed using SHA3-256"),
+        }
+        let store: ChainStore<u32, Sha3_256> = ChainStore::open(&path).unwrap();
+        assert!(store.load().unwrap() == chain);
+    }
+
+    #[test]
+    fn store_without_header_is_hashed_using_sha256() {
+        let dir = ::tempdir::TempDir::new("store_").unwrap();
+        let path = dir.path().join("chain");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut store = Store::open(&path).unwrap();
+        persist(&mut store, &chain);
+        // remove the header, like in stores written before the header was introduced
+        let mut content = Vec::new();
+        File::open(&path).unwrap().read_to_end(&mut content).unwrap();
+        File::create(&path)
+            .unwrap()
+            .write_all(&content[store.offset(0).unwrap() as usize..])
+            .unwrap();
+        assert_eq!(hash_algorithm(&path).unwrap(), Some(HashAlgorithm::Sha256));
+        assert!(ChainStore::<u32, ::hash::Blake2b>::open(&path).is_err());
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.offset(0), Some(0));
+        assert_eq!(store.load().unwrap(), chain);
+        let next = chain.append(vec![3], 0).unwrap();
+        store.append(next.iter().next().unwrap()).unwrap();
+        assert_eq!(Store::open(&path).unwrap().load().unwrap(), next);
+    }
+}
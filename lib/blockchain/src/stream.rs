@@ -0,0 +1,287 @@
+//! This module contains a streaming reader for persisted blockchains (see
+//! [`Blockchain::persist_to_disk`](../blockchain/struct.Blockchain.html#method.persist_to_disk)).
+//! A persisted chain starts with a [header](../format/index.html) consisting of the magic bytes
+//! `EBSS`, the version of the file format, the id of the hash algorithm and the id of the chain.
+//! The header is followed by the body, that contains the number of blocks followed by the blocks,
+//! oldest block first, and a CRC-32 checksum of the body (4 bytes, little endian). The reader
+//! decodes one block at a time, so blocks can be counted or filtered straight from the file
+//! without loading the whole chain into memory. The checksum is verified after the last block has
+//! been read.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use block::Block;
+use format::{decode_u32, read_exact, Checksummed, FileHeader, SNAPSHOT_MAGIC};
+use hash::HashFunction;
+
+use super::PersistingError;
+
+/// Streaming reader, that yields the blocks of a persisted chain one at a time, oldest block
+/// first. If a block cannot be decoded, an error containing its height is yielded and the reader
+/// stops. If the body ends within a block, the offset of the block is reported instead. If the
+/// checksum of the body does not match, an error is yielded after the last block.
+pub struct BlockReader<D, H, S = (), R = BufReader<File>> {
+    reader: Checksummed<R>,
+    header: Option<FileHeader>,
+    start: u64,
+    trailer: bool,
+    remaining: u64,
+    height: usize,
+    _marker: PhantomData<(D, H, S)>,
+}
+
+impl<D, H, S> BlockReader<D, H, S>
+where
+    H: HashFunction,
+{
+    /// Opens a persisted chain. The header is read immediately. Opening fails, if the file is not
+    /// a persisted chain, its header is invalid or the chain is hashed using another algorithm
+    /// than `H`.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// extern crate tempdir;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::stream::BlockReader;
+    /// let dir = tempdir::TempDir::new("stream").unwrap();
+    /// let path = dir.path().join("chain.bin");
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc
+    ///     .append(vec![1, 2], 0)
+    ///     .unwrap()
+    ///     .append(vec![3], 0)
+    ///     .unwrap()
+    ///     .append(vec![4, 5, 6], 0)
+    ///     .unwrap();
+    /// bc.persist_to_disk(&path).unwrap();
+    /// let reader: BlockReader<u32, sha2::Sha256> = BlockReader::open(&path).unwrap();
+    /// let large = reader
+    ///     .filter_map(Result::ok)
+    ///     .filter(|blk| blk.transactions().len() > 1)
+    ///     .count();
+    /// assert_eq!(large, 2);
+    /// # }
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PersistingError> {
+        let file = File::open(path).map_err(|_| PersistingError::IoError)?;
+        let mut file = BufReader::new(file);
+        let header = FileHeader::read(&mut file, SNAPSHOT_MAGIC)?;
+        if header.hash_algorithm() != H::ALGORITHM {
+            return Err(PersistingError::HashMismatch(
+                header.hash_algorithm(),
+                H::ALGORITHM,
+            ));
+        }
+        let start = header.encoded_len();
+        Self::from_body(file, Some(header), start)
+    }
+
+    /// Opens a persisted chain like [`open`](#method.open). A file, that does not start with the
+    /// magic bytes, has been persisted before the header was introduced and is read like in
+    /// [`new`](#method.new) instead.
+    pub(crate) fn open_any<P: AsRef<Path>>(path: P) -> Result<Self, PersistingError> {
+        match Self::open(path.as_ref()) {
+            Err(PersistingError::WrongMagic) => {
+                let file = File::open(path).map_err(|_| PersistingError::IoError)?;
+                Self::new(BufReader::new(file))
+            }
+            reader => reader,
+        }
+    }
+}
+
+impl<D, H, S, R> BlockReader<D, H, S, R>
+where
+    R: Read,
+{
+    /// Creates a reader from the body of a persisted chain without header and checksum, like it
+    /// has been written before the header was introduced. The number of blocks is read
+    /// immediately.
+    pub fn new(reader: R) -> Result<Self, PersistingError> {
+        Self::from_body(reader, None, 0)
+    }
+
+    /// Creates a reader from the body starting at the given offset. The body is followed by a
+    /// checksum, if the file has a header.
+    fn from_body(
+        reader: R,
+        header: Option<FileHeader>,
+        start: u64,
+    ) -> Result<Self, PersistingError> {
+        let mut reader = Checksummed::new(reader, start);
+        let mut len = [0; 8];
+        read_exact(&mut reader, &mut len, start)?;
+        let remaining =
+            ::bincode::deserialize(&len).map_err(|_| PersistingError::DeserializingError)?;
+        Ok(Self {
+            reader: reader,
+            trailer: header.is_some(),
+            header: header,
+            start: start,
+            remaining: remaining,
+            height: 0,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the header of the persisted chain or `None`, if the reader has been created from a
+    /// body without header.
+    pub fn header(&self) -> Option<&FileHeader> {
+        self.header.as_ref()
+    }
+
+    /// Returns the number of blocks, that have not been read yet, according to the length stored
+    /// in the file.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Returns the height of the next block. The genesis block has height `0`.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Reads the checksum following the body and compares it to the checksum of the read body.
+    /// The checksum is only verified once and only if the file has a header.
+    fn verify_checksum(&mut self) -> Result<(), PersistingError> {
+        if !self.trailer {
+            return Ok(());
+        }
+        self.trailer = false;
+        let actual = self.reader.checksum();
+        let mut checksum = [0; 4];
+        let offset = self.reader.offset();
+        read_exact(&mut self.reader, &mut checksum, offset)?;
+        if decode_u32(&checksum) == actual {
+            Ok(())
+        } else {
+            Err(PersistingError::ChecksumMismatch(self.start))
+        }
+    }
+}
+
+impl<D, H, S, R> Iterator for BlockReader<D, H, S, R>
+where
+    for<'de> D: Deserialize<'de>,
+    H: ::digest::Digest,
+    for<'de> S: Deserialize<'de> + Default,
+    R: Read,
+{
+    type Item = Result<Block<D, H, S>, PersistingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return self.verify_checksum().err().map(Err);
+        }
+        let height = self.height;
+        let offset = self.reader.offset();
+        match ::bincode::deserialize_from(&mut self.reader, ::bincode::Infinite) {
+            Ok(block) => {
+                self.remaining -= 1;
+                self.height += 1;
+                Some(Ok(block))
+            }
+            Err(_) => {
+                // the position of the following block is unknown, so reading cannot continue
+                self.remaining = 0;
+                self.trailer = false;
+                let mut probe = [0; 1];
+                match self.reader.read(&mut probe) {
+                    Ok(0) => Some(Err(PersistingError::Truncated(offset))),
+                    _ => Some(Err(PersistingError::CorruptBlock(height))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use blockchain::Blockchain;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    #[test]
+    fn truncated_chain_reports_offset() {
+        let chain = Chain::new()
+            .append(vec![1], 0).unwrap()
+            .append(vec![2], 0).unwrap()
+            .append(vec![3], 0).unwrap();
+        let bytes = ::bincode::serialize(&chain, ::bincode::Infinite).unwrap();
+        let reader: BlockReader<u32, ::sha2::Sha256, (), _> =
+            BlockReader::new(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(reader.remaining(), 3);
+        let results = reader.collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        assert!(results[..2].iter().all(Result::is_ok));
+        // the body of the two oldest blocks ends where the last block starts
+        let offset = ::bincode::serialize(&chain.tail().1, ::bincode::Infinite)
+            .unwrap()
+            .len() as u64;
+        match results[2] {
+            Err(PersistingError::Truncated(at)) => assert_eq!(at, offset),
+            _ => panic!("the last block is truncated"),
+        }
+    }
+
+    #[test]
+    fn header_and_checksum_are_verified() {
+        use std::fs::OpenOptions;
+        use std::io::{Seek, SeekFrom, Write};
+        let dir = ::tempdir::TempDir::new("stream_").unwrap();
+        let path = dir.path().join("chain.bin");
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2, 3], 0).unwrap();
+        chain.persist_to_disk(&path).unwrap();
+        let reader: BlockReader<u32, ::sha2::Sha256> = BlockReader::open(&path).unwrap();
+        let header = reader.header().cloned().unwrap();
+        assert_eq!(header.chain_id(), None);
+        assert_eq!(reader.count(), 2);
+        match BlockReader::<u32, ::hash::Blake2b>::open(&path) {
+            Err(PersistingError::HashMismatch(..)) => {}
+            _ => panic!("the chain is hashed using SHA-256"),
+        }
+        // damage the checksum following the body
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::End(-1)).unwrap();
+        file.write_all(&[0xff]).unwrap();
+        let results = BlockReader::<u32, ::sha2::Sha256>::open(&path)
+            .unwrap()
+            .collect::<Vec<_>>();
+        assert_eq!(results.len(), 3);
+        match results[2] {
+            Err(PersistingError::ChecksumMismatch(offset)) => {
+                assert_eq!(offset, header.encoded_len())
+            }
+            _ => panic!("the checksum does not match"),
+        }
+        // a body without header is not a persisted chain
+        let bytes = ::bincode::serialize(&chain, ::bincode::Infinite).unwrap();
+        ::std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&bytes)
+            .unwrap();
+        match BlockReader::<u32, ::sha2::Sha256>::open(&path) {
+            Err(PersistingError::WrongMagic) => {}
+            _ => panic!("the file has no header"),
+        }
+    }
+
+    #[test]
+    fn blocks_are_read_oldest_first() {
+        let chain = Chain::new().append(vec![1], 0).unwrap().append(vec![2, 3], 0).unwrap();
+        let bytes = ::bincode::serialize(&chain, ::bincode::Infinite).unwrap();
+        let reader: BlockReader<u32, ::sha2::Sha256, (), _> = BlockReader::new(&bytes[..]).unwrap();
+        let blocks = reader.map(Result::unwrap).collect::<Vec<_>>();
+        assert!(blocks.iter().eq(chain.iter_chronological()));
+    }
+}
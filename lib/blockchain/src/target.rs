@@ -0,0 +1,357 @@
+//! This module contains the difficulty target of a block. The hash of a block meets a target if the
+//! first 256 bits of the hash, read as big endian number, are not larger than the target. Lower
+//! targets require more work, so the difficulty can be adjusted in small steps instead of doubling
+//! the work with each additional leading zero bit.
+//!
+//! Headers store the target in a compact encoding of 32 bits (see
+//! [`to_compact`](struct.Target.html#method.to_compact)). Headers of versions before
+//! `version::TARGET_VERSION` store the number of leading zero bits instead, which is migrated to
+//! a target using [`from_difficulty`](struct.Target.html#method.from_difficulty).
+
+use std::fmt;
+
+use work::Work;
+use BlockchainError;
+
+/// Length of a target in bytes.
+const BYTES: usize = 32;
+
+/// Number of bytes of the mantissa of a compact target.
+const MANTISSA_BYTES: usize = 3;
+
+/// A 256 bit difficulty target, stored big endian.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Target([u8; BYTES]);
+
+impl Target {
+    /// Returns the highest target, that is met by every hash.
+    pub fn max_value() -> Self {
+        Target([0xff; BYTES])
+    }
+
+    /// Returns the target of a difficulty given as the number of leading zero bits, e.g.
+    /// `2^(256 - difficulty) - 1`. A hash meets this target exactly if its first 256 bits start
+    /// with `difficulty` zero bits. Difficulties above `256` result in the target `0`. This is the
+    /// migration rule for blocks, that store the number of leading zero bits.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::target::Target;
+    /// assert_eq!(Target::from_difficulty(0), Target::max_value());
+    /// assert_eq!(Target::from_difficulty(12).difficulty(), 12);
+    /// assert!(Target::from_difficulty(12).is_met_by(&[0x00, 0x0f, 0xff]));
+    /// assert!(!Target::from_difficulty(12).is_met_by(&[0x00, 0x10]));
+    /// # }
+    /// ```
+    pub fn from_difficulty(difficulty: usize) -> Self {
+        let mut bytes = [0xff; BYTES];
+        for (idx, byte) in bytes.iter_mut().enumerate().take(difficulty / 8 + 1) {
+            *byte = 0xff_u8
+                .checked_shr((difficulty - idx * 8) as u32)
+                .unwrap_or(0);
+        }
+        Target(bytes)
+    }
+
+    /// Returns the number of leading zero bits of the target. Every hash, that meets the target,
+    /// starts with at least this many zero bits.
+    pub fn difficulty(&self) -> usize {
+        leading_zeros(&self.0)
+    }
+
+    /// Returns the smallest difficulty, whose target (see
+    /// [`from_difficulty`](#method.from_difficulty)) is not higher than this target, so every
+    /// hash with this many leading zero bits meets this target.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::target::Target;
+    /// use blockchain::work::Work;
+    /// let target = Target::from_difficulty(12);
+    /// assert_eq!(target.min_difficulty(), 12);
+    /// let lower = Target::from_work(&target.work().add(&Work::from(1)));
+    /// assert_eq!(lower.difficulty(), 12);
+    /// assert_eq!(lower.min_difficulty(), 13);
+    /// # }
+    /// ```
+    pub fn min_difficulty(&self) -> usize {
+        let difficulty = self.difficulty();
+        if Self::from_difficulty(difficulty) == *self {
+            difficulty
+        } else {
+            difficulty + 1
+        }
+    }
+
+    /// Returns true if the hash meets the target. Only the first 256 bits of the hash are compared,
+    /// shorter hashes are padded with zero bits.
+    pub fn is_met_by(&self, hash: &[u8]) -> bool {
+        let mut prefix = [0; BYTES];
+        for (dst, src) in prefix.iter_mut().zip(hash) {
+            *dst = *src;
+        }
+        prefix <= self.0
+    }
+
+    /// Returns the expected number of hashes that is needed to meet the target, e.g.
+    /// `2^256 / (target + 1)`.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::target::Target;
+    /// use blockchain::work::Work;
+    /// assert_eq!(Target::from_difficulty(20).work(), Work::from_difficulty(20));
+    /// assert_eq!(Target::max_value().work(), Work::from(1));
+    /// # }
+    /// ```
+    pub fn work(&self) -> Work {
+        Work::from_difficulty(BYTES * 8).div(&Work::from_bytes(&self.0).add(&Work::from(1)))
+    }
+
+    /// Returns the target, that needs the given expected number of hashes to be met. This is the
+    /// inverse of [`work`](#method.work), rounded down to the next representable target. The
+    /// result saturates at `0` and at [`max_value`](#method.max_value).
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::target::Target;
+    /// use blockchain::work::Work;
+    /// assert_eq!(Target::from_work(&Work::from(1 << 20)), Target::from_difficulty(20));
+    /// let target = Target::from_work(&Work::from(3 << 20));
+    /// assert!(target < Target::from_difficulty(21) && target > Target::from_difficulty(22));
+    /// # }
+    /// ```
+    pub fn from_work(work: &Work) -> Self {
+        let quotient = Work::from_difficulty(BYTES * 8).div(work);
+        Self::from_value(&quotient.sub(&Work::from(1)))
+    }
+
+    /// Scales the target by `numerator / denominator`, rounding down. The result saturates at
+    /// [`max_value`](#method.max_value).
+    pub(crate) fn scale(&self, numerator: u64, denominator: u64) -> Self {
+        let value = Work::from_bytes(&self.0)
+            .mul(numerator)
+            .div(&Work::from(denominator));
+        Self::from_value(&value)
+    }
+
+    /// Converts a number to a target, saturating at [`max_value`](#method.max_value).
+    fn from_value(value: &Work) -> Self {
+        let bytes = value.to_bytes();
+        let (high, low) = bytes.split_at(bytes.len() - BYTES);
+        if high.iter().any(|byte| *byte != 0) {
+            return Self::max_value();
+        }
+        let mut target = [0; BYTES];
+        target.copy_from_slice(low);
+        Target(target)
+    }
+
+    /// Decodes a compact target (see [`to_compact`](#method.to_compact)). Returns an error unless
+    /// the compact target is the canonical encoding of a target.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::target::Target;
+    /// let target = Target::from_compact(0x1eff_ffff).unwrap();
+    /// assert_eq!(target, Target::from_difficulty(16));
+    /// // the mantissa is not normalized
+    /// assert!(Target::from_compact(0x1f00_ffff).is_err());
+    /// // the target is larger than 256 bits
+    /// assert!(Target::from_compact(0x21ff_ffff).is_err());
+    /// # }
+    /// ```
+    pub fn from_compact(compact: u32) -> Result<Self, BlockchainError> {
+        let size = (compact >> 24) as usize;
+        if size > BYTES {
+            return Err(BlockchainError::InvalidTarget(u64::from(compact)));
+        }
+        let mut bytes = [0; BYTES];
+        let start = BYTES - size;
+        for idx in 0..MANTISSA_BYTES {
+            if let Some(byte) = bytes.get_mut(start + idx) {
+                *byte = (compact >> (8 * (MANTISSA_BYTES - 1 - idx))) as u8;
+            }
+        }
+        for byte in bytes.iter_mut().skip(start + MANTISSA_BYTES) {
+            *byte = 0xff;
+        }
+        let target = Target(bytes);
+        if target.to_compact() == compact {
+            Ok(target)
+        } else {
+            Err(BlockchainError::InvalidTarget(u64::from(compact)))
+        }
+    }
+
+    /// Encodes the target in 32 bits. The highest byte contains the length of the target in bytes
+    /// without its leading zero bytes. The lower three bytes contain the mantissa, the three most
+    /// significant bytes of the target. The remaining bytes of the target are `0xff`, so the
+    /// target of every difficulty given as number of leading zero bits is encoded exactly. Other
+    /// targets are rounded down to the next target, that can be encoded.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::target::Target;
+    /// assert_eq!(Target::from_difficulty(0).to_compact(), 0x20ff_ffff);
+    /// assert_eq!(Target::from_difficulty(3).to_compact(), 0x201f_ffff);
+    /// assert_eq!(Target::default().to_compact(), 0);
+    /// # }
+    /// ```
+    pub fn to_compact(&self) -> u32 {
+        let mut size = BYTES - self.0.iter().take_while(|byte| **byte == 0).count();
+        if size == 0 {
+            return 0;
+        }
+        let significant = &self.0[BYTES - size..];
+        let mut mantissa = (0..MANTISSA_BYTES).fold(0, |mantissa, idx| {
+            (mantissa << 8) | u32::from(significant.get(idx).cloned().unwrap_or(0))
+        });
+        if significant
+            .iter()
+            .skip(MANTISSA_BYTES)
+            .any(|byte| *byte != 0xff)
+        {
+            // round down, the leading byte of the mantissa is not 0, so this does not underflow
+            mantissa -= 1;
+            if mantissa >> (8 * (MANTISSA_BYTES - 1)) == 0 {
+                mantissa = (mantissa << 8) | 0xff;
+                size -= 1;
+            }
+        }
+        ((size as u32) << 24) | mantissa
+    }
+
+    /// Returns the big endian bytes of the target.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in &self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Target({})", self)
+    }
+}
+
+/// Returns the number of leading zero bits of a hash.
+pub(crate) fn leading_zeros(hash: &[u8]) -> usize {
+    let zero_bytes = hash.iter().take_while(|byte| **byte == 0).count();
+    zero_bytes * 8
+        + hash
+            .get(zero_bytes)
+            .map_or(0, |byte| byte.leading_zeros() as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a target from random bytes, that are shifted, so targets of every size are
+    /// generated.
+    fn target(bytes: Vec<u8>, shift: u8) -> Target {
+        let mut target = [0; BYTES];
+        for (dst, src) in target.iter_mut().skip(shift as usize % BYTES).zip(bytes) {
+            *dst = src;
+        }
+        Target(target)
+    }
+
+    #[test]
+    fn difficulty_targets_are_compact() {
+        for difficulty in 0..300 {
+            let target = Target::from_difficulty(difficulty);
+            let bits = ::std::cmp::min(difficulty, BYTES * 8);
+            assert_eq!(target.difficulty(), bits);
+            assert_eq!(target.min_difficulty(), bits);
+            assert_eq!(Target::from_compact(target.to_compact()).unwrap(), target);
+            assert_eq!(target.work(), Work::from_difficulty(bits));
+        }
+    }
+
+    #[test]
+    fn small_targets_are_compact() {
+        let mut bytes = [0; BYTES];
+        bytes[BYTES - 1] = 0x12;
+        let target = Target(bytes);
+        assert_eq!(target.to_compact(), 0x0112_0000);
+        assert_eq!(Target::from_compact(0x0112_0000).unwrap(), target);
+        assert!(Target::from_compact(0x0112_3400).is_err());
+        assert!(Target::from_compact(0x0000_0001).is_err());
+    }
+
+    #[test]
+    fn rounding_down_renormalizes() {
+        let mut bytes = [0; BYTES];
+        bytes[BYTES - 5] = 0x01;
+        let target = Target(bytes);
+        assert_eq!(target.to_compact(), 0x04ff_ffff);
+        let rounded = Target::from_compact(0x04ff_ffff).unwrap();
+        assert_eq!(
+            Work::from_bytes(&rounded.0),
+            Work::from_bytes(&target.0).sub(&Work::from(1))
+        );
+    }
+
+    quickcheck! {
+        fn compact_rounds_down_by_less_than_a_mantissa_step(bytes: Vec<u8>, shift: u8) -> bool {
+            let target = target(bytes, shift);
+            let compact = Target::from_compact(target.to_compact()).unwrap();
+            compact <= target && compact.scale(1 << 16, (1 << 16) - 1) >= target
+        }
+
+        fn compact_encoding_is_canonical(compact: u32) -> bool {
+            match Target::from_compact(compact) {
+                Ok(target) => target.to_compact() == compact,
+                Err(_) => true,
+            }
+        }
+
+        fn lower_targets_need_more_work(a: Vec<u8>, b: Vec<u8>, shift: u8) -> bool {
+            let (a, b) = (target(a, shift), target(b, shift / 2));
+            a > b || a.work() >= b.work()
+        }
+
+        fn work_round_trips(bytes: Vec<u8>, shift: u8) -> bool {
+            let target = target(bytes, shift);
+            let converted = Target::from_work(&target.work());
+            converted.work() == target.work() && converted >= target
+        }
+
+        fn targets_match_leading_zeros(hash: Vec<u8>, difficulty: usize) -> bool {
+            let difficulty = difficulty % 20;
+            let mut hash = hash;
+            hash.resize(BYTES, 0);
+            Target::from_difficulty(difficulty).is_met_by(&hash)
+                == (leading_zeros(&hash) >= difficulty)
+        }
+
+        fn scaling_is_exact(bytes: Vec<u8>, shift: u8, factor: u8) -> bool {
+            let target = target(bytes, shift);
+            let factor = u64::from(factor) + 1;
+            let scaled = target.scale(factor, 1);
+            scaled == Target::max_value() || scaled.scale(1, factor) == target
+        }
+    }
+}
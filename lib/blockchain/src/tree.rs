@@ -0,0 +1,388 @@
+//! This module contains a block tree, that keeps track of competing branches of a blockchain. Each
+//! branch is a persistent [`Blockchain`](../blockchain/struct.Blockchain.html), so branches share
+//! their common history. The best chain is the branch with the most cumulative work. If another
+//! branch accumulates more work than the best chain, the tree reorganizes to that branch.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use generic_array::GenericArray;
+
+use block::Block;
+use blockchain::Blockchain;
+use consensus::{ConsensusRules, FixedDifficulty};
+use version::Hashable;
+use work::Work;
+
+use super::{BlockchainError, HashBytes};
+
+/// A known block, stored as the chain ending in that block, together with the cumulative work of
+/// the chain.
+#[derive(Debug)]
+struct Branch<D, H, S>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    chain: Blockchain<D, H, S>,
+    work: Work,
+}
+
+/// Event that is emitted when the best chain changes. Contains the hashes of the blocks that have
+/// been removed from the best chain (newest first) and the hashes of the blocks that have been
+/// added to the best chain (oldest first). If a block simply extends the best chain, no blocks are
+/// disconnected.
+#[derive(Debug, Clone)]
+pub struct Reorg<H>
+where
+    H: ::digest::Digest,
+{
+    disconnected: Vec<GenericArray<u8, H::OutputSize>>,
+    connected: Vec<GenericArray<u8, H::OutputSize>>,
+}
+
+impl<H> Reorg<H>
+where
+    H: ::digest::Digest,
+{
+    /// Returns the hashes of the blocks that have been removed from the best chain, newest first.
+    pub fn disconnected(&self) -> &[GenericArray<u8, H::OutputSize>] {
+        &self.disconnected
+    }
+
+    /// Returns the hashes of the blocks that have been added to the best chain, oldest first.
+    pub fn connected(&self) -> &[GenericArray<u8, H::OutputSize>] {
+        &self.connected
+    }
+
+    /// Returns true if the best chain has only been extended and no blocks were disconnected.
+    pub fn is_extension(&self) -> bool {
+        self.disconnected.is_empty()
+    }
+}
+
+/// Tree of blocks with most cumulative work fork choice. Unlike the blockchain itself, the tree is
+/// modified in place.
+#[derive(Debug)]
+pub struct BlockTree<D, H, S = ()>
+where
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+{
+    branches: HashMap<Vec<u8>, Branch<D, H, S>>,
+    best: Blockchain<D, H, S>,
+    best_work: Work,
+}
+
+impl<D, H, S> BlockTree<D, H, S>
+where
+    D: Default + Hashable,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Default + Hashable,
+{
+    /// Creates a new and empty block tree.
+    pub fn new() -> Self {
+        Self {
+            branches: HashMap::new(),
+            best: Blockchain::new(),
+            best_work: Work::default(),
+        }
+    }
+
+    /// Creates a block tree from an existing chain. The chain becomes the best chain.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::tree::BlockTree;
+    /// let bc: Blockchain<_, sha2::Sha256> = Blockchain::new();
+    /// let bc = bc.append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+    /// let tree = BlockTree::from_chain(bc.clone());
+    /// assert!(tree.best_chain() == &bc);
+    /// assert_eq!(tree.len(), 2);
+    /// # }
+    /// ```
+    pub fn from_chain(chain: Blockchain<D, H, S>) -> Self {
+        let mut prefixes = Vec::with_capacity(chain.len());
+        let mut current = chain;
+        loop {
+            let (hash, parent) = match current.tail() {
+                (Some(head), parent) => (head.hash(), parent),
+                (None, _) => break,
+            };
+            prefixes.push((hash, current));
+            current = parent;
+        }
+        let mut tree = Self::new();
+        for (hash, prefix) in prefixes.into_iter().rev() {
+            // the chain has not been validated, so a block with an invalid target adds no work
+            let block_work = prefix.iter().next().unwrap().header().work();
+            let work = tree.best_work.add(&block_work.unwrap_or_default());
+            tree.best = prefix.clone();
+            tree.best_work = work;
+            tree.branches.insert(
+                hash.to_vec(),
+                Branch {
+                    chain: prefix,
+                    work: work,
+                },
+            );
+        }
+        tree
+    }
+
+    /// Returns the best chain, e.g. the branch with the most cumulative work.
+    pub fn best_chain(&self) -> &Blockchain<D, H, S> {
+        &self.best
+    }
+
+    /// Returns the cumulative work of the best chain.
+    pub fn best_work(&self) -> Work {
+        self.best_work
+    }
+
+    /// Returns the chain ending in the block with the given hash, if the block is known.
+    pub fn get(&self, hash: &[u8]) -> Option<&Blockchain<D, H, S>> {
+        self.branches.get(hash).map(|branch| &branch.chain)
+    }
+
+    /// Returns true if a block with the given hash is part of the tree.
+    pub fn contains(&self, hash: &[u8]) -> bool {
+        self.branches.contains_key(hash)
+    }
+
+    /// Returns the number of blocks in all branches.
+    pub fn len(&self) -> usize {
+        self.branches.len()
+    }
+
+    /// Checks if the tree is empty.
+    pub fn is_empty(&self) -> bool {
+        self.branches.is_empty()
+    }
+
+    /// Inserts a block into the tree without enforcing a minimum difficulty. See
+    /// [`insert_with_rules`](#method.insert_with_rules).
+    pub fn insert(&mut self, block: Block<D, H, S>) -> Result<Option<Reorg<H>>, BlockchainError> {
+        self.insert_with_rules(block, &FixedDifficulty(0))
+    }
+
+    /// Inserts a block into the tree. The block can be appended to any known block or be a new
+    /// genesis block. It is validated against its parent using the consensus rules. If the branch
+    /// ending in the new block has more cumulative work than the best chain, the tree reorganizes
+    /// to that branch and the resulting reorg event is returned. If the block is appended to a side
+    /// branch, `None` is returned.
+    ///
+    /// # Examples
+    /// ```
+    /// extern crate sha2;
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::blockchain::Blockchain;
+    /// use blockchain::tree::BlockTree;
+    /// let genesis: Blockchain<_, sha2::Sha256> = Blockchain::new().append(vec![0], 0).unwrap();
+    /// let mut tree = BlockTree::from_chain(genesis.clone());
+    /// let weak = genesis.generate_block(vec![1], 1);
+    /// let strong = genesis.generate_block(vec![2], 4);
+    /// let reorg = tree.insert(weak.clone()).unwrap().unwrap();
+    /// assert!(reorg.is_extension());
+    /// let reorg = tree.insert(strong.clone()).unwrap().unwrap();
+    /// assert_eq!(reorg.disconnected(), &[weak.hash()]);
+    /// assert_eq!(reorg.connected(), &[strong.hash()]);
+    /// assert_eq!(tree.best_chain().iter().next(), Some(&strong));
+    /// # }
+    /// ```
+    pub fn insert_with_rules<R>(
+        &mut self,
+        block: Block<D, H, S>,
+        rules: &R,
+    ) -> Result<Option<Reorg<H>>, BlockchainError>
+    where
+        R: ConsensusRules<H, S>,
+    {
+        let hash = block.hash();
+        if self.contains(&hash) {
+            return Err(BlockchainError::DuplicateBlock(HashBytes::from(&hash[..])));
+        }
+        let (parent, parent_work) = if block.is_genesis() {
+            (Blockchain::new(), Work::default())
+        } else {
+            match self.branches.get(&block.prev_hash()[..]) {
+                Some(branch) => (branch.chain.clone(), branch.work),
+                None => {
+                    return Err(BlockchainError::UnknownParent(HashBytes::from(
+                        &block.prev_hash()[..],
+                    )))
+                }
+            }
+        };
+        let block_work = block.header().work()?;
+        let chain = parent.insert_with_rules(block, rules)?;
+        let work = parent_work.add(&block_work);
+        self.branches.insert(
+            hash.to_vec(),
+            Branch {
+                chain: chain.clone(),
+                work: work,
+            },
+        );
+        if work > self.best_work {
+            let reorg = reorg(&self.best, &chain);
+            self.best = chain;
+            self.best_work = work;
+            Ok(Some(reorg))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Computes the reorg event when switching the best chain from `old` to `new`.
+fn reorg<D, H, S>(old: &Blockchain<D, H, S>, new: &Blockchain<D, H, S>) -> Reorg<H>
+where
+    D: Default + Hashable,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Default + Hashable,
+{
+    let mut old_iter = old.iter().map(|blk| blk.hash()).peekable();
+    let mut new_iter = new.iter().map(|blk| blk.hash()).peekable();
+    let mut disconnected = Vec::new();
+    let mut connected = Vec::new();
+    // walk down the longer chain until both chains have the same height
+    for _ in new.len()..old.len() {
+        disconnected.extend(old_iter.next());
+    }
+    for _ in old.len()..new.len() {
+        connected.extend(new_iter.next());
+    }
+    // walk down both chains until the common ancestor is reached
+    loop {
+        match (old_iter.next(), new_iter.next()) {
+            (Some(ref o), Some(ref n)) if o == n => break,
+            (o, n) => {
+                disconnected.extend(o);
+                connected.extend(n);
+                if old_iter.peek().is_none() && new_iter.peek().is_none() {
+                    break;
+                }
+            }
+        }
+    }
+    connected.reverse();
+    Reorg {
+        disconnected: disconnected,
+        connected: connected,
+    }
+}
+
+impl<H> PartialEq for Reorg<H>
+where
+    H: ::digest::Digest,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.disconnected == other.disconnected && self.connected == other.connected
+    }
+}
+
+impl<D, H, S> Default for BlockTree<D, H, S>
+where
+    D: Default + Hashable,
+    H: ::digest::Digest,
+    <H as ::digest::FixedOutput>::OutputSize: Debug + Clone,
+    S: Default + Hashable,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Chain = Blockchain<u8, ::sha2::Sha256>;
+
+    #[test]
+    fn unknown_parent_is_rejected() {
+        let mut tree = BlockTree::new();
+        let chain = Chain::new().append(vec![0], 0).unwrap();
+        let orphan = chain.generate_block(vec![1], 0);
+        assert!(tree.insert(orphan).is_err());
+    }
+
+    #[test]
+    fn invalid_target_adds_no_work() {
+        let chain = Chain::new().append(vec![0], 0).unwrap();
+        let (header, transactions) = chain.generate_block(vec![1], 0).into_parts();
+        // the mantissa of the target is not normalized
+        let header = header.set_compact_target(0x1f00_ffff);
+        assert!(header.work().is_err());
+        let invalid = Block::from_parts(header, transactions).unwrap();
+        let mut tree = BlockTree::from_chain(chain.clone());
+        match tree.insert(invalid.clone()) {
+            Err(BlockchainError::InvalidTarget(0x1f00_ffff)) => {}
+            _ => panic!("the target of the block is invalid"),
+        }
+        // chains are not validated, when a tree is created from them
+        let tree = BlockTree::from_chain(chain.push(invalid));
+        assert_eq!(tree.best_work, BlockTree::from_chain(chain).best_work);
+    }
+
+    #[test]
+    fn duplicate_block_is_rejected() {
+        let chain = Chain::new().append(vec![0], 0).unwrap();
+        let mut tree = BlockTree::from_chain(chain.clone());
+        let block = chain.iter().next().unwrap().clone();
+        assert!(tree.insert(block).is_err());
+    }
+
+    #[test]
+    fn side_branch_does_not_change_best_chain() {
+        let base = Chain::new().append(vec![0], 0).unwrap();
+        let main = base.append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut tree = BlockTree::from_chain(main.clone());
+        assert_eq!(tree.insert(base.generate_block(vec![3], 0)).unwrap(), None);
+        assert!(tree.best_chain() == &main);
+        assert_eq!(tree.len(), 4);
+    }
+
+    #[test]
+    fn reorg_to_heavier_branch() {
+        let base = Chain::new().append(vec![0], 0).unwrap();
+        let main = base.append(vec![1], 0).unwrap().append(vec![2], 0).unwrap();
+        let mut tree = BlockTree::from_chain(main.clone());
+        let side1 = base.generate_block(vec![3], 0);
+        let side = base.insert(side1.clone()).unwrap();
+        let side2 = side.generate_block(vec![4], 0);
+        let side = side.insert(side2.clone()).unwrap();
+        let side3 = side.generate_block(vec![5], 0);
+        assert_eq!(tree.insert(side1.clone()).unwrap(), None);
+        // equal work keeps the current best chain
+        assert_eq!(tree.insert(side2.clone()).unwrap(), None);
+        let reorg = tree.insert(side3.clone()).unwrap().unwrap();
+        let main_hashes = main.iter().take(2).map(|b| b.hash()).collect::<Vec<_>>();
+        assert_eq!(reorg.disconnected(), &main_hashes[..]);
+        assert_eq!(
+            reorg.connected(),
+            &[side1.hash(), side2.hash(), side3.hash()]
+        );
+        assert_eq!(tree.best_chain().len(), 4);
+        assert!(tree.best_chain().validate_chain());
+    }
+
+    #[test]
+    fn reorg_to_competing_genesis() {
+        let mut tree = BlockTree::new();
+        let first = Chain::new().append(vec![0], 0).unwrap();
+        tree.insert(first.iter().next().unwrap().clone()).unwrap();
+        let second = Chain::new().generate_block(vec![1], 2);
+        let reorg = tree.insert(second.clone()).unwrap().unwrap();
+        assert_eq!(reorg.disconnected(), &[first.iter().next().unwrap().hash()]);
+        assert_eq!(reorg.connected(), &[second.hash()]);
+    }
+}
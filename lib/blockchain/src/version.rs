@@ -0,0 +1,455 @@
+//! This module contains the supported block versions. The version of a block decides how its
+//! header and transactions are encoded for hashing:
+//!
+//! * Version `1` ([`LEGACY_VERSION`](constant.LEGACY_VERSION.html)) hashes the `bincode`
+//!   serialization of the blocks, that have been persisted by earlier releases (see
+//!   [`LegacyBlock`](struct.LegacyBlock.html)). Each of these blocks contains a single
+//!   transaction. It is only supported to validate chains, that were created before the
+//!   canonical encoding was introduced.
+//! * Version `2` ([`CANONICAL_VERSION`](constant.CANONICAL_VERSION.html)) hashes the canonical
+//!   encoding (see the [`canonical`](../canonical/index.html) module).
+//! * Version `3` ([`TARGET_VERSION`](constant.TARGET_VERSION.html)) is hashed like version `2`,
+//!   but stores the difficulty as compact target instead of the number of leading zero bits (see
+//!   the [`target`](../target/index.html) module).
+//! * Version `4` ([`SKIP_VERSION`](constant.SKIP_VERSION.html)) is hashed like version `3`, but
+//!   its header additionally contains the hash of an older ancestor (see
+//!   [`skip_height`](../header/fn.skip_height.html)), so inclusion proofs (see the
+//!   [`proof`](../proof/index.html) module) only need a logarithmic number of headers.
+//!
+//! A [`VersionSchedule`](struct.VersionSchedule.html) decides which version a block at a certain
+//! height needs to have, so a new version can be activated at a configured height while the blocks
+//! before that height stay valid under the rules of their own version.
+
+use std::io::Read;
+
+use generic_array::GenericArray;
+
+use serde::{Deserialize, Serialize};
+
+use block::{Block, VERSION};
+use canonical::Canonical;
+use header::Header;
+
+use super::{BlockchainError, PersistingError};
+
+/// Version of blocks, that are hashed using their `bincode` serialization.
+pub const LEGACY_VERSION: u8 = 1;
+/// Version of blocks, that are hashed using their canonical encoding.
+pub const CANONICAL_VERSION: u8 = 2;
+/// Version of blocks, whose difficulty is a compact target.
+pub const TARGET_VERSION: u8 = 3;
+/// Version of blocks, whose header links to an older ancestor in addition to the parent.
+pub const SKIP_VERSION: u8 = 4;
+/// All block versions, that can be validated, oldest version first.
+pub const SUPPORTED_VERSIONS: &[u8] = &[
+    LEGACY_VERSION,
+    CANONICAL_VERSION,
+    TARGET_VERSION,
+    SKIP_VERSION,
+];
+
+/// Types that can be hashed under every supported block version. This is implemented for every
+/// type that has a canonical encoding and can be serialized.
+pub trait Hashable: Canonical + Serialize {}
+
+impl<T> Hashable for T
+where
+    T: Canonical + Serialize + ?Sized,
+{
+}
+
+/// Checks if blocks of the given version can be validated.
+pub fn is_supported(version: u8) -> bool {
+    SUPPORTED_VERSIONS.contains(&version)
+}
+
+/// Encodes a value for hashing according to the rules of the given block version. Unknown
+/// versions use the canonical encoding, blocks with these versions are rejected by the validation
+/// anyway.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::canonical::Canonical;
+/// use blockchain::version::{encode, CANONICAL_VERSION, LEGACY_VERSION};
+/// assert_eq!(encode(CANONICAL_VERSION, &1u32), 1u32.canonical_bytes());
+/// assert_eq!(encode(LEGACY_VERSION, &1u32), vec![1, 0, 0, 0]);
+/// # }
+/// ```
+pub fn encode<T>(version: u8, value: &T) -> Vec<u8>
+where
+    T: Hashable + ?Sized,
+{
+    match version {
+        LEGACY_VERSION => ::bincode::serialize(value, ::bincode::Infinite).unwrap(),
+        _ => value.canonical_bytes(),
+    }
+}
+
+/// A block of version `1` as persisted by earlier releases, which did not separate the header from
+/// the transactions. Each block contains a single transaction and its hash is the hash of its
+/// `bincode` serialization. It can be converted into a [`Block`](../block/struct.Block.html) with
+/// the same hash, e.g. to migrate a chain of an earlier release.
+#[derive(Serialize, Deserialize)]
+#[serde(bound(serialize = "D: Serialize", deserialize = "D: ::serde::Deserialize<'de>"))]
+pub struct LegacyBlock<D, H>
+where
+    H: ::digest::Digest,
+{
+    version: u8,
+    prev_hash: GenericArray<u8, H::OutputSize>,
+    time: u64,
+    difficulty: usize,
+    nonce: u64,
+    data: D,
+}
+
+impl<D, H> LegacyBlock<D, H>
+where
+    D: Hashable,
+    H: ::digest::Digest,
+{
+    /// Converts the block into a block of version `1`, that has the same hash. Returns an error if
+    /// the block has another version.
+    pub fn into_block<S: Default>(self) -> Result<Block<D, H, S>, BlockchainError> {
+        if self.version != LEGACY_VERSION {
+            return Err(BlockchainError::UnknownVersion(self.version));
+        }
+        let header = Header::legacy(
+            self.prev_hash,
+            self.time,
+            self.difficulty,
+            self.nonce,
+            encode(LEGACY_VERSION, &self.data),
+        );
+        Block::from_parts(header, vec![self.data])
+    }
+}
+
+/// Reads a chain, that has been persisted as a whole by an earlier release, i.e. the `bincode`
+/// serialization of its blocks of version `1` (see [`LegacyBlock`](struct.LegacyBlock.html)),
+/// newest block first. The blocks are returned oldest block first. Fails, if the data is not such
+/// a chain, e.g. because it is longer than `limit` bytes or followed by other data.
+pub fn read_legacy_chain<R, D, H, S>(
+    reader: &mut R,
+    limit: u64,
+) -> Result<Vec<Block<D, H, S>>, PersistingError>
+where
+    R: Read,
+    for<'de> D: Deserialize<'de> + Hashable,
+    H: ::digest::Digest,
+    S: Default,
+{
+    let blocks: Vec<LegacyBlock<D, H>> =
+        ::bincode::deserialize_from(&mut *reader, ::bincode::Bounded(limit))
+            .map_err(|_| PersistingError::DeserializingError)?;
+    let mut probe = [0; 1];
+    match reader.read(&mut probe) {
+        Ok(0) => {}
+        Ok(_) => return Err(PersistingError::DeserializingError),
+        Err(_) => return Err(PersistingError::IoError),
+    }
+    blocks
+        .into_iter()
+        .rev()
+        .enumerate()
+        .map(|(height, block)| {
+            block
+                .into_block()
+                .map_err(|err| PersistingError::InvalidBlock(height, err))
+        })
+        .collect()
+}
+
+/// Schedule of the block versions of a chain. Each version is active from its activation height
+/// until the next version is activated. The genesis block has height `0`.
+///
+/// # Examples
+/// ```
+/// # extern crate blockchain;
+/// # fn main() {
+/// use blockchain::version::{VersionSchedule, CANONICAL_VERSION, LEGACY_VERSION};
+/// let schedule = VersionSchedule::new(LEGACY_VERSION).activate(CANONICAL_VERSION, 100);
+/// assert_eq!(schedule.version_at(99), LEGACY_VERSION);
+/// assert_eq!(schedule.version_at(100), CANONICAL_VERSION);
+/// assert_eq!(schedule.versions(), vec![LEGACY_VERSION, CANONICAL_VERSION]);
+/// assert!(schedule.validate(LEGACY_VERSION, 100).is_err());
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionSchedule {
+    /// Pairs of activation height and version, ordered by height. The first version is active
+    /// from the genesis block.
+    activations: Vec<(usize, u8)>,
+}
+
+impl VersionSchedule {
+    /// Creates a schedule, that requires every block to have the given version.
+    ///
+    /// # Panics
+    /// Panics if the version is not supported.
+    pub fn new(version: u8) -> Self {
+        assert!(is_supported(version), "Unsupported block version");
+        Self {
+            activations: vec![(0, version)],
+        }
+    }
+
+    /// Activates a version from the given height on. The method returns a new schedule and
+    /// consumes the old one.
+    ///
+    /// # Panics
+    /// Panics if the version is not supported or if the height is not greater than the height of
+    /// the previous activation.
+    pub fn activate(self, version: u8, height: usize) -> Self {
+        assert!(is_supported(version), "Unsupported block version");
+        assert!(
+            self.activations
+                .last()
+                .map_or(true, |&(prev, _)| prev < height),
+            "Versions must be activated in order"
+        );
+        let mut activations = self.activations;
+        activations.push((height, version));
+        Self {
+            activations: activations,
+        }
+    }
+
+    /// Returns the version, a block at the given height needs to have.
+    pub fn version_at(&self, height: usize) -> u8 {
+        self.activations
+            .iter()
+            .take_while(|&&(activation, _)| activation <= height)
+            .last()
+            .map_or(VERSION, |&(_, version)| version)
+    }
+
+    /// Returns the versions, that are accepted somewhere in the chain, in order of activation.
+    pub fn versions(&self) -> Vec<u8> {
+        let mut versions = self.activations
+            .iter()
+            .map(|&(_, version)| version)
+            .collect::<Vec<_>>();
+        versions.dedup();
+        versions
+    }
+
+    /// Returns pairs of activation height and version, ordered by height.
+    pub fn activations(&self) -> &[(usize, u8)] {
+        &self.activations
+    }
+
+    /// Checks if a block at the given height may have the given version.
+    pub fn validate(&self, version: u8, height: usize) -> Result<(), BlockchainError> {
+        let expected = self.version_at(height);
+        if !is_supported(version) {
+            Err(BlockchainError::UnknownVersion(version))
+        } else if version != expected {
+            Err(BlockchainError::InactiveVersion(version, height, expected))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Default for VersionSchedule {
+    /// Requires every block to have the current version `block::VERSION`.
+    fn default() -> Self {
+        Self::new(VERSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use block::Block;
+    use blockchain::Blockchain;
+    use canonical::{hex, unhex};
+    use consensus::ConsensusRules;
+    use header::Header;
+    use seal::ProofOfWork;
+    use target::Target;
+
+    type Chain = Blockchain<u32, ::sha2::Sha256>;
+
+    /// Proof of work rules without a required target, that follow a version schedule.
+    struct Scheduled(VersionSchedule);
+
+    impl ConsensusRules<::sha2::Sha256> for Scheduled {
+        type Scheme = ProofOfWork;
+
+        fn scheme(&self) -> &ProofOfWork {
+            &ProofOfWork
+        }
+
+        fn required_target<'a, I>(&self, _parent: I) -> Target
+        where
+            I: Iterator<Item = &'a Header<::sha2::Sha256>>,
+        {
+            Target::max_value()
+        }
+
+        fn version_at(&self, height: usize) -> u8 {
+            self.0.version_at(height)
+        }
+    }
+
+    fn block(chain: &Chain, transactions: Vec<u32>, version: u8) -> Block<u32, ::sha2::Sha256> {
+        let prev = chain.headers().next().map(|h| h.hash()).unwrap_or_default();
+        Block::new_with_hash(transactions, prev, 4)
+            .set_version(version)
+            .proof_of_work()
+    }
+
+    fn migrated(legacy: usize, canonical: usize) -> (Chain, Scheduled) {
+        let rules = Scheduled(
+            VersionSchedule::new(LEGACY_VERSION).activate(CANONICAL_VERSION, legacy),
+        );
+        let chain = (0..legacy + canonical).fold(Chain::new(), |chain, i| {
+            let version = rules.version_at(i);
+            let block = block(&chain, vec![i as u32], version);
+            chain.insert_with_rules(block, &rules).unwrap()
+        });
+        (chain, rules)
+    }
+
+    #[test]
+    fn mixed_chain_is_valid() {
+        let (chain, rules) = migrated(3, 2);
+        let versions = chain
+            .iter_chronological()
+            .map(|blk| blk.version())
+            .collect::<Vec<_>>();
+        assert_eq!(versions, vec![1, 1, 1, 2, 2]);
+        assert!(chain.validate_chain_with_rules(&rules));
+        // without the schedule, only the current version is accepted
+        assert!(!chain.validate_chain());
+    }
+
+    #[test]
+    fn versions_are_enforced_by_height() {
+        let (chain, rules) = migrated(2, 0);
+        match chain.insert_with_rules(block(&chain, vec![7], LEGACY_VERSION), &rules) {
+            Err(BlockchainError::InactiveVersion(1, 2, 2)) => (),
+            _ => panic!("the legacy version is no longer active"),
+        }
+        let (chain, rules) = migrated(3, 0);
+        let early = migrated(2, 0).0;
+        match early.insert_with_rules(block(&early, vec![7], CANONICAL_VERSION), &rules) {
+            Err(BlockchainError::InactiveVersion(2, 2, 1)) => (),
+            _ => panic!("the canonical version is not active yet"),
+        }
+        match chain.insert_with_rules(block(&chain, vec![7], TARGET_VERSION), &rules) {
+            Err(BlockchainError::InactiveVersion(3, 3, 2)) => (),
+            _ => panic!("the target version is not active"),
+        }
+        match chain.insert_with_rules(block(&chain, vec![7], 5), &rules) {
+            Err(BlockchainError::UnknownVersion(5)) => (),
+            _ => panic!("version 5 does not exist"),
+        }
+    }
+
+    #[test]
+    fn target_version_migrates_difficulty() {
+        let rules = Scheduled(
+            VersionSchedule::new(CANONICAL_VERSION).activate(TARGET_VERSION, 2),
+        );
+        let chain = (0..4).fold(Chain::new(), |chain, i| {
+            let block = block(&chain, vec![i], rules.version_at(i as usize));
+            chain.insert_with_rules(block, &rules).unwrap()
+        });
+        assert!(chain.validate_chain_with_rules(&rules));
+        for header in chain.headers() {
+            assert_eq!(header.difficulty(), 4);
+            assert_eq!(header.target().unwrap(), Target::from_difficulty(4));
+            assert_eq!(header.work().unwrap(), ::work::Work::from_difficulty(4));
+        }
+        let legacy = chain.headers().last().unwrap();
+        let current = chain.headers().next().unwrap();
+        assert_eq!(
+            (legacy.version(), current.version()),
+            (CANONICAL_VERSION, TARGET_VERSION)
+        );
+        // the block is converted back to the number of leading zero bits
+        let converted = chain.iter().next().unwrap().clone().set_version(CANONICAL_VERSION);
+        assert_eq!(converted.difficulty(), 4);
+        assert!(converted.header().meets_target(&current.hash()));
+    }
+
+    #[test]
+    fn legacy_blocks_keep_their_hash() {
+        // a block with the transaction `1000u32` and the difficulty `8`, that has been persisted by
+        // the release before block headers were introduced, and its hash
+        let persisted = unhex(
+            "0120000000000000000000000000000000000000000000000000000000000000000000000000000000\
+             002f68590000000008000000000000003900000000000000e8030000",
+        );
+        let hash = "003d2f0f61d4c6aede8a35cca63c714b8a8818aaf66bebed53eb05dec35fd230";
+        let legacy: LegacyBlock<u32, ::sha2::Sha256> = ::bincode::deserialize(&persisted).unwrap();
+        let block: Block<u32, ::sha2::Sha256> = legacy.into_block().unwrap();
+        assert_eq!(hex(&block.hash()), hash);
+        assert_eq!(hex(&block.header().hash()), hash);
+        assert!(block.validate_difficulty());
+        assert_eq!(block.transactions(), &[1000]);
+        assert_eq!((block.time(), block.nonce(), block.difficulty()), (1_500_000_000, 57, 8));
+        // the same block is created by setting the version
+        let clock = ::clock::FixedClock(1_500_000_000);
+        let created: Block<u32, ::sha2::Sha256> =
+            Block::new_with_clock(vec![1000], Default::default(), 8, &clock)
+                .set_version(LEGACY_VERSION)
+                .set_nonce(57, 1_500_000_000);
+        assert_eq!(created, block);
+        // the header is persisted with the serialization of the transaction
+        let bytes = ::bincode::serialize(block.header(), ::bincode::Infinite).unwrap();
+        let header: Header<::sha2::Sha256> = ::bincode::deserialize(&bytes).unwrap();
+        assert_eq!(hex(&header.hash()), hash);
+        let mut forged = bytes.clone();
+        let last = forged.len() - 1;
+        forged[last] ^= 1;
+        assert!(::bincode::deserialize::<Header<::sha2::Sha256>>(&forged).is_err());
+        // a persisted chain starts with the number of blocks
+        let mut chain = vec![1, 0, 0, 0, 0, 0, 0, 0];
+        chain.extend_from_slice(&persisted);
+        let limit = chain.len() as u64;
+        let read: Vec<Block<u32, ::sha2::Sha256>> =
+            read_legacy_chain(&mut &chain[..], limit).unwrap();
+        assert_eq!(read, vec![block]);
+        chain.push(0);
+        assert!(read_legacy_chain::<_, u32, ::sha2::Sha256, ()>(&mut &chain[..], limit).is_err());
+    }
+
+    #[test]
+    fn legacy_blocks_contain_a_single_transaction() {
+        for transactions in vec![vec![], vec![1, 2]] {
+            let block = block(&Chain::new(), transactions, LEGACY_VERSION);
+            assert!(!block.validate_merkle_root());
+        }
+        let block = block(&Chain::new(), vec![1], LEGACY_VERSION);
+        assert!(block.validate_merkle_root());
+        assert!(!block.clone().replace_transactions(vec![2]).validate_merkle_root());
+        assert_eq!(block.seal_hash(), block.hash());
+    }
+
+    #[test]
+    #[should_panic]
+    fn activations_are_ordered() {
+        VersionSchedule::new(LEGACY_VERSION)
+            .activate(CANONICAL_VERSION, 10)
+            .activate(LEGACY_VERSION, 10);
+    }
+
+    quickcheck! {
+        fn mixed_chain_survives_persisting(legacy: usize, canonical: usize) -> bool {
+            let (chain, rules) = migrated(legacy % 4 + 1, canonical % 4);
+            let dir = ::tempdir::TempDir::new("versions").unwrap();
+            let path = dir.path().join("chain.bin");
+            chain.persist_to_disk(&path).unwrap();
+            Chain::load_from_disk_with_rules(&path, &rules)
+                .map(|loaded| loaded == chain)
+                .unwrap_or(false)
+        }
+    }
+}
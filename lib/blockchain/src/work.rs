@@ -0,0 +1,173 @@
+//! This module contains a representation of the amount of work that went into a block or chain.
+//! The expected work of a block with difficulty `d` is `2^d` hashes, the expected work of a block
+//! with target `t` is `2^256 / (t + 1)` hashes (see the [`target`](../target/index.html) module).
+//! Since the work of a chain can exceed 256 bits, it is stored as a 320 bit unsigned integer.
+
+use std::fmt;
+
+/// Number of 64 bit limbs.
+const LIMBS: usize = 5;
+
+/// Amount of work as a 320 bit unsigned integer. The limbs are stored most significant first, so
+/// the derived ordering compares the values numerically.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Work([u64; LIMBS]);
+
+impl Work {
+    /// Returns the expected work of a block with the given difficulty, e.g. `2^difficulty`. The
+    /// result saturates at the maximum value.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::work::Work;
+    /// assert_eq!(Work::from_difficulty(0), Work::from(1));
+    /// assert_eq!(Work::from_difficulty(3), Work::from(8));
+    /// assert!(Work::from_difficulty(200) > Work::from(u64::max_value()));
+    /// assert!(Work::from_difficulty(1000) > Work::from_difficulty(300));
+    /// # }
+    /// ```
+    pub fn from_difficulty(difficulty: usize) -> Self {
+        if difficulty >= LIMBS * 64 {
+            return Self::max_value();
+        }
+        let mut limbs = [0; LIMBS];
+        limbs[LIMBS - 1 - difficulty / 64] = 1 << (difficulty % 64);
+        Work(limbs)
+    }
+
+    /// Adds two amounts of work. The result saturates at the maximum value.
+    ///
+    /// # Examples
+    /// ```
+    /// # extern crate blockchain;
+    /// # fn main() {
+    /// use blockchain::work::Work;
+    /// let work = Work::from(u64::max_value()).add(&Work::from(1));
+    /// assert_eq!(work, Work::from_difficulty(64));
+    /// # }
+    /// ```
+    pub fn add(&self, other: &Work) -> Work {
+        let mut limbs = [0; LIMBS];
+        let mut carry = false;
+        for idx in (0..LIMBS).rev() {
+            let (sum, c1) = self.0[idx].overflowing_add(other.0[idx]);
+            let (sum, c2) = sum.overflowing_add(carry as u64);
+            limbs[idx] = sum;
+            carry = c1 || c2;
+        }
+        if carry {
+            Self::max_value()
+        } else {
+            Work(limbs)
+        }
+    }
+
+    /// Returns the maximum amount of work.
+    fn max_value() -> Self {
+        Work([u64::max_value(); LIMBS])
+    }
+
+    /// Creates an amount of work from its big endian representation. Excess leading bytes are
+    /// ignored.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        let mut limbs = [0; LIMBS];
+        for (idx, byte) in bytes.iter().rev().take(LIMBS * 8).enumerate() {
+            limbs[LIMBS - 1 - idx / 8] |= u64::from(*byte) << (8 * (idx % 8));
+        }
+        Work(limbs)
+    }
+
+    /// Returns the big endian representation.
+    pub(crate) fn to_bytes(&self) -> [u8; LIMBS * 8] {
+        let mut bytes = [0; LIMBS * 8];
+        for (idx, byte) in bytes.iter_mut().rev().enumerate() {
+            *byte = (self.0[LIMBS - 1 - idx / 8] >> (8 * (idx % 8))) as u8;
+        }
+        bytes
+    }
+
+    /// Subtracts `other` from this amount of work. The result saturates at `0`.
+    pub(crate) fn sub(&self, other: &Work) -> Work {
+        if self < other {
+            Work::default()
+        } else {
+            self.wrapping_sub(other)
+        }
+    }
+
+    /// Multiplies this amount of work by `factor`. The result saturates at the maximum value.
+    pub(crate) fn mul(&self, factor: u64) -> Work {
+        (0..64).rev().fold(Work::default(), |product, bit| {
+            let product = product.add(&product);
+            if (factor >> bit) & 1 == 1 {
+                product.add(self)
+            } else {
+                product
+            }
+        })
+    }
+
+    /// Divides this amount of work by `divisor`, rounding down. Dividing by `0` results in the
+    /// maximum value.
+    pub(crate) fn div(&self, divisor: &Work) -> Work {
+        if *divisor == Work::default() {
+            return Self::max_value();
+        }
+        let mut quotient = Work::default();
+        let mut remainder = Work::default();
+        for bit in (0..LIMBS * 64).rev() {
+            // the shifted out bit is part of the remainder, which is then larger than the divisor
+            let overflow = remainder.0[0] >> 63 == 1;
+            remainder = remainder.shift_left();
+            remainder.0[LIMBS - 1] |= (self.0[LIMBS - 1 - bit / 64] >> (bit % 64)) & 1;
+            if overflow || remainder >= *divisor {
+                remainder = remainder.wrapping_sub(divisor);
+                quotient.0[LIMBS - 1 - bit / 64] |= 1 << (bit % 64);
+            }
+        }
+        quotient
+    }
+
+    /// Shifts the value one bit to the left. The most significant bit is dropped.
+    fn shift_left(&self) -> Work {
+        let mut limbs = [0; LIMBS];
+        for (idx, limb) in limbs.iter_mut().enumerate() {
+            let carry = self.0.get(idx + 1).map_or(0, |next| next >> 63);
+            *limb = (self.0[idx] << 1) | carry;
+        }
+        Work(limbs)
+    }
+
+    /// Subtracts `other` from this amount of work modulo `2^320`.
+    fn wrapping_sub(&self, other: &Work) -> Work {
+        let mut limbs = [0; LIMBS];
+        let mut borrow = false;
+        for idx in (0..LIMBS).rev() {
+            let (diff, b1) = self.0[idx].overflowing_sub(other.0[idx]);
+            let (diff, b2) = diff.overflowing_sub(borrow as u64);
+            limbs[idx] = diff;
+            borrow = b1 || b2;
+        }
+        Work(limbs)
+    }
+}
+
+impl From<u64> for Work {
+    fn from(value: u64) -> Self {
+        let mut limbs = [0; LIMBS];
+        limbs[LIMBS - 1] = value;
+        Work(limbs)
+    }
+}
+
+impl fmt::Debug for Work {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Work(0x")?;
+        for limb in &self.0 {
+            write!(f, "{:016x}", limb)?;
+        }
+        write!(f, ")")
+    }
+}
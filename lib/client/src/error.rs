@@ -1,5 +1,7 @@
 //! This module contains the error enumeration for the web service client.
 
+use data::{BlockchainError, MiningError};
+
 /// Errors that can occur when communicating with the web service.
 #[derive(Debug, Fail)]
 pub enum ClientError {
@@ -9,9 +11,54 @@ pub enum ClientError {
     /// Getting the latest block failed.
     #[fail(display = "Cannot get latest block")]
     LatestBlock,
+    /// Getting the latest header failed.
+    #[fail(display = "Cannot get latest header")]
+    LatestHeader,
+    /// Getting the headers failed.
+    #[fail(display = "Cannot get headers")]
+    Headers,
+    /// Getting the body of a block failed.
+    #[fail(display = "Cannot get block body")]
+    Body,
+    /// A received header is not valid according to the consensus rules.
+    #[fail(display = "Invalid header: {}", _0)]
+    InvalidHeader(#[cause] BlockchainError),
+    /// A received body does not match the header of its block.
+    #[fail(display = "Invalid block body: {}", _0)]
+    InvalidBody(#[cause] BlockchainError),
+    /// Getting a block by height or hash failed.
+    #[fail(display = "Cannot get block")]
+    Block,
+    /// Getting the height of a block failed.
+    #[fail(display = "Cannot get block height")]
+    Height,
+    /// Getting the required difficulty failed.
+    #[fail(display = "Cannot get required difficulty")]
+    Difficulty,
+    /// Getting the required block versions failed.
+    #[fail(display = "Cannot get block versions")]
+    Version,
+    /// Getting the spec of the chain failed.
+    #[fail(display = "Cannot get chain spec")]
+    Spec,
+    /// Getting the hash algorithm of the chain failed.
+    #[fail(display = "Cannot get hash algorithm")]
+    Hash,
     /// Getting the subchain since the last billing failed.
     #[fail(display = "Cannot get last billing")]
     SinceLastBilling,
+    /// Getting the inclusion proof of a transaction failed.
+    #[fail(display = "Cannot get inclusion proof")]
+    InclusionProof,
+    /// The head of the chain moved too often while mining.
+    #[fail(display = "Head of the chain moved {} times while mining, giving up", _0)]
+    RetriesExhausted(usize),
+    /// A worker thread of the miner panicked.
+    #[fail(display = "Mining failed: {}", _0)]
+    Mining(#[cause] MiningError),
+    /// The web service does not provide the route, e.g. because it is an earlier release.
+    #[fail(display = "Route not provided by the web service")]
+    UnknownRoute,
     /// A invalid url has been supplied.
     #[fail(display = "Invalid url")]
     InvalidUrl,
@@ -8,10 +8,19 @@ extern crate failure;
 extern crate reqwest;
 
 pub mod error;
+pub mod light;
+pub mod mining;
+
+use std::fmt::Debug;
+use std::marker::PhantomData;
 
 use error::ClientError;
 
-use data::{Block, Blockchain};
+use data::{Block, Blockchain, ChainSpec, Header, InclusionProof, Sha256};
+use data::hash::{HashAlgorithm, HashFunction};
+use data::target::Target;
+use data::tx::BlockData;
+use data::version::VersionSchedule;
 
 use cryptography::BillingQuery;
 
@@ -20,16 +29,36 @@ use reqwest::StatusCode;
 // http routes for the webservice
 
 const ROUTE_LATEST_BLOCK: &str = "/latest_block";
+const ROUTE_LATEST_HEADER: &str = "/latest_header";
+const ROUTE_HEADERS: &str = "/headers";
+const ROUTE_BODY: &str = "/body";
+const ROUTE_BLOCK_BY_HEIGHT: &str = "/block_by_height";
+const ROUTE_BLOCK_BY_HASH: &str = "/block_by_hash";
+const ROUTE_HEIGHT_OF: &str = "/height_of";
+const ROUTE_DIFFICULTY: &str = "/difficulty";
+const ROUTE_TARGET: &str = "/target";
+const ROUTE_VERSION: &str = "/version";
+const ROUTE_VERSIONS: &str = "/versions";
+const ROUTE_SPEC: &str = "/spec";
+const ROUTE_HASH: &str = "/hash";
 const ROUTE_APPEND: &str = "/append";
 const ROUTE_LATEST_BILLING: &str = "/since_last_billing";
+const ROUTE_INCLUSION_PROOF: &str = "/inclusion_proof";
 
-/// The client structure containing the host and a HTTP client.
-pub struct Client<'a> {
+/// The client structure containing the host and a HTTP client. Blocks are hashed using `H`, which
+/// must match the hash algorithm of the remote chain (see
+/// [`hash_algorithm`](#method.hash_algorithm)).
+pub struct Client<'a, H = Sha256> {
     client: reqwest::Client,
     host: &'a str,
+    hash: PhantomData<H>,
 }
 
-impl<'a> Client<'a> {
+impl<'a, H> Client<'a, H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
     /// Creates a new client object. This will fail if the host does not start with `http://` or
     /// `https://`
     pub fn new(host: &'a str) -> Result<Self, ClientError> {
@@ -39,12 +68,13 @@ impl<'a> Client<'a> {
             Ok(Self {
                 client: reqwest::Client::new(),
                 host: host,
+                hash: PhantomData,
             })
         }
     }
 
     /// Receives the latest block from the web service.
-    pub fn latest_block(&self) -> Result<Block, ClientError> {
+    pub fn latest_block(&self) -> Result<Block<H>, ClientError> {
         self.client
             .get(&format!("{}{}", self.host, ROUTE_LATEST_BLOCK))
             .send()
@@ -52,9 +82,157 @@ impl<'a> Client<'a> {
             .map_err(|_| ClientError::LatestBlock)
     }
 
+    /// Receives the header of the latest block from the web service.
+    pub fn latest_header(&self) -> Result<Header<H>, ClientError> {
+        self.client
+            .get(&format!("{}{}", self.host, ROUTE_LATEST_HEADER))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::LatestHeader)
+    }
+
+    /// Receives the header of the latest block like [`latest_header`](#method.latest_header), but
+    /// returns `None` if the chain of the web service is empty.
+    pub fn head(&self) -> Result<Option<Header<H>>, ClientError> {
+        let mut response = self.client
+            .get(&format!("{}{}", self.host, ROUTE_LATEST_HEADER))
+            .send()
+            .map_err(|_| ClientError::LatestHeader)?;
+        if response.status() == StatusCode::Conflict {
+            // the web service responds with `409 Conflict` if its chain is empty
+            Ok(None)
+        } else {
+            response
+                .json()
+                .map(Some)
+                .map_err(|_| ClientError::LatestHeader)
+        }
+    }
+
+    /// Receives the headers from height `start` up to the latest block, oldest first. The genesis
+    /// block has height `0`.
+    pub fn headers(&self, start: usize) -> Result<Vec<Header<H>>, ClientError> {
+        self.client
+            .get(&format!("{}{}/{}", self.host, ROUTE_HEADERS, start))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Headers)
+    }
+
+    /// Receives the transactions of the block at the given height. The transactions are not
+    /// validated, use [`LightClient::block`](light/struct.LightClient.html#method.block) to check
+    /// them against the block's header.
+    pub fn body(&self, height: usize) -> Result<Vec<BlockData>, ClientError> {
+        self.client
+            .get(&format!("{}{}/{}", self.host, ROUTE_BODY, height))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Body)
+    }
+
+    /// Receives the block at the given height. The genesis block has height `0`.
+    pub fn block_by_height(&self, height: usize) -> Result<Block<H>, ClientError> {
+        self.client
+            .get(&format!("{}{}/{}", self.host, ROUTE_BLOCK_BY_HEIGHT, height))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Block)
+    }
+
+    /// Receives the block with the given hash.
+    pub fn block_by_hash(&self, hash: &[u8]) -> Result<Block<H>, ClientError> {
+        self.client
+            .get(&format!("{}{}/{}", self.host, ROUTE_BLOCK_BY_HASH, encode_hex(hash)))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Block)
+    }
+
+    /// Receives the height of the block with the given hash.
+    pub fn height_of(&self, hash: &[u8]) -> Result<usize, ClientError> {
+        self.client
+            .get(&format!("{}{}/{}", self.host, ROUTE_HEIGHT_OF, encode_hex(hash)))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Height)
+    }
+
+    /// Receives the difficulty required for the next block from the web service. Returns
+    /// `ClientError::UnknownRoute` if the web service does not provide the route.
+    pub fn difficulty(&self) -> Result<usize, ClientError> {
+        let mut response = self.client
+            .get(&format!("{}{}", self.host, ROUTE_DIFFICULTY))
+            .send()
+            .map_err(|_| ClientError::Difficulty)?;
+        if response.status() == StatusCode::NotFound {
+            Err(ClientError::UnknownRoute)
+        } else {
+            response.json().map_err(|_| ClientError::Difficulty)
+        }
+    }
+
+    /// Receives the highest difficulty target of the next block from the web service. Returns
+    /// `ClientError::UnknownRoute` if the web service does not provide the route.
+    pub fn target(&self) -> Result<Target, ClientError> {
+        let mut response = self.client
+            .get(&format!("{}{}", self.host, ROUTE_TARGET))
+            .send()
+            .map_err(|_| ClientError::Difficulty)?;
+        if response.status() == StatusCode::NotFound {
+            // web services of earlier releases only report the number of leading zero bits
+            Err(ClientError::UnknownRoute)
+        } else {
+            response.json().map_err(|_| ClientError::Difficulty)
+        }
+    }
+
+    /// Receives the block version required for the next block from the web service. Returns
+    /// `ClientError::UnknownRoute` if the web service does not provide the route.
+    pub fn version(&self) -> Result<u8, ClientError> {
+        let mut response = self.client
+            .get(&format!("{}{}", self.host, ROUTE_VERSION))
+            .send()
+            .map_err(|_| ClientError::Version)?;
+        if response.status() == StatusCode::NotFound {
+            // web services of earlier releases do not report versions
+            Err(ClientError::UnknownRoute)
+        } else {
+            response.json().map_err(|_| ClientError::Version)
+        }
+    }
+
+    /// Receives the accepted block versions and their activation heights from the web service.
+    pub fn versions(&self) -> Result<VersionSchedule, ClientError> {
+        self.client
+            .get(&format!("{}{}", self.host, ROUTE_VERSIONS))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Version)
+    }
+
+    /// Receives the spec of the chain, containing the chain id, the genesis block and the consensus
+    /// rules, from the web service.
+    pub fn spec(&self) -> Result<ChainSpec<H>, ClientError> {
+        self.client
+            .get(&format!("{}{}", self.host, ROUTE_SPEC))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Spec)
+    }
+
+    /// Receives the hash algorithm of the chain from the web service. The algorithm does not
+    /// depend on `H`, so any client can be used to find out, which client to create.
+    pub fn hash_algorithm(&self) -> Result<HashAlgorithm, ClientError> {
+        self.client
+            .get(&format!("{}{}", self.host, ROUTE_HASH))
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|_| ClientError::Hash)
+    }
+
     /// Appends a new block to the blockchain. If appending fails because the PoW could not be
     /// validated, this will return an error.
-    pub fn append(&self, block: &Block) -> Result<(), ClientError> {
+    pub fn append(&self, block: &Block<H>) -> Result<(), ClientError> {
         self.client
             .post(&format!("{}{}", self.host, ROUTE_APPEND))
             .json(block)
@@ -74,7 +252,7 @@ impl<'a> Client<'a> {
     pub fn since_last_billing(
         &self,
         query: &BillingQuery,
-    ) -> Result<Option<Blockchain>, ClientError> {
+    ) -> Result<Option<Blockchain<H>>, ClientError> {
         self.client
             .post(&format!("{}{}", self.host, ROUTE_LATEST_BILLING))
             .json(query)
@@ -82,4 +260,25 @@ impl<'a> Client<'a> {
             .and_then(|mut resp| resp.json())
             .map_err(|_| ClientError::SinceLastBilling)
     }
+
+    /// Receives a proof, that the transaction is part of the blockchain. The proof is relative to
+    /// the head of the chain at the time of the request and can be checked using
+    /// `data::proof::verify_inclusion`. If the transaction is not part of the chain, this will
+    /// result in an error.
+    pub fn inclusion_proof(
+        &self,
+        transaction: &BlockData,
+    ) -> Result<InclusionProof<H>, ClientError> {
+        self.client
+            .post(&format!("{}{}", self.host, ROUTE_INCLUSION_PROOF))
+            .json(transaction)
+            .send()
+            .and_then(|mut resp| resp.json())
+            .map_err(|_| ClientError::InclusionProof)
+    }
+}
+
+/// Encodes a hash as lower case hex string, as expected by the web service.
+fn encode_hex(hash: &[u8]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
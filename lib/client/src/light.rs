@@ -0,0 +1,370 @@
+//! This module contains a light client, that only synchronizes and validates the headers of the
+//! remote blockchain. The transactions of a block are fetched on demand and checked against the
+//! Merkle root of the block's header, so the client does not need to store or download the whole
+//! chain.
+//!
+//! If the remote chain reorganizes to another branch, the client finds the latest header, that
+//! both branches have in common, and switches to the remote branch, if it has more cumulative
+//! work than the synchronized headers since that header. Like in the block tree of the
+//! `blockchain` crate, a branch with less or equal work does not replace the synchronized headers.
+
+use std::fmt::Debug;
+
+use data::{Block, Header, HeaderChain, Rules, Sha256};
+use data::hash::HashFunction;
+use data::pow::PowAlgorithm;
+use data::version::VersionSchedule;
+use data::work::Work;
+
+use error::ClientError;
+use Client;
+
+/// Route of the web service, that is needed to synchronize headers.
+pub trait HeaderSource<H>
+where
+    H: HashFunction,
+{
+    /// Receives the headers from height `start` up to the latest block, oldest first.
+    fn headers(&self, start: usize) -> Result<Vec<Header<H>>, ClientError>;
+}
+
+impl<'a, H> HeaderSource<H> for Client<'a, H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    fn headers(&self, start: usize) -> Result<Vec<Header<H>>, ClientError> {
+        Client::headers(self, start)
+    }
+}
+
+/// Changes of the synchronized headers, that are caused by a synchronization.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Synced {
+    disconnected: usize,
+    connected: usize,
+}
+
+impl Synced {
+    /// Returns the number of headers, that have been removed because the remote chain switched
+    /// to another branch.
+    pub fn disconnected(&self) -> usize {
+        self.disconnected
+    }
+
+    /// Returns the number of headers, that have been added.
+    pub fn connected(&self) -> usize {
+        self.connected
+    }
+
+    /// Returns true if the headers have only been extended and no headers were disconnected.
+    pub fn is_extension(&self) -> bool {
+        self.disconnected == 0
+    }
+}
+
+/// Light client containing the validated headers of the remote chain.
+pub struct LightClient<'a, H = Sha256>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    client: Client<'a, H>,
+    headers: HeaderChain<H>,
+    rules: Rules,
+}
+
+impl<'a, H> LightClient<'a, H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    /// Creates a new light client without any headers. Headers are validated using the consensus
+    /// rules of the specific blockchain (see `data::rules`).
+    pub fn new(client: Client<'a, H>) -> Self {
+        Self {
+            client: client,
+            headers: HeaderChain::new(),
+            rules: ::data::rules(),
+        }
+    }
+
+    /// Validates headers against the given version schedule instead of requiring the current
+    /// block version for every header, e.g. using the schedule received from
+    /// [`Client::versions`](../struct.Client.html#method.versions). The method returns a new
+    /// light client and consumes the old one.
+    pub fn with_versions(self, versions: VersionSchedule) -> Self {
+        Self {
+            rules: self.rules.versions(versions),
+            ..self
+        }
+    }
+
+    /// Validates headers against the given proof of work function instead of the function of the
+    /// specific blockchain, e.g. using the function of the rules received using
+    /// [`Client::spec`](../struct.Client.html#method.spec). The method returns a new light client
+    /// and consumes the old one.
+    pub fn with_pow(self, pow: PowAlgorithm) -> Self {
+        Self {
+            rules: self.rules.pow(pow),
+            ..self
+        }
+    }
+
+    /// Receives all headers, that are newer than the latest known header, and validates them
+    /// against the consensus rules. If the remote chain has switched to a branch with more work,
+    /// the client switches as well (see [`synchronize`](fn.synchronize.html)). If a header is
+    /// invalid, an error is returned and none of the new headers are stored.
+    pub fn sync(&mut self) -> Result<Synced, ClientError> {
+        let (headers, synced) = synchronize(&self.client, &self.headers, &self.rules)?;
+        self.headers = headers;
+        Ok(synced)
+    }
+
+    /// Receives the body of the block at the given height and checks it against the Merkle root
+    /// of the synchronized header. The genesis block has height `0`.
+    pub fn block(&self, height: usize) -> Result<Block<H>, ClientError> {
+        let transactions = self.client.body(height)?;
+        self.headers
+            .assemble_block(height, transactions)
+            .map_err(ClientError::InvalidBody)
+    }
+
+    /// Returns the latest synchronized header.
+    pub fn head(&self) -> Option<&Header<H>> {
+        self.headers.head()
+    }
+
+    /// Returns the number of synchronized headers.
+    pub fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Checks if no headers have been synchronized yet.
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Returns the synchronized headers.
+    pub fn headers(&self) -> &HeaderChain<H> {
+        &self.headers
+    }
+
+    /// Returns the underlying client, e.g. to append blocks.
+    pub fn client(&self) -> &Client<'a, H> {
+        &self.client
+    }
+}
+
+/// Synchronizes the headers with the remote chain and returns the new headers. The received
+/// headers overlap the synchronized headers by one header, so a remote chain, that has switched
+/// to another branch, is detected. In that case the overlap is doubled until the latest common
+/// header is found. The remote branch since that header replaces the synchronized branch, if it
+/// has more cumulative work, otherwise the synchronized headers are kept.
+pub fn synchronize<R, H>(
+    remote: &R,
+    headers: &HeaderChain<H>,
+    rules: &Rules,
+) -> Result<(HeaderChain<H>, Synced), ClientError>
+where
+    R: HeaderSource<H>,
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    let mut start = headers.len().saturating_sub(1);
+    let mut overlap = 1;
+    loop {
+        let received = remote.headers(start)?;
+        let fork = received
+            .iter()
+            .zip(start..)
+            .find(|&(header, height)| headers.get(height) != Some(header))
+            .map_or(start + received.len(), |(_, height)| height);
+        if fork > start || start == 0 {
+            let branch = received.into_iter().skip(fork - start).collect();
+            return switch(headers, fork, branch, rules);
+        }
+        overlap *= 2;
+        start = start.saturating_sub(overlap);
+    }
+}
+
+/// Replaces the headers from height `fork` on with the branch, if the branch has more cumulative
+/// work.
+fn switch<H>(
+    headers: &HeaderChain<H>,
+    fork: usize,
+    branch: Vec<Header<H>>,
+    rules: &Rules,
+) -> Result<(HeaderChain<H>, Synced), ClientError>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    let mut base = headers.clone();
+    let mut old_work = Work::default();
+    while base.len() > fork {
+        let (head, parent) = base.tail();
+        let head = head.expect("the chain is not empty");
+        old_work = old_work.add(&head.work().map_err(ClientError::InvalidHeader)?);
+        base = parent;
+    }
+    let new_work = branch
+        .iter()
+        .fold(Ok(Work::default()), |acc, header| {
+            acc.and_then(|work| header.work().map(|header_work| work.add(&header_work)))
+        })
+        .map_err(ClientError::InvalidHeader)?;
+    let connected = branch.len();
+    let switched = branch
+        .into_iter()
+        .fold(Ok(base), |acc, header| {
+            acc.and_then(|chain| chain.insert_with_rules(header, rules))
+        })
+        .map_err(ClientError::InvalidHeader)?;
+    if new_work > old_work {
+        let synced = Synced {
+            disconnected: headers.len() - fork,
+            connected: connected,
+        };
+        Ok((switched, synced))
+    } else {
+        let synced = Synced {
+            disconnected: 0,
+            connected: 0,
+        };
+        Ok((headers.clone(), synced))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use data::{Block, Sha256, DIFFICULTY, GENESIS_TIME};
+    use data::clock::FixedClock;
+    use data::hash::Output;
+
+    use super::*;
+
+    /// Remote chain in memory, that can switch to another branch.
+    struct Stub {
+        headers: RefCell<Vec<Header<Sha256>>>,
+    }
+
+    impl Stub {
+        fn new() -> Self {
+            Self {
+                headers: RefCell::new(Vec::new()),
+            }
+        }
+
+        /// Replaces the headers from height `fork` on with `count` new headers of the given
+        /// difficulty.
+        fn branch(&self, fork: usize, count: usize, difficulty: usize) {
+            let mut headers = self.headers.borrow_mut();
+            headers.truncate(fork);
+            for _ in 0..count {
+                let prev_hash: Output<Sha256> = headers
+                    .last()
+                    .map(|header| header.hash())
+                    .unwrap_or_default();
+                let clock = FixedClock(GENESIS_TIME + 60 * headers.len() as u64);
+                // the difficulty tells the branches apart
+                let block: Block =
+                    Block::new_with_clock(vec![], prev_hash, difficulty, &clock)
+                        .proof_of_work_with_clock(&clock);
+                headers.push(block.header().clone());
+            }
+        }
+
+        fn head(&self) -> Option<Header<Sha256>> {
+            self.headers.borrow().last().cloned()
+        }
+    }
+
+    impl HeaderSource<Sha256> for Stub {
+        fn headers(&self, start: usize) -> Result<Vec<Header<Sha256>>, ClientError> {
+            Ok(self.headers.borrow().iter().skip(start).cloned().collect())
+        }
+    }
+
+    fn sync(remote: &Stub, headers: &HeaderChain) -> (HeaderChain, Synced) {
+        synchronize(remote, headers, &::data::rules()).unwrap()
+    }
+
+    fn synced(disconnected: usize, connected: usize) -> Synced {
+        Synced {
+            disconnected: disconnected,
+            connected: connected,
+        }
+    }
+
+    #[test]
+    fn headers_are_extended() {
+        let remote = Stub::new();
+        remote.branch(0, 3, DIFFICULTY);
+        let (headers, result) = sync(&remote, &HeaderChain::new());
+        assert_eq!(result, synced(0, 3));
+        remote.branch(3, 2, DIFFICULTY);
+        let (headers, result) = sync(&remote, &headers);
+        assert_eq!(result, synced(0, 2));
+        assert_eq!(headers.head().cloned(), remote.head());
+        let (headers, result) = sync(&remote, &headers);
+        assert_eq!(result, synced(0, 0));
+        assert_eq!(headers.len(), 5);
+    }
+
+    #[test]
+    fn heavier_branch_replaces_headers() {
+        let remote = Stub::new();
+        remote.branch(0, 8, DIFFICULTY);
+        let (headers, _) = sync(&remote, &HeaderChain::new());
+        // the fork is deeper than the overlap of the first request
+        remote.branch(3, 6, DIFFICULTY + 1);
+        let (headers, result) = sync(&remote, &headers);
+        assert!(!result.is_extension());
+        assert_eq!(result, synced(5, 6));
+        assert_eq!(headers.len(), 9);
+        assert_eq!(headers.head().cloned(), remote.head());
+        // a shorter branch with more work replaces the headers as well
+        remote.branch(7, 1, DIFFICULTY + 8);
+        let (headers, result) = sync(&remote, &headers);
+        assert_eq!(result, synced(2, 1));
+        assert_eq!(headers.len(), 8);
+        assert_eq!(headers.head().cloned(), remote.head());
+    }
+
+    #[test]
+    fn lighter_branch_is_ignored() {
+        let remote = Stub::new();
+        remote.branch(0, 5, DIFFICULTY + 1);
+        let (headers, _) = sync(&remote, &HeaderChain::new());
+        let head = headers.head().cloned();
+        remote.branch(2, 4, DIFFICULTY);
+        let (headers, result) = sync(&remote, &headers);
+        assert_eq!(result, synced(0, 0));
+        assert_eq!(headers.head().cloned(), head);
+        // another genesis block
+        remote.branch(0, 4, DIFFICULTY);
+        let (headers, result) = sync(&remote, &headers);
+        assert_eq!(result, synced(0, 0));
+        assert_eq!(headers.head().cloned(), head);
+    }
+
+    #[test]
+    fn invalid_branch_is_rejected() {
+        let remote = Stub::new();
+        remote.branch(0, 4, DIFFICULTY);
+        let (headers, _) = sync(&remote, &HeaderChain::new());
+        remote.branch(2, 3, DIFFICULTY + 1);
+        // the time of the header is earlier than the time of its parent
+        let header = remote.headers.borrow()[3].clone();
+        let nonce = header.nonce();
+        remote.headers.borrow_mut()[3] = header.set_nonce(nonce, 0);
+        match synchronize(&remote, &headers, &::data::rules()) {
+            Err(ClientError::InvalidHeader(_)) => {}
+            _ => panic!("the branch contains an invalid header"),
+        }
+    }
+}
@@ -0,0 +1,542 @@
+//! This module contains a mining loop that keeps track of the head of the remote blockchain. If
+//! another block is appended while mining, the current attempt is aborted and mining restarts on
+//! top of the new head.
+//!
+//! The loop only needs a few routes of the web service, that are abstracted by the
+//! [`Remote`](trait.Remote.html) trait, so it can also be driven by other implementations than
+//! the HTTP [`Client`](../struct.Client.html).
+
+use std::cmp;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::Duration;
+
+use data::{Block, Header};
+use data::block::VERSION;
+use data::clock::{Clock, SystemClock};
+use data::hash::{HashFunction, Output};
+use data::header::skip_height;
+use data::miner::{CancellationToken, Miner, Progress};
+use data::pow::PowAlgorithm;
+use data::target::Target;
+use data::tx::BlockData;
+use data::version::SKIP_VERSION;
+
+use error::ClientError;
+use Client;
+
+/// Default number of attempts after the first one, before giving up.
+const DEFAULT_MAX_RETRIES: usize = 5;
+/// Default interval in which the head of the chain is polled.
+const DEFAULT_POLL_INTERVAL_MS: u64 = 1000;
+
+/// Configuration of the mining loop.
+#[derive(Debug, Clone)]
+pub struct MiningConfig {
+    threads: usize,
+    poll_interval: Duration,
+    max_retries: usize,
+    clock: Arc<Clock>,
+    pow: PowAlgorithm,
+}
+
+impl MiningConfig {
+    /// Creates a new configuration using the given number of mining threads and default values
+    /// for the poll interval and the retry limit.
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads,
+            poll_interval: Duration::from_millis(DEFAULT_POLL_INTERVAL_MS),
+            max_retries: DEFAULT_MAX_RETRIES,
+            clock: Arc::new(SystemClock),
+            pow: PowAlgorithm::Hash,
+        }
+    }
+
+    /// Sets the interval in which the head of the chain is polled while mining.
+    pub fn poll_interval(self, interval: Duration) -> Self {
+        Self {
+            poll_interval: interval,
+            ..self
+        }
+    }
+
+    /// Sets the number of times mining is restarted after the head of the chain moved.
+    pub fn max_retries(self, retries: usize) -> Self {
+        Self {
+            max_retries: retries,
+            ..self
+        }
+    }
+
+    /// Sets the clock, that supplies the time of mined blocks. Defaults to the system time.
+    pub fn clock<C>(self, clock: C) -> Self
+    where
+        C: Clock + 'static,
+    {
+        Self {
+            clock: Arc::new(clock),
+            ..self
+        }
+    }
+
+    /// Sets the proof of work function, blocks are mined with. This must be the function of the
+    /// chain's consensus rules, e.g. as received using
+    /// [`Client::spec`](../struct.Client.html#method.spec). Defaults to the leading zero proof of
+    /// work over the header hash.
+    pub fn pow(self, pow: PowAlgorithm) -> Self {
+        Self {
+            pow: pow,
+            ..self
+        }
+    }
+}
+
+impl Default for MiningConfig {
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Routes of the web service, that are needed to mine blocks on top of the remote chain.
+pub trait Remote<H>
+where
+    H: HashFunction,
+{
+    /// Receives the header of the latest block or `None`, if the remote chain is empty.
+    fn head(&self) -> Result<Option<Header<H>>, ClientError>;
+
+    /// Receives the highest difficulty target of the next block. Returns
+    /// `ClientError::UnknownRoute` if the remote chain does not provide the target.
+    fn target(&self) -> Result<Target, ClientError>;
+
+    /// Receives the difficulty of the next block as the number of leading zero bits. Returns
+    /// `ClientError::UnknownRoute` if the remote chain does not provide the difficulty.
+    fn difficulty(&self) -> Result<usize, ClientError>;
+
+    /// Receives the block version required for the next block. Returns
+    /// `ClientError::UnknownRoute` if the remote chain does not provide the version.
+    fn version(&self) -> Result<u8, ClientError>;
+
+    /// Receives the header of the block at the given height or `None`, if the remote chain is not
+    /// that long.
+    fn header(&self, height: usize) -> Result<Option<Header<H>>, ClientError>;
+
+    /// Receives the height of the block with the given hash.
+    fn height_of(&self, hash: &[u8]) -> Result<usize, ClientError>;
+
+    /// Appends a block to the remote chain.
+    fn append(&self, block: &Block<H>) -> Result<(), ClientError>;
+}
+
+impl<'a, H> Remote<H> for Client<'a, H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+{
+    fn head(&self) -> Result<Option<Header<H>>, ClientError> {
+        Client::head(self)
+    }
+
+    fn target(&self) -> Result<Target, ClientError> {
+        Client::target(self)
+    }
+
+    fn difficulty(&self) -> Result<usize, ClientError> {
+        Client::difficulty(self)
+    }
+
+    fn version(&self) -> Result<u8, ClientError> {
+        Client::version(self)
+    }
+
+    fn header(&self, height: usize) -> Result<Option<Header<H>>, ClientError> {
+        Client::headers(self, height).map(|headers| headers.into_iter().next())
+    }
+
+    fn height_of(&self, hash: &[u8]) -> Result<usize, ClientError> {
+        Client::height_of(self, hash)
+    }
+
+    fn append(&self, block: &Block<H>) -> Result<(), ClientError> {
+        Client::append(self, block)
+    }
+}
+
+impl<'a, H> Client<'a, H>
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send,
+{
+    /// Mines a block containing `transactions` on top of the current head of the chain and appends
+    /// it (see [`mine_and_append`](mining/fn.mine_and_append.html)).
+    pub fn mine_and_append<F>(
+        &self,
+        transactions: Vec<BlockData>,
+        difficulty: usize,
+        config: &MiningConfig,
+        progress: F,
+    ) -> Result<Block<H>, ClientError>
+    where
+        F: FnMut(&Progress),
+    {
+        mine_and_append(self, transactions, difficulty, config, progress)
+    }
+}
+
+/// Mines a block containing `transactions` on top of the current head of the remote chain and
+/// appends it. The block is mined using at least `difficulty` or the target required by the remote
+/// chain, if it is lower. The head is polled while mining. If it changes, mining is aborted and
+/// restarted using the new head as `prev_hash`. If appending fails because the head moved in the
+/// meantime, the block is mined again. After `max_retries` restarts,
+/// `ClientError::RetriesExhausted` is returned. If the head, the required target or the required
+/// version cannot be received before mining, the error is returned instead of mining a block, that
+/// the remote chain does not accept. Only if the web service does not provide a route
+/// (`ClientError::UnknownRoute`), e.g. because it is an earlier release, the number of leading zero
+/// bits, `difficulty` and the current block version are used instead. Blocks of version `4` and
+/// later link to the ancestor at `skip_height`, whose header is received before mining.
+pub fn mine_and_append<R, H, F>(
+    remote: &R,
+    transactions: Vec<BlockData>,
+    difficulty: usize,
+    config: &MiningConfig,
+    mut progress: F,
+) -> Result<Block<H>, ClientError>
+where
+    R: Remote<H>,
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send,
+    F: FnMut(&Progress),
+{
+    let miner = Miner::new(config.threads)
+        .report_interval(config.poll_interval)
+        .clock(Arc::clone(&config.clock));
+    for _ in 0..config.max_retries + 1 {
+        let prev_hash = head_hash(remote)?;
+        // web services without the target route only report the number of leading zero bits
+        let required = or_unknown_route(remote.target(), || {
+            or_unknown_route(remote.difficulty(), || Ok(difficulty)).map(Target::from_difficulty)
+        })?;
+        let target = cmp::min(Target::from_difficulty(difficulty), required);
+        let version = or_unknown_route(remote.version(), || Ok(VERSION))?;
+        let skip_hash = skip_hash(remote, &prev_hash, version)?;
+        let block: Block<H> = Block::new_with_clock(
+            transactions.clone(),
+            prev_hash.clone(),
+            difficulty,
+            &*config.clock,
+        ).set_version(version)
+            .set_skip_hash(skip_hash)
+            .set_target(target);
+        let token = CancellationToken::new();
+        let mined = miner.mine_with_pow(block, &config.pow, &token, |p| {
+            progress(p);
+            if head_moved(remote, &prev_hash) {
+                token.cancel();
+            }
+        }).map_err(ClientError::Mining)?;
+        if let Some(block) = mined {
+            match remote.append(&block) {
+                Ok(()) => return Ok(block),
+                Err(err) => {
+                    if !head_moved(remote, &prev_hash) {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+    Err(ClientError::RetriesExhausted(config.max_retries))
+}
+
+/// Returns the hash of the head of the remote chain. The hash of an empty chain is all `0x00`, so
+/// the mined block becomes the genesis block.
+fn head_hash<R, H>(remote: &R) -> Result<Output<H>, ClientError>
+where
+    R: Remote<H>,
+    H: HashFunction,
+{
+    remote
+        .head()
+        .map(|head| head.map(|header| header.hash()).unwrap_or_default())
+}
+
+/// Returns the received value or the result of `fallback`, if the web service does not provide
+/// the route. Other errors are returned.
+fn or_unknown_route<T, F>(received: Result<T, ClientError>, fallback: F) -> Result<T, ClientError>
+where
+    F: FnOnce() -> Result<T, ClientError>,
+{
+    match received {
+        Err(ClientError::UnknownRoute) => fallback(),
+        received => received,
+    }
+}
+
+/// Returns the hash of the ancestor at `skip_height`, that a block on top of the head with the
+/// given hash links to. Blocks before version `4` do not contain a skip hash, so the remote chain
+/// is only queried for later versions.
+fn skip_hash<R, H>(remote: &R, prev_hash: &Output<H>, version: u8) -> Result<Output<H>, ClientError>
+where
+    R: Remote<H>,
+    H: HashFunction,
+{
+    if version < SKIP_VERSION {
+        return Ok(Default::default());
+    }
+    let len = if prev_hash == &Output::<H>::default() {
+        0
+    } else {
+        remote.height_of(prev_hash)? + 1
+    };
+    remote
+        .header(skip_height(len))
+        .map(|header| header.map(|header| header.hash()).unwrap_or_default())
+}
+
+/// Returns true if the head of the remote chain is no longer the block with the given hash. Only
+/// the header of the head is received. Errors while receiving the head are not treated as a
+/// change.
+fn head_moved<R, H>(remote: &R, prev_hash: &[u8]) -> bool
+where
+    R: Remote<H>,
+    H: HashFunction,
+{
+    head_hash(remote)
+        .map(|hash| &hash[..] != prev_hash)
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+
+    use data::Sha256;
+
+    use super::*;
+
+    /// Remote chain in memory, that simulates other miners appending blocks.
+    struct Stub {
+        headers: RefCell<Vec<Header<Sha256>>>,
+        polls: Cell<usize>,
+        attempts: Cell<usize>,
+        // polls of the head, before which another miner appends a block
+        moves_at: Vec<usize>,
+        // another miner appends a block right before each block, that is appended by the client
+        race: bool,
+        reject: bool,
+        unreachable: bool,
+        // number of attempts, that use a target, which cannot be met
+        hard_attempts: usize,
+        version: u8,
+        // web service of an earlier release, that provides neither the target nor the version
+        legacy: bool,
+        // receiving the target fails
+        broken: bool,
+    }
+
+    impl Stub {
+        fn new() -> Self {
+            Self {
+                headers: RefCell::new(vec![
+                    Header::new(Default::default(), Default::default(), 0, 0),
+                ]),
+                polls: Cell::new(0),
+                attempts: Cell::new(0),
+                moves_at: Vec::new(),
+                race: false,
+                reject: false,
+                unreachable: false,
+                hard_attempts: 0,
+                version: VERSION,
+                legacy: false,
+                broken: false,
+            }
+        }
+
+        fn head_hash(&self) -> Output<Sha256> {
+            self.headers.borrow().last().unwrap().hash()
+        }
+
+        fn append_other(&self) {
+            let header = Header::new(self.head_hash(), Default::default(), 0, 0);
+            self.headers.borrow_mut().push(header);
+        }
+    }
+
+    impl Remote<Sha256> for Stub {
+        fn head(&self) -> Result<Option<Header<Sha256>>, ClientError> {
+            if self.unreachable {
+                return Err(ClientError::LatestHeader);
+            }
+            self.polls.set(self.polls.get() + 1);
+            if self.moves_at.contains(&self.polls.get()) {
+                self.append_other();
+            }
+            Ok(self.headers.borrow().last().cloned())
+        }
+
+        fn target(&self) -> Result<Target, ClientError> {
+            if self.broken {
+                return Err(ClientError::Difficulty);
+            }
+            self.attempts.set(self.attempts.get() + 1);
+            if self.legacy {
+                return Err(ClientError::UnknownRoute);
+            }
+            if self.attempts.get() <= self.hard_attempts {
+                Ok(Target::from_difficulty(64))
+            } else {
+                Ok(Target::from_difficulty(0))
+            }
+        }
+
+        fn difficulty(&self) -> Result<usize, ClientError> {
+            Ok(0)
+        }
+
+        fn version(&self) -> Result<u8, ClientError> {
+            if self.legacy {
+                Err(ClientError::UnknownRoute)
+            } else {
+                Ok(self.version)
+            }
+        }
+
+        fn header(&self, height: usize) -> Result<Option<Header<Sha256>>, ClientError> {
+            Ok(self.headers.borrow().get(height).cloned())
+        }
+
+        fn height_of(&self, hash: &[u8]) -> Result<usize, ClientError> {
+            self.headers
+                .borrow()
+                .iter()
+                .position(|header| &header.hash()[..] == hash)
+                .ok_or(ClientError::Height)
+        }
+
+        fn append(&self, block: &Block<Sha256>) -> Result<(), ClientError> {
+            if self.race {
+                self.append_other();
+            }
+            if self.reject || block.prev_hash() != &self.head_hash() {
+                return Err(ClientError::AppendBlock);
+            }
+            self.headers.borrow_mut().push(block.header().clone());
+            Ok(())
+        }
+    }
+
+    fn config() -> MiningConfig {
+        MiningConfig::new(1).poll_interval(Duration::from_millis(10))
+    }
+
+    #[test]
+    fn block_is_mined_on_top_of_the_head() {
+        let stub = Stub::new();
+        let genesis = stub.head_hash();
+        let block = mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()).unwrap();
+        assert_eq!(block.prev_hash(), &genesis);
+        assert_eq!(stub.headers.borrow().len(), 2);
+        assert_eq!(stub.attempts.get(), 1);
+    }
+
+    #[test]
+    fn skip_hash_links_to_the_ancestor() {
+        let stub = Stub {
+            version: SKIP_VERSION,
+            ..Stub::new()
+        };
+        for _ in 1..14 {
+            stub.append_other();
+        }
+        let block = mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()).unwrap();
+        assert_eq!(block.version(), SKIP_VERSION);
+        assert_eq!(block.header().skip_hash(), &stub.headers.borrow()[7].hash());
+        // blocks of earlier versions do not link to an ancestor
+        let block = mine_and_append(&Stub::new(), Vec::new(), 0, &config(), |_| ()).unwrap();
+        assert_eq!(block.header().skip_hash(), &Output::<Sha256>::default());
+    }
+
+    #[test]
+    fn unknown_routes_fall_back_to_the_difficulty() {
+        let stub = Stub {
+            legacy: true,
+            ..Stub::new()
+        };
+        let block = mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()).unwrap();
+        assert_eq!(block.version(), VERSION);
+        assert_eq!(stub.headers.borrow().len(), 2);
+    }
+
+    #[test]
+    fn unavailable_target_is_not_ignored() {
+        let stub = Stub {
+            broken: true,
+            ..Stub::new()
+        };
+        match mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()) {
+            Err(ClientError::Difficulty) => {}
+            _ => panic!("the target cannot be received"),
+        }
+        assert_eq!(stub.headers.borrow().len(), 1);
+    }
+
+    #[test]
+    fn unreachable_head_is_not_mined_as_genesis() {
+        let stub = Stub {
+            unreachable: true,
+            ..Stub::new()
+        };
+        match mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()) {
+            Err(ClientError::LatestHeader) => {}
+            _ => panic!("the head cannot be received"),
+        }
+        assert_eq!(stub.attempts.get(), 0);
+        assert_eq!(stub.headers.borrow().len(), 1);
+    }
+
+    #[test]
+    fn mining_restarts_when_head_moves() {
+        // the first attempt cannot succeed and is cancelled once the head moves while mining
+        let stub = Stub {
+            moves_at: vec![2],
+            hard_attempts: 1,
+            ..Stub::new()
+        };
+        let block = mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()).unwrap();
+        assert_eq!(stub.attempts.get(), 2);
+        assert_eq!(stub.headers.borrow().len(), 3);
+        assert_eq!(block.prev_hash(), &stub.headers.borrow()[1].hash());
+    }
+
+    #[test]
+    fn retries_are_exhausted() {
+        let stub = Stub {
+            race: true,
+            ..Stub::new()
+        };
+        let config = config().max_retries(2);
+        match mine_and_append(&stub, Vec::new(), 0, &config, |_| ()) {
+            Err(ClientError::RetriesExhausted(2)) => {}
+            _ => panic!("the head moves before every append"),
+        }
+        assert_eq!(stub.attempts.get(), 3);
+        assert_eq!(stub.headers.borrow().len(), 4);
+    }
+
+    #[test]
+    fn rejected_block_is_not_mined_again() {
+        let stub = Stub {
+            reject: true,
+            ..Stub::new()
+        };
+        match mine_and_append(&stub, Vec::new(), 0, &config(), |_| ()) {
+            Err(ClientError::AppendBlock) => {}
+            _ => panic!("the block is rejected"),
+        }
+        assert_eq!(stub.attempts.get(), 1);
+    }
+}
@@ -0,0 +1,126 @@
+//! This module contains the proof of authority sealing scheme. A block is sealed if it has been
+//! signed by one of a configured set of validators. This is an alternative to proof of work for
+//! chains that are operated by a small set of known parties.
+
+use data::{AuthorityBlock, AuthorityHeader, BlockchainError, Sha256};
+use data::authority::AuthoritySeal;
+use data::consensus::ConsensusRules;
+use data::seal::SealingScheme;
+use data::target::Target;
+
+use {KeyPair, PublicKey};
+
+/// Proof of authority consensus rules containing the public keys of all validators.
+#[derive(Clone)]
+pub struct ProofOfAuthority {
+    validators: Vec<PublicKey>,
+}
+
+impl ProofOfAuthority {
+    /// Creates a new rule set, that accepts blocks signed by any of the supplied validators.
+    pub fn new(validators: Vec<PublicKey>) -> Self {
+        Self {
+            validators: validators,
+        }
+    }
+
+    /// Returns the public keys of all validators.
+    pub fn validators(&self) -> &[PublicKey] {
+        &self.validators
+    }
+}
+
+impl SealingScheme<Sha256, AuthoritySeal> for ProofOfAuthority {
+    fn verify_seal(&self, header: &AuthorityHeader) -> Result<(), BlockchainError> {
+        let seal = header.seal();
+        if !self.validators
+            .iter()
+            .any(|validator| validator.bytes() == seal.signer())
+        {
+            return Err(BlockchainError::InvalidSeal(
+                "block has not been sealed by a validator".to_owned(),
+            ));
+        }
+        let hash = header.seal_hash();
+        ::ring::signature::verify(
+            &::ring::signature::ED25519,
+            ::untrusted::Input::from(seal.signer()),
+            ::untrusted::Input::from(&hash[..]),
+            ::untrusted::Input::from(seal.signature()),
+        ).map_err(|_| BlockchainError::InvalidSeal("invalid validator signature".to_owned()))
+    }
+}
+
+impl ConsensusRules<Sha256, AuthoritySeal> for ProofOfAuthority {
+    type Scheme = Self;
+
+    fn scheme(&self) -> &Self {
+        self
+    }
+
+    /// Proof of authority does not require any work, so every target is accepted.
+    fn required_target<'a, I>(&self, _parent: I) -> Target
+    where
+        I: Iterator<Item = &'a AuthorityHeader>,
+    {
+        Target::max_value()
+    }
+}
+
+/// Seals a block by signing its seal hash using the key pair of a validator.
+pub fn seal_block(key: &KeyPair, block: AuthorityBlock) -> AuthorityBlock {
+    let signature = key.0.read().sign(&block.seal_hash()).as_ref().to_vec();
+    let signer = key.public_key_bytes().bytes().to_vec();
+    block.set_seal(AuthoritySeal::new(signer, signature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use data::AuthorityBlockchain;
+
+    #[test]
+    fn validator_seals_are_accepted() {
+        let validator = KeyPair::generate();
+        let rules = ProofOfAuthority::new(vec![validator.public_key_bytes()]);
+        let chain = AuthorityBlockchain::new().with_scheme(rules.clone());
+        let block = seal_block(&validator, chain.generate_block(vec![], 0));
+        assert!(rules.verify_seal(block.header()).is_ok());
+        let chain = chain.insert(block).unwrap();
+        let block = seal_block(&validator, chain.generate_block(vec![], 0));
+        let chain = chain.insert(block).unwrap();
+        assert!(chain.validate_chain());
+        assert!(chain.validate_chain_with_rules(&rules));
+    }
+
+    #[test]
+    fn foreign_seals_are_rejected() {
+        let validator = KeyPair::generate();
+        let stranger = KeyPair::generate();
+        let rules = ProofOfAuthority::new(vec![validator.public_key_bytes()]);
+        let chain = AuthorityBlockchain::new().with_scheme(rules.clone());
+        let block = seal_block(&stranger, chain.generate_block(vec![], 0));
+        assert!(rules.verify_seal(block.header()).is_err());
+        assert!(chain.insert(block).is_err());
+    }
+
+    #[test]
+    fn tampered_seals_are_rejected() {
+        let validator = KeyPair::generate();
+        let rules = ProofOfAuthority::new(vec![validator.public_key_bytes()]);
+        let chain = AuthorityBlockchain::new().with_scheme(rules.clone());
+        let block = seal_block(&validator, chain.generate_block(vec![], 0));
+        let mut signature = block.header().seal().signature().to_vec();
+        signature[0] ^= 1;
+        let signer = block.header().seal().signer().to_vec();
+        let tampered = block.clone().set_seal(AuthoritySeal::new(signer, signature));
+        assert!(rules.verify_seal(tampered.header()).is_err());
+        assert!(chain.insert(tampered).is_err());
+        // a valid signature of another block does not seal this block
+        let other = seal_block(&validator, chain.generate_block(vec![], 1));
+        let moved = block.set_seal(other.header().seal().clone());
+        assert!(rules.verify_seal(moved.header()).is_err());
+        assert!(chain.insert(moved).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 #![deny(warnings, missing_docs)]
-//! This crate provides functions for dealing with key pairs, handling secrets, signing data and
-//! validating signatures
+//! This crate provides functions for dealing with key pairs, handling secrets, signing data,
+//! validating signatures and sealing blocks using proof of authority
 
 extern crate argon2rs;
 extern crate data;
@@ -12,6 +12,7 @@ extern crate rand;
 extern crate ring;
 extern crate rpassword;
 extern crate seckey;
+extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 extern crate serde_json;
@@ -22,6 +23,8 @@ extern crate untrusted;
 #[macro_use]
 extern crate quickcheck;
 
+pub mod authority;
+
 use openssl::symm;
 
 use failure::Error;
@@ -34,12 +37,18 @@ use ring::rand as ring_rand;
 use seckey::{zero, SecKey, SecReadGuard};
 use memsec::memzero;
 
+use serde::Serialize;
+
 use sha2::Digest;
 
-use data::tx::{Data, Fingerprint, Signable, SignedData, SIG_SIZE};
+use data::{Block, Blockchain, BlockchainError, ChainSpec};
+use data::hash::{HashFunction, Output};
+use data::report::{Scope, ValidationReport};
+use data::tx::{Data, Fingerprint, Signable, Signature, SignedData, SIG_SIZE};
 
 use std::env;
 use std::fmt;
+use std::fmt::Debug;
 use std::fs::{File, OpenOptions};
 use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
@@ -147,6 +156,15 @@ impl KeyPair {
         Ok(pair)
     }
 
+    #[cfg(test)]
+    /// Generates a new key pair in a secure memory area. (Only used for tests)
+    fn generate() -> Self {
+        let rng = ring_rand::SystemRandom::new();
+        let pkcs8_bytes = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(::untrusted::Input::from(&pkcs8_bytes)).unwrap();
+        KeyPair(SecKey::new(pair).map_err(|_| ()).unwrap())
+    }
+
     /// Returns the public key for a key pair.
     pub fn public_key_bytes(&self) -> PublicKey {
         PublicKey(self.0.read().public_key_bytes().to_vec())
@@ -254,34 +272,123 @@ pub fn get_password() -> Result<Password, KeyError> {
     Password::new(key)
 }
 
-/// Signs data using a `KeyPair`
-pub fn sign_data<S>(key: &KeyPair, data: S) -> Result<SignedData<S>, Error>
+/// Signs data for the chain with the given id using a `KeyPair`. The signature is only valid on
+/// that chain.
+pub fn sign_data<S>(key: &KeyPair, chain_id: &str, data: S) -> Result<SignedData<S>, Error>
 where
     S: Signable,
 {
-    let key = key.0.read();
-    let signature = key.sign(&data.get_bytes()?);
+    let signature = sign(key, &data.get_bytes(chain_id)?);
+    Ok(SignedData::new(signature, data))
+}
+
+/// Signs data for a block with the given version of the chain with the given id, see
+/// [`Signable::versioned_bytes`](../data/tx/trait.Signable.html#method.versioned_bytes). Data of
+/// legacy blocks is signed without the chain id.
+pub fn sign_versioned_data<S>(
+    key: &KeyPair,
+    chain_id: &str,
+    version: u8,
+    data: S,
+) -> Result<SignedData<S>, Error>
+where
+    S: Signable + Serialize,
+{
+    let signature = sign(key, &data.versioned_bytes(chain_id, version)?);
+    Ok(SignedData::new(signature, data))
+}
+
+/// Signs the given bytes using a `KeyPair`.
+fn sign(key: &KeyPair, bytes: &[u8]) -> Signature {
+    let signature = key.0.read().sign(bytes);
     let mut sig_bytes = [0u8; SIG_SIZE];
     for (idx, val) in signature.as_ref().iter().take(SIG_SIZE).enumerate() {
         sig_bytes[idx] = *val;
     }
-    Ok(SignedData::new(sig_bytes, data))
+    sig_bytes
 }
 
-/// Validates a signature.
-pub fn validate_signature<S>(pub_key: &PublicKey, data: &SignedData<S>) -> Result<bool, Error>
+/// Validates a signature of data for the chain with the given id.
+pub fn validate_signature<S>(
+    pub_key: &PublicKey,
+    chain_id: &str,
+    data: &SignedData<S>,
+) -> Result<bool, Error>
 where
     S: Signable,
 {
-    let signature = untrusted::Input::from(data.signature());
-    let data = data.data().get_bytes()?;
-    let data = untrusted::Input::from(&data);
-    let pub_key = untrusted::Input::from(&pub_key.0);
-    Ok(
-        ring::signature::verify(&ring::signature::ED25519, pub_key, data, signature)
-            .map(|_| true)
-            .unwrap_or(false),
-    )
+    Ok(verify(pub_key, &data.data().get_bytes(chain_id)?, data.signature()))
+}
+
+/// Validates a signature of data in a block with the given version, see
+/// [`Signable::versioned_bytes`](../data/tx/trait.Signable.html#method.versioned_bytes).
+pub fn validate_versioned_signature<S>(
+    pub_key: &PublicKey,
+    chain_id: &str,
+    version: u8,
+    data: &SignedData<S>,
+) -> Result<bool, Error>
+where
+    S: Signable + Serialize,
+{
+    let bytes = data.data().versioned_bytes(chain_id, version)?;
+    Ok(verify(pub_key, &bytes, data.signature()))
+}
+
+/// Verifies an Ed25519 signature of the given bytes.
+fn verify(pub_key: &PublicKey, bytes: &[u8], signature: &[u8]) -> bool {
+    ring::signature::verify(
+        &ring::signature::ED25519,
+        untrusted::Input::from(&pub_key.0),
+        untrusted::Input::from(bytes),
+        untrusted::Input::from(signature),
+    ).is_ok()
+}
+
+/// Validates the signatures of all transactions of a block of the chain with the given id. Each
+/// transaction must have been signed by one of the signees. The signed bytes depend on the version
+/// of the block (see [`validate_versioned_signature`](fn.validate_versioned_signature.html)).
+pub fn validate_block_signatures<H>(
+    signees: &[PublicKey],
+    chain_id: &str,
+    block: &Block<H>,
+) -> Result<(), BlockchainError>
+where
+    H: HashFunction,
+{
+    let version = block.version();
+    match block.transactions().iter().position(|tx| {
+        !signees.iter().any(|signee| {
+            validate_versioned_signature(signee, chain_id, version, tx).unwrap_or(false)
+        })
+    }) {
+        Some(idx) => Err(BlockchainError::InvalidTransaction(
+            idx,
+            "not signed by a known signee".to_owned(),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Validates a chain against the consensus rules of its spec and the signatures of all
+/// transactions (see [`validate_block_signatures`](fn.validate_block_signatures.html)) using the
+/// given number of worker threads.
+pub fn validate_chain<H>(
+    chain: &Blockchain<H>,
+    spec: &ChainSpec<H>,
+    signees: Vec<PublicKey>,
+    scope: Scope,
+    threads: usize,
+) -> ValidationReport
+where
+    H: HashFunction,
+    H::OutputSize: Debug + Clone,
+    Output<H>: Send + Sync,
+{
+    let chain_id = spec.chain_id().to_owned();
+    chain.validation_report_parallel(spec.rules(), scope, threads, move |block| {
+        validate_block_signatures(&signees, &chain_id, block)
+    })
 }
 
 /// Read a file into a string.
@@ -355,4 +462,102 @@ mod test {
             dec == &data // compare
         }
     }
+
+    #[test]
+    fn legacy_blocks_are_validated_against_the_legacy_preimage() {
+        use data::version::LEGACY_VERSION;
+        let key = KeyPair::generate();
+        let signees = vec![key.public_key_bytes()];
+        let data = Data::Usage(42);
+        let legacy_tx = sign_versioned_data(&key, "test", LEGACY_VERSION, data.clone()).unwrap();
+        let tx = sign_data(&key, "test", data).unwrap();
+        let legacy: Block = Block::new(vec![legacy_tx.clone()], 0).set_version(LEGACY_VERSION);
+        let current: Block = Block::new(vec![tx.clone()], 0);
+        assert!(validate_block_signatures(&signees, "test", &legacy).is_ok());
+        assert!(validate_block_signatures(&signees, "test", &current).is_ok());
+        // the preimage is chosen by the version of the block, not by the transaction
+        let legacy_in_current: Block = Block::new(vec![legacy_tx], 0);
+        assert!(validate_block_signatures(&signees, "test", &legacy_in_current).is_err());
+        let current_in_legacy: Block = Block::new(vec![tx], 0).set_version(LEGACY_VERSION);
+        assert!(validate_block_signatures(&signees, "test", &current_in_legacy).is_err());
+    }
+
+    /// Returns a spec with the id `test` and a fixed difficulty of `0`.
+    fn spec() -> ChainSpec {
+        let rules = ::data::consensus::Retarget::new(0, 60, 1).bounds(0, 0);
+        ChainSpec::new("test", ::data::genesis(::data::block::VERSION), rules)
+    }
+
+    /// Returns a chain of the spec with `len` blocks on top of the genesis block. Each block
+    /// contains a usage signed by `key` except for the block at height `forged`, whose usage is
+    /// signed by `forger`.
+    fn signed_chain(
+        spec: &ChainSpec,
+        len: usize,
+        key: &KeyPair,
+        forged: usize,
+        forger: &KeyPair,
+    ) -> Blockchain {
+        (1..len + 1).fold(Blockchain::from_spec(spec.clone()).unwrap(), |chain, height| {
+            let signer = if height == forged { forger } else { key };
+            let tx = sign_data(signer, spec.chain_id(), Data::Usage(height as u64)).unwrap();
+            chain.append(vec![tx], 0).unwrap()
+        })
+    }
+
+    #[test]
+    fn signed_chain_is_valid() {
+        let key = KeyPair::generate();
+        let spec = spec();
+        let chain = signed_chain(&spec, 5, &key, 0, &key);
+        let signees = vec![key.public_key_bytes()];
+        assert!(
+            chain
+                .iter()
+                .all(|blk| validate_block_signatures(&signees, "test", blk).is_ok())
+        );
+        let report = validate_chain(&chain, &spec, signees, Scope::All, 4);
+        assert!(report.is_valid());
+        assert_eq!(report.checked(), 6);
+        // without a known signee, every block with transactions is rejected
+        let report = validate_chain(&chain, &spec, Vec::new(), Scope::All, 4);
+        assert_eq!(report.problems().len(), 5);
+    }
+
+    #[test]
+    fn empty_chain_is_valid() {
+        let key = KeyPair::generate();
+        let spec = spec();
+        let signees = vec![key.public_key_bytes()];
+        let report = validate_chain(&Blockchain::new(), &spec, signees, Scope::All, 4);
+        assert!(report.is_valid());
+        assert_eq!(report.checked(), 0);
+        // the genesis block does not contain any transactions
+        let genesis = Blockchain::from_spec(spec.clone()).unwrap();
+        assert!(validate_block_signatures(&[], "test", spec.genesis()).is_ok());
+        let report = validate_chain(&genesis, &spec, Vec::new(), Scope::All, 4);
+        assert!(report.is_valid());
+        assert_eq!(report.checked(), 1);
+    }
+
+    quickcheck! {
+        fn forged_signature_is_reported(len: usize, forged: usize, threads: usize) -> bool {
+            let (key, forger) = (KeyPair::generate(), KeyPair::generate());
+            let spec = spec();
+            let len = len % 16 + 1;
+            let forged = forged % len + 1;
+            let chain = signed_chain(&spec, len, &key, forged, &forger);
+            let signees = vec![key.public_key_bytes()];
+            let block = chain.get_by_height(forged).unwrap();
+            let rejected = match validate_block_signatures(&signees, "test", block) {
+                Err(BlockchainError::InvalidTransaction(0, _)) => true,
+                _ => false,
+            };
+            let report = validate_chain(&chain, &spec, signees, Scope::All, threads % 8);
+            rejected
+                && report.checked() == len + 1
+                && report.problems().len() == 1
+                && report.first().map(|problem| problem.height()) == Some(forged)
+        }
+    }
 }
@@ -0,0 +1,39 @@
+//! This module contains the seal of blocks in a proof of authority chain.
+
+use bc::canonical::Canonical;
+
+/// Seal of a block in a proof of authority chain. Contains the public key of the validator, that
+/// sealed the block, and the validator's signature over the block's seal hash.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct AuthoritySeal {
+    signer: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl AuthoritySeal {
+    /// Creates a new seal from the public key bytes of a validator and a signature.
+    pub fn new(signer: Vec<u8>, signature: Vec<u8>) -> Self {
+        Self {
+            signer: signer,
+            signature: signature,
+        }
+    }
+
+    /// Returns the public key bytes of the validator, that sealed the block.
+    pub fn signer(&self) -> &[u8] {
+        &self.signer
+    }
+
+    /// Returns the signature over the block's seal hash.
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+/// The public key followed by the signature, both prefixed with their length.
+impl Canonical for AuthoritySeal {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.signer.encode(out);
+        self.signature.encode(out);
+    }
+}
@@ -13,17 +13,173 @@ extern crate blockchain as bc;
 
 mod hack;
 
+pub mod authority;
 pub mod tx;
 
 pub use sha2::Sha256;
-pub use bc::{block, blockchain};
+pub use bc::{block, blockchain, canonical, checkpoint, clock, consensus, format, hash, header,
+             headerchain, merkle, miner, pow, proof, report, seal, spec, store, target, version,
+             work, BlockchainError, HashBytes, MiningError, PersistingError};
 
-/// The difficulty factor.
+use clock::FixedClock;
+use consensus::Retarget;
+use hash::HashFunction;
+use version::{VersionSchedule, LEGACY_VERSION};
+
+/// Id of the specific blockchain, that is committed to by the signatures of its transactions.
+pub const CHAIN_ID: &str = "ebsnet";
+/// Time of the genesis block of the specific blockchain (`2018-03-01T00:00:00Z`).
+pub const GENESIS_TIME: u64 = 1_519_862_400;
+
+/// The difficulty factor. This is also the minimum difficulty required by the consensus rules.
 pub const DIFFICULTY: usize = 3;
+/// Upper bound of the difficulty required by the consensus rules.
+pub const MAX_DIFFICULTY: usize = 24;
+/// Desired time between two blocks in seconds.
+pub const TARGET_BLOCK_INTERVAL: u64 = 60;
+/// Number of blocks that are considered when retargeting the difficulty.
+pub const RETARGET_WINDOW: usize = 16;
+/// Number of blocks, whose median time the time of a new block needs to exceed.
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// Number of seconds, the time of a block may be ahead of the current time.
+pub const MAX_FUTURE_DRIFT: u64 = 10 * 60;
+
+/// Convenience type for the consensus rules of the specific blockchain.
+pub type Rules = Retarget;
+
+/// Returns the consensus rules of the specific blockchain. The difficulty is retargeted towards
+/// `TARGET_BLOCK_INTERVAL` and stays between `DIFFICULTY` and `MAX_DIFFICULTY`. The time of a block
+/// must be later than the median time of the previous `MEDIAN_TIME_SPAN` blocks and may be at most
+/// `MAX_FUTURE_DRIFT` seconds ahead of the system time.
+pub fn rules() -> Rules {
+    Retarget::new(DIFFICULTY, TARGET_BLOCK_INTERVAL, RETARGET_WINDOW)
+        .bounds(DIFFICULTY, MAX_DIFFICULTY)
+        .median_time(MEDIAN_TIME_SPAN)
+        .future_drift(MAX_FUTURE_DRIFT)
+}
+
+/// Returns the consensus rules of a chain, that was started with legacy blocks (see
+/// `version::LEGACY_VERSION`) and requires the current block version from height `activation` on.
+/// The time rules are enforced from the same height on, since legacy blocks have been created
+/// without them. An activation height of `0` requires the current version for every block, like
+/// [`rules`](fn.rules.html).
+pub fn migrated_rules(activation: usize) -> Rules {
+    if activation == 0 {
+        rules()
+    } else {
+        rules()
+            .versions(VersionSchedule::new(LEGACY_VERSION).activate(block::VERSION, activation))
+            .time_rules_from(activation)
+    }
+}
+
+/// Evaluates an expression with a type alias for the hash function of the given
+/// [`HashAlgorithm`](hash/enum.HashAlgorithm.html), e.g. to open a store, whose hash algorithm is
+/// only known at runtime. The expression is compiled once for every algorithm.
+///
+/// # Examples
+/// ```
+/// #[macro_use]
+/// extern crate data;
+/// # fn main() {
+/// use data::hash::HashAlgorithm;
+/// let len = with_hash!(HashAlgorithm::Blake2b, H => data::Block::<H>::hash_length_byte());
+/// assert_eq!(len, 64);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! with_hash {
+    ($algorithm:expr, $hash:ident => $body:expr) => {
+        match $algorithm {
+            $crate::hash::HashAlgorithm::Sha256 => {
+                type $hash = $crate::hash::Sha256;
+                $body
+            }
+            $crate::hash::HashAlgorithm::Sha512Trunc256 => {
+                type $hash = $crate::hash::Sha512Trunc256;
+                $body
+            }
+            $crate::hash::HashAlgorithm::Sha3_256 => {
+                type $hash = $crate::hash::Sha3_256;
+                $body
+            }
+            $crate::hash::HashAlgorithm::Blake2b => {
+                type $hash = $crate::hash::Blake2b;
+                $body
+            }
+        }
+    };
+}
+
+/// Returns the genesis block of the specific blockchain with the given version, so chains, that
+/// start with legacy blocks, have a genesis block of their own. The block does not contain any
+/// transactions, except for a legacy genesis block, which contains a single default transaction,
+/// since legacy blocks contain exactly one transaction.
+pub fn genesis<H: HashFunction>(version: u8) -> Block<H> {
+    let clock = FixedClock(GENESIS_TIME);
+    let transactions = if version == LEGACY_VERSION {
+        vec![Default::default()]
+    } else {
+        Vec::new()
+    };
+    Block::new_with_clock(transactions, Default::default(), DIFFICULTY, &clock)
+        .set_version(version)
+        .proof_of_work_with_clock(&clock)
+}
+
+/// Returns the spec of the specific blockchain with the id `CHAIN_ID` and the consensus rules
+/// returned by [`rules`](fn.rules.html). The genesis block is hashed using `H`.
+///
+/// # Examples
+/// ```
+/// # extern crate data;
+/// # fn main() {
+/// let chain: data::Blockchain = data::Blockchain::from_spec(data::spec()).unwrap();
+/// assert_eq!(chain.chain_id(), Some(data::CHAIN_ID));
+/// // the genesis block does not change
+/// let spec: data::ChainSpec = data::spec();
+/// assert_eq!(chain.get_by_height(0).unwrap().hash(), spec.genesis().hash());
+/// # }
+/// ```
+pub fn spec<H: HashFunction>() -> ChainSpec<H> {
+    migrated_spec(0)
+}
+
+/// Returns the spec of the specific blockchain with the consensus rules returned by
+/// [`migrated_rules`](fn.migrated_rules.html).
+pub fn migrated_spec<H: HashFunction>(activation: usize) -> ChainSpec<H> {
+    let rules = migrated_rules(activation);
+    let version = rules.schedule().version_at(0);
+    spec::ChainSpec::new(CHAIN_ID, genesis(version), rules)
+}
+
+// The following types are hashed using SHA-256 by default. Another hash algorithm can be selected
+// using the type parameter `H`, see the `hash` module.
 
 /// Convenience type for the Blockchain struct.
-pub type Blockchain = blockchain::Blockchain<tx::BlockData, Sha256>;
-/// Convenience type for the Block struct.
-pub type Block = block::Block<tx::BlockData, Sha256>;
+pub type Blockchain<H = Sha256> = blockchain::Blockchain<tx::BlockData, H>;
+/// Convenience type for the Block struct. Each block carries a batch of signed transactions, that
+/// is committed to by the Merkle root in the block header.
+pub type Block<H = Sha256> = block::Block<tx::BlockData, H>;
+/// Convenience type for the header of a block of the specific blockchain.
+pub type Header<H = Sha256> = header::Header<H>;
+/// Convenience type for a chain of headers, that is used by light clients, which do not store the
+/// transactions of each block.
+pub type HeaderChain<H = Sha256> = headerchain::HeaderChain<H>;
+/// Convenience type for a proof that a transaction is part of the specific blockchain.
+pub type InclusionProof<H = Sha256> = proof::InclusionProof<tx::BlockData, H>;
+/// Convenience type for the spec of the specific blockchain.
+pub type ChainSpec<H = Sha256> = spec::ChainSpec<tx::BlockData, H>;
+/// Convenience type for the append-only on-disk store of the specific blockchain.
+pub type ChainStore<H = Sha256> = store::ChainStore<tx::BlockData, H>;
 /// Iterator over the specific blockchain.
-pub type BcIter<'a> = blockchain::BlockchainIter<'a, tx::BlockData, Sha256>;
+pub type BcIter<'a, H = Sha256> = blockchain::BlockchainIter<'a, tx::BlockData, H>;
+/// Double-ended iterator over a range of the specific blockchain, oldest block first.
+pub type BcRange<'a, H = Sha256> = blockchain::BlockchainRange<'a, tx::BlockData, H>;
+/// Convenience type for a blockchain that is sealed using proof of authority.
+pub type AuthorityBlockchain =
+    blockchain::Blockchain<tx::BlockData, Sha256, authority::AuthoritySeal>;
+/// Convenience type for a block that is sealed using proof of authority.
+pub type AuthorityBlock = block::Block<tx::BlockData, Sha256, authority::AuthoritySeal>;
+/// Convenience type for the header of a block that is sealed using proof of authority.
+pub type AuthorityHeader = header::Header<Sha256, authority::AuthoritySeal>;
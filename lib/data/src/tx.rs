@@ -2,7 +2,10 @@
 
 use hack::BigArray;
 
-use bincode::serialize;
+use bc::canonical::Canonical;
+use bc::version::LEGACY_VERSION;
+
+use serde::Serialize;
 
 use failure::Error;
 
@@ -12,6 +15,10 @@ pub const SIG_SIZE: usize = 64;
 /// Convenience type for a signature.
 pub type Signature = [u8; SIG_SIZE];
 
+/// Domain tag, that prefixes the bytes of signed data, so they cannot be mistaken for other signed
+/// values like the seal hash of a block.
+pub const DATA_DOMAIN: &[u8] = b"data";
+
 /// Convenience type for signed data inside a block.
 pub type BlockData = SignedData<Data>;
 
@@ -58,17 +65,67 @@ pub enum Data {
 
 /// Typed that implement this trait can be signed.
 pub trait Signable {
-    /// Converts the data to a list of bytes that can be signed.
-    fn get_bytes(&self) -> Result<Vec<u8>, Error>;
+    /// Converts the data to a list of bytes that can be signed. The bytes commit to the id of the
+    /// chain, the data is meant for (see `bc::spec::ChainSpec`), so a signature is not valid on
+    /// another chain.
+    fn get_bytes(&self, chain_id: &str) -> Result<Vec<u8>, Error>;
+
+    /// Converts the data to the bytes, its signature commits to, if it is part of a block with the
+    /// given version. Transactions of legacy blocks (see `bc::version::LEGACY_VERSION`) have been
+    /// signed before the bytes committed to a chain, so their signatures are checked against the
+    /// `bincode` serialization of the data. Newer blocks use [`get_bytes`](#tymethod.get_bytes).
+    fn versioned_bytes(&self, chain_id: &str, version: u8) -> Result<Vec<u8>, Error>
+    where
+        Self: Serialize,
+    {
+        if version == LEGACY_VERSION {
+            Ok(::bincode::serialize(self)?)
+        } else {
+            self.get_bytes(chain_id)
+        }
+    }
 }
 
 impl Signable for Data {
-    fn get_bytes(&self) -> Result<Vec<u8>, Error> {
-        let res = serialize(self)?;
+    /// Returns the domain tag `DATA_DOMAIN`, prefixed with its length, followed by the canonical
+    /// encoding of the chain id and the canonical encoding of the data.
+    fn get_bytes(&self, chain_id: &str) -> Result<Vec<u8>, Error> {
+        let mut res = vec![DATA_DOMAIN.len() as u8];
+        res.extend_from_slice(DATA_DOMAIN);
+        chain_id.encode(&mut res);
+        self.encode(&mut res);
         Ok(res)
     }
 }
 
+/// The signature followed by the canonical encoding of the data.
+impl<T> Canonical for SignedData<T>
+where
+    T: Canonical,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.signature);
+        self.data.encode(out);
+    }
+}
+
+/// A one byte tag, `0x00` for `Billing` and `0x01` for `Usage`, followed by the canonical
+/// encoding of the field.
+impl Canonical for Data {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Data::Billing(ref fingerprint) => {
+                out.push(0x00);
+                fingerprint.encode(out);
+            }
+            Data::Usage(usage) => {
+                out.push(0x01);
+                usage.encode(out);
+            }
+        }
+    }
+}
+
 impl<T> Default for SignedData<T>
 where
     T: Default,
@@ -86,3 +143,53 @@ impl Default for Data {
         Data::Billing(Default::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use HashBytes;
+
+    #[test]
+    fn golden_data_encoding() {
+        assert_eq!(
+            HashBytes(Data::Usage(1000).canonical_bytes()).to_string(),
+            "0100000000000003e8"
+        );
+        assert_eq!(
+            HashBytes(Data::Billing(vec![0xab, 0xcd]).canonical_bytes()).to_string(),
+            "000000000000000002abcd"
+        );
+        assert_eq!(
+            HashBytes(Data::Usage(1000).get_bytes("test").unwrap()).to_string(),
+            "04646174610000000000000004746573740100000000000003e8"
+        );
+    }
+
+    #[test]
+    fn legacy_blocks_sign_the_serialized_data() {
+        use bc::version::CANONICAL_VERSION;
+        let data = Data::Usage(1000);
+        assert_eq!(
+            HashBytes(data.versioned_bytes("test", LEGACY_VERSION).unwrap()).to_string(),
+            "01000000e803000000000000"
+        );
+        assert_eq!(
+            data.versioned_bytes("test", CANONICAL_VERSION).unwrap(),
+            data.get_bytes("test").unwrap()
+        );
+    }
+
+    #[test]
+    fn golden_signed_data_encoding() {
+        let signed = SignedData::new([0x07; SIG_SIZE], Data::Usage(1000));
+        assert_eq!(
+            HashBytes(signed.canonical_bytes()).to_string(),
+            format!("{}0100000000000003e8", "07".repeat(SIG_SIZE))
+        );
+        assert_eq!(
+            HashBytes::from(&::merkle::leaf_hash::<::Sha256, _>(&signed)[..]).to_string(),
+            "b94d02c08d4bc9dbe1591ac523af89efb940bf82733250374387baf4bc77de00"
+        );
+    }
+}